@@ -1,43 +1,360 @@
-use crate::types::FileInfo;
+use crate::display::export_duplicates;
+use crate::perf::{self, CountedFile};
+use crate::quantiles::{SizeQuantiles, RELATIVE_ACCURACY};
+use crate::types::{Clock, DedupeMode, DuplicateGroup, DuplicateOptions, FileInfo, SizeUnit, UnitSystem};
+use crate::utils::{device_id, file_id, group_digits, hash_file, link_count};
 use colored::Colorize;
-use std::collections::HashMap;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
 use std::fs;
-use std::path::Path;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
 
-pub fn find_duplicates(dir: &Path, color: bool) {
+/// Find files with identical contents below a directory
+pub fn find_duplicates(dir: &Path, options: &DuplicateOptions, color: bool) {
     let mut hash_map: HashMap<u64, Vec<String>> = HashMap::new();
-    let mut duplicates = Vec::new();
+    files_by_size(dir, &mut hash_map);
+    report_duplicates(&hash_map, options, color);
+}
 
-    fn scan_for_duplicates(
-        path: &Path,
-        hash_map: &mut HashMap<u64, Vec<String>>,
-        _duplicates: &mut Vec<(u64, Vec<String>)>,
-    ) {
-        if let Ok(entries) = fs::read_dir(path) {
-            for entry in entries.flatten() {
-                let entry_path = entry.path();
-                if entry_path.is_file() {
-                    if let Ok(metadata) = entry.metadata() {
-                        let size = metadata.len();
-                        hash_map
-                            .entry(size)
-                            .or_insert_with(Vec::new)
-                            .push(entry_path.to_string_lossy().to_string());
-                    }
-                } else if entry_path.is_dir() {
-                    scan_for_duplicates(&entry_path, hash_map, _duplicates);
+/// Add every regular file below `path` to `hash_map`, keyed by size
+fn files_by_size(path: &Path, hash_map: &mut HashMap<u64, Vec<String>>) {
+    let listing = fs::read_dir(path);
+    perf::count_listing(&listing);
+    if let Ok(entries) = listing {
+        for entry in entries.flatten() {
+            let entry_path = entry.path();
+            // Not following symlinks: a link is not a copy of its target
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            if file_type.is_file() {
+                let metadata = entry.metadata();
+                perf::count_metadata(&metadata);
+                if let Ok(metadata) = metadata {
+                    let size = metadata.len();
+                    hash_map
+                        .entry(size)
+                        .or_default()
+                        .push(entry_path.to_string_lossy().to_string());
                 }
+            } else if file_type.is_dir() {
+                files_by_size(&entry_path, hash_map);
             }
         }
     }
+}
 
-    scan_for_duplicates(dir, &mut hash_map, &mut duplicates);
+/// Content-level diff of two trees: which contents exist on both sides,
+/// wherever they are, and which only on one
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct TreeComparison {
+    /// Paths holding the same contents, in `a` and in `b`
+    pub in_both: Vec<(Vec<String>, Vec<String>)>,
+    pub only_in_a: Vec<(u64, String)>,
+    pub only_in_b: Vec<(u64, String)>,
+}
+
+/// Compare the files below `a` and `b` by content. Files whose size does
+/// not occur on the other side are known to be one-sided without hashing.
+pub fn compare_trees(a: &Path, b: &Path, options: &DuplicateOptions) -> TreeComparison {
+    let mut sizes_a: HashMap<u64, Vec<String>> = HashMap::new();
+    let mut sizes_b: HashMap<u64, Vec<String>> = HashMap::new();
+    files_by_size(a, &mut sizes_a);
+    files_by_size(b, &mut sizes_b);
+
+    let mut comparison = TreeComparison::default();
+    let mut candidates: Vec<(u64, &String)> = Vec::new();
+    let mut side_b = Vec::new();
+    for (sizes, other, only, is_b) in [
+        (&sizes_a, &sizes_b, &mut comparison.only_in_a, false),
+        (&sizes_b, &sizes_a, &mut comparison.only_in_b, true),
+    ] {
+        for (size, paths) in sizes {
+            for path in paths {
+                if other.contains_key(size) {
+                    candidates.push((*size, path));
+                    side_b.push(is_b);
+                } else {
+                    only.push((*size, path.clone()));
+                }
+            }
+        }
+    }
 
-    for (size, paths) in hash_map.iter() {
-        if paths.len() > 1 {
-            duplicates.push((*size, paths.clone()));
+    let hashes = hash_in_parallel(&candidates, options.threads, hash_file);
+    let mut by_content: HashMap<(u64, String), (Vec<String>, Vec<String>)> = HashMap::new();
+    for (((size, path), hash), is_b) in candidates.into_iter().zip(hashes).zip(side_b) {
+        // Unreadable files cannot be matched, so they count as one-sided
+        let hash = hash.unwrap_or_else(|| format!("unreadable:{}", path));
+        let (in_a, in_b) = by_content.entry((size, hash)).or_default();
+        if is_b {
+            in_b.push(path.clone());
+        } else {
+            in_a.push(path.clone());
         }
     }
+    for ((size, _), (mut in_a, mut in_b)) in by_content {
+        in_a.sort();
+        in_b.sort();
+        match (in_a.is_empty(), in_b.is_empty()) {
+            (false, false) => comparison.in_both.push((in_a, in_b)),
+            (false, true) => comparison.only_in_a.extend(in_a.into_iter().map(|p| (size, p))),
+            _ => comparison.only_in_b.extend(in_b.into_iter().map(|p| (size, p))),
+        }
+    }
+
+    comparison.in_both.sort();
+    comparison.only_in_a.sort_by(|x, y| x.1.cmp(&y.1));
+    comparison.only_in_b.sort_by(|x, y| x.1.cmp(&y.1));
+    comparison
+}
+
+/// Print a [`compare_trees`] report; returns whether the trees hold the
+/// same set of contents
+pub fn show_tree_comparison(a: &Path, b: &Path, options: &DuplicateOptions, color: bool) -> bool {
+    let comparison = compare_trees(a, b, options);
+    let files_in_both: usize = comparison.in_both.iter().map(|(x, y)| x.len() + y.len()).sum();
+    let bytes_in_both: u64 = comparison
+        .in_both
+        .iter()
+        .filter_map(|(x, _)| fs::metadata(&x[0]).ok())
+        .map(|m| m.len())
+        .sum();
+
+    println!("Comparing {} with {}", a.display(), b.display());
+    println!("{}", "─".repeat(50));
+    let summary = format!(
+        "In both: {} distinct files ({} paths, {})",
+        comparison.in_both.len(),
+        files_in_both,
+        SizeUnit::auto_format_size(bytes_in_both)
+    );
+    if color {
+        println!("{}", summary.green());
+    } else {
+        println!("{}", summary);
+    }
+
+    for (root, only) in [(a, &comparison.only_in_a), (b, &comparison.only_in_b)] {
+        let total: u64 = only.iter().map(|(size, _)| size).sum();
+        let heading = format!(
+            "Only in {}: {} files ({})",
+            root.display(),
+            only.len(),
+            SizeUnit::auto_format_size(total)
+        );
+        println!();
+        if color && !only.is_empty() {
+            println!("{}", heading.yellow().bold());
+        } else {
+            println!("{}", heading);
+        }
+        for (size, path) in only {
+            let shown = Path::new(path).strip_prefix(root).map_or(Path::new(path), |p| p);
+            println!("  {} ({})", shown.display(), SizeUnit::auto_format_size(*size));
+        }
+    }
+
+    comparison.only_in_a.is_empty() && comparison.only_in_b.is_empty()
+}
+
+/// Find duplicate files among an explicit list of paths
+pub fn find_duplicates_in_paths(paths: &[String], options: &DuplicateOptions, color: bool) {
+    report_duplicates(&paths_by_size(paths), options, color);
+}
+
+/// The regular files among `paths`, keyed by size. Symlinks are left out,
+/// since a link is not a copy of its target, and a file listed more than
+/// once, under any spelling, is kept under its first path.
+pub fn paths_by_size(paths: &[String]) -> HashMap<u64, Vec<String>> {
+    let mut by_size: HashMap<u64, Vec<String>> = HashMap::new();
+    let mut seen = HashSet::new();
+    for path in paths {
+        let metadata = fs::symlink_metadata(path);
+        perf::count_metadata(&metadata);
+        let Some(metadata) = metadata.ok().filter(|m| m.is_file()) else {
+            continue;
+        };
+        let canonical = fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path));
+        if seen.insert(canonical) {
+            by_size.entry(metadata.len()).or_default().push(path.clone());
+        }
+    }
+    by_size
+}
+
+/// Bytes read from each end of a file by [`partial_hash`]
+pub const PARTIAL_HASH_BLOCK: u64 = 64 * 1024;
+
+/// Split same-size candidates into groups of identical content, skipping
+/// files under `options.min_size`. Hard links to the same file count once,
+/// under their first path.
+///
+/// Files larger than two [`PARTIAL_HASH_BLOCK`]s are first compared by
+/// [`partial_hash`], which rules out most same-size files after reading
+/// 128 KiB of each; only files that still collide are hashed in full.
+pub fn group_duplicates(by_size: &HashMap<u64, Vec<String>>, options: &DuplicateOptions) -> Vec<DuplicateGroup> {
+    let (by_size, _) = split_hardlinks(by_size);
+    let candidates: Vec<(u64, &String)> = by_size
+        .iter()
+        .filter(|(size, paths)| paths.len() > 1 && **size >= options.min_size)
+        .flat_map(|(size, paths)| paths.iter().map(move |path| (*size, path)))
+        .collect();
+
+    let (large, small): (Vec<_>, Vec<_>) = candidates
+        .into_iter()
+        .partition(|(size, _)| *size > 2 * PARTIAL_HASH_BLOCK);
+    let partial_hashes = hash_in_parallel(&large, options.threads, partial_hash);
+    let mut survivors = small;
+    survivors.extend(
+        group_by_hash(large, partial_hashes)
+            .into_iter()
+            .filter(|(_, paths)| paths.len() > 1)
+            .flat_map(|((size, _), paths)| paths.into_iter().map(move |path| (size, path))),
+    );
+
+    let hashes = hash_in_parallel(&survivors, options.threads, hash_file);
+    let mut duplicates: Vec<DuplicateGroup> = group_by_hash(survivors, hashes)
+        .into_iter()
+        .filter(|(_, paths)| paths.len() > 1)
+        .map(|((size, hash), paths)| {
+            let mut paths: Vec<String> = paths.into_iter().cloned().collect();
+            paths.sort();
+            DuplicateGroup {
+                id: 0,
+                hash,
+                size,
+                wasted_bytes: size * (paths.len() as u64 - 1),
+                paths,
+            }
+        })
+        .collect();
+    duplicates.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.paths.cmp(&b.paths)));
+    for (index, group) in duplicates.iter_mut().enumerate() {
+        group.id = index + 1;
+    }
+    duplicates
+}
+
+/// Collapse paths that are hard links to the same file (same device and
+/// inode) into their first path. Returns the remaining candidates and the
+/// sets of names that share a file; removing one of those frees nothing.
+pub fn split_hardlinks(by_size: &HashMap<u64, Vec<String>>) -> (HashMap<u64, Vec<String>>, Vec<Vec<String>>) {
+    let mut candidates = HashMap::new();
+    let mut hardlinks = Vec::new();
+    for (size, paths) in by_size {
+        if paths.len() < 2 {
+            candidates.insert(*size, paths.clone());
+            continue;
+        }
+        let mut by_inode: HashMap<(u64, u64), Vec<String>> = HashMap::new();
+        let mut unique = Vec::new();
+        for path in paths {
+            match fs::metadata(path).ok().filter(|m| link_count(m) > 1).and_then(|m| file_id(&m)) {
+                Some(id) => by_inode.entry(id).or_default().push(path.clone()),
+                None => unique.push(path.clone()),
+            }
+        }
+        for mut names in by_inode.into_values() {
+            names.sort();
+            unique.push(names[0].clone());
+            if names.len() > 1 {
+                hardlinks.push(names);
+            }
+        }
+        candidates.insert(*size, unique);
+    }
+    hardlinks.sort();
+    (candidates, hardlinks)
+}
+
+/// Group files by size and hash, dropping those that could not be read
+fn group_by_hash(
+    files: Vec<(u64, &String)>,
+    hashes: Vec<Option<String>>,
+) -> HashMap<(u64, String), Vec<&String>> {
+    let mut groups: HashMap<(u64, String), Vec<&String>> = HashMap::new();
+    for ((size, path), hash) in files.into_iter().zip(hashes) {
+        if let Some(hash) = hash {
+            groups.entry((size, hash)).or_default().push(path);
+        }
+    }
+    groups
+}
+
+/// SHA-256 of the first and last [`PARTIAL_HASH_BLOCK`] bytes of a file (the
+/// whole file when it is smaller than two blocks), as lowercase hex. Equal
+/// partial hashes only say the files may be identical.
+pub fn partial_hash(path: &Path) -> io::Result<String> {
+    use sha2::{Digest, Sha256};
+
+    let mut file = CountedFile::open(path)?;
+    let len = file.metadata()?.len();
+    let mut hasher = Sha256::new();
+    if len <= 2 * PARTIAL_HASH_BLOCK {
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents)?;
+        hasher.update(&contents);
+    } else {
+        let mut buffer = vec![0u8; PARTIAL_HASH_BLOCK as usize];
+        file.read_exact(&mut buffer)?;
+        hasher.update(&buffer);
+        file.seek(SeekFrom::End(-(PARTIAL_HASH_BLOCK as i64)))?;
+        file.read_exact(&mut buffer)?;
+        hasher.update(&buffer);
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+/// Hash files on `threads` workers. Each worker claims the next unhashed
+/// file when it finishes one, so a few huge files don't hold up the rest.
+/// Files that cannot be read hash to `None`.
+pub(crate) fn hash_in_parallel(
+    files: &[(u64, &String)],
+    threads: usize,
+    hash: fn(&Path) -> io::Result<String>,
+) -> Vec<Option<String>> {
+    let next = AtomicUsize::new(0);
+    let mut hashes = vec![None; files.len()];
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads.clamp(1, files.len().max(1)))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut done = Vec::new();
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some((_, path)) = files.get(index) else {
+                            return done;
+                        };
+                        done.push((index, hash(Path::new(path)).ok()));
+                    }
+                })
+            })
+            .collect();
+        for worker in workers {
+            for (index, hash) in worker.join().unwrap_or_default() {
+                hashes[index] = hash;
+            }
+        }
+    });
+    hashes
+}
+
+fn report_duplicates(hash_map: &HashMap<u64, Vec<String>>, options: &DuplicateOptions, color: bool) {
+    let duplicates = group_duplicates(hash_map, options);
+    let eligible: HashMap<u64, Vec<String>> = hash_map
+        .iter()
+        .filter(|(size, _)| **size >= options.min_size)
+        .map(|(size, paths)| (*size, paths.clone()))
+        .collect();
+    let (_, hardlinks) = split_hardlinks(&eligible);
 
     if duplicates.is_empty() {
         println!("No duplicate files found.");
@@ -45,35 +362,664 @@ pub fn find_duplicates(dir: &Path, color: bool) {
         println!("Duplicate files found:");
         println!("{}", "─".repeat(50));
 
-        for (size, paths) in duplicates {
+        for group in &duplicates {
             if color {
                 println!(
                     "Size: {} ({})",
-                    crate::types::SizeUnit::auto_format_size(size).cyan(),
-                    paths.len().to_string().yellow()
+                    crate::types::SizeUnit::auto_format_size(group.size).cyan(),
+                    group.paths.len().to_string().yellow()
                 );
             } else {
                 println!(
                     "Size: {} ({})",
-                    crate::types::SizeUnit::auto_format_size(size),
-                    paths.len()
+                    crate::types::SizeUnit::auto_format_size(group.size),
+                    group.paths.len()
                 );
             }
-            for path in &paths {
+            for path in &group.paths {
                 println!("  {}", path);
             }
             println!();
         }
     }
+
+    if !hardlinks.is_empty() {
+        println!("Hard links, not counted as duplicates (removing one frees nothing):");
+        for names in &hardlinks {
+            if color {
+                println!("  {}", names.join(" = ").dimmed());
+            } else {
+                println!("  {}", names.join(" = "));
+            }
+        }
+        println!();
+    }
+
+    if options.similar_images {
+        crate::similar::report_similar_images(hash_map, &duplicates, options, color);
+    }
+    if let Some(export_file) = &options.export {
+        export_duplicates(&duplicates, export_file);
+    }
+    if let Some(mode) = options.dedupe {
+        dedupe(&duplicates, mode, options.dry_run, color);
+    }
+}
+
+/// What [`dedupe`] changed, or would change in a dry run
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DedupeSummary {
+    pub replaced: usize,
+    pub skipped: usize,
+    /// Bytes no longer held by any file once the copies are replaced
+    pub freed: u64,
+}
+
+/// Replace every copy in each group except the first with a link to it.
+/// Copies on another filesystem than the kept file, copies that are already
+/// links to it and copies whose contents changed since hashing are left
+/// alone. Each copy is compared byte for byte with the kept file just before
+/// it is replaced.
+pub fn dedupe(duplicates: &[DuplicateGroup], mode: DedupeMode, dry_run: bool, color: bool) -> DedupeSummary {
+    let verb = match (mode, dry_run) {
+        (DedupeMode::Hardlink, true) => "Would hardlink",
+        (DedupeMode::Symlink, true) => "Would symlink",
+        (DedupeMode::Hardlink, false) => "Hardlinked",
+        (DedupeMode::Symlink, false) => "Symlinked",
+    };
+    let mut summary = DedupeSummary::default();
+
+    for DuplicateGroup { size, paths, .. } in duplicates {
+        // Keep the first real file; symlinks in the group point at another member
+        let Some((keep, keep_meta)) = paths.iter().find_map(|path| {
+            fs::symlink_metadata(path)
+                .ok()
+                .filter(|m| m.file_type().is_file())
+                .map(|m| (path, m))
+        }) else {
+            continue;
+        };
+
+        for path in paths.iter().filter(|path| *path != keep) {
+            let skip = match fs::symlink_metadata(path) {
+                Err(e) => Some(e.to_string()),
+                Ok(m) if !m.file_type().is_file() => Some("already a link".to_string()),
+                Ok(m) if file_id(&m).is_some() && file_id(&m) == file_id(&keep_meta) => {
+                    Some("already a link".to_string())
+                }
+                Ok(m) if device_id(&m) != device_id(&keep_meta) => Some(format!("on another filesystem than {}", keep)),
+                Ok(m) if m.len() != *size => Some("changed since it was hashed".to_string()),
+                Ok(m) => match same_contents(Path::new(path), Path::new(keep)) {
+                    Err(e) => Some(e.to_string()),
+                    Ok(false) => Some("changed since it was hashed".to_string()),
+                    Ok(true) => {
+                        let result = if dry_run { Ok(()) } else { replace_with_link(path, keep, mode) };
+                        match result {
+                            Ok(()) => {
+                                summary.replaced += 1;
+                                // Other hard links still hold the data
+                                if link_count(&m) == 1 {
+                                    summary.freed += size;
+                                }
+                                if color {
+                                    println!("{} {} -> {}", verb.green(), path, keep);
+                                } else {
+                                    println!("{} {} -> {}", verb, path, keep);
+                                }
+                                None
+                            }
+                            Err(e) => Some(e.to_string()),
+                        }
+                    }
+                },
+            };
+            if let Some(reason) = skip {
+                summary.skipped += 1;
+                if color {
+                    println!("{} {}: {}", "Skipped".yellow(), path, reason);
+                } else {
+                    println!("Skipped {}: {}", path, reason);
+                }
+            }
+        }
+    }
+
+    let kind = match mode {
+        DedupeMode::Hardlink => "hardlinks",
+        DedupeMode::Symlink => "symlinks",
+    };
+    let message = if dry_run {
+        format!(
+            "Dry run: would replace {} files with {}, freeing {}",
+            summary.replaced,
+            kind,
+            SizeUnit::auto_format_size(summary.freed)
+        )
+    } else {
+        format!(
+            "Replaced {} files with {}, freed {}",
+            summary.replaced,
+            kind,
+            SizeUnit::auto_format_size(summary.freed)
+        )
+    };
+    if color {
+        println!("{}", message.green().bold());
+    } else {
+        println!("{}", message);
+    }
+    summary
+}
+
+/// Whether two files hold the same bytes, read side by side
+fn same_contents(a: &Path, b: &Path) -> io::Result<bool> {
+    /// Read until `buffer` is full or the file ends
+    fn fill(file: &mut CountedFile, buffer: &mut [u8]) -> io::Result<usize> {
+        let mut filled = 0;
+        while filled < buffer.len() {
+            match file.read(&mut buffer[filled..]) {
+                Ok(0) => break,
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(filled)
+    }
+
+    let mut a = CountedFile::open(a)?;
+    let mut b = CountedFile::open(b)?;
+    let mut buffer_a = vec![0u8; 64 * 1024];
+    let mut buffer_b = vec![0u8; 64 * 1024];
+    loop {
+        let read_a = fill(&mut a, &mut buffer_a)?;
+        let read_b = fill(&mut b, &mut buffer_b)?;
+        if buffer_a[..read_a] != buffer_b[..read_b] {
+            return Ok(false);
+        }
+        if read_a == 0 {
+            return Ok(true);
+        }
+    }
+}
+
+/// Swap `path` for a link to `target`. The link is created under a fresh
+/// temporary name next to `path` and renamed over it, so `path` is never
+/// missing and no other file is ever removed or replaced along the way.
+fn replace_with_link(path: &str, target: &str, mode: DedupeMode) -> io::Result<()> {
+    let path = Path::new(path);
+    let name = path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let link_target = match mode {
+        DedupeMode::Hardlink => PathBuf::from(target),
+        DedupeMode::Symlink => fs::canonicalize(target)?,
+    };
+    // Linking fails on a name that exists, and the builder then tries another
+    let temp = tempfile::Builder::new()
+        .prefix(&format!(".{}.", name))
+        .suffix(".filebyte-dedupe")
+        .make_in(dir, |temp| match mode {
+            DedupeMode::Hardlink => fs::hard_link(&link_target, temp),
+            #[cfg(unix)]
+            DedupeMode::Symlink => std::os::unix::fs::symlink(&link_target, temp),
+            #[cfg(windows)]
+            DedupeMode::Symlink => std::os::windows::fs::symlink_file(&link_target, temp),
+        })?;
+    // A failed rename drops the temporary link
+    temp.persist(path).map(|_| ()).map_err(|e| e.error)
+}
+
+/// Show the directories with the most entries under a path
+pub fn show_entry_hotspots(dir: &Path, limit: usize, one_file_system: bool, skip: &[PathBuf], color: bool) {
+    let hotspots = find_busiest_dirs(dir, limit, DirCount::Entries, one_file_system, skip);
+    if hotspots.is_empty() {
+        return;
+    }
+
+    println!();
+    println!("Directories With Most Entries:");
+    println!("{}", "─".repeat(50));
+    for entry in hotspots {
+        if color {
+            println!("{} {}", entry.entries.to_string().cyan(), entry.path.display());
+        } else {
+            println!("{} {}", entry.entries, entry.path.display());
+        }
+    }
+}
+
+/// How many of the largest directories to report, and how deep to look
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DirRanking {
+    pub limit: usize,
+    /// Directories deeper than this below the root are counted in their
+    /// ancestors' totals but not ranked themselves
+    pub max_depth: usize,
+    /// Stay on the root's filesystem instead of descending into mounts
+    pub one_file_system: bool,
+}
+
+/// Rank the directories below `dir` by the total size of the files under
+/// them, in one bottom-up walk that leaves out `skip`.
+pub fn find_largest_dirs(dir: &Path, ranking: DirRanking, skip: &[PathBuf]) -> Vec<(PathBuf, u64)> {
+    let Ok(device) = fs::metadata(dir).map(|m| device_id(&m)) else {
+        return Vec::new();
+    };
+    let mut heap: BinaryHeap<Reverse<(u64, PathBuf)>> = BinaryHeap::new();
+
+    fn walk(
+        path: &Path,
+        depth: usize,
+        device: u64,
+        ranking: DirRanking,
+        skip: &[PathBuf],
+        heap: &mut BinaryHeap<Reverse<(u64, PathBuf)>>,
+    ) -> u64 {
+        let listing = fs::read_dir(path);
+        perf::count_listing(&listing);
+        let Ok(entries) = listing else {
+            return 0;
+        };
+        let mut total = 0;
+        for entry in entries.flatten() {
+            let metadata = entry.metadata();
+            perf::count_metadata(&metadata);
+            let Ok(metadata) = metadata else {
+                continue;
+            };
+            if metadata.is_file() {
+                total += metadata.len();
+                continue;
+            }
+            let entry_path = entry.path();
+            let other_device = ranking.one_file_system && device_id(&metadata) != device;
+            if !metadata.is_dir() || other_device || skip.contains(&entry_path) {
+                continue;
+            }
+            let size = walk(&entry_path, depth + 1, device, ranking, skip, heap);
+            if depth < ranking.max_depth {
+                heap.push(Reverse((size, entry_path)));
+                if heap.len() > ranking.limit {
+                    heap.pop();
+                }
+            }
+            total += size;
+        }
+        total
+    }
+
+    walk(dir, 0, device, ranking, skip, &mut heap);
+    let mut largest: Vec<(PathBuf, u64)> = heap
+        .into_iter()
+        .map(|Reverse((size, path))| (path, size))
+        .collect();
+    largest.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    largest
+}
+
+/// Show the directories holding the most data under a path
+pub fn show_largest_dirs(dir: &Path, ranking: DirRanking, skip: &[PathBuf], color: bool) {
+    let largest = find_largest_dirs(dir, ranking, skip);
+    println!("Largest Directories (depth {}):", ranking.max_depth);
+    println!("{}", "─".repeat(50));
+    if largest.is_empty() {
+        println!("No directories found.");
+        return;
+    }
+    for (path, size) in largest {
+        let size = format!("{:>10}", SizeUnit::auto_format_size(size));
+        if color {
+            println!("{}  {}", size.cyan(), path.display());
+        } else {
+            println!("{}  {}", size, path.display());
+        }
+    }
+}
+
+/// Files sharing a name in different directories
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameGroup {
+    pub name: String,
+    /// Path and size of each file, by path
+    pub files: Vec<(String, u64)>,
+}
+
+impl NameGroup {
+    /// How many different sizes the copies have; 1 suggests true copies
+    pub fn distinct_sizes(&self) -> usize {
+        let mut sizes: Vec<u64> = self.files.iter().map(|(_, size)| *size).collect();
+        sizes.sort_unstable();
+        sizes.dedup();
+        sizes.len()
+    }
+}
+
+/// Group files (not directories) by name, keeping names found in more
+/// than one place, most widespread first
+pub fn find_duplicate_names(files: &[FileInfo]) -> Vec<NameGroup> {
+    let mut by_name: HashMap<&str, Vec<(String, u64)>> = HashMap::new();
+    for file in files.iter().filter(|f| !f.is_directory) {
+        by_name.entry(&file.name).or_default().push((file.path.clone(), file.size));
+    }
+    let mut groups: Vec<NameGroup> = by_name
+        .into_iter()
+        .filter(|(_, files)| files.len() > 1)
+        .map(|(name, mut files)| {
+            files.sort();
+            NameGroup {
+                name: name.to_string(),
+                files,
+            }
+        })
+        .collect();
+    groups.sort_by(|a, b| b.files.len().cmp(&a.files.len()).then_with(|| a.name.cmp(&b.name)));
+    groups
+}
+
+/// Show the `limit` most widespread file names with every path they occur at
+pub fn show_duplicate_names(files: &[FileInfo], limit: usize, color: bool) {
+    let groups = find_duplicate_names(files);
+    println!("Duplicate file names:");
+    println!("{}", "─".repeat(50));
+    if groups.is_empty() {
+        println!("Every file name is unique.");
+        return;
+    }
+    for group in groups.iter().take(limit) {
+        let sizes = match group.distinct_sizes() {
+            1 => "same size".to_string(),
+            n => format!("{} sizes", n),
+        };
+        if color {
+            println!("{} ({} copies, {})", group.name.yellow().bold(), group.files.len(), sizes);
+        } else {
+            println!("{} ({} copies, {})", group.name, group.files.len(), sizes);
+        }
+        for (path, size) in &group.files {
+            println!("  {:>10}  {}", SizeUnit::auto_format_size(*size), path);
+        }
+        println!();
+    }
+    if groups.len() > limit {
+        println!("... and {} more names", group_digits((groups.len() - limit) as u64));
+    }
+    println!(
+        "{} names appear in more than one directory",
+        group_digits(groups.len() as u64)
+    );
 }
 
+/// Names in one directory that differ only in case
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaseCollision {
+    pub dir: PathBuf,
+    /// Sorted
+    pub names: Vec<String>,
+}
+
+/// Find entries (files and directories) that would overwrite each other on
+/// a case-insensitive filesystem, by directory
+pub fn find_case_collisions(files: &[FileInfo]) -> Vec<CaseCollision> {
+    let mut by_key: HashMap<(PathBuf, String), Vec<String>> = HashMap::new();
+    for file in files {
+        let path = Path::new(&file.path);
+        let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        by_key.entry((dir, file.name.to_lowercase())).or_default().push(file.name.clone());
+    }
+    let mut collisions: Vec<CaseCollision> = by_key
+        .into_iter()
+        .filter(|(_, names)| names.len() > 1)
+        .map(|((dir, _), mut names)| {
+            names.sort();
+            CaseCollision { dir, names }
+        })
+        .collect();
+    collisions.sort_by(|a, b| a.dir.cmp(&b.dir).then_with(|| a.names.cmp(&b.names)));
+    collisions
+}
+
+/// Report case collisions; returns whether there were none
+pub fn show_case_collisions(files: &[FileInfo], color: bool) -> bool {
+    let collisions = find_case_collisions(files);
+    println!("Case collisions:");
+    println!("{}", "─".repeat(50));
+    if collisions.is_empty() {
+        println!("No names differ only in case.");
+        return true;
+    }
+    for collision in &collisions {
+        if color {
+            println!("{}: {}", collision.dir.display().to_string().blue().bold(), collision.names.join(", ").yellow());
+        } else {
+            println!("{}: {}", collision.dir.display(), collision.names.join(", "));
+        }
+    }
+    let dirs = collisions.iter().map(|c| &c.dir).collect::<HashSet<_>>().len();
+    println!(
+        "\n{} collisions in {} directories",
+        group_digits(collisions.len() as u64),
+        group_digits(dirs as u64)
+    );
+    false
+}
+
+/// A directory and what it holds directly, see [`find_busiest_dirs`];
+/// ordered by path
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DirFileCount {
+    pub path: PathBuf,
+    /// Entries directly inside the directory, subdirectories included
+    pub entries: u64,
+    /// Files (anything but directories) directly inside the directory
+    pub files: u64,
+    /// Their total size
+    pub bytes: u64,
+    /// Files anywhere below the directory
+    pub files_below: u64,
+    /// Levels below the scanned root, which is 0
+    pub depth: usize,
+}
+
+/// What [`find_busiest_dirs`] ranks directories by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DirCount {
+    /// Every direct entry: what a listing of the directory has to read
+    Entries,
+    /// Direct files: what uses up inodes and slows backups
+    Files,
+}
+
+/// Rank the directories under `dir` (itself included) by how many entries
+/// or files they hold directly, keeping the top `limit` in a bounded heap:
+/// a flat directory of millions of small files is what exhausts inodes and
+/// slows backups and listings, wherever it sits. Leaves out `skip`, and with
+/// `one_file_system` the filesystems mounted below `dir`. Ties are listed
+/// by path.
+pub fn find_busiest_dirs(
+    dir: &Path,
+    limit: usize,
+    by: DirCount,
+    one_file_system: bool,
+    skip: &[PathBuf],
+) -> Vec<DirFileCount> {
+    let Ok(device) = fs::metadata(dir).map(|m| device_id(&m)) else {
+        return Vec::new();
+    };
+    // The top of this max-heap is the directory to drop first: the lowest
+    // count, then the last path
+    let mut heap: BinaryHeap<(Reverse<u64>, DirFileCount)> = BinaryHeap::new();
+
+    /// Returns the number of files below `path`
+    fn walk(
+        path: &Path,
+        depth: usize,
+        device: u64,
+        (limit, by, one_file_system): (usize, DirCount, bool),
+        skip: &[PathBuf],
+        heap: &mut BinaryHeap<(Reverse<u64>, DirFileCount)>,
+    ) -> u64 {
+        let listing = fs::read_dir(path);
+        perf::count_listing(&listing);
+        let Ok(entries) = listing else {
+            return 0;
+        };
+        let (mut count, mut files, mut bytes, mut below) = (0, 0, 0, 0);
+        for entry in entries.flatten() {
+            count += 1;
+            let metadata = entry.metadata();
+            perf::count_metadata(&metadata);
+            let Ok(metadata) = metadata else {
+                continue;
+            };
+            if !metadata.is_dir() {
+                files += 1;
+                bytes += if metadata.is_file() { metadata.len() } else { 0 };
+                continue;
+            }
+            let entry_path = entry.path();
+            if (one_file_system && device_id(&metadata) != device) || skip.contains(&entry_path) {
+                continue;
+            }
+            below += walk(&entry_path, depth + 1, device, (limit, by, one_file_system), skip, heap);
+        }
+        let key = match by {
+            DirCount::Entries => count,
+            DirCount::Files => files,
+        };
+        if key > 0 {
+            heap.push((
+                Reverse(key),
+                DirFileCount {
+                    path: path.to_path_buf(),
+                    entries: count,
+                    files,
+                    bytes,
+                    files_below: files + below,
+                    depth,
+                },
+            ));
+            if heap.len() > limit {
+                heap.pop();
+            }
+        }
+        files + below
+    }
+
+    walk(dir, 0, device, (limit, by, one_file_system), skip, &mut heap);
+    heap.into_sorted_vec().into_iter().map(|(_, entry)| entry).collect()
+}
+
+/// Show the directories holding the most files under a path
+pub fn show_busiest_dirs(dir: &Path, limit: usize, one_file_system: bool, skip: &[PathBuf], color: bool) {
+    let busiest = find_busiest_dirs(dir, limit, DirCount::Files, one_file_system, skip);
+    println!("Directories With the Most Files:");
+    println!("{}", "─".repeat(50));
+    if busiest.is_empty() {
+        println!("No files found.");
+        return;
+    }
+    println!("{:>10}  {:>10}  {:>10}  {:>5}  Path", "Files", "Below", "Avg Size", "Depth");
+    for entry in busiest {
+        let files = format!("{:>10}", group_digits(entry.files));
+        let below = format!("{:>10}", group_digits(entry.files_below));
+        let average = format!("{:>10}", SizeUnit::auto_format_size(entry.bytes / entry.files));
+        if color {
+            println!("{}  {}  {}  {:>5}  {}", files.cyan(), below, average, entry.depth, entry.path.display());
+        } else {
+            println!("{}  {}  {}  {:>5}  {}", files, below, average, entry.depth, entry.path.display());
+        }
+    }
+}
+
+/// Width of the longest histogram bar
+const HISTOGRAM_WIDTH: usize = 30;
+
+/// A bar scaled so that `max` fills [`HISTOGRAM_WIDTH`]; any
+/// non-zero value gets at least one mark
+pub fn histogram_bar(value: u64, max: u64) -> String {
+    if value == 0 || max == 0 {
+        return String::new();
+    }
+    let width = (value as u128 * HISTOGRAM_WIDTH as u128 / max as u128) as usize;
+    "#".repeat(width.max(1))
+}
+
+/// Chart the size buckets twice, by file count and by bytes, so buckets
+/// with few but huge files stand out
+fn show_size_histograms(files: &[FileInfo], size_ranges: &[(String, std::ops::Range<u64>)], color: bool) {
+    let buckets: Vec<(&String, u64, u64)> = size_ranges
+        .iter()
+        .map(|(label, range)| {
+            let in_range = files.iter().filter(|f| !f.is_directory && range.contains(&f.size));
+            let (count, bytes) = in_range.fold((0, 0), |(count, bytes), f| (count + 1, bytes + f.size));
+            (label, count, bytes)
+        })
+        .filter(|(_, count, _)| *count > 0)
+        .collect();
+    if buckets.is_empty() {
+        return;
+    }
+    let label_width = buckets.iter().map(|(label, _, _)| label.len()).max().unwrap_or(0);
+    let max_count = buckets.iter().map(|(_, count, _)| *count).max().unwrap_or(0);
+    let max_bytes = buckets.iter().map(|(_, _, bytes)| *bytes).max().unwrap_or(0);
+
+    println!("\nSize Histogram (files):");
+    for (label, count, _) in &buckets {
+        let bar = format!("{:<width$}", histogram_bar(*count, max_count), width = HISTOGRAM_WIDTH);
+        if color {
+            println!("  {:<label_width$} |{} {}", label, bar.cyan(), count);
+        } else {
+            println!("  {:<label_width$} |{} {}", label, bar, count);
+        }
+    }
+    println!("\nSize Histogram (bytes):");
+    for (label, _, bytes) in &buckets {
+        let bar = format!("{:<width$}", histogram_bar(*bytes, max_bytes), width = HISTOGRAM_WIDTH);
+        let size = SizeUnit::auto_format_size(*bytes);
+        if color {
+            println!("  {:<label_width$} |{} {}", label, bar.green(), size);
+        } else {
+            println!("  {:<label_width$} |{} {}", label, bar, size);
+        }
+    }
+}
+
+/// Print the mean, median, p90 and p99 file size
+pub fn show_size_percentiles(quantiles: &mut SizeQuantiles, color: bool) {
+    let Some(mean) = quantiles.mean() else {
+        return;
+    };
+    if quantiles.is_exact() {
+        println!("\nSize Percentiles:");
+    } else {
+        println!("\nSize Percentiles (within {}%):", RELATIVE_ACCURACY * 100.0);
+    }
+    let stats = [
+        ("Mean", Some(mean.round() as u64)),
+        ("Median", quantiles.quantile(0.5)),
+        ("p90", quantiles.quantile(0.9)),
+        ("p99", quantiles.quantile(0.99)),
+    ];
+    for (label, size) in stats {
+        let Some(size) = size else { continue };
+        let size = SizeUnit::auto_format_size(size);
+        if color {
+            println!("  {}: {}", label.magenta(), size.green());
+        } else {
+            println!("  {}: {}", label, size);
+        }
+    }
+}
 
 pub fn show_detailed_analysis(files: &[FileInfo], color: bool) {
     let total_files = files.len();
     let total_dirs = files.iter().filter(|f| f.is_directory).count();
     let total_regular_files = total_files - total_dirs;
     let _total_size: u64 = files.iter().map(|f| f.size).sum();
-    println!("");
+    println!();
     println!("Detailed Analysis:");
     println!("{}", "-".repeat(50));
 
@@ -90,19 +1036,26 @@ pub fn show_detailed_analysis(files: &[FileInfo], color: bool) {
         );
     }
 
+    let system = UnitSystem::active();
+    let [_, kilo, mega, giga, _] = system.labels();
+    let k = system.base();
     let size_ranges = [
-        ("Empty (0 B)", 0..1),
-        ("Tiny (< 1 KB)", 1..1024),
-        ("Small (1 KB - 1 MB)", 1024..1024 * 1024),
-        ("Medium (1 MB - 100 MB)", 1024 * 1024..100 * 1024 * 1024),
-        ("Large (100 MB - 1 GB)", 100 * 1024 * 1024..1024 * 1024 * 1024),
-        ("Huge (> 1 GB)", 1024 * 1024 * 1024..u64::MAX),
+        ("Empty (0 B)".to_string(), 0..1),
+        (format!("Tiny (< 1 {})", kilo), 1..k),
+        (format!("Small (1 {} - 1 {})", kilo, mega), k..k * k),
+        (format!("Medium (1 {} - 100 {})", mega, mega), k * k..100 * k * k),
+        (format!("Large (100 {} - 1 {})", mega, giga), 100 * k * k..k * k * k),
+        (format!("Huge (> 1 {})", giga), k * k * k..u64::MAX),
     ];
+    // Directory sizes are only known with --du, so only files are bucketed
     println!("\nSize Distribution:");
     for (label, range) in &size_ranges {
-        let count = files.iter().filter(|f| range.contains(&f.size)).count();
+        let count = files
+            .iter()
+            .filter(|f| !f.is_directory && range.contains(&f.size))
+            .count();
         if count > 0 {
-            let percentage = count as f64 / total_files as f64 * 100.0;
+            let percentage = count as f64 / total_regular_files as f64 * 100.0;
             if color {
                 println!(
                     "  {}: {} files ({:.1}%)",
@@ -115,8 +1068,14 @@ pub fn show_detailed_analysis(files: &[FileInfo], color: bool) {
             }
         }
     }
+    show_size_histograms(files, &size_ranges, color);
+    let mut quantiles = SizeQuantiles::new();
+    for file in files.iter().filter(|f| !f.is_directory) {
+        quantiles.push(file.size);
+    }
+    show_size_percentiles(&mut quantiles, color);
 
-    let now = std::time::SystemTime::now();
+    let now = Clock::active().now();
     let age_ranges = [
         ("Today", 0..86400),
         ("This Week", 86400..604800),
@@ -128,22 +1087,7 @@ pub fn show_detailed_analysis(files: &[FileInfo], color: bool) {
     for (label, range) in &age_ranges {
         let count = files
             .iter()
-            .filter(|f| {
-                if let Some(modified_str) = &f.modified {
-                    if let Ok(modified_time) =
-                        chrono::DateTime::parse_from_rfc3339(&format!("{}Z", modified_str.replace(" UTC", "")))
-                    {
-                        let duration = now
-                            .duration_since(modified_time.with_timezone(&chrono::Utc).into())
-                            .unwrap_or_default();
-                        range.contains(&duration.as_secs())
-                    } else {
-                        false
-                    }
-                } else {
-                    false
-                }
-            })
+            .filter(|f| file_age_secs(f, now).is_some_and(|age| range.contains(&age)))
             .count();
 
         if count > 0 {
@@ -161,6 +1105,7 @@ pub fn show_detailed_analysis(files: &[FileInfo], color: bool) {
         }
     }
 
+    show_data_age(files, &age_ranges, now, color);
 
     if let Some(largest) = files.iter().filter(|f| !f.is_directory).max_by_key(|f| f.size) {
         if color {
@@ -234,3 +1179,227 @@ pub fn show_detailed_analysis(files: &[FileInfo], color: bool) {
         );
     }
 }
+
+/// Seconds since a file was last modified, if its timestamp is known
+pub(crate) fn file_age_secs(file: &FileInfo, now: std::time::SystemTime) -> Option<u64> {
+    let modified_str = file.modified.as_ref()?;
+    let modified_time =
+        chrono::DateTime::parse_from_rfc3339(&format!("{}Z", modified_str.replace(" UTC", ""))).ok()?;
+    let duration = now
+        .duration_since(modified_time.with_timezone(&chrono::Utc).into())
+        .unwrap_or_default();
+    Some(duration.as_secs())
+}
+
+/// Show how old the data is, weighting each file's age by its size
+fn show_data_age(
+    files: &[FileInfo],
+    age_ranges: &[(&str, std::ops::Range<u64>)],
+    now: std::time::SystemTime,
+    color: bool,
+) {
+    let regular_files: Vec<&FileInfo> = files.iter().filter(|f| !f.is_directory).collect();
+    let total_bytes: u64 = regular_files.iter().map(|f| f.size).sum();
+    if total_bytes == 0 {
+        return;
+    }
+
+    println!("\nData Age (by bytes):");
+    for (label, range) in age_ranges {
+        let bytes: u64 = regular_files
+            .iter()
+            .filter(|f| file_age_secs(f, now).is_some_and(|age| range.contains(&age)))
+            .map(|f| f.size)
+            .sum();
+
+        if bytes > 0 {
+            let percentage = bytes as f64 / total_bytes as f64 * 100.0;
+            let size = crate::types::SizeUnit::auto_format_size(bytes);
+            if color {
+                println!("  {}: {} ({:.1}%)", label.magenta(), size.cyan(), percentage);
+            } else {
+                println!("  {}: {} ({:.1}%)", label, size, percentage);
+            }
+        }
+    }
+
+    let stale_bytes: u64 = regular_files
+        .iter()
+        .filter(|f| file_age_secs(f, now).is_some_and(|age| age >= 31536000))
+        .map(|f| f.size)
+        .sum();
+    let stale_percentage = stale_bytes as f64 / total_bytes as f64 * 100.0;
+    if color {
+        println!(
+            "  {} of bytes untouched for more than a year",
+            format!("{:.1}%", stale_percentage).yellow()
+        );
+    } else {
+        println!("  {:.1}% of bytes untouched for more than a year", stale_percentage);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const BLOCK: usize = PARTIAL_HASH_BLOCK as usize;
+
+    /// Write each file and group the copies the way `--duplicates` does,
+    /// returning the groups as file names
+    fn duplicates(dir: &TempDir, files: &[(&str, Vec<u8>)]) -> Vec<Vec<String>> {
+        let mut by_size: HashMap<u64, Vec<String>> = HashMap::new();
+        for (name, contents) in files {
+            let path = dir.path().join(name);
+            fs::write(&path, contents).unwrap();
+            by_size
+                .entry(contents.len() as u64)
+                .or_default()
+                .push(path.to_string_lossy().to_string());
+        }
+        names(&group_duplicates(&by_size, &DuplicateOptions::default()))
+    }
+
+    fn names(groups: &[DuplicateGroup]) -> Vec<Vec<String>> {
+        groups
+            .iter()
+            .map(|group| {
+                group
+                    .paths
+                    .iter()
+                    .map(|p| Path::new(p).file_name().unwrap().to_string_lossy().to_string())
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn same_size_different_contents() {
+        let dir = TempDir::new().unwrap();
+        let files = [("a", b"same".to_vec()), ("b", b"same".to_vec()), ("c", b"diff".to_vec())];
+        assert_eq!(duplicates(&dir, &files), [["a", "b"]]);
+    }
+
+    #[test]
+    fn partial_hash_collision_is_settled_by_the_full_hash() {
+        let dir = TempDir::new().unwrap();
+        // Equal first and last blocks; only the middle tells them apart
+        let mut a = vec![1u8; 3 * BLOCK];
+        let mut b = a.clone();
+        a[BLOCK + 1] = 2;
+        b[BLOCK + 1] = 3;
+        let files = [("a", a), ("b", b)];
+        assert!(duplicates(&dir, &files).is_empty());
+        assert_eq!(
+            partial_hash(&dir.path().join("a")).unwrap(),
+            partial_hash(&dir.path().join("b")).unwrap()
+        );
+
+        // Only the first block equal: the prefilter already separates them
+        let mut c = vec![1u8; 3 * BLOCK];
+        *c.last_mut().unwrap() = 9;
+        fs::write(dir.path().join("c"), c).unwrap();
+        assert_ne!(
+            partial_hash(&dir.path().join("a")).unwrap(),
+            partial_hash(&dir.path().join("c")).unwrap()
+        );
+    }
+
+    #[test]
+    fn zero_length_files() {
+        let dir = TempDir::new().unwrap();
+        let files = [("a", Vec::new()), ("b", Vec::new()), ("c", b"x".to_vec())];
+        let groups = duplicates(&dir, &files);
+        assert_eq!(groups, [["a", "b"]]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn hard_links_to_one_inode() {
+        let dir = TempDir::new().unwrap();
+        let path = |name: &str| dir.path().join(name).to_string_lossy().to_string();
+        fs::write(path("a"), "same").unwrap();
+        fs::write(path("c"), "same").unwrap();
+        fs::hard_link(path("a"), path("b")).unwrap();
+        let mut by_size = HashMap::from([(4, vec![path("a"), path("b"), path("c")])]);
+
+        // a and b are one file; c is a real copy of it
+        let (_, hardlinks) = split_hardlinks(&by_size);
+        assert_eq!(hardlinks, [[path("a"), path("b")]]);
+        let groups = group_duplicates(&by_size, &DuplicateOptions::default());
+        assert_eq!(names(&groups), [["a", "c"]]);
+        assert_eq!(groups[0].wasted_bytes, 4);
+
+        // Without the copy nothing is left to report
+        by_size.get_mut(&4).unwrap().pop();
+        assert!(group_duplicates(&by_size, &DuplicateOptions::default()).is_empty());
+    }
+
+    /// Directories and their counts, as ranked by [`find_busiest_dirs`]
+    fn busiest(root: &Path, limit: usize, by: DirCount, skip: &[PathBuf]) -> Vec<(PathBuf, u64)> {
+        find_busiest_dirs(root, limit, by, true, skip)
+            .into_iter()
+            .map(|entry| {
+                let count = match by {
+                    DirCount::Entries => entry.entries,
+                    DirCount::Files => entry.files,
+                };
+                (entry.path, count)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn keeps_the_directories_with_most_entries() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        for (sub, files) in [("few", 3), ("many", 7), ("many/nested", 5), ("some", 4), ("proc", 9)] {
+            fs::create_dir_all(root.join(sub)).unwrap();
+            for i in 0..files {
+                fs::write(root.join(sub).join(format!("{}.txt", i)), b"").unwrap();
+            }
+        }
+        // Skipped directories, like the virtual ones below `/`, are not walked
+        let skip = [root.join("proc")];
+
+        // Subdirectories count as entries of their parent too; ties are
+        // listed by path whether or not the limit cuts them
+        assert_eq!(
+            busiest(root, 3, DirCount::Entries, &skip),
+            [(root.join("many"), 8), (root.join("many/nested"), 5), (root.to_path_buf(), 4)]
+        );
+        assert_eq!(
+            busiest(root, 10, DirCount::Entries, &skip)[2..],
+            [(root.to_path_buf(), 4), (root.join("some"), 4), (root.join("few"), 3)]
+        );
+        assert_eq!(busiest(root, 1, DirCount::Entries, &[]), [(root.join("proc"), 9)]);
+        assert!(busiest(root, 0, DirCount::Entries, &skip).is_empty());
+
+        // Ranked by files, the root holds none and the counts match the
+        // files written
+        let by_files = find_busiest_dirs(root, 10, DirCount::Files, true, &skip);
+        let counts: Vec<_> = by_files.iter().map(|e| (e.path.strip_prefix(root).unwrap(), e.files, e.depth)).collect();
+        assert_eq!(
+            counts,
+            [
+                (Path::new("many"), 7, 1),
+                (Path::new("many/nested"), 5, 2),
+                (Path::new("some"), 4, 1),
+                (Path::new("few"), 3, 1),
+            ]
+        );
+        assert_eq!((by_files[0].entries, by_files[0].files_below), (8, 12));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn busiest_dirs_stay_on_one_filesystem() {
+        // /dev usually has /dev/pts, /dev/shm or /dev/mqueue mounted below it
+        let root = Path::new("/dev");
+        let device = device_id(&fs::metadata(root).unwrap());
+        for entry in find_busiest_dirs(root, usize::MAX, DirCount::Entries, true, &[]) {
+            assert_eq!(device_id(&fs::metadata(&entry.path).unwrap()), device, "{}", entry.path.display());
+        }
+    }
+}
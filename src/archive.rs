@@ -0,0 +1,1146 @@
+//! Archive compression ratios (`--archives`).
+//!
+//! Sizes come from each format's own bookkeeping instead of decompressing:
+//! the central directory of a zip, the member headers of a tar, the trailer
+//! of a gzip stream and the index of an xz stream. gzip only records the
+//! uncompressed size of its last member, modulo 4 GiB, so bigger `.gz` files
+//! are understated just as with `gzip -l`. Formats that keep no such record
+//! (bzip2, zstd, 7z, rar) are left out.
+//!
+//! `--deep-archives` goes further and decompresses zip members and gzip
+//! streams, counting the files inside and opening archives within archives
+//! down to a depth limit. No stream is decompressed past [`MAX_INFLATED`],
+//! so a compression bomb costs bounded time. xz streams are still only sized
+//! from their index.
+//!
+//! `--verify-archives` reads zip, tar and gzip files to the end, checking
+//! every member against its CRC-32 and every tar header against its
+//! checksum.
+//!
+//! `--tree --expand-archives` lists the members of zip, tar and gzip files,
+//! see [`list_archive`]; the members of a tar.xz are out of reach without an
+//! xz decoder.
+
+use crate::perf::CountedFile;
+use crate::types::{FileInfo, FileKind, SizeUnit};
+use crate::utils::group_digits;
+use colored::Colorize;
+use flate2::read::{DeflateDecoder, MultiGzDecoder};
+use flate2::Crc;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// Archives saving less than this share of their contents barely compress,
+/// usually because the contents were compressed already
+pub const MIN_SAVING: f64 = 0.10;
+
+/// Tar header and data block size
+const TAR_BLOCK: u64 = 512;
+
+/// Longest zip comment, which may follow the end of central directory record
+const ZIP_MAX_COMMENT: u64 = 65535;
+
+/// Levels of archives `--deep-archives` opens by default, counting the ones
+/// in the scanned tree
+pub const DEFAULT_DEPTH: usize = 3;
+
+/// Nested zip and xz files need random access, so they are read into memory;
+/// bigger ones are counted at their own size instead
+const MAX_BUFFERED: u64 = 256 * 1024 * 1024;
+
+/// Most bytes one gzip stream or zip member is decompressed to; past this,
+/// reading fails with [`io::ErrorKind::FileTooLarge`]
+pub const MAX_INFLATED: u64 = 4 * 1024 * 1024 * 1024;
+
+/// Decompressed data, failing once it runs past [`MAX_INFLATED`]
+struct Capped<R> {
+    inner: R,
+    left: u64,
+}
+
+impl<R: Read> Read for Capped<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.left == 0 {
+            return match self.inner.read(&mut [0u8])? {
+                0 => Ok(0),
+                _ => Err(io::Error::new(
+                    io::ErrorKind::FileTooLarge,
+                    format!("decompresses to more than {}", SizeUnit::auto_format_size(MAX_INFLATED)),
+                )),
+            };
+        }
+        let max = buf.len().min(self.left.try_into().unwrap_or(usize::MAX));
+        let read = self.inner.read(&mut buf[..max])?;
+        self.left -= read as u64;
+        Ok(read)
+    }
+}
+
+/// A gzip stream with any number of members, decompressed up to the cap
+fn gunzip<R: Read>(reader: R) -> Capped<MultiGzDecoder<R>> {
+    Capped {
+        inner: MultiGzDecoder::new(reader),
+        left: MAX_INFLATED,
+    }
+}
+
+/// A raw DEFLATE stream, as stored in zip files, decompressed up to the cap
+fn inflate<R: Read>(reader: R) -> Capped<DeflateDecoder<R>> {
+    Capped {
+        inner: DeflateDecoder::new(reader),
+        left: MAX_INFLATED,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Zip,
+    Tar,
+    Gzip,
+    TarGzip,
+    Xz,
+    TarXz,
+}
+
+impl ArchiveFormat {
+    pub fn name(self) -> &'static str {
+        match self {
+            ArchiveFormat::Zip => "zip",
+            ArchiveFormat::Tar => "tar",
+            ArchiveFormat::Gzip => "gzip",
+            ArchiveFormat::TarGzip => "tar.gz",
+            ArchiveFormat::Xz => "xz",
+            ArchiveFormat::TarXz => "tar.xz",
+        }
+    }
+
+    /// Whether the format compresses its contents at all
+    pub fn compresses(self) -> bool {
+        self != ArchiveFormat::Tar
+    }
+
+    /// The format of a file starting with `head` (at least the first 512
+    /// bytes, when the file has them)
+    pub fn detect(path: &Path, head: &[u8]) -> Option<ArchiveFormat> {
+        let name = path.file_name()?.to_string_lossy().to_lowercase();
+        if head.starts_with(b"PK\x03\x04") || head.starts_with(b"PK\x05\x06") {
+            Some(ArchiveFormat::Zip)
+        } else if head.starts_with(b"\x1f\x8b") {
+            let tar = name.ends_with(".tar.gz") || name.ends_with(".tgz");
+            Some(if tar { ArchiveFormat::TarGzip } else { ArchiveFormat::Gzip })
+        } else if head.starts_with(b"\xfd7zXZ\0") {
+            let tar = name.ends_with(".tar.xz") || name.ends_with(".txz");
+            Some(if tar { ArchiveFormat::TarXz } else { ArchiveFormat::Xz })
+        } else if head.get(257..262) == Some(b"ustar") {
+            Some(ArchiveFormat::Tar)
+        } else {
+            None
+        }
+    }
+}
+
+/// What an archive holds, as recorded in its own headers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchiveSizes {
+    pub format: ArchiveFormat,
+    /// Size of the archive file itself
+    pub compressed: u64,
+    /// Total size of the contents once extracted
+    pub uncompressed: u64,
+    /// Number of members, for formats that list them (zip and tar)
+    pub entries: Option<u64>,
+    /// The file ends before its headers say it should, or they stop making
+    /// sense part way; the sizes cover what could be read
+    pub truncated: bool,
+}
+
+impl ArchiveSizes {
+    /// Archive size as a fraction of its contents; `None` when empty
+    pub fn ratio(&self) -> Option<f64> {
+        (self.uncompressed > 0).then(|| self.compressed as f64 / self.uncompressed as f64)
+    }
+
+    /// A compressed format that saves less than [`MIN_SAVING`]
+    pub fn barely_compresses(&self) -> bool {
+        self.format.compresses() && !self.truncated && self.ratio().is_some_and(|r| r > 1.0 - MIN_SAVING)
+    }
+}
+
+/// Little-endian integer from up to 8 bytes
+fn le(bytes: &[u8]) -> u64 {
+    bytes.iter().rev().fold(0, |n, &b| n << 8 | b as u64)
+}
+
+fn read_at(file: &mut (impl Read + Seek), offset: u64, len: u64) -> io::Result<Vec<u8>> {
+    file.seek(SeekFrom::Start(offset))?;
+    let mut bytes = Vec::new();
+    file.take(len).read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// A member of a zip, from its central directory record
+struct ZipEntry {
+    name: String,
+    flags: u64,
+    method: u64,
+    crc: u32,
+    compressed: u64,
+    uncompressed: u64,
+    local_offset: u64,
+}
+
+/// The members of a zip, from its central directory (zip64 included), and
+/// whether the listing was cut short
+fn zip_entries(file: &mut (impl Read + Seek), size: u64) -> io::Result<(Vec<ZipEntry>, bool)> {
+    let tail_start = size.saturating_sub(22 + ZIP_MAX_COMMENT);
+    let tail = read_at(file, tail_start, size - tail_start)?;
+    let records: Vec<usize> = (0..tail.len().saturating_sub(21))
+        .rev()
+        .filter(|&at| tail[at..].starts_with(b"PK\x05\x06"))
+        .collect();
+    // The record whose comment runs to the end of the file, in case the
+    // comment holds the signature too; then any, for trailing junk
+    let exact = records.iter().find(|&&at| at + 22 + le(&tail[at + 20..at + 22]) as usize == tail.len());
+    let Some(&at) = exact.or(records.first()) else {
+        return Ok((Vec::new(), true));
+    };
+    let eocd = &tail[at..];
+    let (mut entries, mut cd_size, mut cd_offset) = (le(&eocd[10..12]), le(&eocd[12..16]), le(&eocd[16..20]));
+    if entries == 0xffff || cd_size == 0xffff_ffff || cd_offset == 0xffff_ffff {
+        let locator = at.checked_sub(20).map(|start| &tail[start..at]);
+        let Some(locator) = locator.filter(|l| l.starts_with(b"PK\x06\x07")) else {
+            return Ok((Vec::new(), true));
+        };
+        let eocd64 = read_at(file, le(&locator[8..16]), 56)?;
+        if eocd64.len() < 56 || !eocd64.starts_with(b"PK\x06\x06") {
+            return Ok((Vec::new(), true));
+        }
+        (entries, cd_size, cd_offset) = (le(&eocd64[32..40]), le(&eocd64[40..48]), le(&eocd64[48..56]));
+    }
+    if cd_offset.saturating_add(cd_size) > tail_start + at as u64 {
+        return Ok((Vec::new(), true));
+    }
+
+    let directory = read_at(file, cd_offset, cd_size)?;
+    let (mut found, mut pos) = (Vec::new(), 0usize);
+    while (found.len() as u64) < entries {
+        let Some(header) = directory.get(pos..pos + 46).filter(|h| h.starts_with(b"PK\x01\x02")) else {
+            return Ok((found, true));
+        };
+        let mut compressed_size = le(&header[20..24]);
+        let mut uncompressed_size = le(&header[24..28]);
+        let mut local_offset = le(&header[42..46]);
+        let (name_len, extra_len) = (le(&header[28..30]) as usize, le(&header[30..32]) as usize);
+        let comment_len = le(&header[32..34]) as usize;
+        let extra_start = pos + 46 + name_len;
+        let Some(mut extra) = directory.get(extra_start..extra_start + extra_len) else {
+            return Ok((found, true));
+        };
+        // The zip64 extra field holds, in order, whichever sizes overflowed
+        while extra.len() >= 4 {
+            let (id, len) = (le(&extra[0..2]), le(&extra[2..4]) as usize);
+            let data = extra.get(4..4 + len).unwrap_or_default();
+            if id == 1 {
+                let mut values = data.chunks_exact(8).map(le);
+                for field in [&mut uncompressed_size, &mut compressed_size, &mut local_offset] {
+                    if *field == 0xffff_ffff {
+                        *field = values.next().unwrap_or(*field);
+                    }
+                }
+            }
+            extra = extra.get(4 + len..).unwrap_or_default();
+        }
+        if local_offset.saturating_add(compressed_size) > cd_offset {
+            return Ok((found, true));
+        }
+        found.push(ZipEntry {
+            name: String::from_utf8_lossy(&directory[pos + 46..extra_start]).to_string(),
+            flags: le(&header[8..10]),
+            method: le(&header[10..12]),
+            crc: le(&header[16..20]) as u32,
+            compressed: compressed_size,
+            uncompressed: uncompressed_size,
+            local_offset,
+        });
+        pos = extra_start + extra_len + comment_len;
+    }
+    Ok((found, false))
+}
+
+/// Uncompressed total, entry count and truncation of a zip
+fn zip_contents(file: &mut (impl Read + Seek), size: u64) -> io::Result<(u64, u64, bool)> {
+    let (entries, truncated) = zip_entries(file, size)?;
+    // Sizes come from the file itself and may claim anything
+    let uncompressed = entries.iter().map(|entry| entry.uncompressed).fold(0u64, u64::saturating_add);
+    Ok((uncompressed, entries.len() as u64, truncated))
+}
+
+/// The size field of a tar header: octal, or base-256 for large files
+fn tar_size(field: &[u8]) -> Option<u64> {
+    if field[0] & 0x80 != 0 {
+        return Some(field[1..].iter().fold(0, |n, &b| n << 8 | b as u64));
+    }
+    let digits = std::str::from_utf8(field).ok()?.trim_matches(|c| c == '\0' || c == ' ');
+    if digits.is_empty() {
+        return Some(0);
+    }
+    u64::from_str_radix(digits, 8).ok()
+}
+
+/// Whether a tar header's checksum matches its bytes
+fn tar_checksum_ok(header: &[u8]) -> bool {
+    let sum: u64 = header
+        .iter()
+        .enumerate()
+        .map(|(i, &b)| if (148..156).contains(&i) { b' ' as u64 } else { b as u64 })
+        .sum();
+    tar_size(&header[148..156]) == Some(sum)
+}
+
+/// A member of a tar, from its header
+struct TarMember {
+    name: String,
+    kind: u8,
+    size: u64,
+}
+
+impl TarMember {
+    fn is_file(&self) -> bool {
+        matches!(self.kind, b'0' | b'\0' | b'7')
+    }
+}
+
+/// Walk the members of a tar, handing each with its data to `visit`, which
+/// may read as much of the data as it likes. Returns the uncompressed total,
+/// entry count and truncation, like [`tar_contents`].
+fn walk_tar(reader: &mut dyn Read, visit: &mut dyn FnMut(&TarMember, &mut dyn Read)) -> io::Result<(u64, u64, bool)> {
+    let (mut uncompressed, mut entries) = (0u64, 0u64);
+    let mut header = [0u8; TAR_BLOCK as usize];
+    loop {
+        let mut read = 0;
+        while read < header.len() {
+            match reader.read(&mut header[read..])? {
+                0 => return Ok((uncompressed, entries, true)),
+                n => read += n,
+            }
+        }
+        if header.iter().all(|&b| b == 0) {
+            return Ok((uncompressed, entries, false));
+        }
+        let Some(size) = tar_size(&header[124..136]).filter(|_| tar_checksum_ok(&header)) else {
+            return Ok((uncompressed, entries, true));
+        };
+        // Extended headers and GNU long names describe the next member
+        if !matches!(header[156], b'x' | b'g' | b'L' | b'K') {
+            uncompressed = uncompressed.saturating_add(size);
+            entries += 1;
+            let name = |field: &[u8]| String::from_utf8_lossy(field.split(|&b| b == 0).next().unwrap_or_default()).to_string();
+            let (prefix, name) = (name(&header[345..500]), name(&header[..100]));
+            let member = TarMember {
+                name: if prefix.is_empty() { name } else { format!("{}/{}", prefix, name) },
+                kind: header[156],
+                size,
+            };
+            let mut data = reader.take(size);
+            visit(&member, &mut data);
+            if io::copy(&mut data, &mut io::sink())? < data.limit() {
+                return Ok((uncompressed, entries, true));
+            }
+        } else if io::copy(&mut reader.take(size), &mut io::sink())? < size {
+            return Ok((uncompressed, entries, true));
+        }
+        let padding = size.div_ceil(TAR_BLOCK) * TAR_BLOCK - size;
+        if io::copy(&mut reader.take(padding), &mut io::sink())? < padding {
+            return Ok((uncompressed, entries, true));
+        }
+    }
+}
+
+/// Uncompressed total, entry count and truncation of a tar, from its member
+/// headers; `reader` is positioned at the start of the archive
+fn tar_contents(reader: &mut impl Read) -> io::Result<(u64, u64, bool)> {
+    walk_tar(reader, &mut |_, _| {})
+}
+
+/// The variable-length integers of the xz index
+fn xz_varint(bytes: &[u8], pos: &mut usize) -> Option<u64> {
+    let mut value = 0u64;
+    for shift in (0..63).step_by(7) {
+        let byte = *bytes.get(*pos)?;
+        *pos += 1;
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+/// Uncompressed total and truncation of an xz file, from the index of its
+/// last stream
+fn xz_contents(file: &mut (impl Read + Seek), size: u64) -> io::Result<(u64, bool)> {
+    // Stream padding: zeros, in multiples of four bytes
+    let tail_start = size.saturating_sub(4096);
+    let tail = read_at(file, tail_start, size - tail_start)?;
+    let padding = tail.iter().rev().take_while(|&&b| b == 0).count() / 4 * 4;
+    let end = tail.len() - padding;
+    let Some(footer) = end.checked_sub(12).map(|start| &tail[start..end]).filter(|f| f.ends_with(b"YZ")) else {
+        return Ok((0, true));
+    };
+    let index_size = (le(&footer[4..8]) + 1) * 4;
+    let Some(index_start) = (tail_start + end as u64).checked_sub(12 + index_size) else {
+        return Ok((0, true));
+    };
+    let index = read_at(file, index_start, index_size)?;
+    let mut pos = 1;
+    let records = match (index.first(), xz_varint(&index, &mut pos)) {
+        (Some(0), Some(records)) => records,
+        _ => return Ok((0, true)),
+    };
+    let mut uncompressed = 0u64;
+    for _ in 0..records {
+        let sizes = xz_varint(&index, &mut pos).and_then(|_| xz_varint(&index, &mut pos));
+        let Some(size) = sizes else {
+            return Ok((uncompressed, true));
+        };
+        uncompressed = uncompressed.saturating_add(size);
+    }
+    Ok((uncompressed, false))
+}
+
+/// The sizes recorded in the archive at `path`; `None` if it is not a zip,
+/// tar, gzip or xz file
+pub fn read_archive(path: &Path) -> io::Result<Option<ArchiveSizes>> {
+    let mut file = CountedFile::open(path)?;
+    let size = file.metadata()?.len();
+    let mut head = Vec::new();
+    (&mut file).take(TAR_BLOCK).read_to_end(&mut head)?;
+    let Some(format) = ArchiveFormat::detect(path, &head) else {
+        return Ok(None);
+    };
+
+    let (uncompressed, entries, truncated) = match format {
+        ArchiveFormat::Zip => {
+            let (uncompressed, entries, truncated) = zip_contents(&mut file, size)?;
+            (uncompressed, Some(entries), truncated)
+        }
+        ArchiveFormat::Tar => {
+            file.seek(SeekFrom::Start(0))?;
+            let (uncompressed, entries, truncated) = tar_contents(&mut io::BufReader::new(&mut file))?;
+            (uncompressed, Some(entries), truncated)
+        }
+        ArchiveFormat::Gzip | ArchiveFormat::TarGzip => {
+            // A 10-byte header and the 8-byte CRC and size trailer at least
+            if size < 18 {
+                (0, None, true)
+            } else {
+                (le(&read_at(&mut file, size - 4, 4)?), None, false)
+            }
+        }
+        ArchiveFormat::Xz | ArchiveFormat::TarXz => {
+            let (uncompressed, truncated) = xz_contents(&mut file, size)?;
+            (uncompressed, None, truncated)
+        }
+    };
+    Ok(Some(ArchiveSizes {
+        format,
+        compressed: size,
+        uncompressed,
+        entries,
+        truncated,
+    }))
+}
+
+/// A file or directory inside an archive
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchiveMember {
+    /// Path inside the archive, with `/` between components
+    pub path: String,
+    pub is_dir: bool,
+    /// Uncompressed size
+    pub size: u64,
+}
+
+/// The members of an archive, in the order it stores them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchiveListing {
+    pub format: ArchiveFormat,
+    /// Empty for a tar.xz, whose members are not read
+    pub members: Vec<ArchiveMember>,
+    /// The archive ends early or fails to decompress; `members` holds the
+    /// ones read before that
+    pub truncated: bool,
+}
+
+/// The members of the archive at `path`; `None` if it is not a zip, tar,
+/// gzip or xz file. A gzip or xz stream holds one member, named after the
+/// file without its suffix.
+pub fn list_archive(path: &Path) -> io::Result<Option<ArchiveListing>> {
+    let mut file = CountedFile::open(path)?;
+    let size = file.metadata()?.len();
+    let head = read_head(&mut file)?;
+    let Some(format) = ArchiveFormat::detect(path, &head) else {
+        return Ok(None);
+    };
+    file.seek(SeekFrom::Start(0))?;
+
+    let mut members = Vec::new();
+    let truncated = match format {
+        ArchiveFormat::Zip => {
+            let (entries, truncated) = zip_entries(&mut file, size)?;
+            members.extend(entries.into_iter().map(|entry| ArchiveMember {
+                is_dir: entry.name.ends_with('/'),
+                path: entry.name,
+                size: entry.uncompressed,
+            }));
+            truncated
+        }
+        ArchiveFormat::Tar | ArchiveFormat::TarGzip => {
+            let reader = io::BufReader::new(file);
+            let mut reader: Box<dyn Read> = if format == ArchiveFormat::Tar {
+                Box::new(reader)
+            } else {
+                Box::new(gunzip(reader))
+            };
+            let mut visit = |member: &TarMember, _: &mut dyn Read| {
+                members.push(ArchiveMember {
+                    path: member.name.clone(),
+                    is_dir: member.kind == b'5',
+                    size: member.size,
+                })
+            };
+            // Decompression errors cut the listing short like a truncated tar
+            walk_tar(&mut reader, &mut visit).map_or(true, |(_, _, truncated)| truncated)
+        }
+        ArchiveFormat::Gzip | ArchiveFormat::Xz => {
+            let sizes = read_archive(path)?.expect("format detected above");
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            members.push(ArchiveMember {
+                path: decompressed_name(&name),
+                is_dir: false,
+                size: sizes.uncompressed,
+            });
+            sizes.truncated
+        }
+        ArchiveFormat::TarXz => false,
+    };
+    Ok(Some(ArchiveListing {
+        format,
+        members,
+        truncated,
+    }))
+}
+
+/// Archive contents counted through nested archives (`--deep-archives`)
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct DeepContents {
+    /// Archives in the scanned tree
+    pub archives: u64,
+    /// Their size on disk
+    pub compressed: u64,
+    /// Archives found inside them and opened, at any depth
+    pub nested: u64,
+    /// Files inside, apart from the archives that were opened; an xz stream
+    /// counts as one file
+    pub files: u64,
+    /// Uncompressed size of those files
+    pub bytes: u64,
+    /// Nested archives left closed, past the depth limit or too big to
+    /// read into memory; counted as files at their own size
+    pub closed: u64,
+    /// Archives, at any depth, that end early or fail to decompress
+    pub truncated: u64,
+    /// Streams, at any depth, cut off at [`MAX_INFLATED`]; what they held
+    /// up to there is counted
+    pub oversized: u64,
+}
+
+impl DeepContents {
+    fn add(&mut self, other: &DeepContents) {
+        self.archives += other.archives;
+        self.compressed = self.compressed.saturating_add(other.compressed);
+        self.nested += other.nested;
+        self.files += other.files;
+        self.bytes = self.bytes.saturating_add(other.bytes);
+        self.closed += other.closed;
+        self.truncated += other.truncated;
+        self.oversized += other.oversized;
+    }
+
+    /// `total`, the size of a tree holding these archives, with the archives
+    /// replaced by their contents
+    pub fn expanded(&self, total: u64) -> u64 {
+        total.saturating_sub(self.compressed).saturating_add(self.bytes)
+    }
+}
+
+/// One file of `size` bytes, not opened
+fn leaf(totals: &mut DeepContents, size: u64) {
+    totals.files += 1;
+    totals.bytes = totals.bytes.saturating_add(size);
+}
+
+/// `name` without a compression suffix, to recognize the archive inside
+fn decompressed_name(name: &str) -> String {
+    let lower = name.to_lowercase();
+    if lower.ends_with(".tgz") || lower.ends_with(".txz") {
+        format!("{}.tar", &name[..name.len() - 4])
+    } else if lower.ends_with(".gz") || lower.ends_with(".xz") {
+        name[..name.len() - 3].to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+fn read_head(reader: &mut dyn Read) -> io::Result<Vec<u8>> {
+    let mut head = Vec::new();
+    reader.take(TAR_BLOCK).read_to_end(&mut head)?;
+    Ok(head)
+}
+
+struct DeepWalk {
+    max_depth: usize,
+    totals: DeepContents,
+}
+
+impl DeepWalk {
+    /// Count a member of `size` uncompressed bytes inside an archive at
+    /// `depth`, opening it if it is an archive itself and the limit allows.
+    /// A `None` size is learned by reading `data` to the end.
+    fn member(&mut self, name: &str, size: Option<u64>, data: &mut dyn Read, depth: usize) {
+        let head = match read_head(data) {
+            Ok(head) => head,
+            Err(e) => {
+                self.failed(&e);
+                return leaf(&mut self.totals, size.unwrap_or(0));
+            }
+        };
+        let Some(format) = ArchiveFormat::detect(Path::new(name), &head) else {
+            let size = self.rest_size(size, &head, data);
+            return leaf(&mut self.totals, size);
+        };
+        let random_access = matches!(format, ArchiveFormat::Zip | ArchiveFormat::Xz | ArchiveFormat::TarXz);
+        if depth >= self.max_depth || (random_access && size.is_some_and(|size| size > MAX_BUFFERED)) {
+            self.totals.closed += 1;
+            let size = self.rest_size(size, &head, data);
+            return leaf(&mut self.totals, size);
+        }
+        self.totals.nested += 1;
+        if let Err(e) = self.open(format, name, &mut (&head[..]).chain(data), depth + 1) {
+            self.failed(&e);
+        }
+    }
+
+    /// Count an archive that could not be read to the end
+    fn failed(&mut self, error: &io::Error) {
+        if error.kind() == io::ErrorKind::FileTooLarge {
+            self.totals.oversized += 1;
+        } else {
+            self.totals.truncated += 1;
+        }
+    }
+
+    /// `size`, or the bytes in `head` and left in `data` when it is unknown
+    fn rest_size(&mut self, size: Option<u64>, head: &[u8], data: &mut dyn Read) -> u64 {
+        if let Some(size) = size {
+            return size;
+        }
+        match io::copy(data, &mut io::sink()) {
+            Ok(rest) => head.len() as u64 + rest,
+            Err(e) if e.kind() == io::ErrorKind::FileTooLarge => {
+                self.totals.oversized += 1;
+                MAX_INFLATED
+            }
+            Err(e) => {
+                self.failed(&e);
+                head.len() as u64
+            }
+        }
+    }
+
+    /// Count the contents of an archive at `depth`, read from the start
+    fn open(&mut self, format: ArchiveFormat, name: &str, reader: &mut dyn Read, depth: usize) -> io::Result<()> {
+        match format {
+            ArchiveFormat::Tar => {
+                let (_, _, truncated) = walk_tar(reader, &mut |member, data| {
+                    if member.is_file() {
+                        self.member(&member.name, Some(member.size), data, depth);
+                    }
+                })?;
+                self.totals.truncated += truncated as u64;
+            }
+            ArchiveFormat::Gzip | ArchiveFormat::TarGzip => {
+                let mut stream = gunzip(reader);
+                let name = decompressed_name(name);
+                let head = read_head(&mut stream)?;
+                let mut inner = (&head[..]).chain(stream);
+                match ArchiveFormat::detect(Path::new(&name), &head) {
+                    // The tar of a .tar.gz is a layer of the same archive,
+                    // not an archive of its own
+                    Some(ArchiveFormat::Tar) => self.open(ArchiveFormat::Tar, &name, &mut inner, depth)?,
+                    // Anything else is its one member, so gzip inside gzip
+                    // counts towards the depth limit
+                    _ => self.member(&name, None, &mut inner, depth),
+                }
+            }
+            ArchiveFormat::Zip | ArchiveFormat::Xz | ArchiveFormat::TarXz => {
+                let mut bytes = Vec::new();
+                reader.take(MAX_BUFFERED + 1).read_to_end(&mut bytes)?;
+                if bytes.len() as u64 > MAX_BUFFERED {
+                    self.totals.closed += 1;
+                    let size = bytes.len() as u64 + io::copy(reader, &mut io::sink())?;
+                    leaf(&mut self.totals, size);
+                    return Ok(());
+                }
+                let size = bytes.len() as u64;
+                self.open_seekable(format, &mut io::Cursor::new(bytes), size, depth)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Count the contents of a zip or xz file at `depth`
+    fn open_seekable(&mut self, format: ArchiveFormat, file: &mut (impl Read + Seek), size: u64, depth: usize) -> io::Result<()> {
+        if format != ArchiveFormat::Zip {
+            let (uncompressed, truncated) = xz_contents(file, size)?;
+            leaf(&mut self.totals, uncompressed);
+            self.totals.truncated += truncated as u64;
+            return Ok(());
+        }
+
+        let (entries, truncated) = zip_entries(file, size)?;
+        self.totals.truncated += truncated as u64;
+        for entry in entries.iter().filter(|entry| !entry.name.ends_with('/')) {
+            // Encrypted, or compressed with something other than deflate
+            if entry.flags & 1 != 0 || !matches!(entry.method, 0 | 8) {
+                leaf(&mut self.totals, entry.uncompressed);
+                continue;
+            }
+            let local = read_at(file, entry.local_offset, 30)?;
+            if local.len() < 30 || !local.starts_with(b"PK\x03\x04") {
+                self.totals.truncated += 1;
+                leaf(&mut self.totals, entry.uncompressed);
+                continue;
+            }
+            file.seek(SeekFrom::Start(entry.local_offset + 30 + le(&local[26..28]) + le(&local[28..30])))?;
+            let data = file.by_ref().take(entry.compressed);
+            if entry.method == 0 {
+                self.member(&entry.name, Some(entry.uncompressed), &mut { data }, depth);
+            } else {
+                self.member(&entry.name, Some(entry.uncompressed), &mut inflate(data), depth);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Count what the archive at `path` holds, opening archives inside it down
+/// to `max_depth` levels; `None` if it is not a zip, tar, gzip or xz file
+pub fn deep_contents(path: &Path, max_depth: usize) -> io::Result<Option<DeepContents>> {
+    let mut file = CountedFile::open(path)?;
+    let size = file.metadata()?.len();
+    let head = read_head(&mut file)?;
+    let Some(format) = ArchiveFormat::detect(path, &head) else {
+        return Ok(None);
+    };
+    let mut walk = DeepWalk {
+        max_depth,
+        totals: DeepContents {
+            archives: 1,
+            compressed: size,
+            ..DeepContents::default()
+        },
+    };
+    file.seek(SeekFrom::Start(0))?;
+    let name = path.to_string_lossy();
+    let opened = match format {
+        ArchiveFormat::Zip | ArchiveFormat::Xz | ArchiveFormat::TarXz => walk.open_seekable(format, &mut file, size, 1),
+        _ => walk.open(format, &name, &mut io::BufReader::new(file), 1),
+    };
+    if let Err(e) = opened {
+        walk.failed(&e);
+    }
+    Ok(Some(walk.totals))
+}
+
+/// [`deep_contents`] of every archive among the regular files in `files`;
+/// unreadable files are left out
+pub fn deep_totals(files: &[FileInfo], max_depth: usize) -> DeepContents {
+    let mut totals = DeepContents::default();
+    for file in files.iter().filter(|f| f.kind == FileKind::File) {
+        if let Ok(Some(contents)) = deep_contents(Path::new(&file.path), max_depth) {
+            totals.add(&contents);
+        }
+    }
+    totals
+}
+
+/// The lines `-p --deep-archives` adds below the total size: what the
+/// archives hold and `total` with them expanded
+pub fn show_deep_contents(contents: &DeepContents, total: u64, max_depth: usize, color: bool) {
+    if contents.archives == 0 {
+        println!("Archive Contents: no zip, tar, gzip or xz archives");
+        return;
+    }
+    let archives = if contents.nested > 0 {
+        format!("{} archives ({} nested)", group_digits(contents.archives), group_digits(contents.nested))
+    } else {
+        format!("{} archives", group_digits(contents.archives))
+    };
+    let files = format!("{} files", group_digits(contents.files));
+    let bytes = SizeUnit::auto_format_size(contents.bytes);
+    let expanded = SizeUnit::auto_format_size(contents.expanded(total));
+    if color {
+        println!("Archive Contents: {} holding {}, {}", archives.cyan(), files.yellow(), bytes.green());
+        println!("Total Size (archives expanded): {}", expanded.green().bold());
+    } else {
+        println!("Archive Contents: {} holding {}, {}", archives, files, bytes);
+        println!("Total Size (archives expanded): {}", expanded);
+    }
+    if contents.closed > 0 {
+        println!(
+            "  {} nested archives past depth {} or too big to open, counted as is",
+            group_digits(contents.closed),
+            max_depth
+        );
+    }
+    if contents.oversized > 0 {
+        println!(
+            "  {} streams decompress to more than {}, counted up to there",
+            group_digits(contents.oversized),
+            SizeUnit::auto_format_size(MAX_INFLATED)
+        );
+    }
+    if contents.truncated > 0 {
+        let line = format!("  {} archives end early or fail to decompress", group_digits(contents.truncated));
+        if color {
+            println!("{}", line.red());
+        } else {
+            println!("{}", line);
+        }
+    }
+}
+
+/// Problems shown per damaged archive before the rest are summed up
+const MAX_PROBLEMS_SHOWN: usize = 5;
+
+/// What reading an archive to the end found (`--verify-archives`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchiveCheck {
+    pub format: ArchiveFormat,
+    /// Members read and checked
+    pub checked: u64,
+    /// Zip members that are encrypted or use a compression method other
+    /// than deflate, so their contents could not be checked
+    pub unchecked: u64,
+    /// What is wrong, one line each; empty for a sound archive
+    pub problems: Vec<String>,
+}
+
+/// `error` as a problem line, naming truncation plainly
+fn describe_error(error: &io::Error) -> String {
+    match error.kind() {
+        io::ErrorKind::UnexpectedEof => "compressed data ends early (truncated)".to_string(),
+        io::ErrorKind::InvalidData | io::ErrorKind::InvalidInput => format!("corrupt data: {}", error),
+        io::ErrorKind::FileTooLarge => format!("not checked past {} ({})", SizeUnit::auto_format_size(MAX_INFLATED), error),
+        _ => error.to_string(),
+    }
+}
+
+/// Read `data` to the end, returning its CRC-32 and length
+fn checksum(data: &mut dyn Read) -> io::Result<(u32, u64)> {
+    let (mut crc, mut length) = (Crc::new(), 0u64);
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let read = data.read(&mut buffer)?;
+        if read == 0 {
+            return Ok((crc.sum(), length));
+        }
+        crc.update(&buffer[..read]);
+        length += read as u64;
+    }
+}
+
+fn verify_zip(file: &mut (impl Read + Seek), size: u64, check: &mut ArchiveCheck) -> io::Result<()> {
+    let (entries, truncated) = zip_entries(file, size)?;
+    if truncated {
+        check.problems.push("central directory missing or cut short (truncated)".to_string());
+    }
+    for entry in &entries {
+        if entry.flags & 1 != 0 || !matches!(entry.method, 0 | 8) {
+            check.unchecked += 1;
+            continue;
+        }
+        let local = read_at(file, entry.local_offset, 30)?;
+        if local.len() < 30 || !local.starts_with(b"PK\x03\x04") {
+            check.problems.push(format!("{}: local header missing", entry.name));
+            continue;
+        }
+        file.seek(SeekFrom::Start(entry.local_offset + 30 + le(&local[26..28]) + le(&local[28..30])))?;
+        let data = file.by_ref().take(entry.compressed);
+        let result = if entry.method == 0 {
+            checksum(&mut { data })
+        } else {
+            checksum(&mut inflate(data))
+        };
+        check.checked += 1;
+        match result {
+            Ok((_, length)) if length != entry.uncompressed => check.problems.push(format!(
+                "{}: {} bytes instead of {}",
+                entry.name,
+                group_digits(length),
+                group_digits(entry.uncompressed)
+            )),
+            Ok((crc, _)) if crc != entry.crc => check.problems.push(format!("{}: CRC-32 mismatch", entry.name)),
+            Ok(_) => {}
+            Err(e) => check.problems.push(format!("{}: {}", entry.name, describe_error(&e))),
+        }
+    }
+    Ok(())
+}
+
+/// Check the tar in `reader`, reading each member to the end
+fn verify_tar(reader: &mut dyn Read, check: &mut ArchiveCheck) -> io::Result<()> {
+    let (_, entries, truncated) = walk_tar(reader, &mut |_, _| {})?;
+    check.checked += entries;
+    if truncated {
+        check.problems.push(format!(
+            "ends early or has a damaged header after {} members (truncated)",
+            group_digits(entries)
+        ));
+    }
+    Ok(())
+}
+
+/// Read the archive at `path` to the end and check it; `None` if it is not
+/// a zip, tar or gzip file
+pub fn verify_archive(path: &Path) -> io::Result<Option<ArchiveCheck>> {
+    let mut file = CountedFile::open(path)?;
+    let size = file.metadata()?.len();
+    let head = read_head(&mut file)?;
+    let format = match ArchiveFormat::detect(path, &head) {
+        Some(ArchiveFormat::Xz | ArchiveFormat::TarXz) | None => return Ok(None),
+        Some(format) => format,
+    };
+    let mut check = ArchiveCheck {
+        format,
+        checked: 0,
+        unchecked: 0,
+        problems: Vec::new(),
+    };
+    file.seek(SeekFrom::Start(0))?;
+    match format {
+        ArchiveFormat::Zip => verify_zip(&mut file, size, &mut check)?,
+        ArchiveFormat::Tar => verify_tar(&mut io::BufReader::new(file), &mut check)?,
+        _ => {
+            let mut stream = gunzip(file);
+            let name = decompressed_name(&path.to_string_lossy());
+            // The tar inside, then whatever follows its end marker, so the
+            // gzip trailer is reached and checked too
+            let result = read_head(&mut stream).and_then(|head| {
+                let mut data = (&head[..]).chain(&mut stream);
+                if ArchiveFormat::detect(Path::new(&name), &head) == Some(ArchiveFormat::Tar) {
+                    verify_tar(&mut data, &mut check)?;
+                } else {
+                    check.checked += 1;
+                }
+                io::copy(&mut data, &mut io::sink())
+            });
+            if let Err(e) = result {
+                check.problems.push(describe_error(&e));
+            }
+        }
+    }
+    Ok(Some(check))
+}
+
+/// Verify every zip, tar and gzip file in `files` and list the damaged ones;
+/// returns whether all are sound. Unreadable files are skipped.
+pub fn show_archive_verification(files: &[FileInfo], color: bool) -> bool {
+    let mut files: Vec<&FileInfo> = files.iter().filter(|f| f.kind == FileKind::File).collect();
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    println!("Archive Verification:");
+    println!("{}", "─".repeat(50));
+    let (mut archives, mut bytes, mut damaged, mut unchecked) = (0u64, 0u64, 0u64, 0u64);
+    for file in files {
+        let Ok(Some(check)) = verify_archive(Path::new(&file.path)) else {
+            continue;
+        };
+        archives += 1;
+        bytes += file.size;
+        unchecked += check.unchecked;
+        if check.problems.is_empty() {
+            continue;
+        }
+        damaged += 1;
+        if color {
+            println!("{}", file.path.red().bold());
+        } else {
+            println!("{}", file.path);
+        }
+        for problem in check.problems.iter().take(MAX_PROBLEMS_SHOWN) {
+            println!("  {}", problem);
+        }
+        if check.problems.len() > MAX_PROBLEMS_SHOWN {
+            println!("  ... and {} more problems", group_digits((check.problems.len() - MAX_PROBLEMS_SHOWN) as u64));
+        }
+    }
+
+    if archives == 0 {
+        println!("No zip, tar or gzip archives found.");
+        return true;
+    }
+    if damaged == 0 {
+        let line = format!("All {} archives ({}) are intact.", group_digits(archives), SizeUnit::auto_format_size(bytes));
+        if color {
+            println!("{}", line.green());
+        } else {
+            println!("{}", line);
+        }
+    } else {
+        println!(
+            "\n{} of {} archives ({}) are damaged",
+            group_digits(damaged),
+            group_digits(archives),
+            SizeUnit::auto_format_size(bytes)
+        );
+    }
+    if unchecked > 0 {
+        println!(
+            "{} encrypted zip members or ones compressed with methods other than deflate were not checked",
+            group_digits(unchecked)
+        );
+    }
+    damaged == 0
+}
+
+/// An archive in the scanned tree
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchiveReport {
+    pub path: String,
+    pub sizes: ArchiveSizes,
+}
+
+/// Read every archive among the regular files in `files`, largest first;
+/// unreadable files are left out
+pub fn scan_archives(files: &[FileInfo]) -> Vec<ArchiveReport> {
+    let mut archives: Vec<ArchiveReport> = files
+        .iter()
+        .filter(|f| f.kind == FileKind::File)
+        .filter_map(|f| {
+            let sizes = read_archive(Path::new(&f.path)).ok()??;
+            Some(ArchiveReport { path: f.path.clone(), sizes })
+        })
+        .collect();
+    archives.sort_by(|a, b| b.sizes.compressed.cmp(&a.sizes.compressed).then_with(|| a.path.cmp(&b.path)));
+    archives
+}
+
+fn format_ratio(ratio: Option<f64>) -> String {
+    ratio.map_or("-".to_string(), |ratio| format!("{:.1}%", ratio * 100.0))
+}
+
+/// List the `limit` largest archives in `files` with their uncompressed
+/// size and ratio, then totals and how many barely compress or look truncated
+pub fn show_archive_report(files: &[FileInfo], limit: usize, color: bool) {
+    let archives = scan_archives(files);
+    println!("Archive Compression:");
+    println!("{}", "─".repeat(50));
+    if archives.is_empty() {
+        println!("No zip, tar, gzip or xz archives found.");
+        return;
+    }
+
+    println!("{:>10}  {:>10}  {:>7}  {:<6}  Path", "Archive", "Contents", "Ratio", "Format");
+    for archive in archives.iter().take(limit) {
+        let sizes = &archive.sizes;
+        let compressed = format!("{:>10}", SizeUnit::auto_format_size(sizes.compressed));
+        let uncompressed = format!("{:>10}", SizeUnit::auto_format_size(sizes.uncompressed));
+        let ratio = format!("{:>7}", format_ratio(sizes.ratio()));
+        let note = if sizes.truncated {
+            "  [truncated?]"
+        } else if sizes.barely_compresses() {
+            "  [barely compresses]"
+        } else {
+            ""
+        };
+        if color {
+            let note = if sizes.truncated { note.red() } else { note.yellow() };
+            println!(
+                "{}  {}  {}  {:<6}  {}{}",
+                compressed.cyan(),
+                uncompressed.cyan(),
+                ratio,
+                sizes.format.name(),
+                archive.path.blue(),
+                note
+            );
+        } else {
+            println!("{}  {}  {}  {:<6}  {}{}", compressed, uncompressed, ratio, sizes.format.name(), archive.path, note);
+        }
+    }
+    if archives.len() > limit {
+        println!("  ... and {} more archives", group_digits((archives.len() - limit) as u64));
+    }
+
+    let compressed = archives.iter().map(|a| a.sizes.compressed).fold(0u64, u64::saturating_add);
+    let uncompressed = archives.iter().map(|a| a.sizes.uncompressed).fold(0u64, u64::saturating_add);
+    let ratio = (uncompressed > 0).then(|| compressed as f64 / uncompressed as f64);
+    println!(
+        "\nTotal: {} archives, {} holding {} ({})",
+        group_digits(archives.len() as u64),
+        SizeUnit::auto_format_size(compressed),
+        SizeUnit::auto_format_size(uncompressed),
+        format_ratio(ratio)
+    );
+    let barely: Vec<&ArchiveReport> = archives.iter().filter(|a| a.sizes.barely_compresses()).collect();
+    if !barely.is_empty() {
+        let bytes: u64 = barely.iter().map(|a| a.sizes.compressed).sum();
+        println!(
+            "Barely compressing (saving under {:.0}%): {} archives, {}",
+            MIN_SAVING * 100.0,
+            group_digits(barely.len() as u64),
+            SizeUnit::auto_format_size(bytes)
+        );
+    }
+    let truncated = archives.iter().filter(|a| a.sizes.truncated).count();
+    if truncated > 0 {
+        let line = format!("Possibly truncated: {} archives", group_digits(truncated as u64));
+        if color {
+            println!("{}", line.red());
+        } else {
+            println!("{}", line);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A ustar header for a member of `size` bytes, followed by its padded data
+    fn tar_member(name: &str, kind: u8, size: usize) -> Vec<u8> {
+        let mut header = [0u8; 512];
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        header[124..136].copy_from_slice(format!("{:011o}\0", size).as_bytes());
+        header[148..156].fill(b' ');
+        header[156] = kind;
+        header[257..263].copy_from_slice(b"ustar\0");
+        let checksum: u32 = header.iter().map(|&b| b as u32).sum();
+        header[148..156].copy_from_slice(format!("{:06o}\0 ", checksum).as_bytes());
+        [&header[..], &vec![b'x'; size.div_ceil(512) * 512]].concat()
+    }
+
+    #[test]
+    fn tar_members_add_up() {
+        let members = [tar_member("a.txt", b'0', 700), tar_member("pax", b'x', 30), tar_member("b", b'0', 5)].concat();
+        let complete = [&members[..], &[0; 1024]].concat();
+        assert_eq!(tar_contents(&mut &complete[..]).unwrap(), (705, 2, false));
+        // Cut inside the last member's data, then inside a header
+        assert_eq!(tar_contents(&mut &members[..members.len() - 100]).unwrap(), (705, 2, true));
+        assert_eq!(tar_contents(&mut &members[..1600]).unwrap(), (700, 1, true));
+        let mut corrupt = complete.clone();
+        corrupt[0] = b'z';
+        assert_eq!(tar_contents(&mut &corrupt[..]).unwrap(), (0, 0, true));
+    }
+}
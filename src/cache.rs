@@ -0,0 +1,241 @@
+//! On-disk cache of recursive scans, used by `--refresh`.
+//!
+//! Each scanned directory is stored with its modification time and the
+//! records of its direct entries. A refresh walks the tree again but only
+//! lists directories whose mtime changed; the others reuse their cached
+//! entries. A directory's mtime only changes when entries are added, removed
+//! or renamed, so in-place edits to existing files are not picked up until a
+//! full scan.
+//!
+//! The same file also keeps the content hashes `--detect-changes` records,
+//! see [`crate::drift`]; refreshes carry them over untouched.
+
+use crate::collect::{build_file_info, matches_search, set_size, DirAccess};
+use crate::migrate;
+use crate::perf;
+use crate::types::{FileInfo, FileKind, Filter};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ScanCache {
+    pub root: String,
+    pub dirs: HashMap<String, CachedDir>,
+    /// By path relative to the root, with `/` separators
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub hashes: HashMap<String, CachedHash>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedDir {
+    pub mtime_secs: u64,
+    pub mtime_nanos: u32,
+    pub entries: Vec<FileInfo>,
+    /// Listing the directory was refused
+    #[serde(default)]
+    pub denied: bool,
+}
+
+/// A file's SHA-256 and the size and modification time it had when hashed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CachedHash {
+    pub size: u64,
+    pub mtime_secs: u64,
+    pub mtime_nanos: u32,
+    pub sha256: String,
+}
+
+/// How much of a refresh came from the cache
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RefreshStats {
+    pub rescanned: usize,
+    pub reused: usize,
+}
+
+/// Directory holding the scan caches, following the XDG cache directory
+/// convention
+pub fn cache_dir() -> Option<PathBuf> {
+    let cache_home = std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))?;
+    Some(cache_home.join("filebyte").join("scans"))
+}
+
+/// Location of the cache for a scan root
+pub fn cache_path(root: &Path) -> Option<PathBuf> {
+    use sha2::{Digest, Sha256};
+
+    let digest = Sha256::digest(root.to_string_lossy().as_bytes());
+    let key: String = digest[..8].iter().map(|b| format!("{:02x}", b)).collect();
+    Some(cache_dir()?.join(format!("{}.json", key)))
+}
+
+/// The cache for `root`, or an empty one when there is none or it cannot be
+/// used; a refresh then rescans everything and rewrites it
+fn load(path: &Path, root: &str) -> ScanCache {
+    migrate::load::<ScanCache>(path, &migrate::SCAN_CACHE)
+        .ok()
+        .flatten()
+        .filter(|cache| cache.root == root)
+        .unwrap_or_default()
+}
+
+/// The cache for `dir` and where it is kept; `None` for the location when
+/// there is no cache directory to keep it in
+pub fn load_cache(dir: &Path) -> (ScanCache, Option<PathBuf>) {
+    let root = fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf());
+    let root_key = root.to_string_lossy().to_string();
+    let path = cache_path(&root);
+    let mut cache = path.as_deref().map(|path| load(path, &root_key)).unwrap_or_default();
+    cache.root = root_key;
+    (cache, path)
+}
+
+/// Write `cache` to `path`, reporting failures on stderr
+pub fn save_cache(path: &Path, cache: &ScanCache) {
+    if let Err(e) = migrate::save(path, &migrate::SCAN_CACHE, cache) {
+        eprintln!("Could not write scan cache {}: {}", path.display(), e);
+    }
+}
+
+/// Recursively collect `dir` into `files`, reusing cached entries for
+/// directories whose mtime is unchanged, then write the updated cache
+pub fn refresh(
+    dir: &Path,
+    files: &mut Vec<FileInfo>,
+    filter: &Filter,
+    excluding_regex: Option<&Regex>,
+    exclude: &[PathBuf],
+    du: bool,
+    access: &mut DirAccess,
+) -> RefreshStats {
+    let (previous, path) = load_cache(dir);
+
+    let mut walk = Walk {
+        previous: previous.dirs,
+        current: HashMap::new(),
+        stats: RefreshStats::default(),
+        filter,
+        excluding_regex,
+        exclude,
+        du,
+        access,
+    };
+    walk.dir(dir, files);
+
+    if let Some(path) = path {
+        let cache = ScanCache {
+            root: previous.root,
+            dirs: walk.current,
+            hashes: previous.hashes,
+        };
+        save_cache(&path, &cache);
+    }
+    walk.stats
+}
+
+struct Walk<'a> {
+    previous: HashMap<String, CachedDir>,
+    current: HashMap<String, CachedDir>,
+    stats: RefreshStats,
+    filter: &'a Filter,
+    excluding_regex: Option<&'a Regex>,
+    exclude: &'a [PathBuf],
+    du: bool,
+    access: &'a mut DirAccess,
+}
+
+impl Walk<'_> {
+    /// Returns the total size of the regular files below `path`
+    fn dir(&mut self, path: &Path, files: &mut Vec<FileInfo>) -> u64 {
+        let Some((mtime_secs, mtime_nanos)) = fs::metadata(path)
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| (d.as_secs(), d.subsec_nanos()))
+        else {
+            return 0;
+        };
+
+        let key = path.to_string_lossy().to_string();
+        let (entries, denied) = match self.previous.remove(&key) {
+            Some(cached) if cached.mtime_secs == mtime_secs && cached.mtime_nanos == mtime_nanos => {
+                self.stats.reused += 1;
+                perf::count_cache_hit();
+                (cached.entries, cached.denied)
+            }
+            _ => {
+                self.stats.rescanned += 1;
+                let listing = fs::read_dir(path);
+                perf::count_listing(&listing);
+                let denied = listing
+                    .as_ref()
+                    .is_err_and(|e| e.kind() == std::io::ErrorKind::PermissionDenied);
+                (list_entries(listing), denied)
+            }
+        };
+        self.access.count(denied);
+
+        let mut total = 0;
+        for entry in &entries {
+            if let Some(regex) = self.excluding_regex {
+                if regex.is_match(&entry.name) {
+                    continue;
+                }
+            }
+            let entry_path = PathBuf::from(&entry.path);
+            if self.exclude.contains(&entry_path) {
+                continue;
+            }
+
+            let index = if matches_search(&entry.name, self.filter.search_pattern.as_ref())
+                && self.filter.matches_kind(entry.kind)
+            {
+                files.push(entry.clone());
+                Some(files.len() - 1)
+            } else {
+                None
+            };
+
+            if entry.kind == FileKind::Dir {
+                let subtotal = self.dir(&entry_path, files);
+                if let (true, Some(index)) = (self.du, index) {
+                    set_size(&mut files[index], subtotal);
+                }
+                total += subtotal;
+            } else if entry.kind == FileKind::File {
+                total += entry.size;
+            }
+        }
+
+        self.current.insert(
+            key,
+            CachedDir {
+                mtime_secs,
+                mtime_nanos,
+                entries,
+                denied,
+            },
+        );
+        total
+    }
+}
+
+/// The records of a directory's direct entries, before any filtering
+fn list_entries(listing: std::io::Result<fs::ReadDir>) -> Vec<FileInfo> {
+    let mut entries = Vec::new();
+    if let Ok(read_dir) = listing {
+        for entry in read_dir.flatten() {
+            let metadata = entry.metadata();
+            perf::count_metadata(&metadata);
+            if let Ok(metadata) = metadata {
+                entries.push(build_file_info(&entry.path(), &metadata));
+            }
+        }
+    }
+    entries
+}
@@ -0,0 +1,98 @@
+//! File checksums (`filebyte hash`).
+//!
+//! Digests are printed as `<hex>  <path>`, the format of `sha256sum` and
+//! friends, so their `-c` mode can verify a saved listing later. `--tag`
+//! switches to the BSD layout, `SHA256 (<path>) = <hex>`, which BSD
+//! `sha256 -c` and `shasum -c` read and `sha256sum -c` accepts too.
+//! Paths holding a backslash or a line break are escaped the way coreutils
+//! does it, with a leading backslash marking the line.
+
+use crate::analysis::hash_in_parallel;
+use crate::collect::{build_file_info, walk_files};
+use crate::types::{CollectOptions, FileInfo, FileKind, Filter, HashAlgorithm};
+use crate::utils::file_hasher;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Layout of checksum lines
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChecksumStyle {
+    /// `<hex>  <path>`, as `sha256sum` prints
+    #[default]
+    Gnu,
+    /// `SHA256 (<path>) = <hex>`, as BSD `sha256` and `sha256sum --tag` print
+    Bsd,
+}
+
+/// The algorithm name BSD-style lines start with
+fn bsd_tag(algorithm: HashAlgorithm) -> &'static str {
+    match algorithm {
+        HashAlgorithm::Md5 => "MD5",
+        HashAlgorithm::Sha1 => "SHA1",
+        HashAlgorithm::Sha256 => "SHA256",
+        HashAlgorithm::Blake3 => "BLAKE3",
+    }
+}
+
+/// One checksum line for `path`, escaped like coreutils when the path holds
+/// a backslash, newline or carriage return
+pub fn checksum_line(hash: &str, path: &str, algorithm: HashAlgorithm, style: ChecksumStyle) -> String {
+    let escape = path.contains(['\\', '\n', '\r']);
+    let (marker, path) = if escape {
+        ("\\", path.replace('\\', "\\\\").replace('\n', "\\n").replace('\r', "\\r"))
+    } else {
+        ("", path.to_string())
+    };
+    match style {
+        ChecksumStyle::Gnu => format!("{}{}  {}", marker, hash, path),
+        ChecksumStyle::Bsd => format!("{}{} ({}) = {}", marker, bsd_tag(algorithm), path, hash),
+    }
+}
+
+/// Hash `path`, or every regular file below it, on `threads` workers.
+/// Files come back sorted by path with `hash` set, or left `None` when the
+/// file could not be read.
+pub fn checksum_files(
+    path: &Path,
+    algorithm: HashAlgorithm,
+    threads: usize,
+    collect: &CollectOptions,
+) -> io::Result<Vec<FileInfo>> {
+    let metadata = fs::metadata(path)?;
+    let mut files = if metadata.is_dir() {
+        let mut files = Vec::new();
+        walk_files(path, &Filter::default(), collect, &mut |file| {
+            if file.kind == FileKind::File {
+                files.push(file);
+            }
+        });
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        files
+    } else {
+        vec![build_file_info(path, &metadata)]
+    };
+
+    let paths: Vec<(u64, &String)> = files.iter().map(|f| (f.size, &f.path)).collect();
+    let hashes = hash_in_parallel(&paths, threads, file_hasher(algorithm));
+    for (file, hash) in files.iter_mut().zip(hashes) {
+        file.hash = hash;
+    }
+    Ok(files)
+}
+
+/// Print one line per file in `style`, reporting unreadable files on
+/// stderr; returns whether every file was hashed
+pub fn show_checksums(files: &[FileInfo], algorithm: HashAlgorithm, style: ChecksumStyle) -> bool {
+    let mut complete = true;
+    for file in files {
+        match &file.hash {
+            Some(hash) => println!("{}", checksum_line(hash, &file.path, algorithm, style)),
+            None => {
+                eprintln!("Error: cannot read {}", file.path);
+                complete = false;
+            }
+        }
+    }
+    complete
+}
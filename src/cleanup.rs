@@ -0,0 +1,188 @@
+//! Cleanup suggestions (`--suggest-cleanup`).
+//!
+//! A file is a candidate when it is at least [`MIN_SIZE`] and has been
+//! neither modified nor read for [`MIN_IDLE_DAYS`]. Candidates are grouped
+//! by their directory, and each group is ranked by the sum of its files'
+//! size times their idle time in years, so huge files nobody has opened in
+//! a long time come first.
+//!
+//! Access times are only as good as the mount options: with `noatime` a
+//! file that is read but never written still looks idle. Reading a file
+//! also updates its access time, so callers should detect types by
+//! extension while walking (see [`crate::types::TypeDetection`]).
+
+use crate::analysis::file_age_secs;
+use crate::collect::walk_files;
+use crate::types::{Clock, CollectOptions, FileKind, Filter, SizeUnit};
+use crate::utils::{group_digits, link_count};
+use colored::Colorize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Files smaller than this are not worth suggesting
+pub const MIN_SIZE: u64 = 1024 * 1024;
+
+/// Files touched more recently than this are not suggested
+pub const MIN_IDLE_DAYS: u64 = 180;
+
+const DAY: u64 = 24 * 60 * 60;
+const YEAR: u64 = 365 * DAY;
+
+/// A file that looks safe to archive or delete
+#[derive(Debug, Clone, PartialEq)]
+pub struct IdleFile {
+    pub path: PathBuf,
+    pub size: u64,
+    /// Seconds since the file was last modified or read
+    pub idle_secs: u64,
+    /// Whether deleting this path alone frees its space (no other hard links)
+    pub reclaimable: bool,
+}
+
+/// The candidates in one directory
+#[derive(Debug, Clone, PartialEq)]
+pub struct CleanupSuggestion {
+    pub dir: PathBuf,
+    /// Largest first
+    pub files: Vec<IdleFile>,
+    /// Bytes freed by deleting every file, hard-linked ones excluded
+    pub reclaimable: u64,
+    /// Sum of size times idle years, the ranking key
+    pub score: f64,
+}
+
+/// Seconds since `path` was last modified or read, from its metadata and
+/// the modification time already known from the walk
+fn idle_secs(metadata: &fs::Metadata, modified_age: u64, now: SystemTime) -> u64 {
+    let read_age = metadata
+        .accessed()
+        .ok()
+        .map(|accessed| now.duration_since(accessed).unwrap_or_default().as_secs());
+    read_age.map_or(modified_age, |read_age| read_age.min(modified_age))
+}
+
+/// Find idle files below `dir` and group them into suggestions, best first
+pub fn suggest_cleanup(dir: &Path, filter: &Filter, collect: &CollectOptions) -> Vec<CleanupSuggestion> {
+    let now = Clock::active().now();
+    let mut by_dir: HashMap<PathBuf, Vec<IdleFile>> = HashMap::new();
+    walk_files(dir, filter, collect, &mut |file| {
+        if file.kind != FileKind::File || file.size < MIN_SIZE {
+            return;
+        }
+        let Some(modified_age) = file_age_secs(&file, now) else {
+            return;
+        };
+        if modified_age < MIN_IDLE_DAYS * DAY {
+            return;
+        }
+        let Ok(metadata) = fs::metadata(&file.path) else {
+            return;
+        };
+        let idle_secs = idle_secs(&metadata, modified_age, now);
+        if idle_secs < MIN_IDLE_DAYS * DAY {
+            return;
+        }
+        let path = PathBuf::from(&file.path);
+        let parent = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        by_dir.entry(parent).or_default().push(IdleFile {
+            path,
+            size: file.size,
+            idle_secs,
+            reclaimable: link_count(&metadata) <= 1,
+        });
+    });
+
+    let mut suggestions: Vec<CleanupSuggestion> = by_dir
+        .into_iter()
+        .map(|(dir, mut files)| {
+            files.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.path.cmp(&b.path)));
+            CleanupSuggestion {
+                reclaimable: files.iter().filter(|f| f.reclaimable).map(|f| f.size).sum(),
+                score: files
+                    .iter()
+                    .map(|f| f.size as f64 * f.idle_secs as f64 / YEAR as f64)
+                    .sum(),
+                dir,
+                files,
+            }
+        })
+        .collect();
+    suggestions.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.dir.cmp(&b.dir)));
+    suggestions
+}
+
+/// "3 days", "5 months" or "2.5 years"
+pub fn format_idle(secs: u64) -> String {
+    if secs >= YEAR {
+        format!("{:.1} years", secs as f64 / YEAR as f64)
+    } else if secs >= 60 * DAY {
+        format!("{} months", secs / (30 * DAY))
+    } else {
+        format!("{} days", secs / DAY)
+    }
+}
+
+/// Print the best `limit` suggestions below `dir`
+pub fn show_cleanup_suggestions(dir: &Path, limit: usize, filter: &Filter, collect: &CollectOptions, color: bool) {
+    let suggestions = suggest_cleanup(dir, filter, collect);
+    println!(
+        "Cleanup Suggestions (files of {} or more, untouched for {}+ days):",
+        SizeUnit::auto_format_size(MIN_SIZE),
+        MIN_IDLE_DAYS
+    );
+    println!("{}", "─".repeat(50));
+    if suggestions.is_empty() {
+        println!("Nothing large has gone unused that long.");
+        return;
+    }
+
+    for (rank, suggestion) in suggestions.iter().take(limit).enumerate() {
+        let oldest = suggestion.files.iter().map(|f| f.idle_secs).max().unwrap_or(0);
+        let reclaimable = SizeUnit::auto_format_size(suggestion.reclaimable);
+        let count = group_digits(suggestion.files.len() as u64);
+        if color {
+            println!("{:>2}. {}", rank + 1, suggestion.dir.display().to_string().blue().bold());
+            println!(
+                "    {} reclaimable in {} files, idle up to {}",
+                reclaimable.cyan(),
+                count,
+                format_idle(oldest).yellow()
+            );
+        } else {
+            println!("{:>2}. {}", rank + 1, suggestion.dir.display());
+            println!("    {} reclaimable in {} files, idle up to {}", reclaimable, count, format_idle(oldest));
+        }
+        for file in suggestion.files.iter().take(3) {
+            let name = file.path.file_name().unwrap_or_default().to_string_lossy();
+            let linked = if file.reclaimable { "" } else { ", hard-linked" };
+            println!(
+                "      {} ({}, idle {}{})",
+                name,
+                SizeUnit::auto_format_size(file.size),
+                format_idle(file.idle_secs),
+                linked
+            );
+        }
+        if suggestion.files.len() > 3 {
+            println!("      ... and {} more", group_digits(suggestion.files.len() as u64 - 3));
+        }
+    }
+
+    let shown = &suggestions[..limit.min(suggestions.len())];
+    let total: u64 = shown.iter().map(|s| s.reclaimable).sum();
+    println!();
+    let total = format!("Estimated reclaimable: {}", SizeUnit::auto_format_size(total));
+    if color {
+        println!("{}", total.green().bold());
+    } else {
+        println!("{}", total);
+    }
+    if suggestions.len() > limit {
+        println!(
+            "({} more directories with idle files not shown)",
+            group_digits((suggestions.len() - limit) as u64)
+        );
+    }
+}
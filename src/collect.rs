@@ -1,27 +1,40 @@
-use crate::types::{FileInfo, SizeUnit, SortBy};
-use crate::utils::{can_delete, get_file_size};
+use crate::dimensions::{format_dimensions, image_dimensions};
+use crate::mounts::{mounts_below, network_mounts_below};
+use crate::perf;
+use crate::types::{CollectOptions, FileInfo, FileKind, Filter, SizeUnit, SortBy, SortSpec};
+use crate::utils::{
+    detect_file_type, directory_size_excluding, get_file_size, get_group_name, get_owner_name, short_permissions,
+};
 use chrono::{DateTime, Utc};
-use infer;
 use regex::Regex;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
-/// Collect files from a directory (non-recursively)
+/// Kernel and runtime pseudo-filesystems below `/`. They report bogus sizes
+/// and are slow to walk, so scans of `/` skip them unless --include-virtual.
+pub const VIRTUAL_DIRS: [&str; 4] = ["proc", "sys", "dev", "run"];
+
+/// Collect files from a directory (non-recursively). Subdirectory sizes are
+/// only walked with `options.du`.
 pub fn collect_files(
     dir: &Path,
-    search_pattern: Option<&String>,
-    excluding_pattern: Option<&String>,
-    sort_by: Option<SortBy>,
+    filter: &Filter,
+    sort: &SortSpec,
+    options: &CollectOptions,
 ) -> Vec<FileInfo> {
     let mut files = Vec::new();
 
     fn collect_recursive(
         path: &Path,
         files: &mut Vec<FileInfo>,
-        search_pattern: Option<&String>,
+        filter: &Filter,
         excluding_regex: Option<&Regex>,
+        exclude: &[PathBuf],
+        du: bool,
     ) {
-        if let Ok(entries) = fs::read_dir(path) {
+        let listing = fs::read_dir(path);
+        perf::count_listing(&listing);
+        if let Ok(entries) = listing {
             for entry in entries.flatten() {
                 let entry_path = entry.path();
                 let file_name = entry_path.file_name().unwrap_or_default().to_string_lossy();
@@ -31,124 +44,82 @@ pub fn collect_files(
                         continue;
                     }
                 }
+                if exclude.contains(&entry_path) {
+                    continue;
+                }
 
-                if let Ok(metadata) = entry.metadata() {
-                    let should_collect = if let Some(pattern) = search_pattern {
-                        let matches = if pattern.starts_with('^')
-                            || pattern.ends_with('$')
-                            || pattern.contains(".*")
-                            || pattern.contains('[')
-                            || pattern.contains(']')
-                        {
-                            if let Ok(regex) = Regex::new(pattern) {
-                                regex.is_match(&file_name)
-                            } else {
-                                false
-                            }
-                        } else {
-                            file_name.contains(pattern)
-                        };
-                        matches
-                    } else {
-                        true
-                    };
-
-                    if should_collect {
-                        let file_type = if entry_path.is_dir() {
-                            "directory".to_string()
-                        } else {
-                            infer::get_from_path(&entry_path)
-                                .ok()
-                                .flatten()
-                                .map(|kind| kind.mime_type().to_string())
-                                .unwrap_or_else(|| "unknown".to_string())
-                        };
-
-                        let created = metadata
-                            .created()
-                            .ok()
-                            .map(|t| DateTime::<Utc>::from(t).format("%Y-%m-%d %H:%M:%S UTC").to_string());
-
-                        let modified = metadata
-                            .modified()
-                            .ok()
-                            .map(|t| DateTime::<Utc>::from(t).format("%Y-%m-%d %H:%M:%S UTC").to_string());
-
-                        let permissions = if metadata.permissions().readonly() {
-                            if can_delete(&entry_path) { "r-x" } else { "r--" }
-                        } else {
-                            if can_delete(&entry_path) { "rwx" } else { "rw-" }
-                        };
-
-                        files.push(FileInfo {
-                            name: file_name.to_string(),
-                            path: entry_path.to_string_lossy().to_string(),
-                            size: get_file_size(&entry_path),
-                            size_human: SizeUnit::auto_format_size(get_file_size(&entry_path)),
-                            file_type,
-                            created,
-                            modified,
-                            permissions: permissions.to_string(),
-                            is_directory: entry_path.is_dir(),
-                        });
+                let metadata = entry.metadata();
+                perf::count_metadata(&metadata);
+                if let Ok(metadata) = metadata {
+                    let kind = FileKind::from_file_type(metadata.file_type());
+                    if matches_search(&file_name, filter.search_pattern.as_ref())
+                        && filter.matches_kind(kind)
+                    {
+                        let mut file = build_file_info(&entry_path, &metadata);
+                        if du && file.is_directory {
+                            set_size(&mut file, directory_size_excluding(&entry_path, exclude));
+                        }
+                        files.push(file);
                     }
                 }
             }
         }
     }
 
-    let excluding_regex = excluding_pattern.and_then(|p| Regex::new(p).ok());
-    collect_recursive(dir, &mut files, search_pattern, excluding_regex.as_ref());
-
-    if let Some(sort_criteria) = sort_by {
-        match sort_criteria {
-            SortBy::Name => files.sort_by(|a, b| match (a.is_directory, b.is_directory) {
-                (true, false) => std::cmp::Ordering::Less,
-                (false, true) => std::cmp::Ordering::Greater,
-                _ => a.name.cmp(&b.name),
-            }),
-            SortBy::Size => files.sort_by(|a, b| match (a.is_directory, b.is_directory) {
-                (true, false) => std::cmp::Ordering::Less,
-                (false, true) => std::cmp::Ordering::Greater,
-                _ => b.size.cmp(&a.size),
-            }),
-            SortBy::Date => files.sort_by(|a, b| match (a.is_directory, b.is_directory) {
-                (true, false) => std::cmp::Ordering::Less,
-                (false, true) => std::cmp::Ordering::Greater,
-                _ => {
-                    let a_date = a.modified.as_ref().map(|s| s.as_str()).unwrap_or("");
-                    let b_date = b.modified.as_ref().map(|s| s.as_str()).unwrap_or("");
-                    b_date.cmp(a_date)
-                }
-            }),
-        }
-    } else {
-        files.sort_by(|a, b| match (a.is_directory, b.is_directory) {
-            (true, false) => std::cmp::Ordering::Less,
-            (false, true) => std::cmp::Ordering::Greater,
-            _ => a.name.cmp(&b.name),
-        });
-    }
+    let excluding_regex = filter.excluding_pattern.as_ref().and_then(|p| Regex::new(p).ok());
+    collect_recursive(
+        dir,
+        &mut files,
+        filter,
+        excluding_regex.as_ref(),
+        &skipped_paths(dir, options),
+        options.du,
+    );
+    files.iter_mut().for_each(|file| add_dimensions(file, options));
+
+    sort_files(&mut files, sort);
 
     files
 }
 
-/// Collect files from a directory recursively
+/// Collect files from a directory recursively. With `options.du`, directory
+/// totals are summed bottom-up during the same walk; with `options.refresh`,
+/// unchanged directories are read from the scan cache.
 pub fn collect_files_recursive(
     dir: &Path,
-    search_pattern: Option<&String>,
-    excluding_pattern: Option<&String>,
-    sort_by: Option<SortBy>,
+    filter: &Filter,
+    sort: &SortSpec,
+    options: &CollectOptions,
 ) -> Vec<FileInfo> {
     let mut files = Vec::new();
+    walk_files(dir, filter, options, &mut |file| files.push(file));
+    sort_files(&mut files, sort);
+    files
+}
 
-    fn collect_all_recursive(
+/// Walk a directory recursively, handing each matching entry to `visit` as
+/// soon as it is known, in walk order. With `options.du` a directory is
+/// visited after its contents, once its total is known.
+pub fn walk_files(
+    dir: &Path,
+    filter: &Filter,
+    options: &CollectOptions,
+    visit: &mut dyn FnMut(FileInfo),
+) {
+    /// Returns the total size of the regular files below `path`
+    fn walk_recursive(
         path: &Path,
-        files: &mut Vec<FileInfo>,
-        search_pattern: Option<&String>,
+        visit: &mut dyn FnMut(FileInfo),
+        filter: &Filter,
         excluding_regex: Option<&Regex>,
-    ) {
-        if let Ok(entries) = fs::read_dir(path) {
+        exclude: &[PathBuf],
+        du: bool,
+        access: &mut DirAccess,
+    ) -> u64 {
+        let mut total = 0;
+        let entries = fs::read_dir(path);
+        access.record(&entries);
+        if let Ok(entries) = entries {
             for entry in entries.flatten() {
                 let entry_path = entry.path();
                 let file_name = entry_path.file_name().unwrap_or_default().to_string_lossy();
@@ -158,108 +129,343 @@ pub fn collect_files_recursive(
                         continue;
                     }
                 }
+                if exclude.contains(&entry_path) {
+                    continue;
+                }
 
-                if let Ok(metadata) = entry.metadata() {
-                    let should_collect = if let Some(pattern) = search_pattern {
-                        let matches = if pattern.starts_with('^')
-                            || pattern.ends_with('$')
-                            || pattern.contains(".*")
-                            || pattern.contains('[')
-                            || pattern.contains(']')
-                        {
-                            if let Ok(regex) = Regex::new(pattern) {
-                                regex.is_match(&file_name)
-                            } else {
-                                false
-                            }
-                        } else {
-                            file_name.contains(pattern)
-                        };
-                        matches
+                let metadata = entry.metadata();
+                perf::count_metadata(&metadata);
+                if let Ok(metadata) = metadata {
+                    let kind = FileKind::from_file_type(metadata.file_type());
+                    let mut file = if matches_search(&file_name, filter.search_pattern.as_ref())
+                        && filter.matches_kind(kind)
+                    {
+                        Some(build_file_info(&entry_path, &metadata))
                     } else {
-                        true
+                        None
                     };
 
-                    if should_collect {
-                        let file_type = if entry_path.is_dir() {
-                            "directory".to_string()
-                        } else {
-                            infer::get_from_path(&entry_path)
-                                .ok()
-                                .flatten()
-                                .map(|kind| kind.mime_type().to_string())
-                                .unwrap_or_else(|| "unknown".to_string())
-                        };
-
-                        let created = metadata
-                            .created()
-                            .ok()
-                            .map(|t| DateTime::<Utc>::from(t).format("%Y-%m-%d %H:%M:%S UTC").to_string());
-
-                        let modified = metadata
-                            .modified()
-                            .ok()
-                            .map(|t| DateTime::<Utc>::from(t).format("%Y-%m-%d %H:%M:%S UTC").to_string());
-
-                        let permissions = if metadata.permissions().readonly() {
-                            if can_delete(&entry_path) { "r-x" } else { "r--" }
-                        } else {
-                            if can_delete(&entry_path) { "rwx" } else { "rw-" }
-                        };
-
-                        files.push(FileInfo {
-                            name: file_name.to_string(),
-                            path: entry_path.to_string_lossy().to_string(),
-                            size: get_file_size(&entry_path),
-                            size_human: SizeUnit::auto_format_size(get_file_size(&entry_path)),
-                            file_type,
-                            created,
-                            modified,
-                            permissions: permissions.to_string(),
-                            is_directory: entry_path.is_dir(),
-                        });
+                    if entry.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+                        if !du {
+                            if let Some(file) = file.take() {
+                                visit(file);
+                            }
+                        }
+                        let subtotal = walk_recursive(
+                            &entry_path,
+                            visit,
+                            filter,
+                            excluding_regex,
+                            exclude,
+                            du,
+                            access,
+                        );
+                        if let Some(file) = file.as_mut() {
+                            set_size(file, subtotal);
+                        }
+                        total += subtotal;
+                    } else if metadata.is_file() {
+                        total += metadata.len();
                     }
-
-                    if entry_path.is_dir() {
-                        collect_all_recursive(&entry_path, files, search_pattern, excluding_regex);
+                    if let Some(file) = file {
+                        visit(file);
                     }
                 }
             }
         }
+        total
+    }
+
+    let mut visit_with_dimensions = |mut file: FileInfo| {
+        add_dimensions(&mut file, options);
+        visit(file);
+    };
+    let visit: &mut dyn FnMut(FileInfo) = &mut visit_with_dimensions;
+    let excluding_regex = filter.excluding_pattern.as_ref().and_then(|p| Regex::new(p).ok());
+    let mut access = DirAccess::default();
+    if options.refresh {
+        let mut files = Vec::new();
+        let stats = crate::cache::refresh(
+            dir,
+            &mut files,
+            filter,
+            excluding_regex.as_ref(),
+            &skipped_paths(dir, options),
+            options.du,
+            &mut access,
+        );
+        eprintln!(
+            "Refreshed {}: {} directories rescanned, {} unchanged",
+            dir.display(),
+            stats.rescanned,
+            stats.reused
+        );
+        access.report();
+        files.into_iter().for_each(visit);
+        return;
+    }
+    walk_recursive(
+        dir,
+        visit,
+        filter,
+        excluding_regex.as_ref(),
+        &skipped_paths(dir, options),
+        options.du,
+        &mut access,
+    );
+    access.report();
+}
+
+/// How many directories a walk listed, and how many it was refused
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DirAccess {
+    pub dirs: usize,
+    pub denied: usize,
+}
+
+impl DirAccess {
+    /// Count the outcome of listing one directory
+    pub fn record<T>(&mut self, listing: &std::io::Result<T>) {
+        perf::count_listing(listing);
+        self.count(matches!(listing, Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied));
+    }
+
+    /// Count one directory, refused or not
+    pub fn count(&mut self, denied: bool) {
+        self.dirs += 1;
+        if denied {
+            self.denied += 1;
+        }
+    }
+
+    /// Warn on stderr when part of the tree could not be read, since the
+    /// totals then undercount
+    pub fn report(&self) {
+        if self.denied == 0 {
+            return;
+        }
+        let percentage = self.denied as f64 / self.dirs as f64 * 100.0;
+        eprintln!(
+            "Warning: {} of {} directories ({:.1}%) could not be read due to permissions; \
+             totals are incomplete. Rerun with elevated rights (e.g. sudo) for a full scan.",
+            self.denied, self.dirs, percentage
+        );
     }
+}
 
-    let excluding_regex = excluding_pattern.and_then(|p| Regex::new(p).ok());
-    collect_all_recursive(dir, &mut files, search_pattern, excluding_regex.as_ref());
-
-    if let Some(sort_criteria) = sort_by {
-        match sort_criteria {
-            SortBy::Name => files.sort_by(|a, b| match (a.is_directory, b.is_directory) {
-                (true, false) => std::cmp::Ordering::Less,
-                (false, true) => std::cmp::Ordering::Greater,
-                _ => a.name.cmp(&b.name),
-            }),
-            SortBy::Size => files.sort_by(|a, b| match (a.is_directory, b.is_directory) {
-                (true, false) => std::cmp::Ordering::Less,
-                (false, true) => std::cmp::Ordering::Greater,
-                _ => b.size.cmp(&a.size),
-            }),
-            SortBy::Date => files.sort_by(|a, b| match (a.is_directory, b.is_directory) {
-                (true, false) => std::cmp::Ordering::Less,
-                (false, true) => std::cmp::Ordering::Greater,
-                _ => {
-                    let a_date = a.modified.as_ref().map(|s| s.as_str()).unwrap_or("");
-                    let b_date = b.modified.as_ref().map(|s| s.as_str()).unwrap_or("");
-                    b_date.cmp(a_date)
+/// Collect an explicit list of paths, e.g. produced by fd or ripgrep
+pub fn collect_from_paths(
+    paths: &[String],
+    filter: &Filter,
+    sort: &SortSpec,
+    options: &CollectOptions,
+) -> Vec<FileInfo> {
+    let search_pattern = filter.search_pattern.as_ref();
+    let excluding_regex = filter.excluding_pattern.as_ref().and_then(|p| Regex::new(p).ok());
+    let mut files = Vec::new();
+
+    for path_str in paths {
+        let path = Path::new(path_str);
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+
+        if let Some(regex) = &excluding_regex {
+            if regex.is_match(&file_name) {
+                continue;
+            }
+        }
+
+        match fs::metadata(path) {
+            Ok(metadata) => {
+                let kind = fs::symlink_metadata(path)
+                    .map(|m| FileKind::from_file_type(m.file_type()))
+                    .unwrap_or_default();
+                if matches_search(&file_name, search_pattern) && filter.matches_kind(kind) {
+                    let mut file = build_file_info(path, &metadata);
+                    if options.du && file.is_directory {
+                        set_size(&mut file, get_file_size(path));
+                    }
+                    add_dimensions(&mut file, options);
+                    files.push(file);
                 }
-            }),
+            }
+            Err(e) => eprintln!("Skipping {}: {}", path_str, e),
         }
-    } else {
-        files.sort_by(|a, b| match (a.is_directory, b.is_directory) {
-            (true, false) => std::cmp::Ordering::Less,
-            (false, true) => std::cmp::Ordering::Greater,
-            _ => a.name.cmp(&b.name),
-        });
     }
 
+    sort_files(&mut files, sort);
+
     files
 }
+
+/// The virtual directories to skip when collecting below `dir`: only a scan
+/// of `/` itself has any
+pub fn virtual_exclusions(dir: &Path, options: &CollectOptions) -> Vec<PathBuf> {
+    let is_root = fs::canonicalize(dir)
+        .map(|p| p == Path::new("/"))
+        .unwrap_or(false);
+    if options.include_virtual || !is_root {
+        return Vec::new();
+    }
+    VIRTUAL_DIRS.iter().map(|name| dir.join(name)).collect()
+}
+
+/// Everything a walk of `dir` leaves out: the virtual directories, plus
+/// other filesystems mounted below it with `options.one_file_system`, or
+/// just the network ones with `options.local_only`
+pub fn skipped_paths(dir: &Path, options: &CollectOptions) -> Vec<PathBuf> {
+    let mut skipped = virtual_exclusions(dir, options);
+    if options.one_file_system {
+        skipped.extend(mounts_below(dir));
+    } else if options.local_only {
+        skipped.extend(network_mounts_below(dir));
+    }
+    skipped
+}
+
+/// Total size of a file or directory tree, honouring [`skipped_paths`]
+pub fn total_size(path: &Path, options: &CollectOptions) -> u64 {
+    if path.is_dir() {
+        directory_size_excluding(path, &skipped_paths(path, options))
+    } else {
+        get_file_size(path)
+    }
+}
+
+/// Check a file name against a search pattern, treating it as a regex only
+/// when it contains regex syntax
+pub(crate) fn matches_search(file_name: &str, search_pattern: Option<&String>) -> bool {
+    if let Some(pattern) = search_pattern {
+        if pattern.starts_with('^')
+            || pattern.ends_with('$')
+            || pattern.contains(".*")
+            || pattern.contains('[')
+            || pattern.contains(']')
+        {
+            if let Ok(regex) = Regex::new(pattern) {
+                regex.is_match(file_name)
+            } else {
+                false
+            }
+        } else {
+            file_name.contains(pattern.as_str())
+        }
+    } else {
+        true
+    }
+}
+
+/// Build the FileInfo record for a single path
+pub(crate) fn build_file_info(path: &Path, metadata: &fs::Metadata) -> FileInfo {
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+
+    // `metadata` decides, so a symlink to a directory is only a directory
+    // when the caller followed it
+    let file_type = if metadata.is_dir() {
+        "directory".to_string()
+    } else {
+        detect_file_type(path)
+    };
+
+    let created = metadata
+        .created()
+        .ok()
+        .map(|t| DateTime::<Utc>::from(t).format("%Y-%m-%d %H:%M:%S UTC").to_string());
+
+    let modified = metadata
+        .modified()
+        .ok()
+        .map(|t| DateTime::<Utc>::from(t).format("%Y-%m-%d %H:%M:%S UTC").to_string());
+
+
+    // Directory totals are filled in by the caller when --du is on;
+    // `metadata` may describe a symlink, so sizes come from its target
+    let size = if metadata.is_file() {
+        metadata.len()
+    } else if metadata.is_symlink() && path.is_file() {
+        fs::metadata(path).map(|m| m.len()).unwrap_or(0)
+    } else {
+        0
+    };
+
+    // The entry itself, which `metadata` is not when it followed a symlink
+    let entry = fs::symlink_metadata(path).ok();
+    let (mode, uid) = entry.as_ref().and_then(ownership).unzip();
+
+    FileInfo {
+        name: file_name.to_string(),
+        path: path.to_string_lossy().to_string(),
+        size,
+        size_human: SizeUnit::auto_format_size(size),
+        file_type,
+        created,
+        modified,
+        permissions: short_permissions(path, metadata),
+        owner: get_owner_name(metadata),
+        group: get_group_name(metadata),
+        is_directory: metadata.is_dir(),
+        kind: entry
+            .as_ref()
+            .map(|m| FileKind::from_file_type(m.file_type()))
+            .unwrap_or_default(),
+        hash: None,
+        dimensions: None,
+        mode,
+        uid,
+    }
+}
+
+/// Permission bits and owning uid, for snapshots
+#[cfg(unix)]
+fn ownership(metadata: &fs::Metadata) -> Option<(u32, u32)> {
+    use std::os::unix::fs::MetadataExt;
+
+    Some((metadata.mode() & 0o7777, metadata.uid()))
+}
+
+#[cfg(not(unix))]
+fn ownership(_metadata: &fs::Metadata) -> Option<(u32, u32)> {
+    None
+}
+
+/// Read the dimensions of an image when `options.dimensions` asks for them
+fn add_dimensions(file: &mut FileInfo, options: &CollectOptions) {
+    if options.dimensions && file.kind == FileKind::File && file.file_type.starts_with("image/") {
+        file.dimensions = image_dimensions(Path::new(&file.path)).map(format_dimensions);
+    }
+}
+
+pub(crate) fn set_size(file: &mut FileInfo, size: u64) {
+    file.size = size;
+    file.size_human = SizeUnit::auto_format_size(size);
+}
+
+/// Sort collected files, keeping directories ahead of regular files.
+/// `reverse` inverts the active criteria without moving directories.
+pub fn sort_files(files: &mut [FileInfo], sort: &SortSpec) {
+    files.sort_by(|a, b| compare_files(a, b, sort));
+}
+
+/// The ordering used by [`sort_files`]
+pub fn compare_files(a: &FileInfo, b: &FileInfo, sort: &SortSpec) -> std::cmp::Ordering {
+    match (a.is_directory, b.is_directory) {
+        (true, false) => std::cmp::Ordering::Less,
+        (false, true) => std::cmp::Ordering::Greater,
+        _ => {
+            let ordering = match sort.sort_by {
+                Some(SortBy::Size) => b.size.cmp(&a.size).then_with(|| a.name.cmp(&b.name)),
+                Some(SortBy::Date) => {
+                    let a_date = a.modified.as_deref().unwrap_or("");
+                    let b_date = b.modified.as_deref().unwrap_or("");
+                    b_date.cmp(a_date).then_with(|| a.name.cmp(&b.name))
+                }
+                Some(SortBy::Owner) => a.owner.cmp(&b.owner).then_with(|| a.name.cmp(&b.name)),
+                Some(SortBy::Name) | None => a.name.cmp(&b.name),
+            };
+            if sort.reverse {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        }
+    }
+}
@@ -0,0 +1,213 @@
+//! Comparing two live directories (`filebyte diff`).
+//!
+//! Both trees are walked the same way as for a snapshot and matched by
+//! relative path. Files present on both sides are compared by type, size
+//! and modification time; with hashing on, equally sized files are also
+//! compared by content, which is the only check that catches a copy that
+//! went wrong without changing the size.
+
+use crate::analysis::hash_in_parallel;
+use crate::snapshot::{self, one_sided, SnapshotEntry};
+use crate::types::{CollectOptions, SizeUnit};
+use crate::utils::{group_digits, hash_file};
+use colored::Colorize;
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+
+/// How to compare the two trees
+#[derive(Debug, Clone, Default)]
+pub struct CompareOptions {
+    /// Compare the contents of equally sized files
+    pub hash: bool,
+    /// Don't report files that only differ in modification time
+    pub ignore_mtime: bool,
+    pub threads: usize,
+}
+
+/// One way a path differs between the two trees
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Difference {
+    /// A file on one side, a directory on the other
+    Type,
+    Size(u64, u64),
+    Modified(String, String),
+    Content,
+    /// One of the files could not be read for hashing
+    Unreadable,
+}
+
+impl Difference {
+    pub fn describe(&self) -> String {
+        match self {
+            Difference::Type => "file/directory".to_string(),
+            Difference::Size(a, b) => format!(
+                "size {} -> {}",
+                SizeUnit::auto_format_size(*a),
+                SizeUnit::auto_format_size(*b)
+            ),
+            Difference::Modified(a, b) => format!("modified {} -> {}", a, b),
+            Difference::Content => "content".to_string(),
+            Difference::Unreadable => "unreadable".to_string(),
+        }
+    }
+}
+
+/// A path found in both trees that does not match
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffEntry {
+    pub path: String,
+    pub differences: Vec<Difference>,
+}
+
+/// The result of [`compare_dirs`]. Paths are relative, with `/`
+/// separators; a directory found on one side only is listed once rather
+/// than with everything below it.
+#[derive(Debug, Clone, Default)]
+pub struct DirComparison {
+    pub only_in_a: Vec<SnapshotEntry>,
+    pub only_in_b: Vec<SnapshotEntry>,
+    pub different: Vec<DiffEntry>,
+    /// Files present in both trees with nothing to report
+    pub identical: usize,
+}
+
+impl DirComparison {
+    pub fn is_identical(&self) -> bool {
+        self.only_in_a.is_empty() && self.only_in_b.is_empty() && self.different.is_empty()
+    }
+}
+
+/// Compare the trees below `a` and `b`
+pub fn compare_dirs(a: &Path, b: &Path, options: &CompareOptions, collect: &CollectOptions) -> io::Result<DirComparison> {
+    let left = snapshot::take(a, "a", false, options.threads, collect)?;
+    let right = snapshot::take(b, "b", false, options.threads, collect)?;
+    let right_entries: HashMap<&str, &SnapshotEntry> = right.entries.iter().map(|e| (e.path.as_str(), e)).collect();
+    let left_entries: HashMap<&str, &SnapshotEntry> = left.entries.iter().map(|e| (e.path.as_str(), e)).collect();
+
+    let mut comparison = DirComparison {
+        only_in_a: one_sided(&left.entries, &right_entries).0.into_iter().cloned().collect(),
+        only_in_b: one_sided(&right.entries, &left_entries).0.into_iter().cloned().collect(),
+        ..DirComparison::default()
+    };
+
+    let mut to_hash = Vec::new();
+    for entry in &left.entries {
+        let Some(other) = right_entries.get(entry.path.as_str()) else {
+            continue;
+        };
+        if entry.dir && other.dir {
+            continue;
+        }
+        let mut differences = Vec::new();
+        if entry.dir != other.dir {
+            differences.push(Difference::Type);
+        } else {
+            if entry.size != other.size {
+                differences.push(Difference::Size(entry.size, other.size));
+            } else if options.hash {
+                to_hash.push(comparison.different.len());
+            }
+            if !options.ignore_mtime && entry.modified != other.modified {
+                differences.push(Difference::Modified(
+                    entry.modified.clone().unwrap_or_default(),
+                    other.modified.clone().unwrap_or_default(),
+                ));
+            }
+        }
+        comparison.different.push(DiffEntry {
+            path: entry.path.clone(),
+            differences,
+        });
+    }
+
+    // Hash both sides of every equally sized pair in one pass
+    let full_paths: Vec<(u64, String, String)> = to_hash
+        .iter()
+        .map(|&i| {
+            let path = &comparison.different[i].path;
+            let size = right_entries[path.as_str()].size;
+            (
+                size,
+                Path::new(&left.root).join(path).to_string_lossy().to_string(),
+                Path::new(&right.root).join(path).to_string_lossy().to_string(),
+            )
+        })
+        .collect();
+    let files: Vec<(u64, &String)> = full_paths
+        .iter()
+        .flat_map(|(size, a, b)| [(*size, a), (*size, b)])
+        .collect();
+    let hashes = hash_in_parallel(&files, options.threads, hash_file);
+    for (&i, pair) in to_hash.iter().zip(hashes.chunks(2)) {
+        let difference = match (&pair[0], &pair[1]) {
+            (Some(a), Some(b)) if a == b => continue,
+            (Some(_), Some(_)) => Difference::Content,
+            _ => Difference::Unreadable,
+        };
+        comparison.different[i].differences.push(difference);
+    }
+
+    comparison.identical = comparison.different.iter().filter(|d| d.differences.is_empty()).count();
+    comparison.different.retain(|d| !d.differences.is_empty());
+    comparison.different.sort_by(|x, y| x.path.cmp(&y.path));
+    Ok(comparison)
+}
+
+/// Print the comparison of `a` and `b`
+pub fn show_comparison(a: &Path, b: &Path, comparison: &DirComparison, color: bool) {
+    println!("Comparing {} (A) with {} (B)", a.display(), b.display());
+
+    let one_sided = [
+        ("Only in A", &comparison.only_in_a),
+        ("Only in B", &comparison.only_in_b),
+    ];
+    for (title, entries) in one_sided {
+        if entries.is_empty() {
+            continue;
+        }
+        println!();
+        println!("{} ({}):", title, group_digits(entries.len() as u64));
+        for entry in entries {
+            let name = if entry.dir { format!("{}/", entry.path) } else { entry.path.clone() };
+            let line = format!("  {} ({})", name, SizeUnit::auto_format_size(entry.size));
+            if color {
+                let line = if title == "Only in A" { line.red() } else { line.green() };
+                println!("{}", line);
+            } else {
+                println!("{}", line);
+            }
+        }
+    }
+
+    if !comparison.different.is_empty() {
+        println!();
+        println!("Different ({}):", group_digits(comparison.different.len() as u64));
+        for entry in &comparison.different {
+            let reasons: Vec<String> = entry.differences.iter().map(Difference::describe).collect();
+            if color {
+                println!("  {}: {}", entry.path.yellow(), reasons.join(", "));
+            } else {
+                println!("  {}: {}", entry.path, reasons.join(", "));
+            }
+        }
+    }
+
+    println!();
+    if comparison.is_identical() {
+        let message = format!("No differences ({} files compared)", group_digits(comparison.identical as u64));
+        if color {
+            println!("{}", message.green().bold());
+        } else {
+            println!("{}", message);
+        }
+    } else {
+        println!(
+            "{} only in A, {} only in B, {} different, {} identical files",
+            group_digits(comparison.only_in_a.len() as u64),
+            group_digits(comparison.only_in_b.len() as u64),
+            group_digits(comparison.different.len() as u64),
+            group_digits(comparison.identical as u64)
+        );
+    }
+}
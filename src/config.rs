@@ -0,0 +1,64 @@
+//! User defaults in `config.toml`.
+//!
+//! The file lives in `$XDG_CONFIG_HOME/filebyte` (or `~/.config/filebyte`)
+//! and holds defaults for the global display options, named after their
+//! long flags:
+//!
+//! ```toml
+//! version = 1
+//! si = true
+//! size-precision = 1
+//! truncate-sizes = false
+//! no-color = false
+//! no-mime = false
+//! ```
+//!
+//! A flag given on the command line always wins. The file is versioned like
+//! the other saved files, see [`crate::migrate`]; upgrading rewrites it
+//! without its comments, which stay in the backup of the old version.
+
+use crate::migrate::{self, LoadError, CONFIG};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+/// Defaults read from `config.toml`; anything missing keeps the built-in default
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Config {
+    /// `--si`
+    pub si: bool,
+    /// `--size-precision`, 0-3
+    pub size_precision: Option<u8>,
+    /// `--truncate-sizes`
+    pub truncate_sizes: bool,
+    /// `--no-color`
+    pub no_color: bool,
+    /// `--no-mime`
+    pub no_mime: bool,
+}
+
+/// Location of `config.toml`, following the XDG config directory convention
+pub fn config_path() -> Option<PathBuf> {
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+    Some(config_home.join("filebyte").join("config.toml"))
+}
+
+/// Read `config.toml`, upgrading an older one in place. A missing file is
+/// the default config; unknown keys are refused so typos do not go unnoticed.
+pub fn load_config(path: &Path) -> Result<Config, LoadError> {
+    let Some(mut document) = migrate::load::<Value>(path, &CONFIG)? else {
+        return Ok(Config::default());
+    };
+    if let Some(table) = document.as_object_mut() {
+        table.remove("version");
+    }
+    let invalid = |reason: String| LoadError::Invalid { reason, backup: None };
+    let config: Config = serde_json::from_value(document).map_err(|e| invalid(format!("unreadable config: {}", e)))?;
+    if config.size_precision.is_some_and(|digits| digits > 3) {
+        return Err(invalid("size-precision must be 0-3".to_string()));
+    }
+    Ok(config)
+}
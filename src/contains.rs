@@ -0,0 +1,135 @@
+//! Content search (`--contains`).
+//!
+//! The files that pass the usual name and type filters are read line by
+//! line and matched against a regular expression, much like `grep -r`.
+//! Files that look binary (see [`crate::loc::open_text`]) are skipped, and
+//! lines that are not valid UTF-8 are matched after lossy conversion.
+
+use crate::loc::open_text;
+use crate::types::{FileInfo, FileKind};
+use crate::utils::group_digits;
+use colored::Colorize;
+use regex::Regex;
+use std::io::{self, BufRead};
+use std::path::Path;
+
+/// A matching line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineMatch {
+    /// Starting at 1
+    pub line_number: u64,
+    /// Without the line break
+    pub line: String,
+}
+
+/// A file with at least one matching line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContentMatch {
+    pub path: String,
+    pub size: u64,
+    pub lines: Vec<LineMatch>,
+}
+
+/// The lines of the file at `path` that match `pattern`; `None` if the file
+/// looks binary
+pub fn search_file(path: &Path, pattern: &Regex) -> io::Result<Option<Vec<LineMatch>>> {
+    let Some(text) = open_text(path)? else {
+        return Ok(None);
+    };
+    let mut lines = Vec::new();
+    for (index, line) in text.split(b'\n').enumerate() {
+        let line = line?;
+        let line = String::from_utf8_lossy(&line);
+        let line = line.strip_suffix('\r').unwrap_or(&line);
+        if pattern.is_match(line) {
+            lines.push(LineMatch {
+                line_number: index as u64 + 1,
+                line: line.to_string(),
+            });
+        }
+    }
+    Ok(Some(lines))
+}
+
+/// Search every regular file in `files`, returning the matching ones in
+/// path order and how many text files were searched. Unreadable files are
+/// skipped.
+pub fn search_files(files: &[FileInfo], pattern: &Regex) -> (Vec<ContentMatch>, usize) {
+    let mut files: Vec<&FileInfo> = files.iter().filter(|f| f.kind == FileKind::File).collect();
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    let mut searched = 0;
+    let mut matches = Vec::new();
+    for file in files {
+        let Ok(Some(lines)) = search_file(Path::new(&file.path), pattern) else {
+            continue;
+        };
+        searched += 1;
+        if !lines.is_empty() {
+            matches.push(ContentMatch {
+                path: file.path.clone(),
+                size: file.size,
+                lines,
+            });
+        }
+    }
+    (matches, searched)
+}
+
+/// `line` with every match of `pattern` highlighted
+fn highlight(line: &str, pattern: &Regex) -> String {
+    let mut highlighted = String::new();
+    let mut last = 0;
+    for found in pattern.find_iter(line) {
+        highlighted.push_str(&line[last..found.start()]);
+        highlighted.push_str(&found.as_str().red().bold().to_string());
+        last = found.end();
+    }
+    highlighted.push_str(&line[last..]);
+    highlighted
+}
+
+/// List the files in `files` whose contents match `pattern`, with the
+/// matching lines if `line_numbers`, up to `limit` files; returns whether
+/// any file matched
+pub fn show_content_matches(
+    files: &[FileInfo],
+    pattern: &Regex,
+    line_numbers: bool,
+    limit: Option<usize>,
+    color: bool,
+) -> bool {
+    let (matches, searched) = search_files(files, pattern);
+    println!("Files containing \"{}\":", pattern.as_str());
+    println!("{}", "─".repeat(50));
+    let limit = limit.unwrap_or(matches.len());
+    for file in matches.iter().take(limit) {
+        let count = file.lines.len();
+        let count = format!("{} {}", group_digits(count as u64), if count == 1 { "match" } else { "matches" });
+        if color {
+            println!("{} ({})", file.path.blue().bold(), count);
+        } else {
+            println!("{} ({})", file.path, count);
+        }
+        if !line_numbers {
+            continue;
+        }
+        for line in &file.lines {
+            if color {
+                let number = format!("{:>6}", line.line_number);
+                println!("  {}: {}", number.yellow(), highlight(&line.line, pattern));
+            } else {
+                println!("  {:>6}: {}", line.line_number, line.line);
+            }
+        }
+    }
+    if matches.len() > limit {
+        println!("  ... and {} more files", group_digits((matches.len() - limit) as u64));
+    }
+
+    println!(
+        "\n{} of {} text files matched",
+        group_digits(matches.len() as u64),
+        group_digits(searched as u64)
+    );
+    !matches.is_empty()
+}
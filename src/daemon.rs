@@ -0,0 +1,1142 @@
+//! `filebyte serve`: a small HTTP daemon that runs scans on request.
+//!
+//! Scans are submitted to a queue and run on at most `max_concurrent` worker
+//! threads, so a dashboard can start several large scans without them all
+//! walking the disk at once. At most `max_queued` scans wait; past that,
+//! submitting gets `503 Service Unavailable`. Finished jobs and their results
+//! are forgotten after `results_ttl`, or sooner once more than `max_finished`
+//! have piled up.
+//!
+//! Connections are answered by a fixed pool of `max_connections` threads;
+//! one arriving while all are busy gets a `503` and is closed. Each client
+//! has `request_timeout` to send its whole request. Endpoints, all JSON:
+//!
+//! | Method   | Path                  |                                            |
+//! |----------|-----------------------|--------------------------------------------|
+//! | `GET`    | `/health`             | Liveness and version                       |
+//! | `GET`    | `/openapi.json`       | OpenAPI 3 description of these endpoints   |
+//! | `POST`   | `/scans`              | Queue a scan, body: [`ScanRequest`]        |
+//! | `GET`    | `/scans`              | Every job, oldest first                    |
+//! | `GET`    | `/scans/{id}`         | One job's status and progress              |
+//! | `GET`    | `/scans/{id}/results` | The finished scan, as a [`ScanExport`]     |
+//! | `GET`    | `/scans/{id}/stats`   | File count and sizes by type, as [`ScanStats`] |
+//! | `GET`    | `/scans/{id}/duplicates` | Files with identical contents, as a [`DuplicatesExport`] |
+//! | `DELETE` | `/scans/{id}`         | Cancel a queued or running job, or forget a finished one |
+//!
+//! With API tokens configured, every endpoint except `/health` needs an
+//! `Authorization: Bearer <token>` header. `read` tokens may only `GET`;
+//! queueing and cancelling scans needs an `admin` token.
+//!
+//! Each client address may make `rate_limit` requests per minute (bursts
+//! included) before getting `429 Too Many Requests`. With a `cache_ttl`,
+//! submitting a scan identical to one that is queued, running or finished
+//! within the TTL returns that job (`200`) instead of walking the disk again.
+//!
+//! Given a certificate and key (see [`load_tls`]), the daemon speaks HTTPS
+//! only, so bearer tokens and results are encrypted on the wire. TLS needs
+//! the `tls` feature, on by default.
+//!
+//! Built with the `webui` feature, `GET /` serves a single-page browser UI
+//! (treemap, top files, duplicates and diffs between scans) on top of these.
+
+use crate::analysis::group_duplicates;
+use crate::collect::sort_files;
+use crate::display::{extension_key, sizes_by, TypeSize};
+use crate::scan::ScanBuilder;
+use crate::types::{
+    Clock, DuplicateOptions, DuplicatesExport, FileInfo, FileKind, ScanExport, ScanMetadata, SortSpec,
+};
+use schemars::generate::SchemaSettings;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{IpAddr, SocketAddr, TcpListener, TcpStream};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Largest request body accepted
+const MAX_BODY: usize = 1024 * 1024;
+
+/// Largest request line and headers accepted, together
+const MAX_HEADER_BYTES: u64 = 16 * 1024;
+
+/// Most header lines accepted
+const MAX_HEADERS: usize = 100;
+
+/// How long writing a response may stall before the client is dropped
+const WRITE_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[cfg(feature = "webui")]
+const WEB_UI: &str = include_str!("webui/index.html");
+
+#[derive(Debug, Clone)]
+pub struct DaemonConfig {
+    /// Scans running at the same time; further jobs wait in the queue
+    pub max_concurrent: usize,
+    /// Accepted API tokens; when empty, requests are not authenticated
+    pub tokens: Vec<ApiToken>,
+    /// Requests per minute allowed from one client address
+    pub rate_limit: Option<u32>,
+    /// How long a finished scan answers identical scan requests
+    pub cache_ttl: Option<Duration>,
+    /// Scans waiting for a worker before new ones are refused
+    pub max_queued: usize,
+    /// How long a finished job and its results are kept
+    pub results_ttl: Duration,
+    /// Finished jobs kept at most; the oldest are forgotten first
+    pub max_finished: usize,
+    /// Connections handled at the same time
+    pub max_connections: usize,
+    /// How long a client has to send its whole request
+    pub request_timeout: Duration,
+    /// Serve HTTPS with this certificate instead of plain HTTP
+    #[cfg(feature = "tls")]
+    pub tls: Option<Arc<rustls::ServerConfig>>,
+}
+
+impl Default for DaemonConfig {
+    fn default() -> Self {
+        DaemonConfig {
+            max_concurrent: 2,
+            tokens: Vec::new(),
+            rate_limit: None,
+            cache_ttl: None,
+            max_queued: 100,
+            results_ttl: Duration::from_secs(60 * 60),
+            max_finished: 100,
+            max_connections: 64,
+            request_timeout: Duration::from_secs(30),
+            #[cfg(feature = "tls")]
+            tls: None,
+        }
+    }
+}
+
+/// What a token may do
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Scope {
+    /// Read job status and results
+    Read,
+    /// Also queue and cancel scans
+    Admin,
+}
+
+impl std::str::FromStr for Scope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s.to_lowercase().as_str() {
+            "read" => Ok(Scope::Read),
+            "admin" => Ok(Scope::Admin),
+            _ => Err(format!("Invalid token scope: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ApiToken {
+    pub token: String,
+    pub scope: Scope,
+}
+
+/// Parse a token file: one `<scope> <token>` pair per line, where scope is
+/// `read` or `admin`. Blank lines and lines starting with `#` are skipped.
+pub fn parse_tokens(contents: &str) -> Result<Vec<ApiToken>, String> {
+    let mut tokens = Vec::new();
+    for (number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut fields = line.split_whitespace();
+        let (Some(scope), Some(token), None) = (fields.next(), fields.next(), fields.next()) else {
+            return Err(format!("line {}: expected '<scope> <token>'", number + 1));
+        };
+        tokens.push(ApiToken {
+            token: token.to_string(),
+            scope: scope.parse().map_err(|e| format!("line {}: {}", number + 1, e))?,
+        });
+    }
+    Ok(tokens)
+}
+
+/// Check a request's bearer token against the configured tokens
+pub fn authorize(request: &Request, tokens: &[ApiToken]) -> Result<(), Response> {
+    let required = match (request.method.as_str(), request.path.trim_matches('/')) {
+        _ if tokens.is_empty() => return Ok(()),
+        ("GET", "health") | ("GET", "openapi.json") => return Ok(()),
+        // The page itself holds no data; it asks for a token before calling the API
+        #[cfg(feature = "webui")]
+        ("GET", "") => return Ok(()),
+        ("GET", _) => Scope::Read,
+        _ => Scope::Admin,
+    };
+    let Some(presented) = request
+        .header("authorization")
+        .and_then(|value| value.strip_prefix("Bearer "))
+    else {
+        return Err(Response::error(401, "Missing bearer token").with_header("WWW-Authenticate", "Bearer"));
+    };
+    let scope = tokens
+        .iter()
+        .filter(|known| constant_time_eq(known.token.as_bytes(), presented.trim().as_bytes()))
+        .map(|known| known.scope)
+        .max();
+    match scope {
+        None => Err(Response::error(401, "Invalid token").with_header("WWW-Authenticate", "Bearer")),
+        Some(scope) if scope < required => Err(Response::error(403, "This action needs an admin token")),
+        Some(_) => Ok(()),
+    }
+}
+
+/// Token buckets per client address: each holds up to a minute's worth of
+/// requests and refills continuously
+pub struct RateLimiter {
+    per_minute: u32,
+    buckets: Mutex<HashMap<IpAddr, (f64, Instant)>>,
+}
+
+impl RateLimiter {
+    pub fn new(per_minute: u32) -> Self {
+        RateLimiter {
+            per_minute: per_minute.max(1),
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Take one request from the client's bucket, or return the seconds
+    /// until one is available
+    pub fn check(&self, client: IpAddr, now: Instant) -> Result<(), u64> {
+        let capacity = self.per_minute as f64;
+        let per_second = capacity / 60.0;
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() > 4096 {
+            // Forget clients whose buckets have refilled
+            buckets.retain(|_, (tokens, last)| {
+                *tokens + now.duration_since(*last).as_secs_f64() * per_second < capacity
+            });
+        }
+        let (tokens, last) = buckets.entry(client).or_insert((capacity, now));
+        *tokens = (*tokens + now.duration_since(*last).as_secs_f64() * per_second).min(capacity);
+        *last = now;
+        if *tokens >= 1.0 {
+            *tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(((1.0 - *tokens) / per_second).ceil() as u64)
+        }
+    }
+}
+
+/// A TLS server configuration from PEM files: `cert` holds the certificate
+/// chain, leaf first, and `key` its private key (PKCS#8, PKCS#1 or SEC1)
+#[cfg(feature = "tls")]
+pub fn load_tls(cert: &Path, key: &Path) -> Result<Arc<rustls::ServerConfig>, String> {
+    use rustls::pki_types::pem::PemObject;
+    use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+
+    let certs = CertificateDer::pem_file_iter(cert)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| format!("cannot read certificates from {}: {}", cert.display(), e))?;
+    if certs.is_empty() {
+        return Err(format!("no certificates in {}", cert.display()));
+    }
+    let private_key =
+        PrivateKeyDer::from_pem_file(key).map_err(|e| format!("cannot read a private key from {}: {}", key.display(), e))?;
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let config = rustls::ServerConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .map_err(|e| e.to_string())?
+        .with_no_client_auth()
+        .with_single_cert(certs, private_key)
+        .map_err(|e| format!("unusable certificate or key: {}", e))?;
+    Ok(Arc::new(config))
+}
+
+/// Compare secrets without stopping at the first differing byte
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// Body of `POST /scans`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ScanRequest {
+    pub path: String,
+    /// Scan the whole tree instead of only the directory's entries
+    #[serde(default)]
+    pub recursive: bool,
+    #[serde(default)]
+    pub search: Option<String>,
+    #[serde(default)]
+    pub excluding: Option<String>,
+    /// Fill in SHA-256 hashes of regular files
+    #[serde(default)]
+    pub hashing: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Done,
+    Failed,
+    Cancelled,
+}
+
+impl JobStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            JobStatus::Queued => "queued",
+            JobStatus::Running => "running",
+            JobStatus::Done => "done",
+            JobStatus::Failed => "failed",
+            JobStatus::Cancelled => "cancelled",
+        }
+    }
+
+    pub fn is_finished(self) -> bool {
+        matches!(self, JobStatus::Done | JobStatus::Failed | JobStatus::Cancelled)
+    }
+}
+
+/// What `GET /scans/{id}` reports about a job
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct JobSummary {
+    pub id: u64,
+    pub path: String,
+    pub status: JobStatus,
+    pub files_found: usize,
+    pub submitted_at: String,
+    pub started_at: Option<String>,
+    pub finished_at: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Body of `GET /health`
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Health {
+    pub status: String,
+    pub version: String,
+}
+
+/// Body of every error response
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(rename = "Error")]
+pub struct ApiError {
+    pub error: String,
+}
+
+/// Body of `GET /scans/{id}/stats`: totals of a finished scan
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct ScanStats {
+    pub metadata: ScanMetadata,
+    pub files: u64,
+    pub directories: u64,
+    /// Bytes in files, not counting directories
+    pub total_bytes: u64,
+    /// Largest first
+    pub by_extension: Vec<TypeSize>,
+    /// Largest first
+    pub by_mime_type: Vec<TypeSize>,
+}
+
+impl ScanStats {
+    fn new(metadata: ScanMetadata, files: &[FileInfo]) -> Self {
+        let directories = files.iter().filter(|file| file.is_directory).count() as u64;
+        ScanStats {
+            metadata,
+            files: files.len() as u64 - directories,
+            directories,
+            total_bytes: files.iter().filter(|file| !file.is_directory).map(|file| file.size).sum(),
+            by_extension: sizes_by(files, extension_key),
+            by_mime_type: sizes_by(files, |file| file.file_type.clone()),
+        }
+    }
+}
+
+struct Job {
+    id: u64,
+    request: ScanRequest,
+    submitted_at: String,
+    cancelled: AtomicBool,
+    state: Mutex<JobState>,
+}
+
+struct JobState {
+    status: JobStatus,
+    files_found: usize,
+    started_at: Option<String>,
+    finished_at: Option<String>,
+    /// When the job finished, for the result cache
+    finished: Option<Instant>,
+    error: Option<String>,
+    results: Option<Vec<FileInfo>>,
+}
+
+impl Job {
+    fn summary(&self) -> JobSummary {
+        let state = self.state.lock().unwrap();
+        JobSummary {
+            id: self.id,
+            path: self.request.path.clone(),
+            status: state.status,
+            files_found: state.files_found,
+            submitted_at: self.submitted_at.clone(),
+            started_at: state.started_at.clone(),
+            finished_at: state.finished_at.clone(),
+            error: state.error.clone(),
+        }
+    }
+
+    fn finish(&self, status: JobStatus, results: Option<Vec<FileInfo>>, error: Option<String>) {
+        let mut state = self.state.lock().unwrap();
+        state.status = status;
+        state.finished_at = Some(timestamp());
+        state.finished = Some(Instant::now());
+        state.results = results;
+        state.error = error;
+    }
+}
+
+#[derive(Default)]
+struct QueueState {
+    next_id: u64,
+    jobs: BTreeMap<u64, Arc<Job>>,
+    pending: VecDeque<u64>,
+}
+
+impl QueueState {
+    /// Forget finished jobs older than `ttl`, then the oldest finished ones
+    /// past `max_finished`
+    fn prune(&mut self, ttl: Duration, max_finished: usize) {
+        let mut finished: Vec<(u64, Instant)> = self
+            .jobs
+            .values()
+            .filter_map(|job| job.state.lock().unwrap().finished.map(|at| (job.id, at)))
+            .collect();
+        finished.sort_by_key(|&(_, at)| at);
+        let excess = finished.len().saturating_sub(max_finished);
+        for (index, (id, at)) in finished.into_iter().enumerate() {
+            if index < excess || at.elapsed() >= ttl {
+                self.jobs.remove(&id);
+            }
+        }
+    }
+}
+
+/// Scan jobs by id, plus the ids waiting for a worker
+pub struct ScanQueue {
+    state: Mutex<QueueState>,
+    wake: Condvar,
+    max_queued: usize,
+    results_ttl: Duration,
+    max_finished: usize,
+}
+
+impl Default for ScanQueue {
+    fn default() -> Self {
+        ScanQueue::new(&DaemonConfig::default())
+    }
+}
+
+impl ScanQueue {
+    /// An empty queue with the limits in `config`
+    pub fn new(config: &DaemonConfig) -> Self {
+        ScanQueue {
+            state: Mutex::new(QueueState::default()),
+            wake: Condvar::new(),
+            max_queued: config.max_queued,
+            results_ttl: config.results_ttl,
+            max_finished: config.max_finished,
+        }
+    }
+
+    /// The state, without finished jobs that are due to be forgotten
+    fn lock(&self) -> std::sync::MutexGuard<'_, QueueState> {
+        let mut state = self.state.lock().unwrap();
+        state.prune(self.results_ttl, self.max_finished);
+        state
+    }
+
+    /// Queue a scan and return its summary; `None` when [`max_queued`]
+    /// scans are already waiting
+    ///
+    /// [`max_queued`]: DaemonConfig::max_queued
+    pub fn submit(&self, request: ScanRequest) -> Option<JobSummary> {
+        let mut state = self.lock();
+        if state.pending.len() >= self.max_queued {
+            return None;
+        }
+        state.next_id += 1;
+        let job = Arc::new(Job {
+            id: state.next_id,
+            request,
+            submitted_at: timestamp(),
+            cancelled: AtomicBool::new(false),
+            state: Mutex::new(JobState {
+                status: JobStatus::Queued,
+                files_found: 0,
+                started_at: None,
+                finished_at: None,
+                finished: None,
+                error: None,
+                results: None,
+            }),
+        });
+        state.jobs.insert(job.id, Arc::clone(&job));
+        state.pending.push_back(job.id);
+        self.wake.notify_one();
+        Some(job.summary())
+    }
+
+    /// A job for the same request that is queued, running, or finished
+    /// successfully less than `ttl` ago
+    pub fn find_reusable(&self, request: &ScanRequest, ttl: Duration) -> Option<JobSummary> {
+        let state = self.lock();
+        state
+            .jobs
+            .values()
+            .rev()
+            .find(|job| {
+                let job_state = job.state.lock().unwrap();
+                job.request == *request
+                    && match job_state.status {
+                        JobStatus::Queued | JobStatus::Running => !job.cancelled.load(Ordering::Relaxed),
+                        JobStatus::Done => job_state.finished.is_some_and(|at| at.elapsed() < ttl),
+                        JobStatus::Failed | JobStatus::Cancelled => false,
+                    }
+            })
+            .map(|job| job.summary())
+    }
+
+    pub fn list(&self) -> Vec<JobSummary> {
+        let state = self.lock();
+        state.jobs.values().map(|job| job.summary()).collect()
+    }
+
+    pub fn status(&self, id: u64) -> Option<JobSummary> {
+        self.job(id).map(|job| job.summary())
+    }
+
+    /// The files of a finished scan; `Err` carries the job's current status
+    pub fn results(&self, id: u64) -> Option<Result<(Vec<FileInfo>, String), JobStatus>> {
+        let job = self.job(id)?;
+        let state = job.state.lock().unwrap();
+        Some(match (&state.results, state.status) {
+            (Some(files), JobStatus::Done) => {
+                Ok((files.clone(), state.finished_at.clone().unwrap_or_default()))
+            }
+            (_, status) => Err(status),
+        })
+    }
+
+    /// Cancel a queued or running job, or forget a finished one
+    pub fn cancel(&self, id: u64) -> Option<JobSummary> {
+        let mut state = self.lock();
+        let job = Arc::clone(state.jobs.get(&id)?);
+        let status = job.state.lock().unwrap().status;
+        match status {
+            JobStatus::Queued => {
+                state.pending.retain(|pending| *pending != id);
+                job.cancelled.store(true, Ordering::Relaxed);
+                job.finish(JobStatus::Cancelled, None, None);
+            }
+            // The worker notices between entries and marks the job cancelled
+            JobStatus::Running => job.cancelled.store(true, Ordering::Relaxed),
+            _ => {
+                state.jobs.remove(&id);
+            }
+        }
+        Some(job.summary())
+    }
+
+    fn job(&self, id: u64) -> Option<Arc<Job>> {
+        self.lock().jobs.get(&id).cloned()
+    }
+
+    /// Block until a job is queued, then take it
+    fn next_job(&self) -> Arc<Job> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if let Some(id) = state.pending.pop_front() {
+                if let Some(job) = state.jobs.get(&id) {
+                    return Arc::clone(job);
+                }
+            } else {
+                state = self.wake.wait(state).unwrap();
+            }
+        }
+    }
+
+    /// Take and run jobs forever
+    fn work(&self) {
+        loop {
+            let job = self.next_job();
+            run_job(&job);
+            drop(self.lock());
+        }
+    }
+}
+
+fn run_job(job: &Job) {
+    {
+        let mut state = job.state.lock().unwrap();
+        state.status = JobStatus::Running;
+        state.started_at = Some(timestamp());
+    }
+
+    let request = &job.request;
+    let mut builder = ScanBuilder::new(&request.path).hashing(request.hashing);
+    if !request.recursive {
+        builder = builder.max_depth(1);
+    }
+    if let Some(search) = &request.search {
+        builder = builder.search(search);
+    }
+    if let Some(excluding) = &request.excluding {
+        builder = builder.excluding(excluding);
+    }
+    let scan = match builder.build() {
+        Ok(scan) => scan,
+        Err(e) => {
+            job.finish(JobStatus::Failed, None, Some(format!("Invalid exclusion pattern: {}", e)));
+            return;
+        }
+    };
+
+    let mut files = Vec::new();
+    for file in scan {
+        if job.cancelled.load(Ordering::Relaxed) {
+            // Dropping the scan stops its threads
+            job.finish(JobStatus::Cancelled, None, None);
+            return;
+        }
+        files.push(file);
+        job.state.lock().unwrap().files_found = files.len();
+    }
+    sort_files(&mut files, &SortSpec::default());
+    job.finish(JobStatus::Done, Some(files), None);
+}
+
+fn timestamp() -> String {
+    chrono::DateTime::<chrono::Utc>::from(Clock::active().now())
+        .format("%Y-%m-%d %H:%M:%S UTC")
+        .to_string()
+}
+
+/// A parsed HTTP request
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub method: String,
+    /// Path without the query string
+    pub path: String,
+    pub query: String,
+    /// Header names are lowercased
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Response {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub headers: Vec<(&'static str, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn json<T: Serialize>(status: u16, value: &T) -> Response {
+        Response {
+            status,
+            content_type: "application/json",
+            headers: Vec::new(),
+            body: serde_json::to_vec_pretty(value).unwrap_or_default(),
+        }
+    }
+
+    pub fn error(status: u16, message: impl Into<String>) -> Response {
+        Response::json(status, &ApiError { error: message.into() })
+    }
+
+    pub fn with_header(mut self, name: &'static str, value: impl Into<String>) -> Response {
+        self.headers.push((name, value.into()));
+        self
+    }
+
+    fn write_to(&self, stream: &mut impl Write) -> io::Result<()> {
+        write!(
+            stream,
+            "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+            self.status,
+            reason(self.status),
+            self.content_type,
+            self.body.len()
+        )?;
+        for (name, value) in &self.headers {
+            write!(stream, "{}: {}\r\n", name, value)?;
+        }
+        stream.write_all(b"\r\n")?;
+        stream.write_all(&self.body)?;
+        stream.flush()
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        202 => "Accepted",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        413 => "Payload Too Large",
+        431 => "Request Header Fields Too Large",
+        429 => "Too Many Requests",
+        503 => "Service Unavailable",
+        _ => "Error",
+    }
+}
+
+/// Read one request. `Ok(Err(response))` is the error to answer with when
+/// the request line is malformed, the headers are over [`MAX_HEADER_BYTES`]
+/// or [`MAX_HEADERS`], or the body is over [`MAX_BODY`].
+fn read_request(stream: &mut impl Read) -> io::Result<Result<Request, Response>> {
+    let mut reader = BufReader::new(stream);
+    let mut head = (&mut reader).take(MAX_HEADER_BYTES);
+    let too_large = || Ok(Err(Response::error(431, "Request headers too large")));
+    let mut line = String::new();
+    head.read_line(&mut line)?;
+    // Cut off by the limit before the end of the line
+    if head.limit() == 0 && !line.ends_with('\n') {
+        return too_large();
+    }
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Ok(Err(Response::error(400, "Malformed request line")));
+    };
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let mut request = Request {
+        method: method.to_string(),
+        path: path.to_string(),
+        query: query.to_string(),
+        headers: Vec::new(),
+        body: Vec::new(),
+    };
+
+    loop {
+        line.clear();
+        let read = head.read_line(&mut line)?;
+        if head.limit() == 0 && !line.ends_with('\n') {
+            return too_large();
+        }
+        if read == 0 {
+            break;
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if request.headers.len() == MAX_HEADERS {
+            return too_large();
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            request
+                .headers
+                .push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+        }
+    }
+
+    let length: usize = request
+        .header("content-length")
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(0);
+    if length > MAX_BODY {
+        return Ok(Err(Response::error(413, "Request body too large")));
+    }
+    request.body = vec![0; length];
+    reader.read_exact(&mut request.body)?;
+    Ok(Ok(request))
+}
+
+/// Route a request to the queue
+pub fn handle(request: &Request, queue: &ScanQueue, config: &DaemonConfig) -> Response {
+    let segments: Vec<&str> = request.path.trim_matches('/').split('/').collect();
+    match (request.method.as_str(), segments.as_slice()) {
+        #[cfg(feature = "webui")]
+        ("GET", [""]) => Response {
+            status: 200,
+            content_type: "text/html; charset=utf-8",
+            headers: Vec::new(),
+            body: WEB_UI.as_bytes().to_vec(),
+        },
+        ("GET", ["health"]) => Response::json(
+            200,
+            &Health {
+                status: "ok".to_string(),
+                version: env!("CARGO_PKG_VERSION").to_string(),
+            },
+        ),
+        ("GET", ["openapi.json"]) => Response::json(200, &openapi()),
+        ("POST", ["scans"]) => {
+            let scan: ScanRequest = match serde_json::from_slice(&request.body) {
+                Ok(scan) => scan,
+                Err(e) => return Response::error(400, format!("Invalid scan request: {}", e)),
+            };
+            if !Path::new(&scan.path).is_dir() {
+                return Response::error(400, format!("'{}' is not a directory", scan.path));
+            }
+            if let Some(job) = config.cache_ttl.and_then(|ttl| queue.find_reusable(&scan, ttl)) {
+                return Response::json(200, &job);
+            }
+            match queue.submit(scan) {
+                Some(job) => Response::json(202, &job),
+                None => Response::error(503, "Scan queue is full").with_header("Retry-After", "60"),
+            }
+        }
+        ("GET", ["scans"]) => Response::json(200, &queue.list()),
+        ("GET", ["scans", id]) => match id.parse().ok().and_then(|id| queue.status(id)) {
+            Some(summary) => Response::json(200, &summary),
+            None => Response::error(404, format!("No scan {}", id)),
+        },
+        ("GET", ["scans", id, "results"]) => match finished_scan(queue, id) {
+            Ok((metadata, files)) => Response::json(200, &ScanExport { metadata, files }),
+            Err(response) => response,
+        },
+        ("GET", ["scans", id, "stats"]) => match finished_scan(queue, id) {
+            Ok((metadata, files)) => Response::json(200, &ScanStats::new(metadata, &files)),
+            Err(response) => response,
+        },
+        ("GET", ["scans", id, "duplicates"]) => match finished_scan(queue, id) {
+            Ok((metadata, files)) => {
+                let mut by_size: HashMap<u64, Vec<String>> = HashMap::new();
+                for file in files.into_iter().filter(|file| file.kind == FileKind::File) {
+                    by_size.entry(file.size).or_default().push(file.path);
+                }
+                let groups = group_duplicates(&by_size, &DuplicateOptions::default());
+                Response::json(
+                    200,
+                    &DuplicatesExport {
+                        metadata,
+                        total_wasted_bytes: groups.iter().map(|group| group.wasted_bytes).sum(),
+                        groups,
+                    },
+                )
+            }
+            Err(response) => response,
+        },
+        ("DELETE", ["scans", id]) => match id.parse().ok().and_then(|id| queue.cancel(id)) {
+            Some(summary) => Response::json(200, &summary),
+            None => Response::error(404, format!("No scan {}", id)),
+        },
+        (_, ["health"])
+        | (_, ["openapi.json"])
+        | (_, ["scans"])
+        | (_, ["scans", _])
+        | (_, ["scans", _, "results" | "stats" | "duplicates"]) => {
+            Response::error(405, format!("{} is not allowed on {}", request.method, request.path))
+        }
+        _ => Response::error(404, format!("No route for {}", request.path)),
+    }
+}
+
+/// The files of job `id` and when it finished, or the 404 or 409 to answer
+/// with when there is no such job or it has not finished successfully
+fn finished_scan(queue: &ScanQueue, id: &str) -> Result<(ScanMetadata, Vec<FileInfo>), Response> {
+    match id.parse().ok().and_then(|id| queue.results(id)) {
+        Some(Ok((files, finished_at))) => Ok((
+            ScanMetadata {
+                scanned_at: finished_at,
+                ..ScanMetadata::current()
+            },
+            files,
+        )),
+        Some(Err(status)) => Err(Response::error(
+            409,
+            format!("Scan {} has no results ({})", id, status.as_str()),
+        )),
+        None => Err(Response::error(404, format!("No scan {}", id))),
+    }
+}
+
+/// OpenAPI 3.0 description of the API, served at `/openapi.json` so clients
+/// can be generated from it. The schemas are derived from the types the
+/// handlers serialize, so they cannot drift from the responses.
+pub fn openapi() -> serde_json::Value {
+    use serde_json::json;
+
+    let mut responses = SchemaSettings::openapi3().for_serialize().into_generator();
+    let mut requests = SchemaSettings::openapi3().for_deserialize().into_generator();
+    let reply = |description: &str, schema: schemars::Schema| {
+        json!({ "description": description, "content": { "application/json": { "schema": schema } } })
+    };
+    let error = responses.subschema_for::<ApiError>();
+    let error = |description: &str| {
+        json!({ "description": description, "content": { "application/json": { "schema": error.clone() } } })
+    };
+    let id = json!([{
+        "name": "id", "in": "path", "required": true,
+        "schema": { "type": "integer", "format": "int64", "minimum": 1 }
+    }]);
+    let finished = |operation: &str, description: &str, schema: schemars::Schema| {
+        json!({ "get": {
+            "operationId": operation,
+            "parameters": id,
+            "responses": {
+                "200": reply(description, schema),
+                "404": error("No such job"),
+                "409": error("Job has not finished successfully")
+            }
+        }})
+    };
+    let paths = json!({
+        "/health": { "get": {
+            "operationId": "health",
+            "security": [],
+            "responses": { "200": reply("Daemon is up", responses.subschema_for::<Health>()) }
+        }},
+        "/scans": {
+            "get": {
+                "operationId": "listScans",
+                "responses": {
+                    "200": reply("Every job, oldest first", responses.subschema_for::<Vec<JobSummary>>())
+                }
+            },
+            "post": {
+                "operationId": "submitScan",
+                "description": "Needs an admin token when tokens are configured",
+                "requestBody": {
+                    "required": true,
+                    "content": { "application/json": { "schema": requests.subschema_for::<ScanRequest>() } }
+                },
+                "responses": {
+                    "200": reply(
+                        "An identical scan that is in progress or recently finished",
+                        responses.subschema_for::<JobSummary>()
+                    ),
+                    "202": reply("Scan queued", responses.subschema_for::<JobSummary>()),
+                    "400": error("Invalid request or path is not a directory"),
+                    "403": error("Token is read-only"),
+                    "503": error("Too many scans are waiting already")
+                }
+            }
+        },
+        "/scans/{id}": {
+            "get": {
+                "operationId": "getScan",
+                "parameters": id,
+                "responses": {
+                    "200": reply("Job status", responses.subschema_for::<JobSummary>()),
+                    "404": error("No such job")
+                }
+            },
+            "delete": {
+                "operationId": "cancelScan",
+                "description": "Cancel a queued or running job, or forget a finished one",
+                "parameters": id,
+                "responses": {
+                    "200": reply("Job status after cancelling", responses.subschema_for::<JobSummary>()),
+                    "403": error("Token is read-only"),
+                    "404": error("No such job")
+                }
+            }
+        },
+        "/scans/{id}/results": finished("getScanResults", "The finished scan", responses.subschema_for::<ScanExport>()),
+        "/scans/{id}/stats": finished(
+            "getScanStats",
+            "File count and sizes by extension and MIME type",
+            responses.subschema_for::<ScanStats>()
+        ),
+        "/scans/{id}/duplicates": finished(
+            "getScanDuplicates",
+            "Files of the scan with identical contents, largest first",
+            responses.subschema_for::<DuplicatesExport>()
+        )
+    });
+
+    let mut schemas = responses.take_definitions(true);
+    schemas.extend(requests.take_definitions(true));
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "filebyte",
+            "version": env!("CARGO_PKG_VERSION"),
+            "description": "Queue filesystem scans and fetch their results. Clients over the daemon's per-minute request limit get 429 with a Retry-After header."
+        },
+        "security": [{ "bearer": [] }],
+        "paths": paths,
+        "components": {
+            "securitySchemes": { "bearer": { "type": "http", "scheme": "bearer" } },
+            "schemas": schemas
+        }
+    })
+}
+
+/// A bound daemon, ready to [`run`](Daemon::run)
+pub struct Daemon {
+    listener: TcpListener,
+    queue: Arc<ScanQueue>,
+    config: Arc<DaemonConfig>,
+    limiter: Option<Arc<RateLimiter>>,
+}
+
+impl Daemon {
+    pub fn bind(addr: &str, config: DaemonConfig) -> io::Result<Daemon> {
+        Ok(Daemon {
+            listener: TcpListener::bind(addr)?,
+            queue: Arc::new(ScanQueue::new(&config)),
+            limiter: config.rate_limit.map(|limit| Arc::new(RateLimiter::new(limit))),
+            config: Arc::new(config),
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Start the scan workers and serve requests until the process exits
+    pub fn run(self) {
+        for _ in 0..self.config.max_concurrent.max(1) {
+            let queue = Arc::clone(&self.queue);
+            thread::spawn(move || queue.work());
+        }
+
+        let open = Arc::new(AtomicUsize::new(0));
+        for mut stream in self.listener.incoming().flatten() {
+            if open.fetch_add(1, Ordering::AcqRel) >= self.config.max_connections.max(1) {
+                open.fetch_sub(1, Ordering::AcqRel);
+                // A TLS client could not read a plain answer; it only sees the close
+                if !self.serves_tls() && stream.set_nonblocking(true).is_ok() {
+                    // Whatever part of the request has arrived is read first,
+                    // or closing would reset the connection before the client
+                    // reads the answer
+                    let _ = io::copy(&mut (&stream).take(MAX_HEADER_BYTES), &mut io::sink());
+                    let _ = Response::error(503, "Too many connections").write_to(&mut stream);
+                }
+                continue;
+            }
+            let slot = ConnectionSlot(Arc::clone(&open));
+            let queue = Arc::clone(&self.queue);
+            let config = Arc::clone(&self.config);
+            let limiter = self.limiter.clone();
+            thread::spawn(move || {
+                let _slot = slot;
+                handle_connection(stream, &queue, &config, limiter.as_deref());
+            });
+        }
+    }
+
+    fn serves_tls(&self) -> bool {
+        #[cfg(feature = "tls")]
+        return self.config.tls.is_some();
+        #[cfg(not(feature = "tls"))]
+        false
+    }
+}
+
+/// Counts a connection as open until it is dropped
+struct ConnectionSlot(Arc<AtomicUsize>);
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// A client connection whose reads share one deadline, so a client sending a
+/// byte at a time cannot hold it open past the request timeout
+struct DeadlineStream {
+    stream: TcpStream,
+    deadline: Instant,
+}
+
+impl Read for DeadlineStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let left = self.deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+        self.stream.set_read_timeout(Some(left))?;
+        self.stream.read(buf)
+    }
+}
+
+impl Write for DeadlineStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+/// Read the request on `stream`, over TLS if configured, and answer it
+fn handle_connection(stream: TcpStream, queue: &ScanQueue, config: &DaemonConfig, limiter: Option<&RateLimiter>) {
+    if stream.set_write_timeout(Some(WRITE_TIMEOUT)).is_err() {
+        return;
+    }
+    let peer = stream.peer_addr().ok().map(|peer| peer.ip());
+    let mut stream = DeadlineStream {
+        stream,
+        deadline: Instant::now() + config.request_timeout,
+    };
+    #[cfg(feature = "tls")]
+    if let Some(tls) = &config.tls {
+        let Ok(connection) = rustls::ServerConnection::new(Arc::clone(tls)) else {
+            return;
+        };
+        // The handshake happens on the first read, within the deadline
+        let mut stream = rustls::StreamOwned::new(connection, stream);
+        serve_connection(&mut stream, peer, queue, config, limiter);
+        // Written directly: flushing the stream would wait for a handshake
+        // that failed
+        stream.conn.send_close_notify();
+        let _ = stream.conn.write_tls(&mut stream.sock);
+        return;
+    }
+    serve_connection(&mut stream, peer, queue, config, limiter);
+}
+
+/// Answer the one request a client sends on `stream`
+fn serve_connection(
+    stream: &mut (impl Read + Write),
+    peer: Option<IpAddr>,
+    queue: &ScanQueue,
+    config: &DaemonConfig,
+    limiter: Option<&RateLimiter>,
+) {
+    let response = match read_request(stream) {
+        Ok(Ok(request)) => {
+            let limited = match (limiter, peer) {
+                (Some(limiter), Some(peer)) if request.path != "/health" => limiter.check(peer, Instant::now()).err(),
+                _ => None,
+            };
+            match (limited, authorize(&request, &config.tokens)) {
+                (Some(retry_after), _) => {
+                    Response::error(429, "Too many requests").with_header("Retry-After", retry_after.to_string())
+                }
+                (None, Ok(())) => handle(&request, queue, config),
+                (None, Err(response)) => response,
+            }
+        }
+        Ok(Err(response)) => response,
+        Err(_) => return,
+    };
+    let _ = response.write_to(stream);
+}
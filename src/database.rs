@@ -0,0 +1,283 @@
+use crate::types::FileInfo;
+use crate::utils::redact_url;
+use std::io::{self, BufWriter, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+/// Table that exported rows are inserted into
+const TABLE_NAME: &str = "filebyte_files";
+/// How long to wait for the ClickHouse server to accept a connection
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long a single read or write to the ClickHouse server may stall
+const IO_TIMEOUT: Duration = Duration::from_secs(120);
+/// Bytes buffered before a chunk of the insert is sent
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Export files to a database table, dispatching on the URL scheme
+pub fn export_to_database(files: &[FileInfo], url: &str) -> Result<(), String> {
+    if url.starts_with("postgres://") || url.starts_with("postgresql://") {
+        export_to_postgres(files, url)
+    } else if url.starts_with("clickhouse://") {
+        export_to_clickhouse(files, url)
+    } else {
+        Err(format!(
+            "Unsupported database URL '{}' (expected postgres:// or clickhouse://)",
+            redact_url(url)
+        ))
+    }?;
+    println!("Exported {} rows to {}", files.len(), TABLE_NAME);
+    Ok(())
+}
+
+/// Bulk-load rows with COPY through psql, so no client library is needed.
+/// Rows are written to psql as they are formatted.
+fn export_to_postgres(files: &[FileInfo], url: &str) -> Result<(), String> {
+    // CSV COPY reads an empty unquoted field as NULL; only the timestamps
+    // may be NULL, so an empty owner or group must stay an empty string
+    let script = format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+            name TEXT NOT NULL,
+            path TEXT NOT NULL,
+            size BIGINT NOT NULL,
+            file_type TEXT NOT NULL,
+            created TEXT,
+            modified TEXT,
+            permissions TEXT NOT NULL,
+            owner TEXT NOT NULL,
+            \"group\" TEXT NOT NULL DEFAULT '',
+            is_directory BOOLEAN NOT NULL
+        );
+        ALTER TABLE {} ADD COLUMN IF NOT EXISTS \"group\" TEXT NOT NULL DEFAULT '';
+        COPY {} (name, path, size, file_type, created, modified, permissions, owner, \"group\", is_directory) FROM STDIN WITH (FORMAT csv, FORCE_NOT_NULL (name, path, file_type, permissions, owner, \"group\"));\n",
+        TABLE_NAME, TABLE_NAME, TABLE_NAME
+    );
+
+    let mut child = Command::new("psql")
+        .arg(url)
+        .args(["-q", "-v", "ON_ERROR_STOP=1"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("could not run psql: {}", e))?;
+
+    // A write error means psql stopped reading; its exit status says why
+    let written = match child.stdin.take() {
+        Some(stdin) => write_copy(BufWriter::new(stdin), &script, files),
+        None => Ok(()),
+    };
+
+    let status = child.wait().map_err(|e| e.to_string())?;
+    if !status.success() {
+        Err(format!("psql exited with {}", status))
+    } else {
+        written.map_err(|e| e.to_string())
+    }
+}
+
+/// The COPY script followed by one CSV record per file
+fn write_copy(mut writer: impl Write, script: &str, files: &[FileInfo]) -> io::Result<()> {
+    writer.write_all(script.as_bytes())?;
+    let mut wtr = csv::WriterBuilder::new().has_headers(false).from_writer(&mut writer);
+    for file in files {
+        wtr.write_record([
+            file.name.as_str(),
+            file.path.as_str(),
+            &file.size.to_string(),
+            file.file_type.as_str(),
+            file.created.as_deref().unwrap_or(""),
+            file.modified.as_deref().unwrap_or(""),
+            file.permissions.as_str(),
+            file.owner.as_str(),
+            file.group.as_str(),
+            if file.is_directory { "true" } else { "false" },
+        ])?;
+    }
+    wtr.flush()?;
+    drop(wtr);
+    writer.write_all(b"\\.\n")?;
+    writer.flush()
+}
+
+/// Insert rows through the ClickHouse HTTP interface as JSONEachRow
+fn export_to_clickhouse(files: &[FileInfo], url: &str) -> Result<(), String> {
+    let rest = url.trim_start_matches("clickhouse://");
+    let (credentials, rest) = match rest.rsplit_once('@') {
+        Some((creds, rest)) => (Some(creds), rest),
+        None => (None, rest),
+    };
+    let (host, database) = rest.split_once('/').unwrap_or((rest, "default"));
+    let host = if host.contains(':') {
+        host.to_string()
+    } else {
+        format!("{}:8123", host)
+    };
+    let database = if database.is_empty() { "default" } else { database };
+
+    let create = format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+            name String,
+            path String,
+            size UInt64,
+            file_type String,
+            created Nullable(String),
+            modified Nullable(String),
+            permissions String,
+            owner String,
+            `group` String,
+            is_directory Bool
+        ) ENGINE = MergeTree ORDER BY path",
+        TABLE_NAME
+    );
+    clickhouse_query(&host, database, credentials, &create, |_| Ok(()))?;
+    // Tables created before the group column was exported
+    let upgrade = format!("ALTER TABLE {} ADD COLUMN IF NOT EXISTS `group` String", TABLE_NAME);
+    clickhouse_query(&host, database, credentials, &upgrade, |_| Ok(()))?;
+
+    let insert = format!("INSERT INTO {} FORMAT JSONEachRow", TABLE_NAME);
+    clickhouse_query(&host, database, credentials, &insert, |body| {
+        for file in files {
+            let row = serde_json::json!({
+                "name": file.name,
+                "path": file.path,
+                "size": file.size,
+                "file_type": file.file_type,
+                "created": file.created,
+                "modified": file.modified,
+                "permissions": file.permissions,
+                "owner": file.owner,
+                "group": file.group,
+                "is_directory": file.is_directory,
+            });
+            serde_json::to_writer(&mut *body, &row)?;
+            body.write_all(b"\n")?;
+        }
+        Ok(())
+    })
+}
+
+/// Run one query, sending whatever `body` writes as the request body in
+/// chunks so a large insert is never held in memory at once
+fn clickhouse_query(
+    host: &str,
+    database: &str,
+    credentials: Option<&str>,
+    query: &str,
+    body: impl FnOnce(&mut dyn Write) -> io::Result<()>,
+) -> Result<(), String> {
+    let stream = connect(host).map_err(|e| format!("could not connect to {}: {}", host, e))?;
+    stream.set_read_timeout(Some(IO_TIMEOUT)).map_err(|e| e.to_string())?;
+    stream.set_write_timeout(Some(IO_TIMEOUT)).map_err(|e| e.to_string())?;
+
+    let mut request = format!(
+        "POST /?database={}&query={} HTTP/1.1\r\nHost: {}\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n",
+        percent_encode(database),
+        percent_encode(query),
+        host,
+    );
+    if let Some(credentials) = credentials {
+        let (user, password) = credentials.split_once(':').unwrap_or((credentials, ""));
+        request.push_str(&format!(
+            "X-ClickHouse-User: {}\r\nX-ClickHouse-Key: {}\r\n",
+            user, password
+        ));
+    }
+    request.push_str("\r\n");
+
+    let mut writer = Chunked::new(&stream);
+    let sent = writer
+        .inner
+        .write_all(request.as_bytes())
+        .and_then(|()| body(&mut writer))
+        .and_then(|()| writer.finish());
+    // The server may answer early, e.g. to reject the query; its answer
+    // explains more than the broken pipe does
+    let mut response = String::new();
+    let received = (&stream).read_to_string(&mut response);
+
+    let status_line = response.lines().next().unwrap_or("");
+    if status_line.contains(" 200 ") {
+        sent.map_err(|e| e.to_string())
+    } else if response.is_empty() {
+        Err(match sent.and(received) {
+            Err(e) => e.to_string(),
+            Ok(_) => "the server closed the connection without answering".to_string(),
+        })
+    } else {
+        let message = response.split("\r\n\r\n").nth(1).unwrap_or(status_line);
+        Err(message.trim().to_string())
+    }
+}
+
+/// Connect to the first address of `host` that answers within
+/// [`CONNECT_TIMEOUT`]
+fn connect(host: &str) -> io::Result<TcpStream> {
+    let mut last_error = io::Error::new(io::ErrorKind::NotFound, "no address found");
+    for addr in host.to_socket_addrs()? {
+        match TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT) {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last_error = e,
+        }
+    }
+    Err(last_error)
+}
+
+/// HTTP/1.1 chunked transfer encoding over a writer, sending a chunk each
+/// time [`CHUNK_SIZE`] bytes have been buffered
+struct Chunked<W: Write> {
+    inner: W,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> Chunked<W> {
+    fn new(inner: W) -> Self {
+        Chunked {
+            inner,
+            buffer: Vec::with_capacity(CHUNK_SIZE),
+        }
+    }
+
+    fn send_chunk(&mut self) -> io::Result<()> {
+        if !self.buffer.is_empty() {
+            write!(self.inner, "{:x}\r\n", self.buffer.len())?;
+            self.inner.write_all(&self.buffer)?;
+            self.inner.write_all(b"\r\n")?;
+            self.buffer.clear();
+        }
+        Ok(())
+    }
+
+    /// Send what is buffered and the closing empty chunk
+    fn finish(&mut self) -> io::Result<()> {
+        self.send_chunk()?;
+        self.inner.write_all(b"0\r\n\r\n")?;
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> Write for Chunked<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(data);
+        if self.buffer.len() >= CHUNK_SIZE {
+            self.send_chunk()?;
+        }
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.send_chunk()?;
+        self.inner.flush()
+    }
+}
+
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
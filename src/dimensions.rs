@@ -0,0 +1,136 @@
+//! Image dimensions from file headers (`--dimensions`).
+//!
+//! Only the header is read: a fixed prefix for PNG, GIF, BMP and WebP, and
+//! for JPEG the segment headers up to the first start-of-frame marker, so
+//! even large photos cost a few small reads. Other formats are left without
+//! dimensions.
+
+use crate::perf::CountedFile;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// Bytes covering the size fields of every format but JPEG
+const PREFIX_LEN: usize = 32;
+
+fn u16_be(bytes: &[u8]) -> u32 {
+    u16::from_be_bytes([bytes[0], bytes[1]]) as u32
+}
+
+fn u16_le(bytes: &[u8]) -> u32 {
+    u16::from_le_bytes([bytes[0], bytes[1]]) as u32
+}
+
+fn u32_be(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+fn u24_le(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0])
+}
+
+/// Width and height from the start of a PNG, GIF, BMP or WebP file
+pub fn dimensions_from_prefix(prefix: &[u8]) -> Option<(u32, u32)> {
+    if prefix.len() < 26 {
+        return None;
+    }
+    if prefix.starts_with(b"\x89PNG\r\n\x1a\n") && &prefix[12..16] == b"IHDR" {
+        return Some((u32_be(&prefix[16..]), u32_be(&prefix[20..])));
+    }
+    if prefix.starts_with(b"GIF87a") || prefix.starts_with(b"GIF89a") {
+        return Some((u16_le(&prefix[6..]), u16_le(&prefix[8..])));
+    }
+    if prefix.starts_with(b"BM") {
+        let width = i32::from_le_bytes([prefix[18], prefix[19], prefix[20], prefix[21]]);
+        // Negative heights mark top-down bitmaps
+        let height = i32::from_le_bytes([prefix[22], prefix[23], prefix[24], prefix[25]]);
+        return Some((width.unsigned_abs(), height.unsigned_abs()));
+    }
+    if prefix.starts_with(b"RIFF") && &prefix[8..12] == b"WEBP" && prefix.len() >= 30 {
+        return match &prefix[12..16] {
+            // Lossy: 14-bit sizes after the frame tag and start code
+            b"VP8 " => Some((u16_le(&prefix[26..]) & 0x3fff, u16_le(&prefix[28..]) & 0x3fff)),
+            // Lossless: two 14-bit sizes, minus one, packed after the signature
+            b"VP8L" => {
+                let bits = u32::from_le_bytes([prefix[21], prefix[22], prefix[23], prefix[24]]);
+                Some(((bits & 0x3fff) + 1, ((bits >> 14) & 0x3fff) + 1))
+            }
+            // Extended: 24-bit sizes, minus one
+            b"VP8X" => Some((u24_le(&prefix[24..]) + 1, u24_le(&prefix[27..]) + 1)),
+            _ => None,
+        };
+    }
+    None
+}
+
+/// Width and height from the first start-of-frame segment of a JPEG,
+/// with `reader` positioned just after the SOI marker
+fn jpeg_dimensions(reader: &mut (impl Read + Seek)) -> io::Result<Option<(u32, u32)>> {
+    let mut marker = [0u8; 2];
+    loop {
+        reader.read_exact(&mut marker)?;
+        if marker[0] != 0xFF {
+            return Ok(None);
+        }
+        // Fill bytes before a marker
+        while marker[1] == 0xFF {
+            reader.read_exact(&mut marker[1..])?;
+        }
+        // Markers without a length
+        if marker[1] == 0x01 || (0xD0..=0xD7).contains(&marker[1]) {
+            continue;
+        }
+        let mut length = [0u8; 2];
+        reader.read_exact(&mut length)?;
+        let length = u16_be(&length);
+        if length < 2 {
+            return Ok(None);
+        }
+        // SOF0 to SOF15, except DHT (C4), JPG (C8) and DAC (CC)
+        if (0xC0..=0xCF).contains(&marker[1]) && ![0xC4, 0xC8, 0xCC].contains(&marker[1]) {
+            let mut frame = [0u8; 5];
+            reader.read_exact(&mut frame)?;
+            return Ok(Some((u16_be(&frame[3..]), u16_be(&frame[1..]))));
+        }
+        reader.seek(SeekFrom::Current(length as i64 - 2))?;
+    }
+}
+
+/// Width and height of the image at `path`, if it is a format this module
+/// reads and its header is intact
+pub fn image_dimensions(path: &Path) -> Option<(u32, u32)> {
+    let mut reader = BufReader::new(CountedFile::open(path).ok()?);
+    let mut prefix = Vec::with_capacity(PREFIX_LEN);
+    (&mut reader).take(PREFIX_LEN as u64).read_to_end(&mut prefix).ok()?;
+    if prefix.starts_with(b"\xFF\xD8") {
+        reader.seek(SeekFrom::Start(2)).ok()?;
+        return jpeg_dimensions(&mut reader).ok().flatten();
+    }
+    dimensions_from_prefix(&prefix)
+}
+
+/// `4032x3024`, as shown in listings and exports
+pub fn format_dimensions((width, height): (u32, u32)) -> String {
+    format!("{}x{}", width, height)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn image_headers_give_dimensions() {
+        let with_header = |header: &[u8]| {
+            let mut prefix = header.to_vec();
+            prefix.resize(32, 0);
+            dimensions_from_prefix(&prefix)
+        };
+        // Negative height: a top-down bitmap
+        let bmp = [&b"BM"[..], &[0; 16], &100i32.to_le_bytes(), &(-50i32).to_le_bytes()].concat();
+        assert_eq!(with_header(&bmp), Some((100, 50)));
+        let vp8x = [&b"RIFF\0\0\0\0WEBPVP8X"[..], &[0; 8], &[0xff, 0x0e, 0, 0x37, 0x0b, 0]].concat();
+        assert_eq!(with_header(&vp8x), Some((3840, 2872)));
+        assert_eq!(with_header(b"GIF87a\x01\x00\x02\x00"), Some((1, 2)));
+        assert_eq!(with_header(b"plain text"), None);
+        assert_eq!(dimensions_from_prefix(b"GIF89a"), None);
+    }
+}
@@ -1,73 +1,679 @@
-use crate::analysis::{find_duplicates, show_detailed_analysis};
-use crate::collect::{collect_files, collect_files_recursive};
-use crate::display::{display_files, show_file_type_stats};
+use crate::analysis::{find_duplicates, show_detailed_analysis, show_entry_hotspots, show_largest_dirs, DirRanking};
+use crate::collect::{collect_files, collect_files_recursive, skipped_paths, total_size, virtual_exclusions};
+use crate::mounts::{is_network_source, list_mounts as mount_table, path_to_mount, Mount};
+use crate::display::{display_files, limit_files, show_file_type_stats, show_virtual_exclusions};
 use crate::tree::print_tree;
-use crate::types::{SizeUnit, SortBy};
+use crate::types::{
+    CollectOptions, DisplayOptions, DuplicateOptions, Filter, ScanMetadata, SizeUnit, SortSpec, TreeOptions,
+};
 use colored::Colorize;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use sysinfo::Disks;
-use std::path::Path;
 
-/// List all available disks
-pub fn list_disks(color: bool, size_unit: &SizeUnit, auto_size: bool) {
-    let disks = Disks::new_with_refreshed_list();
-    println!("");
-    println!("Available disks:");
-    println!("{}", "─".repeat(60));
+/// Inode counts of a filesystem. Running out of inodes makes file creation
+/// fail with "No space left on device" while space is still free.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InodeUsage {
+    pub total: u64,
+    pub free: u64,
+}
 
-    for disk in &disks {
-        let name = disk.name().to_string_lossy();
-        let mount_point = disk.mount_point().display();
-        let total_space = if auto_size {
-            SizeUnit::auto_format_size(disk.total_space())
-        } else {
-            size_unit.format_size(disk.total_space())
-        };
-        let available_space = if auto_size {
-            SizeUnit::auto_format_size(disk.available_space())
+impl InodeUsage {
+    pub fn used(&self) -> u64 {
+        self.total.saturating_sub(self.free)
+    }
+
+    pub fn percent_used(&self) -> f64 {
+        self.used() as f64 / self.total as f64 * 100.0
+    }
+}
+
+impl std::fmt::Display for InodeUsage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:.1}% ({} of {})", self.percent_used(), self.used(), self.total)
+    }
+}
+
+/// Inode counts of the filesystem holding `path`. `None` when they cannot be
+/// read or the filesystem allocates inodes dynamically (btrfs, most network
+/// filesystems), so it reports none.
+#[cfg(unix)]
+pub fn inode_usage(path: &Path) -> Option<InodeUsage> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = std::ffi::CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stats: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: `path` is NUL-terminated and `stats` is a valid out pointer
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stats) } != 0 || stats.f_files == 0 {
+        return None;
+    }
+    Some(InodeUsage {
+        total: stats.f_files as u64,
+        free: stats.f_ffree as u64,
+    })
+}
+
+/// Windows filesystems have no fixed inode table
+#[cfg(not(unix))]
+pub fn inode_usage(_path: &Path) -> Option<InodeUsage> {
+    None
+}
+
+/// Filesystems that hold no user data of their own: memory-backed, kernel
+/// interfaces, read-only images (snaps) and container layers
+pub const PSEUDO_FS_TYPES: &[&str] = &[
+    "tmpfs", "devtmpfs", "ramfs", "squashfs", "overlay", "proc", "sysfs", "cgroup", "cgroup2", "devpts",
+    "debugfs", "tracefs", "securityfs", "pstore", "bpf", "configfs", "fusectl", "mqueue", "hugetlbfs",
+    "autofs", "efivarfs", "binfmt_misc", "nsfs", "rpc_pipefs",
+];
+
+/// Where a disk's data lives
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiskLocation {
+    Local,
+    /// USB sticks, SD cards and other media that can be unplugged
+    Removable,
+    /// Served by another machine, see [`Mount::is_network`]
+    Network,
+}
+
+impl DiskLocation {
+    /// Classify a disk by its device name, filesystem type and sysinfo's
+    /// removable flag. Network wins: a removable network share is still slow.
+    pub fn classify(name: &str, fs_type: &str, removable: bool) -> DiskLocation {
+        if is_network_source(name, fs_type) {
+            DiskLocation::Network
+        } else if removable {
+            DiskLocation::Removable
         } else {
-            size_unit.format_size(disk.available_space())
-        };
-        let used_space = if auto_size {
-            SizeUnit::auto_format_size(disk.total_space() - disk.available_space())
+            DiskLocation::Local
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DiskLocation::Local => "local",
+            DiskLocation::Removable => "removable",
+            DiskLocation::Network => "network",
+        }
+    }
+}
+
+fn disk_location(disk: &sysinfo::Disk) -> DiskLocation {
+    DiskLocation::classify(
+        &disk.name().to_string_lossy(),
+        &disk.file_system().to_string_lossy(),
+        disk.is_removable(),
+    )
+}
+
+/// Which disks `--disk list` shows
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DiskFilter {
+    /// Also show pseudo filesystems and loop devices
+    pub all: bool,
+    /// Hide network filesystems
+    pub local_only: bool,
+    /// Only these filesystem types; pseudo filesystems named here are shown
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+impl DiskFilter {
+    /// Parse a `--fs-type` list in the style of `mount -t`: `ext4,xfs` shows
+    /// only those types, `notmpfs,nosquashfs` hides them
+    pub fn with_fs_types(mut self, list: &str) -> Self {
+        for fs_type in list.split(',').map(str::trim).filter(|t| !t.is_empty()) {
+            match fs_type.strip_prefix("no") {
+                Some(excluded) => self.exclude.push(excluded.to_string()),
+                None => self.include.push(fs_type.to_string()),
+            }
+        }
+        self
+    }
+
+    pub fn matches(&self, name: &str, fs_type: &str) -> bool {
+        if self.exclude.iter().any(|t| t == fs_type) || (self.local_only && is_network_source(name, fs_type)) {
+            return false;
+        }
+        if !self.include.is_empty() {
+            return self.include.iter().any(|t| t == fs_type);
+        }
+        self.all || !is_pseudo(name, fs_type)
+    }
+}
+
+/// Whether a disk is a pseudo filesystem or a loop device, see
+/// [`PSEUDO_FS_TYPES`]
+pub fn is_pseudo(name: &str, fs_type: &str) -> bool {
+    PSEUDO_FS_TYPES.contains(&fs_type) || name.starts_with("/dev/loop")
+}
+
+/// Print the SMART health of the drive holding `name`, querying each drive
+/// once however many partitions it has
+#[cfg(feature = "smart")]
+fn show_drive_health(name: &str, checked: &mut HashMap<String, Result<crate::smart::SmartHealth, String>>, color: bool) {
+    let Some(drive) = crate::smart::physical_device(name) else {
+        return;
+    };
+    let health = checked
+        .entry(drive.clone())
+        .or_insert_with(|| crate::smart::query(&drive));
+    match health {
+        Ok(health) if color && health.passed == Some(false) => println!("    {} {}", drive, health.to_string().red().bold()),
+        Ok(health) => println!("    {} {}", drive, health),
+        Err(e) if color => println!("    {} {}", drive, format!("Health: unavailable ({})", e).dimmed()),
+        Err(e) => println!("    {} Health: unavailable ({})", drive, e),
+    }
+}
+
+/// Without the `smart` feature `--health` is refused before listing
+#[cfg(not(feature = "smart"))]
+fn show_drive_health(_name: &str, _checked: &mut HashMap<String, ()>, _color: bool) {}
+
+/// Percentage of a filesystem's space in use; 0 for filesystems without a
+/// size, such as some pseudo filesystems
+pub fn usage_percent(total: u64, available: u64) -> f64 {
+    if total == 0 {
+        return 0.0;
+    }
+    total.saturating_sub(available) as f64 / total as f64 * 100.0
+}
+
+/// A filesystem fuller than the `--warn-usage` threshold
+#[derive(Debug, Clone, PartialEq)]
+pub struct UsageAlert {
+    pub mount_point: PathBuf,
+    pub percent: f64,
+}
+
+/// Disks more than `threshold` percent full: the disk `query` names, or
+/// every disk `filter` lets through
+pub fn usage_alerts(threshold: f64, query: Option<&str>, filter: &DiskFilter) -> Vec<UsageAlert> {
+    Disks::new_with_refreshed_list()
+        .iter()
+        .filter(|d| {
+            let name = d.name().to_string_lossy();
+            match query {
+                Some(query) => disk_matches(&name, d.mount_point(), query),
+                None => filter.matches(&name, &d.file_system().to_string_lossy()),
+            }
+        })
+        .map(|d| UsageAlert {
+            mount_point: d.mount_point().to_path_buf(),
+            percent: usage_percent(d.total_space(), d.available_space()),
+        })
+        .filter(|alert| alert.percent > threshold)
+        .collect()
+}
+
+/// Print one warning per alert on stderr
+pub fn show_usage_alerts(alerts: &[UsageAlert], threshold: f64, color: bool) {
+    for alert in alerts {
+        let message = format!(
+            "Warning: {} is {:.1}% full (threshold {}%)",
+            alert.mount_point.display(),
+            alert.percent,
+            threshold
+        );
+        if color {
+            eprintln!("{}", message.yellow().bold());
         } else {
-            size_unit.format_size(disk.total_space() - disk.available_space())
-        };
+            eprintln!("{}", message);
+        }
+    }
+}
+
+/// One row of `--disk list`, also its `--porcelain` and `--json` forms
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DiskRecord {
+    /// Device name, e.g. `/dev/sda1`
+    pub filesystem: String,
+    pub fs_type: String,
+    pub size: u64,
+    pub used: u64,
+    pub available: u64,
+    /// Rounded up like df; `None` for filesystems without a size
+    pub use_percent: Option<u8>,
+    pub mounted_on: PathBuf,
+    pub read_only: bool,
+    pub location: DiskLocation,
+    pub inodes_total: Option<u64>,
+    pub inodes_used: Option<u64>,
+}
+
+/// Every disk, in sysinfo's order
+pub fn all_disk_records() -> Vec<DiskRecord> {
+    let mounts = mount_table();
+    Disks::new_with_refreshed_list()
+        .iter()
+        .map(|disk| {
+            let (size, available) = (disk.total_space(), disk.available_space());
+            let inodes = inode_usage(disk.mount_point());
+            DiskRecord {
+                filesystem: disk.name().to_string_lossy().to_string(),
+                fs_type: disk.file_system().to_string_lossy().to_string(),
+                size,
+                used: size.saturating_sub(available),
+                available,
+                use_percent: (size > 0).then(|| usage_percent(size, available).ceil() as u8),
+                mounted_on: disk.mount_point().to_path_buf(),
+                // The last mount at a point is the visible one
+                read_only: mounts
+                    .iter()
+                    .rev()
+                    .find(|m| m.mount_point == disk.mount_point())
+                    .is_some_and(Mount::is_read_only),
+                location: disk_location(disk),
+                inodes_total: inodes.map(|i| i.total),
+                inodes_used: inodes.map(|i| i.used()),
+            }
+        })
+        .collect()
+}
+
+/// The disks `filter` lets through
+pub fn disk_records(filter: &DiskFilter) -> Vec<DiskRecord> {
+    all_disk_records()
+        .into_iter()
+        .filter(|r| filter.matches(&r.filesystem, &r.fs_type))
+        .collect()
+}
+
+/// df-style table (`df -T -B1`): whitespace-separated columns in a fixed
+/// order, sizes in bytes, no colors
+pub fn format_porcelain(records: &[DiskRecord]) -> String {
+    let header = ["Filesystem", "Type", "1B-blocks", "Used", "Available", "Use%", "Mounted on"];
+    let mut rows = vec![header.map(str::to_string)];
+    for r in records {
+        rows.push([
+            r.filesystem.clone(),
+            r.fs_type.clone(),
+            r.size.to_string(),
+            r.used.to_string(),
+            r.available.to_string(),
+            r.use_percent.map_or("-".to_string(), |p| format!("{}%", p)),
+            r.mounted_on.display().to_string(),
+        ]);
+    }
+    let widths: Vec<usize> = (0..header.len())
+        .map(|column| rows.iter().map(|row| row[column].len()).max().unwrap_or(0))
+        .collect();
+
+    let mut out = String::new();
+    for row in &rows {
+        let mut line = format!("{:<w0$} {:<w1$}", row[0], row[1], w0 = widths[0], w1 = widths[1]);
+        for column in 2..6 {
+            line.push_str(&format!(" {:>width$}", row[column], width = widths[column]));
+        }
+        line.push(' ');
+        line.push_str(&row[6]);
+        out.push_str(&line);
+        out.push('\n');
+    }
+    out
+}
+
+/// JSON layout of `--disk list --json`
+#[derive(Debug, Serialize)]
+pub struct DisksExport {
+    pub metadata: ScanMetadata,
+    pub disks: Vec<DiskRecord>,
+}
+
+/// List the disks `filter` lets through with their filesystem type, access
+/// mode, space and inode usage
+pub fn list_disks(options: &DisplayOptions, filter: &DiskFilter, health: bool) {
+    let color = options.color;
+    let size_unit = &options.size_unit;
+    let mut checked_drives = HashMap::new();
+    println!();
+    println!("Available disks:");
+    println!("{}", "─".repeat(60));
+
+    let mut hidden = 0;
+    for disk in all_disk_records() {
+        if !filter.matches(&disk.filesystem, &disk.fs_type) {
+            hidden += 1;
+            continue;
+        }
+        let name = &disk.filesystem;
+        let mount_point = disk.mounted_on.display();
+        let total_space = size_unit.format_size(disk.size);
+        let available_space = size_unit.format_size(disk.available);
+        let used_space = size_unit.format_size(disk.used);
+        let access = if disk.read_only { "ro" } else { "rw" };
+        let location = disk.location.as_str();
+        let fs_type = &disk.fs_type;
+        let inodes = disk
+            .inodes_total
+            .zip(disk.inodes_used)
+            .map(|(total, used)| format!(" | Inodes: {}", InodeUsage { total, free: total - used }))
+            .unwrap_or_default();
 
         if color {
             println!(
-                "{} ({}) - Total: {} | Used: {} | Available: {}",
+                "{} ({}) [{}, {}, {}] - Total: {} | Used: {} | Available: {}{}",
                 name.blue().bold(),
                 mount_point,
+                fs_type.cyan(),
+                if disk.read_only { access.yellow() } else { access.normal() },
+                if location == "local" { location.normal() } else { location.magenta() },
                 total_space.cyan(),
                 used_space.red(),
-                available_space.green()
+                available_space.green(),
+                inodes
+            );
+        } else {
+            println!(
+                "{} ({}) [{}, {}, {}] - Total: {} | Used: {} | Available: {}{}",
+                name, mount_point, fs_type, access, location, total_space, used_space, available_space, inodes
+            );
+        }
+        if health {
+            show_drive_health(name, &mut checked_drives, color);
+        }
+    }
+
+    if hidden > 0 && !filter.all {
+        let note = format!("{} more filtered out (use --all to show every filesystem)", hidden);
+        if color {
+            println!("{}", note.dimmed());
+        } else {
+            println!("{}", note);
+        }
+    }
+}
+
+/// List all mount points with filesystem type, access options and device
+pub fn list_mounts(color: bool) {
+    let mounts = crate::mounts::list_mounts();
+    println!();
+    println!("Mount points:");
+    println!("{}", "─".repeat(60));
+
+    for mount in &mounts {
+        let options = mount.notable_options().join(", ");
+        if color {
+            let options = if mount.is_read_only() {
+                options.yellow()
+            } else {
+                options.normal()
+            };
+            println!(
+                "{} ({}) - Device: {} | Options: {}",
+                mount.mount_point.display().to_string().blue().bold(),
+                mount.fs_type.cyan(),
+                mount.device,
+                options
             );
         } else {
             println!(
-                "{} ({}) - Total: {} | Used: {} | Available: {}",
-                name, mount_point, total_space, used_space, available_space
+                "{} ({}) - Device: {} | Options: {}",
+                mount.mount_point.display(),
+                mount.fs_type,
+                mount.device,
+                options
             );
         }
     }
 }
 
+/// One-line summary of a mount for headers, e.g. `ext4 on /dev/sda1 (rw, nosuid)`
+fn describe_mount(mount: &Mount) -> String {
+    let options = mount.notable_options();
+    if options.is_empty() {
+        format!("{} on {}", mount.fs_type, mount.device)
+    } else {
+        format!("{} on {} ({})", mount.fs_type, mount.device, options.join(", "))
+    }
+}
+
+/// Whether `query` names a disk: its device name, its mount point, a Windows
+/// drive letter (`C:`, `c:\`) or a UNC share (`\\server\share`)
+pub fn disk_matches(name: &str, mount_point: &Path, query: &str) -> bool {
+    if name == query || mount_point == Path::new(query) {
+        return true;
+    }
+    let mount = mount_point.to_string_lossy();
+    let normalize = |s: &str| s.trim_end_matches(['\\', '/']).replace('/', "\\").to_ascii_lowercase();
+
+    let bytes = query.as_bytes();
+    let is_drive = bytes.len() >= 2
+        && bytes[0].is_ascii_alphabetic()
+        && bytes[1] == b':'
+        && query[2..].chars().all(|c| c == '\\' || c == '/');
+    let is_unc = query.starts_with("\\\\") || query.starts_with("//");
+    if is_drive || is_unc {
+        let query = normalize(query);
+        return normalize(&mount) == query || normalize(name) == query;
+    }
+    false
+}
+
+/// Space a volume's Volume Shadow Copies use, as reported by
+/// `vssadmin list shadowstorage`. `None` means unbounded or unreadable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShadowStorage {
+    /// The shadowed volume, e.g. `C:`
+    pub volume: String,
+    pub used: Option<u64>,
+    pub allocated: Option<u64>,
+    pub maximum: Option<u64>,
+}
+
+/// Parse the output of `vssadmin list shadowstorage` (English locale)
+pub fn parse_shadow_storage(output: &str) -> Vec<ShadowStorage> {
+    let mut storages: Vec<ShadowStorage> = Vec::new();
+    for line in output.lines().map(str::trim) {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        match key {
+            "For volume" => {
+                // "(C:)\\?\Volume{...}\"
+                let volume = value
+                    .strip_prefix('(')
+                    .and_then(|v| v.split_once(')'))
+                    .map(|(volume, _)| volume.to_string())
+                    .unwrap_or_else(|| value.to_string());
+                storages.push(ShadowStorage {
+                    volume,
+                    used: None,
+                    allocated: None,
+                    maximum: None,
+                });
+            }
+            "Used Shadow Copy Storage space" => {
+                if let Some(storage) = storages.last_mut() {
+                    storage.used = parse_vss_size(value);
+                }
+            }
+            "Allocated Shadow Copy Storage space" => {
+                if let Some(storage) = storages.last_mut() {
+                    storage.allocated = parse_vss_size(value);
+                }
+            }
+            "Maximum Shadow Copy Storage space" => {
+                if let Some(storage) = storages.last_mut() {
+                    storage.maximum = parse_vss_size(value);
+                }
+            }
+            _ => {}
+        }
+    }
+    storages
+}
+
+/// Parse a vssadmin size like `1.48 GB (1%)`; its units are powers of 1024
+fn parse_vss_size(value: &str) -> Option<u64> {
+    let mut parts = value.split_whitespace();
+    let number: f64 = parts.next()?.parse().ok()?;
+    let multiplier: u64 = match parts.next()? {
+        "B" | "bytes" => 1,
+        "KB" => 1 << 10,
+        "MB" => 1 << 20,
+        "GB" => 1 << 30,
+        "TB" => 1 << 40,
+        "PB" => 1 << 50,
+        _ => return None,
+    };
+    Some((number * multiplier as f64) as u64)
+}
+
+/// Shadow copy storage for all volumes. Needs an elevated prompt; empty when
+/// vssadmin is unavailable or refuses.
+#[cfg(windows)]
+pub fn query_shadow_storage() -> Vec<ShadowStorage> {
+    std::process::Command::new("vssadmin")
+        .args(["list", "shadowstorage"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| parse_shadow_storage(&String::from_utf8_lossy(&output.stdout)))
+        .unwrap_or_default()
+}
+
+/// Shadow copies only exist on Windows
+#[cfg(not(windows))]
+pub fn query_shadow_storage() -> Vec<ShadowStorage> {
+    Vec::new()
+}
+
+/// Show the shadow copy space of the volume mounted at `mount_point`, if any
+fn show_shadow_storage(storages: &[ShadowStorage], mount_point: &Path, color: bool) {
+    let Some(storage) = storages
+        .iter()
+        .find(|s| disk_matches("", mount_point, &s.volume))
+    else {
+        return;
+    };
+    let format = |size: Option<u64>, missing: &str| {
+        size.map(SizeUnit::auto_format_size).unwrap_or_else(|| missing.to_string())
+    };
+    let used = format(storage.used, "unknown");
+    let details = format!(
+        "{} allocated, max {}",
+        format(storage.allocated, "unknown"),
+        format(storage.maximum, "unbounded")
+    );
+    if color {
+        println!("Shadow Copies: {} ({})", used.red(), details);
+    } else {
+        println!("Shadow Copies: {} ({})", used, details);
+    }
+}
+
+/// A block device's I/O counters since boot
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IoCounters {
+    pub reads: u64,
+    pub read_bytes: u64,
+    pub writes: u64,
+    pub written_bytes: u64,
+}
+
+/// Parse `/proc/diskstats`, keyed by kernel device name (`sda1`, `dm-0`).
+/// Sector counts there are always in 512-byte units.
+pub fn parse_diskstats(stats: &str) -> HashMap<String, IoCounters> {
+    stats
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let number = |index: usize| fields.get(index)?.parse::<u64>().ok();
+            let counters = IoCounters {
+                reads: number(3)?,
+                read_bytes: number(5)? * 512,
+                writes: number(7)?,
+                written_bytes: number(9)? * 512,
+            };
+            Some((fields[2].to_string(), counters))
+        })
+        .collect()
+}
+
+/// I/O counters of the block device behind a disk name such as `/dev/sda1`
+/// or `/dev/mapper/root`
+#[cfg(target_os = "linux")]
+pub fn io_counters(name: &str) -> Option<IoCounters> {
+    // Device mapper names are symlinks to the dm-N node the kernel reports
+    let device = std::fs::canonicalize(name).ok()?;
+    let device = device.file_name()?.to_string_lossy().to_string();
+    let stats = std::fs::read_to_string("/proc/diskstats").ok()?;
+    parse_diskstats(&stats).remove(&device)
+}
+
+/// Per-disk counters are only read from Linux's /proc/diskstats
+#[cfg(not(target_os = "linux"))]
+pub fn io_counters(_name: &str) -> Option<IoCounters> {
+    None
+}
+
+/// Show a disk's I/O totals since boot, then its throughput measured over
+/// `seconds`
+fn show_io_stats(name: &str, seconds: u64, color: bool) {
+    let Some(before) = io_counters(name) else {
+        println!("I/O statistics are not available for this disk");
+        return;
+    };
+    let totals = format!(
+        "read {} ({} ops), written {} ({} ops)",
+        SizeUnit::auto_format_size(before.read_bytes),
+        before.reads,
+        SizeUnit::auto_format_size(before.written_bytes),
+        before.writes
+    );
+    if color {
+        println!("I/O Since Boot: {}", totals.cyan());
+    } else {
+        println!("I/O Since Boot: {}", totals);
+    }
+
+    std::thread::sleep(std::time::Duration::from_secs(seconds));
+    let Some(after) = io_counters(name) else {
+        return;
+    };
+    let rate = |bytes: u64, earlier: u64| SizeUnit::auto_format_size(bytes.saturating_sub(earlier) / seconds.max(1));
+    let read = rate(after.read_bytes, before.read_bytes);
+    let write = rate(after.written_bytes, before.written_bytes);
+    if color {
+        println!("Throughput ({}s): read {}/s, write {}/s", seconds, read.green(), write.red());
+    } else {
+        println!("Throughput ({}s): read {}/s, write {}/s", seconds, read, write);
+    }
+}
+
+/// What `--disk <DISK>` shows after the disk's header
+#[derive(Debug, Clone, Copy)]
+pub enum DiskView<'a> {
+    /// The mount root's entries, as filtered and sorted; `-p` adds analysis
+    Listing,
+    Tree(&'a TreeOptions),
+    Duplicates(&'a DuplicateOptions),
+    /// The directories holding the most data, see `--top-dirs`
+    LargestDirs(DirRanking),
+    /// I/O totals since boot and throughput sampled over this many seconds
+    Io(u64),
+}
+
 /// Show detailed information about a specific disk
 pub fn show_disk_info(
     disk_name: &str,
-    size_unit: &SizeUnit,
-    color: bool,
-    auto_size: bool,
-    tree: bool,
-    properties: bool,
-    search_pattern: Option<&String>,
-    excluding_pattern: Option<&String>,
-    sort_by: Option<SortBy>,
-    duplicates: bool,
-    show_size: bool,
-    show_detailed_permissions: bool,
+    filter: &Filter,
+    sort: &SortSpec,
+    collect: &CollectOptions,
+    options: &DisplayOptions,
+    view: DiskView,
 ) {
+    let color = options.color;
     let disks = Disks::new_with_refreshed_list();
-    let disk = disks.iter().find(|d| d.name().to_string_lossy() == disk_name);
+    let disk = disks
+        .iter()
+        .find(|d| disk_matches(&d.name().to_string_lossy(), d.mount_point(), disk_name));
 
     match disk {
         Some(disk) => {
@@ -75,12 +681,22 @@ pub fn show_disk_info(
             let total_space = disk.total_space();
             let available_space = disk.available_space();
             let used_space = total_space - available_space;
-            let usage_percentage = used_space as f64 / total_space as f64 * 100.0;
+            let usage_percentage = usage_percent(total_space, available_space);
+            let filesystem = path_to_mount(mount_point).map(|m| describe_mount(&m));
+            let location = disk_location(disk);
+            if collect.local_only && location == DiskLocation::Network {
+                eprintln!("Error: Disk '{}' is a network filesystem, excluded by --local-only", disk_name);
+                crate::perf::exit(1);
+            }
 
-            println!("");
+            println!();
             if color {
                 println!("Disk Information: {}", disk_name.blue().bold());
                 println!("Mount Point: {}", mount_point.display().to_string().cyan());
+                if let Some(filesystem) = &filesystem {
+                    println!("Filesystem: {}", filesystem.cyan());
+                }
+                println!("Location: {}", location.as_str().cyan());
                 println!("Total Space: {}", SizeUnit::auto_format_size(total_space).cyan());
                 println!("Used Space: {}", SizeUnit::auto_format_size(used_space).red());
                 println!(
@@ -88,9 +704,16 @@ pub fn show_disk_info(
                     SizeUnit::auto_format_size(available_space).green()
                 );
                 println!("Usage: {:.1}%", usage_percentage.to_string().yellow());
+                if let Some(inodes) = inode_usage(mount_point) {
+                    println!("Inodes Used: {}", inodes.to_string().yellow());
+                }
             } else {
                 println!("Disk Information: {}", disk_name);
                 println!("Mount Point: {}", mount_point.display());
+                if let Some(filesystem) = &filesystem {
+                    println!("Filesystem: {}", filesystem);
+                }
+                println!("Location: {}", location.as_str());
                 println!("Total Space: {}", SizeUnit::auto_format_size(total_space));
                 println!("Used Space: {}", SizeUnit::auto_format_size(used_space));
                 println!(
@@ -98,14 +721,23 @@ pub fn show_disk_info(
                     SizeUnit::auto_format_size(available_space)
                 );
                 println!("Usage: {:.1}%", usage_percentage);
+                if let Some(inodes) = inode_usage(mount_point) {
+                    println!("Inodes Used: {}", inodes);
+                }
+            }
+            show_shadow_storage(&query_shadow_storage(), mount_point, color);
+            if let DiskView::Io(seconds) = view {
+                show_io_stats(&disk.name().to_string_lossy(), seconds, color);
+                return;
             }
 
-            let files = collect_files(mount_point, None, None, None);
+            show_virtual_exclusions(&virtual_exclusions(mount_point, collect), color);
+            let files = collect_files(mount_point, &Filter::default(), &SortSpec::default(), collect);
             if !files.is_empty() {
                 let total_files = files.len();
                 let total_dirs = files.iter().filter(|f| f.is_directory).count();
                 let total_regular_files = total_files - total_dirs;
-                let dir_size = get_file_size(mount_point);
+                let dir_size = total_size(mount_point, collect);
                 if color {
                     println!("Directory: {}", mount_point.display());
                     println!(
@@ -127,18 +759,16 @@ pub fn show_disk_info(
                 }
             }
 
-            if duplicates {
-                find_duplicates(mount_point, color);
-            } else if tree {
+            if let DiskView::Duplicates(duplicates) = view {
+                find_duplicates(mount_point, duplicates, color);
+            } else if let DiskView::LargestDirs(ranking) = view {
+                println!();
+                show_largest_dirs(mount_point, ranking, &skipped_paths(mount_point, collect), color);
+            } else if let DiskView::Tree(tree) = view {
                 println!("\nDirectory Tree:");
-                print_tree(mount_point, "", color);
-            } else if properties {
-                let files = collect_files_recursive(
-                    mount_point,
-                    search_pattern,
-                    excluding_pattern,
-                    sort_by,
-                );
+                print_tree(mount_point, filter, collect, options, tree);
+            } else if options.properties {
+                let files = collect_files_recursive(mount_point, filter, sort, collect);
                 if files.is_empty() {
                     println!("No files found.");
                 } else {
@@ -146,8 +776,8 @@ pub fn show_disk_info(
                     let total_dirs = files.iter().filter(|f| f.is_directory).count();
                     let total_regular_files = total_files - total_dirs;
                     let _total_size: u64 = files.iter().map(|f| f.size).sum();
-                    let dir_size = get_file_size(mount_point);
-                    println!("");
+                    let dir_size = total_size(mount_point, collect);
+                    println!();
                     if color {
                         println!("Directory: {}", mount_point.display());
                         println!(
@@ -167,29 +797,26 @@ pub fn show_disk_info(
                         );
                         println!("Total Size: {}", SizeUnit::auto_format_size(dir_size));
                     }
-                    println!("");
+                    println!();
                     show_file_type_stats(&files, color);
                     show_detailed_analysis(&files, color);
+                    // Entries on other devices do not use this disk's inodes
+                    show_entry_hotspots(mount_point, 10, true, &skipped_paths(mount_point, collect), color);
                 }
-            } else if search_pattern.is_some() || excluding_pattern.is_some() || sort_by.is_some() {
-                let files = collect_files(mount_point, search_pattern, excluding_pattern, sort_by);
+            } else if filter.is_active() || sort.is_active() {
+                let files = collect_files(mount_point, filter, sort, collect);
                 if files.is_empty() {
-                    if let Some(pattern) = search_pattern {
+                    if let Some(pattern) = &filter.search_pattern {
                         println!("No files found matching pattern: {}", pattern);
                     } else {
                         println!("No files found.");
                     }
                 } else {
-                    display_files(
-                        &files,
-                        size_unit,
-                        color,
-                        false,
-                        auto_size,
-                        show_size,
-                        None,
-                        show_detailed_permissions,
-                    );
+                    let listing = DisplayOptions {
+                        properties: false,
+                        ..options.clone()
+                    };
+                    display_files(limit_files(&files, sort, color), &listing, None);
                 }
                 show_file_type_stats(&files, color);
             }
@@ -197,11 +824,7 @@ pub fn show_disk_info(
         None => {
             eprintln!("Error: Disk '{}' not found", disk_name);
             eprintln!("Use 'filebyte --disk list' to see available disks");
-            std::process::exit(1);
+            crate::perf::exit(1);
         }
     }
 }
-
-fn get_file_size(path: &Path) -> u64 {
-    crate::utils::get_file_size(path)
-}
@@ -1,133 +1,257 @@
-use crate::types::FileInfo;
-use colored::Colorize;
+use crate::analysis::file_age_secs;
+use crate::types::{
+    Clock, CsvSchema, DisplayOptions, DuplicateGroup, DuplicatesExport, FileInfo, FileKind, ScanExport, ScanMetadata,
+    SortSpec,
+};
+#[cfg(not(windows))]
+use crate::utils::finder_hidden;
+use schemars::JsonSchema;
+use serde::Serialize;
+use colored::{Color, Colorize};
 use std::collections::HashMap;
 use std::fs;
-use std::path::Path;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 
 /// Display files with various formatting options
-pub fn display_files(
-    files: &[FileInfo],
-    size_unit: &crate::types::SizeUnit,
-    color: bool,
-    properties: bool,
-    auto_size: bool,
-    show_size: bool,
-    export_path: Option<&String>,
-    show_detailed_permissions: bool,
-) {
+pub fn display_files(files: &[FileInfo], options: &DisplayOptions, export_path: Option<&String>) {
+    let now = Clock::active().now();
     for file in files {
-        let size_str = if auto_size {
-            file.size_human.clone()
-        } else {
-            size_unit.format_size(file.size)
-        };
+        println!("{}", format_file(file, options, now));
+    }
 
-        let mut output = if color {
-            if file.is_directory {
-                if show_size {
-                    format!(
-                        "{} {} {}",
-                        file.name.blue().bold(),
-                        size_str.cyan().bold(),
-                        "[DIR]".blue()
-                    )
+    if let Some(export_file) = export_path {
+        if export_file.ends_with(".json") {
+            export_to_json(files, export_file);
+        } else if export_file.ends_with(".csv") {
+            export_to_csv(files, export_file, options.csv_schema);
+        }
+    }
+}
+
+/// `owner:group` for the listing, as `ls -l` shows them; `None` where
+/// owners are not known, as on Windows
+fn ownership(file: &FileInfo) -> Option<String> {
+    match (file.owner.as_str(), file.group.as_str()) {
+        ("" | "unknown", _) => None,
+        (owner, "" | "unknown") => Some(owner.to_string()),
+        (owner, group) => Some(format!("{}:{}", owner, group)),
+    }
+}
+
+/// Format one listing line for a file
+pub fn format_file(file: &FileInfo, options: &DisplayOptions, now: std::time::SystemTime) -> String {
+    let color = options.color;
+    let show_size = options.show_size;
+    let size_str = options.size_unit.format_size(file.size);
+    let age_color = if options.age_colors {
+        file_age_secs(file, now).map(age_color)
+    } else {
+        None
+    };
+
+    let mut output = if color {
+        if file.is_directory {
+            // Directories only carry a size when --du computed one
+            if show_size && file.size > 0 {
+                let size_color = if options.size_colors {
+                    size_color(file.size)
                 } else {
-                    format!("{} {}", file.name.blue().bold(), "[DIR]".blue())
-                }
+                    Color::Cyan
+                };
+                format!(
+                    "{} {} {}",
+                    file.name.blue().bold(),
+                    size_str.color(size_color).bold(),
+                    "[DIR]".blue()
+                )
             } else {
-                if show_size {
-                    format!("{} {}", file.name, size_str.green())
+                format!("{} {}", file.name.blue().bold(), "[DIR]".blue())
+            }
+        } else {
+            if show_size {
+                let name = match age_color {
+                    Some(age_color) => file.name.color(age_color).to_string(),
+                    None => file.name.clone(),
+                };
+                let size_color = if options.size_colors {
+                    size_color(file.size)
                 } else {
-                    let modified_short = file.modified.as_ref().map(|m| {
-                        if let Some(date_part) = m.split(' ').next() {
-                            date_part.to_string()
-                        } else {
-                            m.clone()
-                        }
-                    }).unwrap_or_else(|| "unknown".to_string());
-                    let permissions_display = if show_detailed_permissions {
-                        if let Ok(metadata) = fs::metadata(&Path::new(&file.path)) {
-                            crate::utils::format_unix_permissions(&metadata, true)
-                        } else {
-                            file.permissions.clone()
-                        }
+                    Color::Green
+                };
+                format!("{} {}", name, size_str.color(size_color))
+            } else {
+                let modified_short = file.modified.as_ref().map(|m| {
+                    if let Some(date_part) = m.split(' ').next() {
+                        date_part.to_string()
+                    } else {
+                        m.clone()
+                    }
+                }).unwrap_or_else(|| "unknown".to_string());
+                let permissions_display = if options.show_detailed_permissions {
+                    if let Ok(metadata) = fs::metadata(Path::new(&file.path)) {
+                        crate::utils::format_permissions(Path::new(&file.path), &metadata, true)
                     } else {
                         file.permissions.clone()
-                    };
-                    format!(
+                    }
+                } else {
+                    file.permissions.clone()
+                };
+                let name = if options.size_colors {
+                    file.name.color(size_color(file.size)).to_string()
+                } else {
+                    file.name.clone()
+                };
+                match ownership(file) {
+                    Some(ownership) => format!(
+                        "{} {} {} {}",
+                        name,
+                        permissions_display.magenta(),
+                        ownership.cyan(),
+                        modified_short.color(age_color.unwrap_or(Color::Yellow))
+                    ),
+                    None => format!(
                         "{} {} {}",
-                        file.name,
+                        name,
                         permissions_display.magenta(),
-                        modified_short.yellow()
-                    )
+                        modified_short.color(age_color.unwrap_or(Color::Yellow))
+                    ),
                 }
             }
+        }
+    } else {
+        if file.is_directory {
+            if show_size && file.size > 0 {
+                format!("{} {} [DIR]", file.name, size_str)
+            } else {
+                format!("{} [DIR]", file.name)
+            }
         } else {
-            if file.is_directory {
-                if show_size {
-                    format!("{} {} [DIR]", file.name, size_str)
-                } else {
-                    format!("{} [DIR]", file.name)
-                }
+            if show_size {
+                format!("{} {}", file.name, size_str)
             } else {
-                if show_size {
-                    format!("{} {}", file.name, size_str)
-                } else {
-                    let modified_short = file.modified.as_ref().map(|m| {
-                        if let Some(date_part) = m.split(' ').next() {
-                            date_part.to_string()
-                        } else {
-                            m.clone()
-                        }
-                    }).unwrap_or_else(|| "unknown".to_string());
-                    format!("{} {} {}", file.name, file.permissions, modified_short)
+                let modified_short = file.modified.as_ref().map(|m| {
+                    if let Some(date_part) = m.split(' ').next() {
+                        date_part.to_string()
+                    } else {
+                        m.clone()
+                    }
+                }).unwrap_or_else(|| "unknown".to_string());
+                match ownership(file) {
+                    Some(ownership) => format!("{} {} {} {}", file.name, file.permissions, ownership, modified_short),
+                    None => format!("{} {} {}", file.name, file.permissions, modified_short),
                 }
             }
-        };
+        }
+    };
 
-        if properties {
-            let created_info = file.created.as_ref().map(|c| format!("Created: {}", c)).unwrap_or_default();
-            let modified_info = file.modified.as_ref().map(|m| format!("Modified: {}", m)).unwrap_or_default();
-            if color {
-                output.push_str(&format!(
-                    " [{} {} {}]",
-                    file.permissions.yellow(),
-                    created_info.yellow(),
-                    modified_info.yellow()
-                ));
-            } else {
-                output.push_str(&format!(" [{} {} {}]", file.permissions, created_info, modified_info));
-            }
+    if let Some(dimensions) = &file.dimensions {
+        if color {
+            output.push_str(&format!(" {}", dimensions.cyan()));
+        } else {
+            output.push_str(&format!(" {}", dimensions));
         }
+    }
 
-        println!("{}", output);
+    if options.properties {
+        let created_info = file.created.as_ref().map(|c| format!("Created: {}", c)).unwrap_or_default();
+        let modified_info = file.modified.as_ref().map(|m| format!("Modified: {}", m)).unwrap_or_default();
+        if color {
+            output.push_str(&format!(
+                " [{} {} {}]",
+                file.permissions.yellow(),
+                created_info.yellow(),
+                modified_info.yellow()
+            ));
+        } else {
+            output.push_str(&format!(" [{} {} {}]", file.permissions, created_info, modified_info));
+        }
     }
 
-    if let Some(export_file) = export_path {
-        if export_file.ends_with(".json") {
-            export_to_json(files, export_file);
-        } else if export_file.ends_with(".csv") {
-            export_to_csv(files, export_file);
+    output
+}
+
+/// Heatmap color for a file's age, from fresh (green) to untouched for years (grey)
+pub fn age_color(age_secs: u64) -> Color {
+    const DAY: u64 = 24 * 60 * 60;
+    match age_secs {
+        s if s < DAY => Color::BrightGreen,
+        s if s < 7 * DAY => Color::Green,
+        s if s < 30 * DAY => Color::Yellow,
+        s if s < 365 * DAY => Color::Red,
+        _ => Color::BrightBlack,
+    }
+}
+
+/// Heat color for a size, from tiny (dim) to multi-GB (bright red)
+pub fn size_color(bytes: u64) -> Color {
+    const KIB: u64 = 1024;
+    match bytes {
+        b if b < KIB => Color::BrightBlack,
+        b if b < KIB * KIB => Color::Green,
+        b if b < 100 * KIB * KIB => Color::Yellow,
+        b if b < KIB * KIB * KIB => Color::Red,
+        _ => Color::BrightRed,
+    }
+}
+
+/// Note which virtual filesystems a scan of `/` skipped
+pub fn show_virtual_exclusions(excluded: &[PathBuf], color: bool) {
+    if excluded.is_empty() {
+        return;
+    }
+    let paths: Vec<String> = excluded.iter().map(|p| p.display().to_string()).collect();
+    let note = format!(
+        "Skipping virtual filesystems: {} (use --include-virtual to scan them)",
+        paths.join(", ")
+    );
+    if color {
+        println!("{}", note.dimmed());
+    } else {
+        println!("{}", note);
+    }
+}
+
+/// Limit a sorted listing to its first `top` entries, noting how many were hidden
+pub fn limit_files<'a>(files: &'a [FileInfo], sort: &SortSpec, color: bool) -> &'a [FileInfo] {
+    match sort.top {
+        Some(n) if n < files.len() => {
+            show_limit_note(n, files.len(), color);
+            &files[..n]
         }
+        _ => files,
+    }
+}
+
+/// Note that only the first `shown` of `total` entries are listed
+pub fn show_limit_note(shown: usize, total: usize, color: bool) {
+    let note = format!("Showing top {} of {} entries", shown, total);
+    if color {
+        println!("{}", note.dimmed());
+    } else {
+        println!("{}", note);
     }
 }
 
 /// Show file type statistics
 pub fn show_file_type_stats(files: &[FileInfo], color: bool) {
     let mut type_counts = HashMap::new();
-    let mut _total_size = 0u64;
     let mut total_files = 0u64;
 
     for file in files {
         if !file.is_directory {
-            *type_counts.entry(&file.file_type).or_insert(0) += 1;
-            _total_size += file.size;
+            *type_counts.entry(file.file_type.clone()).or_insert(0) += 1;
             total_files += 1;
         }
     }
 
+    show_type_counts(&type_counts, total_files, color);
+}
+
+/// Print per-type file counts, as tallied by [`show_file_type_stats`]
+pub fn show_type_counts(type_counts: &HashMap<String, u64>, total_files: u64, color: bool) {
     if !type_counts.is_empty() {
-        println!("");
+        println!();
         println!("File Type Statistics:");
         println!("{}", "─".repeat(40));
 
@@ -135,7 +259,7 @@ pub fn show_file_type_stats(files: &[FileInfo], color: bool) {
             .iter()
             .filter(|(file_type, _)| file_type.as_str() != "unknown")
             .collect();
-        sorted_types.sort_by(|a, b| b.1.cmp(a.1));
+        sorted_types.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
 
         for (file_type, count) in sorted_types {
             let percentage = (*count as f64 / total_files as f64) * 100.0;
@@ -159,9 +283,97 @@ pub fn show_file_type_stats(files: &[FileInfo], color: bool) {
     }
 }
 
-/// Export files to JSON format
+/// Files sharing a key in [`sizes_by`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct TypeSize {
+    pub key: String,
+    pub count: u64,
+    pub bytes: u64,
+}
+
+/// Lowercased extension with its dot, or "(none)"
+pub fn extension_key(file: &FileInfo) -> String {
+    Path::new(&file.name)
+        .extension()
+        .map_or_else(|| "(none)".to_string(), |ext| format!(".{}", ext.to_string_lossy().to_lowercase()))
+}
+
+/// Count and total the files (not directories) per `key`, largest first
+pub fn sizes_by(files: &[FileInfo], key: impl Fn(&FileInfo) -> String) -> Vec<TypeSize> {
+    let mut totals: HashMap<String, (u64, u64)> = HashMap::new();
+    for file in files.iter().filter(|f| !f.is_directory) {
+        let entry = totals.entry(key(file)).or_default();
+        entry.0 += 1;
+        entry.1 += file.size;
+    }
+    let mut sizes: Vec<TypeSize> = totals
+        .into_iter()
+        .map(|(key, (count, bytes))| TypeSize { key, count, bytes })
+        .collect();
+    sizes.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.key.cmp(&b.key)));
+    sizes
+}
+
+/// Print where the bytes go: per extension and per MIME type, the file
+/// count, total size and share of the total size
+pub fn show_size_by_type(files: &[FileInfo], options: &DisplayOptions) {
+    let sections = [
+        ("Size by Extension:", sizes_by(files, extension_key)),
+        ("Size by MIME Type:", sizes_by(files, |file| file.file_type.clone())),
+    ];
+    show_size_sections(files, &sections, options);
+}
+
+/// Print who uses the space: per owner and per group, the file count,
+/// total size and share of the total size
+pub fn show_size_by_owner(files: &[FileInfo], options: &DisplayOptions) {
+    let sections = [
+        ("Size by Owner:", sizes_by(files, |file| file.owner.clone())),
+        ("Size by Group:", sizes_by(files, |file| file.group.clone())),
+    ];
+    show_size_sections(files, &sections, options);
+}
+
+/// Print titled [`sizes_by`] breakdowns followed by the total size
+fn show_size_sections(files: &[FileInfo], sections: &[(&str, Vec<TypeSize>)], options: &DisplayOptions) {
+    let total: u64 = files.iter().filter(|f| !f.is_directory).map(|f| f.size).sum();
+    for (i, (title, sizes)) in sections.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        println!("{}", title);
+        println!("{}", "─".repeat(40));
+        for size in sizes {
+            let percentage = if total == 0 { 0.0 } else { size.bytes as f64 / total as f64 * 100.0 };
+            let bytes = options.size_unit.format_size(size.bytes);
+            if options.color {
+                println!(
+                    "{}: {} files, {} ({:.1}%)",
+                    size.key.magenta(),
+                    size.count.to_string().cyan(),
+                    bytes.green(),
+                    percentage
+                );
+            } else {
+                println!("{}: {} files, {} ({:.1}%)", size.key, size.count, bytes, percentage);
+            }
+        }
+    }
+    let total = options.size_unit.format_size(total);
+    if options.color {
+        println!("\nTotal Size: {}", total.green().bold());
+    } else {
+        println!("\nTotal Size: {}", total);
+    }
+}
+
+/// Export files to JSON format, tagged with the host they were scanned on
 pub fn export_to_json(files: &[FileInfo], filename: &str) {
-    if let Ok(json) = serde_json::to_string_pretty(files) {
+    let export = ScanExport {
+        metadata: ScanMetadata::current(),
+        files: files.to_vec(),
+    };
+    if let Ok(json) = serde_json::to_string_pretty(&export) {
         if fs::write(filename, json).is_ok() {
             println!("Results exported to {}", filename);
         } else {
@@ -173,11 +385,177 @@ pub fn export_to_json(files: &[FileInfo], filename: &str) {
 }
 
 /// Export files to CSV format
-pub fn export_to_csv(files: &[FileInfo], filename: &str) {
-    let mut wtr = csv::Writer::from_path(filename).unwrap();
+pub fn export_to_csv(files: &[FileInfo], filename: &str, schema: CsvSchema) {
+    let mut wtr = csv_writer(filename, schema).unwrap();
     for file in files {
-        wtr.serialize(file).unwrap();
+        write_csv_row(&mut wtr, file, schema).unwrap();
     }
     wtr.flush().unwrap();
     println!("Results exported to {}", filename);
 }
+
+/// One CSV row per duplicate file
+#[derive(Serialize)]
+struct DuplicateCsvRow<'a> {
+    group_id: usize,
+    hash: &'a str,
+    size: u64,
+    wasted_bytes: u64,
+    path: &'a str,
+}
+
+/// Export duplicate groups to JSON, or to CSV with one row per file
+pub fn export_duplicates(groups: &[DuplicateGroup], filename: &str) {
+    let result = if filename.ends_with(".json") {
+        let export = DuplicatesExport {
+            metadata: ScanMetadata::current(),
+            total_wasted_bytes: groups.iter().map(|g| g.wasted_bytes).sum(),
+            groups: groups.to_vec(),
+        };
+        serde_json::to_string_pretty(&export)
+            .map_err(std::io::Error::other)
+            .and_then(|json| fs::write(filename, json))
+    } else if filename.ends_with(".csv") {
+        write_duplicates_csv(groups, filename)
+    } else {
+        eprintln!("Unsupported export format for {}: use .json or .csv", filename);
+        return;
+    };
+    match result {
+        Ok(()) => println!("Results exported to {}", filename),
+        Err(e) => eprintln!("Failed to write to {}: {}", filename, e),
+    }
+}
+
+fn write_duplicates_csv(groups: &[DuplicateGroup], filename: &str) -> std::io::Result<()> {
+    let mut wtr = csv::Writer::from_path(filename)?;
+    for group in groups {
+        for path in &group.paths {
+            wtr.serialize(DuplicateCsvRow {
+                group_id: group.id,
+                hash: &group.hash,
+                size: group.size,
+                wasted_bytes: group.wasted_bytes,
+                path,
+            })
+            .map_err(std::io::Error::other)?;
+        }
+    }
+    wtr.flush()
+}
+
+/// Open a CSV export. The Windows schema quotes every field, ends lines with
+/// CRLF and starts with a BOM, like `Export-Csv`.
+pub fn csv_writer(filename: &str, schema: CsvSchema) -> std::io::Result<csv::Writer<fs::File>> {
+    match schema {
+        CsvSchema::Default => Ok(csv::Writer::from_path(filename)?),
+        CsvSchema::Windows => {
+            let mut file = fs::File::create(filename)?;
+            file.write_all("\u{feff}".as_bytes())?;
+            Ok(csv::WriterBuilder::new()
+                .quote_style(csv::QuoteStyle::Always)
+                .terminator(csv::Terminator::CRLF)
+                .from_writer(file))
+        }
+    }
+}
+
+pub fn write_csv_row(wtr: &mut csv::Writer<fs::File>, file: &FileInfo, schema: CsvSchema) -> csv::Result<()> {
+    match schema {
+        CsvSchema::Default => wtr.serialize(file),
+        CsvSchema::Windows => wtr.serialize(WindowsCsvRow::new(file)),
+    }
+}
+
+/// A row shaped like PowerShell's `Get-ChildItem | Export-Csv`
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct WindowsCsvRow<'a> {
+    name: &'a str,
+    full_name: String,
+    directory_name: String,
+    extension: String,
+    /// Empty for directories, as in PowerShell
+    length: Option<u64>,
+    mode: String,
+    creation_time_utc: String,
+    last_write_time_utc: String,
+    owner: &'a str,
+    #[serde(rename = "PSIsContainer")]
+    ps_is_container: &'static str,
+    content_type: &'a str,
+    hash: Option<&'a str>,
+    dimensions: Option<&'a str>,
+}
+
+impl<'a> WindowsCsvRow<'a> {
+    fn new(file: &'a FileInfo) -> Self {
+        let path = Path::new(&file.path);
+        let full_path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+        WindowsCsvRow {
+            name: &file.name,
+            full_name: full_path.display().to_string(),
+            directory_name: full_path
+                .parent()
+                .map(|p| p.display().to_string())
+                .unwrap_or_default(),
+            extension: path
+                .extension()
+                .map(|e| format!(".{}", e.to_string_lossy()))
+                .unwrap_or_default(),
+            length: (!file.is_directory).then_some(file.size),
+            mode: windows_mode(file),
+            creation_time_utc: file.created.as_deref().map(iso_utc).unwrap_or_default(),
+            last_write_time_utc: file.modified.as_deref().map(iso_utc).unwrap_or_default(),
+            owner: &file.owner,
+            ps_is_container: if file.is_directory { "True" } else { "False" },
+            content_type: &file.file_type,
+            hash: file.hash.as_deref(),
+            dimensions: file.dimensions.as_deref(),
+        }
+    }
+}
+
+/// PowerShell's `darhsl` mode string: directory, archive, read-only, hidden,
+/// system, link. Made up from the Unix permissions here, with dotfiles and,
+/// on macOS, entries Finder hides counted as hidden.
+#[cfg(not(windows))]
+fn windows_mode(file: &FileInfo) -> String {
+    let hidden = file.name.starts_with('.') || finder_hidden(Path::new(&file.path));
+    [
+        if file.is_directory { 'd' } else { '-' },
+        if file.is_directory { '-' } else { 'a' },
+        if file.permissions.contains('w') { '-' } else { 'r' },
+        if hidden { 'h' } else { '-' },
+        '-',
+        if file.kind == FileKind::Symlink { 'l' } else { '-' },
+    ]
+    .iter()
+    .collect()
+}
+
+/// PowerShell's `darhsl` mode string, from the attribute letters Windows
+/// listings keep in `permissions`, see [`crate::utils::short_permissions`]
+#[cfg(windows)]
+fn windows_mode(file: &FileInfo) -> String {
+    let has = |letter: char| file.permissions.contains(letter);
+    [
+        if file.is_directory { 'd' } else { '-' },
+        if has('A') { 'a' } else { '-' },
+        if has('R') { 'r' } else { '-' },
+        if has('H') { 'h' } else { '-' },
+        if has('S') { 's' } else { '-' },
+        if has('L') { 'l' } else { '-' },
+    ]
+    .iter()
+    .collect()
+}
+
+/// `2024-01-01 12:00:00 UTC` to `2024-01-01T12:00:00Z`, which `[datetime]`
+/// parses the same under every culture
+fn iso_utc(timestamp: &str) -> String {
+    match timestamp.strip_suffix(" UTC") {
+        Some(time) => format!("{}Z", time.replacen(' ', "T", 1)),
+        None => timestamp.to_string(),
+    }
+}
@@ -0,0 +1,251 @@
+use crate::usage::{self, UsageStats};
+use colored::Colorize;
+use filebyte::cache::{cache_dir, ScanCache};
+use filebyte::config::{config_path, load_config};
+use filebyte::migrate::{self, SCAN_CACHE, USAGE_STATS};
+use std::fs;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Ok,
+    Warn,
+    Fail,
+}
+
+struct Check {
+    name: &'static str,
+    status: Status,
+    detail: String,
+    /// What to do about a warning or failure
+    fix: Option<String>,
+}
+
+impl Check {
+    fn ok(name: &'static str, detail: impl Into<String>) -> Check {
+        Check {
+            name,
+            status: Status::Ok,
+            detail: detail.into(),
+            fix: None,
+        }
+    }
+
+    fn warn(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Check {
+        Check {
+            name,
+            status: Status::Warn,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+
+    fn fail(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Check {
+        Check {
+            name,
+            status: Status::Fail,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+}
+
+/// Handle `filebyte doctor`: check saved files, the cache directory, optional
+/// tools and the terminal, printing a fix for each problem. Exits with status
+/// 1 when a check fails.
+pub fn run_doctor(color: bool) {
+    let mut checks = vec![check_config(), check_usage_stats(), check_cache_dir()];
+    checks.extend(check_scan_caches());
+    checks.extend(check_tools());
+    checks.push(check_terminal());
+
+    println!("filebyte {} diagnostics", env!("CARGO_PKG_VERSION"));
+    println!("{}", "─".repeat(50));
+    for check in &checks {
+        let label = match check.status {
+            Status::Ok => "[ok]  ",
+            Status::Warn => "[warn]",
+            Status::Fail => "[fail]",
+        };
+        if color {
+            let label = match check.status {
+                Status::Ok => label.green(),
+                Status::Warn => label.yellow(),
+                Status::Fail => label.red().bold(),
+            };
+            println!("{} {}: {}", label, check.name.bold(), check.detail);
+        } else {
+            println!("{} {}: {}", label, check.name, check.detail);
+        }
+        if let Some(fix) = &check.fix {
+            println!("       Fix: {}", fix);
+        }
+    }
+
+    let failed = checks.iter().filter(|c| c.status == Status::Fail).count();
+    let warned = checks.iter().filter(|c| c.status == Status::Warn).count();
+    println!();
+    println!("{} checks, {} warnings, {} failures", checks.len(), warned, failed);
+    if failed > 0 {
+        crate::perf::exit(1);
+    }
+}
+
+fn check_config() -> Check {
+    const NAME: &str = "Config";
+    let Some(path) = config_path() else {
+        return Check::warn(NAME, "cannot locate a config directory", "set HOME or XDG_CONFIG_HOME");
+    };
+    if !path.exists() {
+        return Check::ok(NAME, "none, using the defaults");
+    }
+    match load_config(&path) {
+        Ok(_) => Check::ok(NAME, path.display().to_string()),
+        Err(e) => Check::fail(
+            NAME,
+            format!("{}: {}", path.display(), e),
+            format!("fix or remove {}", path.display()),
+        ),
+    }
+}
+
+fn check_usage_stats() -> Check {
+    const NAME: &str = "Usage stats";
+    let Some(path) = usage::stats_path() else {
+        return Check::warn(NAME, "cannot locate a data directory", "set HOME or XDG_DATA_HOME");
+    };
+    if !path.exists() {
+        return Check::ok(NAME, "not enabled");
+    }
+    match migrate::load::<UsageStats>(&path, &USAGE_STATS) {
+        Ok(stats) => {
+            let roots = stats.map_or(0, |s| s.roots.len());
+            Check::ok(NAME, format!("{} ({} roots)", path.display(), roots))
+        }
+        Err(e) => Check::fail(
+            NAME,
+            format!("{}: {}", path.display(), e),
+            "run 'filebyte stats-self --reset' to start a new file",
+        ),
+    }
+}
+
+fn check_cache_dir() -> Check {
+    const NAME: &str = "Cache directory";
+    let Some(dir) = cache_dir() else {
+        return Check::warn(NAME, "cannot locate a cache directory", "set HOME or XDG_CACHE_HOME");
+    };
+    // The first --refresh creates it
+    let existing = dir.ancestors().find(|p| p.exists()).unwrap_or(Path::new("/"));
+    let probe = existing.join(format!(".filebyte-doctor-{}", std::process::id()));
+    match fs::write(&probe, b"") {
+        Ok(()) => {
+            let _ = fs::remove_file(&probe);
+            Check::ok(NAME, format!("{} is writable", dir.display()))
+        }
+        Err(e) => Check::fail(
+            NAME,
+            format!("cannot write to {}: {}", existing.display(), e),
+            format!(
+                "fix the permissions of {} or point XDG_CACHE_HOME elsewhere; --refresh needs it",
+                existing.display()
+            ),
+        ),
+    }
+}
+
+/// One check per unreadable scan cache, or a single summary line
+fn check_scan_caches() -> Vec<Check> {
+    const NAME: &str = "Scan caches";
+    let Some(dir) = cache_dir() else {
+        return Vec::new();
+    };
+    let Ok(entries) = fs::read_dir(&dir) else {
+        return vec![Check::ok(NAME, "none yet")];
+    };
+    let files: Vec<PathBuf> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+
+    let mut checks = Vec::new();
+    for path in &files {
+        if let Err(e) = migrate::load::<ScanCache>(path, &SCAN_CACHE) {
+            checks.push(Check::warn(
+                NAME,
+                format!("{}: {}", path.display(), e),
+                format!("delete {}; the next --refresh rescans that tree", path.display()),
+            ));
+        }
+    }
+    if checks.is_empty() {
+        checks.push(Check::ok(NAME, format!("{} readable", files.len())));
+    }
+    checks
+}
+
+/// Optional external programs and what they are used for
+fn check_tools() -> Vec<Check> {
+    let mut tools: Vec<(&'static str, &str, &str)> = vec![
+        ("git", "status markers in --tree", "install git"),
+        ("gzip", "compressed snapshots", "install gzip"),
+        ("smartctl", "--disk list --health", "install smartmontools"),
+        ("ffprobe", "audio and video metadata", "install ffmpeg"),
+    ];
+    if cfg!(feature = "db") {
+        tools.push(("psql", "--export-db postgres://", "install the PostgreSQL client"));
+    }
+    if cfg!(windows) {
+        tools.push(("vssadmin", "shadow copy storage in --disk", "run from an elevated prompt"));
+    }
+
+    tools
+        .into_iter()
+        .map(|(tool, used_for, fix)| match find_in_path(tool) {
+            Some(path) => Check::ok(tool, path.display().to_string()),
+            None => Check::warn(tool, format!("not found (optional, for {})", used_for), fix),
+        })
+        .collect()
+}
+
+fn find_in_path(program: &str) -> Option<PathBuf> {
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path)
+        .flat_map(|dir| {
+            let exe = dir.join(format!("{}{}", program, std::env::consts::EXE_SUFFIX));
+            [exe, dir.join(program)]
+        })
+        .find(|candidate| is_executable(candidate))
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path).is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+fn check_terminal() -> Check {
+    const NAME: &str = "Terminal";
+    let term = std::env::var("TERM").unwrap_or_default();
+    if std::env::var_os("NO_COLOR").is_some() {
+        return Check::ok(NAME, "NO_COLOR is set, output is plain");
+    }
+    if !std::io::stdout().is_terminal() {
+        return Check::ok(NAME, "output is redirected; add --no-color to keep escape codes out of files");
+    }
+    if term == "dumb" {
+        return Check::warn(
+            NAME,
+            "TERM=dumb may not show colors or box-drawing characters",
+            "use --no-color, or set TERM to your terminal's type (e.g. xterm-256color)",
+        );
+    }
+    Check::ok(NAME, format!("interactive, TERM={}", if term.is_empty() { "unset" } else { &term }))
+}
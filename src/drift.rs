@@ -0,0 +1,197 @@
+//! Silent content changes (`--detect-changes`).
+//!
+//! Each run hashes every file below the directory with SHA-256 and records
+//! the digests, with each file's size and modification time, in the
+//! directory's scan cache (see [`crate::cache`]). A file whose digest no
+//! longer matches while its size and modification time are unchanged was
+//! not rewritten the usual way: bit rot, a failing disk, or tampering that
+//! put the timestamp back. Edited, new and removed files are only counted.
+//!
+//! A drifted file keeps its recorded digest, so it is reported again on
+//! every run until it is restored or rewritten. The first run only records.
+
+use crate::analysis::hash_in_parallel;
+use crate::cache::{load_cache, save_cache, CachedHash};
+use crate::collect::walk_files;
+use crate::types::{CollectOptions, FileKind, Filter, HashAlgorithm, SizeUnit};
+use crate::utils::{file_hasher, group_digits};
+use colored::Colorize;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+/// A file whose content changed behind an unchanged size and mtime
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Drift {
+    /// Relative to the scanned directory, with `/` separators
+    pub path: String,
+    pub size: u64,
+    pub modified: Option<String>,
+    pub recorded: String,
+    pub current: String,
+}
+
+/// The outcome of [`detect_changes`]
+#[derive(Debug, Clone, Default)]
+pub struct DriftReport {
+    /// Nothing was recorded for this directory before
+    pub first_run: bool,
+    /// Files hashed this time, and their total size
+    pub files: usize,
+    pub bytes: u64,
+    /// Files with a recorded digest and the same size and mtime
+    pub compared: usize,
+    pub drifted: Vec<Drift>,
+    /// Files whose size or mtime changed since they were recorded
+    pub modified: usize,
+    pub added: usize,
+    pub removed: usize,
+    /// Files that could not be read, or changed while being hashed
+    pub skipped: Vec<String>,
+}
+
+/// Size and modification time of `path`
+fn stamp(path: &Path) -> Option<(u64, u64, u32)> {
+    let metadata = fs::metadata(path).ok()?;
+    let mtime = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    Some((metadata.len(), mtime.as_secs(), mtime.subsec_nanos()))
+}
+
+/// Hash every file below `root`, compare with the digests recorded by the
+/// last run and record the new ones
+pub fn detect_changes(root: &Path, threads: usize, collect: &CollectOptions) -> io::Result<DriftReport> {
+    let (mut cache, cache_path) = load_cache(root);
+    let Some(cache_path) = cache_path else {
+        return Err(io::Error::other("no cache directory; set XDG_CACHE_HOME or HOME"));
+    };
+
+    let mut files = Vec::new();
+    walk_files(root, &Filter::default(), collect, &mut |file| {
+        if file.kind == FileKind::File {
+            files.push(file);
+        }
+    });
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    let before: Vec<_> = files.iter().map(|file| stamp(Path::new(&file.path))).collect();
+    let paths: Vec<(u64, &String)> = files.iter().map(|file| (file.size, &file.path)).collect();
+    let hashes = hash_in_parallel(&paths, threads, file_hasher(HashAlgorithm::Sha256));
+
+    let mut previous = std::mem::take(&mut cache.hashes);
+    let mut report = DriftReport {
+        first_run: previous.is_empty(),
+        ..DriftReport::default()
+    };
+    let mut recorded = HashMap::new();
+    for ((file, before), hash) in files.into_iter().zip(before).zip(hashes) {
+        let relative = Path::new(&file.path).strip_prefix(root).unwrap_or(Path::new(&file.path));
+        let relative = relative.to_string_lossy().replace('\\', "/");
+        let old = previous.remove(&relative);
+        // A file written to while it was hashed has no trustworthy digest
+        let (Some(hash), Some(stamp)) = (hash, before.filter(|&b| stamp(Path::new(&file.path)) == Some(b))) else {
+            report.skipped.push(file.path);
+            if let Some(old) = old {
+                recorded.insert(relative, old);
+            }
+            continue;
+        };
+        report.files += 1;
+        report.bytes += stamp.0;
+        let current = CachedHash {
+            size: stamp.0,
+            mtime_secs: stamp.1,
+            mtime_nanos: stamp.2,
+            sha256: hash,
+        };
+        match old {
+            None => report.added += usize::from(!report.first_run),
+            Some(old) if (old.size, old.mtime_secs, old.mtime_nanos) == stamp => {
+                report.compared += 1;
+                if old.sha256 != current.sha256 {
+                    report.drifted.push(Drift {
+                        path: relative.clone(),
+                        size: stamp.0,
+                        modified: file.modified,
+                        recorded: old.sha256.clone(),
+                        current: current.sha256,
+                    });
+                    recorded.insert(relative, old);
+                    continue;
+                }
+            }
+            Some(_) => report.modified += 1,
+        }
+        recorded.insert(relative, current);
+    }
+    report.removed = previous.len();
+
+    cache.hashes = recorded;
+    save_cache(&cache_path, &cache);
+    Ok(report)
+}
+
+/// Print a [`DriftReport`] for `root`; returns whether no file drifted
+pub fn show_drift(root: &Path, report: &DriftReport, color: bool) -> bool {
+    if color {
+        println!("{}", format!("Content Drift: {}", root.display()).bold());
+    } else {
+        println!("Content Drift: {}", root.display());
+    }
+    println!("{}", "─".repeat(50));
+    for path in &report.skipped {
+        eprintln!("Warning: cannot read {} or it changed while being hashed, skipped", path);
+    }
+    let hashed = format!(
+        "{} files ({})",
+        group_digits(report.files as u64),
+        SizeUnit::auto_format_size(report.bytes)
+    );
+    if report.first_run {
+        println!("Recorded SHA-256 of {}; run again to compare", hashed);
+        return true;
+    }
+
+    if !report.drifted.is_empty() {
+        println!(
+            "Changed content with unchanged size and modification time ({}):",
+            group_digits(report.drifted.len() as u64)
+        );
+        for drift in &report.drifted {
+            let modified = drift.modified.as_deref().unwrap_or("-");
+            let size = SizeUnit::auto_format_size(drift.size);
+            if color {
+                println!("  {}  {}  modified {}", drift.path.red().bold(), size, modified);
+            } else {
+                println!("  {}  {}  modified {}", drift.path, size, modified);
+            }
+            println!("    recorded {}", drift.recorded);
+            println!("    now      {}", drift.current);
+        }
+        println!();
+    }
+    println!(
+        "Hashed {}: {} compared, {} edited, {} new, {} removed",
+        hashed,
+        group_digits(report.compared as u64),
+        group_digits(report.modified as u64),
+        group_digits(report.added as u64),
+        group_digits(report.removed as u64)
+    );
+    if report.drifted.is_empty() {
+        let message = "No silent changes";
+        if color {
+            println!("{}", message.green().bold());
+        } else {
+            println!("{}", message);
+        }
+        return true;
+    }
+    let message = format!("{} files changed silently", group_digits(report.drifted.len() as u64));
+    if color {
+        println!("{}", message.red().bold());
+    } else {
+        println!("{}", message);
+    }
+    false
+}
@@ -0,0 +1,300 @@
+//! Text encoding and line ending detection (`--check-encoding`, and the
+//! Encoding lines of `--properties`).
+//!
+//! A file is text if it has a UTF-16 byte order mark, looks like UTF-16
+//! without one (every other byte zero), or holds no NUL byte at all. Text
+//! that is not valid UTF-8 is reported as Latin-1, the usual culprit,
+//! although any other 8-bit encoding looks the same. Files are judged by
+//! their first [`MAX_READ`] bytes.
+
+use crate::perf::CountedFile;
+use crate::types::{FileInfo, FileKind};
+use crate::utils::group_digits;
+use colored::Colorize;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// Bytes read from each file
+pub const MAX_READ: u64 = 16 * 1024 * 1024;
+
+/// How the characters of a text file are stored
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    /// 7-bit only, so also valid UTF-8 and Latin-1
+    Ascii,
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    /// Not valid UTF-8; Latin-1 or another 8-bit encoding
+    Latin1,
+}
+
+impl Encoding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Encoding::Ascii => "ASCII",
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Utf16Le => "UTF-16LE",
+            Encoding::Utf16Be => "UTF-16BE",
+            Encoding::Latin1 => "Latin-1",
+        }
+    }
+
+    pub fn is_utf8(&self) -> bool {
+        matches!(self, Encoding::Ascii | Encoding::Utf8)
+    }
+}
+
+/// Line breaks found in a text file
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LineEndings {
+    pub lf: u64,
+    pub crlf: u64,
+    /// A carriage return not followed by a line feed (classic Mac OS)
+    pub cr: u64,
+}
+
+impl LineEndings {
+    pub fn is_mixed(&self) -> bool {
+        [self.lf, self.crlf, self.cr].iter().filter(|&&n| n > 0).count() > 1
+    }
+
+    /// "LF", "CRLF", "CR", "none" or "mixed (2 CRLF, 5 LF)"
+    pub fn describe(&self) -> String {
+        if self.is_mixed() {
+            let counts: Vec<String> = [(self.crlf, "CRLF"), (self.lf, "LF"), (self.cr, "CR")]
+                .iter()
+                .filter(|(n, _)| *n > 0)
+                .map(|(n, name)| format!("{} {}", group_digits(*n), name))
+                .collect();
+            format!("mixed ({})", counts.join(", "))
+        } else if self.crlf > 0 {
+            "CRLF".to_string()
+        } else if self.lf > 0 {
+            "LF".to_string()
+        } else if self.cr > 0 {
+            "CR".to_string()
+        } else {
+            "none".to_string()
+        }
+    }
+
+    /// Count the breaks in a sequence of code units (bytes, or UTF-16
+    /// units)
+    fn count(units: impl Iterator<Item = u16>) -> Self {
+        let mut endings = LineEndings::default();
+        let mut after_cr = false;
+        for unit in units {
+            match unit {
+                0x0A if after_cr => endings.crlf += 1,
+                0x0A => endings.lf += 1,
+                _ if after_cr => endings.cr += 1,
+                _ => {}
+            }
+            after_cr = unit == 0x0D;
+        }
+        if after_cr {
+            endings.cr += 1;
+        }
+        endings
+    }
+}
+
+/// What a text file is made of
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextEncoding {
+    pub encoding: Encoding,
+    /// Starts with a byte order mark
+    pub bom: bool,
+    pub line_endings: LineEndings,
+}
+
+impl TextEncoding {
+    /// "UTF-8", "UTF-16LE with BOM" and so on
+    pub fn describe(&self) -> String {
+        if self.bom {
+            format!("{} with BOM", self.encoding.as_str())
+        } else {
+            self.encoding.as_str().to_string()
+        }
+    }
+}
+
+/// Whether every byte pair in `bytes` has a zero at index `zero` and a
+/// non-zero at the other, as ASCII text stored in UTF-16 does
+fn looks_like_utf16(bytes: &[u8], zero: usize) -> bool {
+    bytes.len() >= 2 && bytes.len().is_multiple_of(2) && bytes.chunks(2).all(|pair| pair[zero] == 0 && pair[1 - zero] != 0)
+}
+
+fn utf16_units(bytes: &[u8], big_endian: bool) -> impl Iterator<Item = u16> + '_ {
+    bytes.chunks_exact(2).map(move |pair| {
+        if big_endian {
+            u16::from_be_bytes([pair[0], pair[1]])
+        } else {
+            u16::from_le_bytes([pair[0], pair[1]])
+        }
+    })
+}
+
+/// Detect the encoding of `bytes`, the start of a file if `truncated`;
+/// `None` if they look binary
+pub fn detect(bytes: &[u8], truncated: bool) -> Option<TextEncoding> {
+    let (encoding, bom, body) = if let Some(body) = bytes.strip_prefix(b"\xEF\xBB\xBF") {
+        (Encoding::Utf8, true, body)
+    } else if let Some(body) = bytes.strip_prefix(b"\xFF\xFE") {
+        (Encoding::Utf16Le, true, body)
+    } else if let Some(body) = bytes.strip_prefix(b"\xFE\xFF") {
+        (Encoding::Utf16Be, true, body)
+    } else if looks_like_utf16(bytes, 1) {
+        (Encoding::Utf16Le, false, bytes)
+    } else if looks_like_utf16(bytes, 0) {
+        (Encoding::Utf16Be, false, bytes)
+    } else if bytes.contains(&0) {
+        return None;
+    } else if bytes.is_ascii() {
+        (Encoding::Ascii, false, bytes)
+    } else {
+        let valid = match std::str::from_utf8(bytes) {
+            Ok(_) => true,
+            // A character cut off by the read limit is not an error
+            Err(e) => truncated && e.error_len().is_none(),
+        };
+        (if valid { Encoding::Utf8 } else { Encoding::Latin1 }, false, bytes)
+    };
+
+    let line_endings = match encoding {
+        Encoding::Utf16Le => LineEndings::count(utf16_units(body, false)),
+        Encoding::Utf16Be => LineEndings::count(utf16_units(body, true)),
+        _ => LineEndings::count(body.iter().map(|&b| b as u16)),
+    };
+    Some(TextEncoding {
+        encoding,
+        bom,
+        line_endings,
+    })
+}
+
+/// Detect the encoding of the file at `path`; `None` if it looks binary
+pub fn detect_file(path: &Path) -> io::Result<Option<TextEncoding>> {
+    let file = CountedFile::open(path)?;
+    let size = file.metadata()?.len();
+    let mut bytes = Vec::new();
+    file.take(MAX_READ).read_to_end(&mut bytes)?;
+    Ok(detect(&bytes, size > MAX_READ))
+}
+
+/// A text file worth a look, with what was found
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncodingIssue {
+    pub path: String,
+    pub text: TextEncoding,
+}
+
+/// Text files that may trip up other tools, each list sorted by path
+#[derive(Debug, Clone, Default)]
+pub struct EncodingReport {
+    /// Text files looked at
+    pub checked: usize,
+    pub not_utf8: Vec<EncodingIssue>,
+    pub bom: Vec<EncodingIssue>,
+    pub not_lf: Vec<EncodingIssue>,
+}
+
+/// Sort the text files in `files` into those that are not UTF-8, those that
+/// are but start with a BOM, and those with CRLF, CR or mixed line endings
+pub fn check_encodings(files: &[FileInfo]) -> EncodingReport {
+    let mut report = EncodingReport::default();
+    let mut files: Vec<&FileInfo> = files.iter().filter(|f| f.kind == FileKind::File && f.size > 0).collect();
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    for file in files {
+        let Ok(Some(text)) = detect_file(Path::new(&file.path)) else {
+            continue;
+        };
+        report.checked += 1;
+        let issue = || EncodingIssue {
+            path: file.path.clone(),
+            text,
+        };
+        if !text.encoding.is_utf8() {
+            report.not_utf8.push(issue());
+        } else if text.bom {
+            report.bom.push(issue());
+        }
+        let endings = text.line_endings;
+        if endings.crlf > 0 || endings.cr > 0 {
+            report.not_lf.push(issue());
+        }
+    }
+    report
+}
+
+/// Print the text files that are not valid UTF-8, then those with a BOM or
+/// non-LF line endings; returns whether every text file was UTF-8
+pub fn show_encoding_report(files: &[FileInfo], color: bool) -> bool {
+    let report = check_encodings(files);
+    println!("Text Encodings:");
+    println!("{}", "─".repeat(50));
+    let sections = [
+        ("Not valid UTF-8", &report.not_utf8, false),
+        ("UTF-8 with BOM", &report.bom, false),
+        ("CRLF, CR or mixed line endings", &report.not_lf, true),
+    ];
+    for (title, issues, line_endings) in sections {
+        if issues.is_empty() {
+            continue;
+        }
+        println!("{} ({}):", title, group_digits(issues.len() as u64));
+        for issue in issues {
+            let detail = if line_endings {
+                issue.text.line_endings.describe()
+            } else {
+                issue.text.describe()
+            };
+            if color && !issue.text.encoding.is_utf8() && !line_endings {
+                println!("  {}: {}", issue.path.red(), detail);
+            } else if color {
+                println!("  {}: {}", issue.path.yellow(), detail);
+            } else {
+                println!("  {}: {}", issue.path, detail);
+            }
+        }
+        println!();
+    }
+
+    let summary = format!(
+        "{} of {} text files are not valid UTF-8",
+        group_digits(report.not_utf8.len() as u64),
+        group_digits(report.checked as u64)
+    );
+    if color && report.not_utf8.is_empty() {
+        println!("{}", summary.green());
+    } else if color {
+        println!("{}", summary.red().bold());
+    } else {
+        println!("{}", summary);
+    }
+    report.not_utf8.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encoding_edges() {
+        let encoding = |bytes: &[u8]| detect(bytes, false).map(|text| text.encoding);
+        assert_eq!(encoding(b""), Some(Encoding::Ascii));
+        assert_eq!(encoding(b"\x89PNG\r\n\x1a\n\0\0"), None);
+        assert_eq!(encoding("naïve".as_bytes()), Some(Encoding::Utf8));
+        assert_eq!(encoding(b"na\xefve"), Some(Encoding::Latin1));
+        assert_eq!(encoding(b"h\0i\0"), Some(Encoding::Utf16Le));
+        assert_eq!(encoding(b"\0h\0i"), Some(Encoding::Utf16Be));
+        // A character cut in half by the read limit is still UTF-8
+        assert_eq!(detect(&"é".as_bytes()[..1], true).map(|text| text.encoding), Some(Encoding::Utf8));
+        assert_eq!(encoding(&"é".as_bytes()[..1]), Some(Encoding::Latin1));
+
+        let endings = detect(b"a\r\nb\rc\n\r", false).unwrap().line_endings;
+        assert_eq!((endings.crlf, endings.cr, endings.lf), (1, 2, 1));
+        assert_eq!(endings.describe(), "mixed (1 CRLF, 1 LF, 2 CR)");
+    }
+}
@@ -0,0 +1,165 @@
+//! Byte entropy of file contents (`--entropy`).
+//!
+//! Each file is sampled at its start, middle and end rather than read in
+//! full. Compressed and encrypted data sits close to 8 bits per byte, so the
+//! bytes above [`HIGH_ENTROPY`] are ones compression cannot shrink further,
+//! while files of unknown type up there may be encrypted containers or
+//! payloads worth a look.
+
+use crate::perf::CountedFile;
+use crate::types::{FileInfo, FileKind, SizeUnit};
+use crate::utils::group_digits;
+use colored::Colorize;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// Bytes read from each of the three sample points
+pub const SAMPLE_CHUNK: u64 = 16 * 1024;
+
+/// Files smaller than this give too few bytes for a meaningful entropy
+pub const MIN_SIZE: u64 = 1024;
+
+/// Bits per byte from which content counts as compressed or encrypted
+pub const HIGH_ENTROPY: f64 = 7.5;
+
+/// Bits per byte below which content would still compress well
+pub const LOW_ENTROPY: f64 = 6.0;
+
+/// Shannon entropy of `bytes` in bits per byte, from 0 (one repeated byte)
+/// to 8 (uniformly random)
+pub fn shannon_entropy(bytes: &[u8]) -> f64 {
+    if bytes.is_empty() {
+        return 0.0;
+    }
+    let mut counts = [0u64; 256];
+    for &byte in bytes {
+        counts[byte as usize] += 1;
+    }
+    let len = bytes.len() as f64;
+    counts
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let p = count as f64 / len;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+/// Entropy of a file's start, middle and end (the whole file when it is
+/// small enough)
+pub fn sample_entropy(path: &Path) -> io::Result<f64> {
+    let mut file = CountedFile::open(path)?;
+    let size = file.metadata()?.len();
+    let mut sample = Vec::new();
+    if size <= 3 * SAMPLE_CHUNK {
+        file.read_to_end(&mut sample)?;
+    } else {
+        for offset in [0, size / 2 - SAMPLE_CHUNK / 2, size - SAMPLE_CHUNK] {
+            file.seek(SeekFrom::Start(offset))?;
+            (&mut file).take(SAMPLE_CHUNK).read_to_end(&mut sample)?;
+        }
+    }
+    Ok(shannon_entropy(&sample))
+}
+
+/// A sampled file
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileEntropy {
+    pub path: String,
+    pub size: u64,
+    pub file_type: String,
+    pub entropy: f64,
+}
+
+/// Sample every regular file of at least [`MIN_SIZE`]; unreadable files are
+/// left out
+pub fn measure(files: &[FileInfo]) -> Vec<FileEntropy> {
+    files
+        .iter()
+        .filter(|f| f.kind == FileKind::File && f.size >= MIN_SIZE)
+        .filter_map(|f| {
+            let entropy = sample_entropy(Path::new(&f.path)).ok()?;
+            Some(FileEntropy {
+                path: f.path.clone(),
+                size: f.size,
+                file_type: f.file_type.clone(),
+                entropy,
+            })
+        })
+        .collect()
+}
+
+/// Print how the bytes split between compressible and incompressible
+/// content, then the `limit` largest high-entropy files of unknown type
+pub fn show_entropy_report(files: &[FileInfo], limit: usize, color: bool) {
+    let measured = measure(files);
+    println!("Entropy Analysis (sampled, bits per byte):");
+    println!("{}", "─".repeat(50));
+    if measured.is_empty() {
+        println!("No files of {} or more to sample.", SizeUnit::auto_format_size(MIN_SIZE));
+        return;
+    }
+
+    let total: u64 = measured.iter().map(|f| f.size).sum();
+    let bands = [
+        (format!("High (>= {:.1}, compressed or encrypted)", HIGH_ENTROPY), HIGH_ENTROPY..f64::INFINITY),
+        (format!("Medium ({:.1} to {:.1})", LOW_ENTROPY, HIGH_ENTROPY), LOW_ENTROPY..HIGH_ENTROPY),
+        (format!("Low (< {:.1}, compresses well)", LOW_ENTROPY), f64::NEG_INFINITY..LOW_ENTROPY),
+    ];
+    for (label, range) in &bands {
+        let in_band = measured.iter().filter(|f| range.contains(&f.entropy));
+        let (count, bytes) = in_band.fold((0u64, 0u64), |(count, bytes), f| (count + 1, bytes + f.size));
+        let share = bytes as f64 / total.max(1) as f64 * 100.0;
+        let size = SizeUnit::auto_format_size(bytes);
+        if color {
+            println!("  {}: {} files, {} ({:.1}%)", label.magenta(), group_digits(count), size.cyan(), share);
+        } else {
+            println!("  {}: {} files, {} ({:.1}%)", label, group_digits(count), size, share);
+        }
+    }
+
+    let mut unknown: Vec<&FileEntropy> = measured
+        .iter()
+        .filter(|f| f.entropy >= HIGH_ENTROPY && f.file_type == "unknown")
+        .collect();
+    if unknown.is_empty() {
+        return;
+    }
+    unknown.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.path.cmp(&b.path)));
+    println!("\nHigh-Entropy Unknown Files ({}):", group_digits(unknown.len() as u64));
+    for file in unknown.iter().take(limit) {
+        let size = format!("{:>10}", SizeUnit::auto_format_size(file.size));
+        if color {
+            println!("  {:.2}  {}  {}", file.entropy, size.cyan(), file.path.yellow());
+        } else {
+            println!("  {:.2}  {}  {}", file.entropy, size, file.path);
+        }
+    }
+    if unknown.len() > limit {
+        println!("  ... and {} more", group_digits((unknown.len() - limit) as u64));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn entropy_edges() {
+        assert_eq!(shannon_entropy(&[]), 0.0);
+        assert_eq!(shannon_entropy(&[7; 1000]), 0.0);
+        assert_eq!(shannon_entropy(&[0, 1, 0, 1]), 1.0);
+        let every_byte: Vec<u8> = (0..=255).collect();
+        assert_eq!(shannon_entropy(&every_byte), 8.0);
+    }
+
+    proptest! {
+        #[test]
+        fn entropy_stays_within_a_byte(bytes in prop::collection::vec(any::<u8>(), 0..4096)) {
+            let entropy = shannon_entropy(&bytes);
+            prop_assert!((0.0..=8.0 + 1e-9).contains(&entropy), "{}", entropy);
+        }
+    }
+}
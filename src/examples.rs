@@ -0,0 +1,278 @@
+use colored::Colorize;
+
+/// A copy-pastable recipe for `filebyte examples`
+pub struct Example {
+    pub topic: &'static str,
+    pub title: &'static str,
+    /// Shell lines; the `filebyte` part of each is checked against the CLI
+    pub commands: &'static [&'static str],
+}
+
+pub const EXAMPLES: &[Example] = &[
+    Example {
+        topic: "cleanup",
+        title: "Find the 20 largest files below a directory",
+        commands: &["filebyte -r --sort-by size --top 20 ~/Downloads"],
+    },
+    Example {
+        topic: "cleanup",
+        title: "See which subdirectories take the most space",
+        commands: &["filebyte --du --sort-by size ~"],
+    },
+    Example {
+        topic: "cleanup",
+        title: "Spot files nobody has touched in years",
+        commands: &["filebyte -r -p --age-colors --sort-by date -R /srv/shared"],
+    },
+    Example {
+        topic: "cleanup",
+        title: "Total the node_modules, build output and caches under your projects",
+        commands: &["filebyte --junk-report --top 20 ~/code"],
+    },
+    Example {
+        topic: "cleanup",
+        title: "Rank directories by how much idle data deleting would free",
+        commands: &["filebyte --suggest-cleanup --top 20 /srv/shared"],
+    },
+    Example {
+        topic: "cleanup",
+        title: "List old logs and archives for review",
+        commands: &["filebyte -r -e '\\.(log|gz|zip)$' --sort-by size /var/log"],
+    },
+    Example {
+        topic: "ci",
+        title: "Record build artifact sizes as a CI artifact",
+        commands: &["filebyte -r --type file --sort-by size --export artifacts.json target/release"],
+    },
+    Example {
+        topic: "ci",
+        title: "Fail the build when an artifact exceeds 50 MB (with jq)",
+        commands: &[
+            "filebyte -r --type file --export sizes.json dist",
+            "jq -e '[.files[].size] | max < 50000000' sizes.json",
+        ],
+    },
+    Example {
+        topic: "ci",
+        title: "Check the files touched by a change",
+        commands: &["git diff --name-only origin/main | filebyte --ingest-paths - -s b --sort-by size"],
+    },
+    Example {
+        topic: "ci",
+        title: "Fail the build when a source file is not UTF-8",
+        commands: &["filebyte --check-encoding -x target ."],
+    },
+    Example {
+        topic: "ci",
+        title: "Fail the build when credentials were committed",
+        commands: &["filebyte --scan-secrets -x '^(target|node_modules)$' ."],
+    },
+    Example {
+        topic: "backups",
+        title: "Combine scans of the source and the backup into one CSV",
+        commands: &[
+            "filebyte -r --export before.json /srv/data",
+            "filebyte -r --export after.json /mnt/backup/data",
+            "filebyte merge before.json after.json --export compare.csv",
+        ],
+    },
+    Example {
+        topic: "backups",
+        title: "Check that a backup holds every file, even if some were renamed",
+        commands: &["filebyte --duplicates-against /mnt/backup/data /srv/data"],
+    },
+    Example {
+        topic: "backups",
+        title: "Verify a copy file by file, including contents",
+        commands: &["filebyte diff /srv/data /mnt/backup/data --hash"],
+    },
+    Example {
+        topic: "backups",
+        title: "Record checksums of a tree with the metadata, for a later integrity check",
+        commands: &["filebyte hash /srv/data --algorithm blake3 --export checksums.csv"],
+    },
+    Example {
+        topic: "backups",
+        title: "Record a tree in a manifest and later check it for missing, added or altered files",
+        commands: &[
+            "filebyte --manifest write /srv/release.manifest.json /srv/release",
+            "filebyte --manifest verify /srv/release.manifest.json /srv/release",
+        ],
+    },
+    Example {
+        topic: "backups",
+        title: "Re-list a large backup quickly, re-reading only changed directories",
+        commands: &["filebyte -r --refresh --export backup.json /mnt/backup"],
+    },
+    Example {
+        topic: "backups",
+        title: "Keep a backup scan on one filesystem",
+        commands: &["filebyte -r --one-file-system --stream --export backup.csv /mnt/backup"],
+    },
+    Example {
+        topic: "backups",
+        title: "Snapshot a tree before a risky change, then browse it later",
+        commands: &[
+            "filebyte snapshot save before-upgrade /srv/data --hash",
+            "filebyte snapshot list",
+            "filebyte snapshot show before-upgrade",
+        ],
+    },
+    Example {
+        topic: "duplicates",
+        title: "Find duplicate photos, ignoring thumbnails",
+        commands: &["filebyte --duplicates --dup-min-size 200K ~/Pictures"],
+    },
+    Example {
+        topic: "duplicates",
+        title: "Preview, then replace duplicates with hardlinks",
+        commands: &[
+            "filebyte --duplicates --dedupe hardlink --dry-run ~/Pictures",
+            "filebyte --duplicates --dedupe hardlink ~/Pictures",
+        ],
+    },
+    Example {
+        topic: "duplicates",
+        title: "Find the same photo saved at different sizes",
+        commands: &["filebyte --duplicates --similar-images ~/Pictures"],
+    },
+    Example {
+        topic: "duplicates",
+        title: "Export duplicate groups for a spreadsheet",
+        commands: &["filebyte --duplicates --export dupes.csv ~/Pictures"],
+    },
+    Example {
+        topic: "duplicates",
+        title: "Only compare JPEGs found by fd",
+        commands: &["fd -0 -e jpg . ~/Pictures | filebyte --ingest-paths - --duplicates"],
+    },
+    Example {
+        topic: "disk",
+        title: "Show every disk and its usage",
+        commands: &["filebyte --disk list"],
+    },
+    Example {
+        topic: "disk",
+        title: "Largest files on one mount",
+        commands: &["filebyte --disk /home --sort-by size --top 50"],
+    },
+    Example {
+        topic: "disk",
+        title: "Cron job that mails a warning when a disk passes 90%",
+        commands: &["filebyte --no-color --disk list --warn-usage 90"],
+    },
+    Example {
+        topic: "disk",
+        title: "Check mount options such as read-only or noexec",
+        commands: &["filebyte mounts"],
+    },
+    Example {
+        topic: "daemon",
+        title: "Serve the scan API on a shared host",
+        commands: &["filebyte serve --bind 0.0.0.0:7878 --max-scans 2 --token-file /etc/filebyte/tokens"],
+    },
+    Example {
+        topic: "daemon",
+        title: "Queue a scan and fetch the results",
+        commands: &[
+            "curl -X POST localhost:7878/scans -d '{\"path\": \"/srv\", \"recursive\": true}'",
+            "curl localhost:7878/scans/1/results",
+        ],
+    },
+];
+
+/// The distinct topics, in registry order
+pub fn topics() -> Vec<&'static str> {
+    let mut topics: Vec<&str> = Vec::new();
+    for example in EXAMPLES {
+        if !topics.contains(&example.topic) {
+            topics.push(example.topic);
+        }
+    }
+    topics
+}
+
+/// Handle `filebyte examples [TOPIC]`
+pub fn run_examples(topic: Option<&str>, color: bool) {
+    if let Some(topic) = topic {
+        if !topics().contains(&topic) {
+            eprintln!("Error: no examples for '{}'. Topics: {}", topic, topics().join(", "));
+            crate::perf::exit(1);
+        }
+    }
+
+    let mut current = "";
+    for example in EXAMPLES.iter().filter(|e| topic.is_none_or(|t| t == e.topic)) {
+        if example.topic != current {
+            current = example.topic;
+            if color {
+                println!("{}", format!("# {}", current).blue().bold());
+            } else {
+                println!("# {}", current);
+            }
+            println!();
+        }
+        if color {
+            println!("{}", example.title.green());
+        } else {
+            println!("{}", example.title);
+        }
+        for command in example.commands {
+            println!("  {}", command);
+            if let Err(e) = check_command(command) {
+                eprintln!("Warning: example '{}' no longer matches the CLI: {}", example.title, e);
+            }
+        }
+        println!();
+    }
+}
+
+/// Parse the `filebyte` invocations of a shell line with the real CLI
+/// definition, so recipes that use a removed or renamed flag are caught
+fn check_command(command: &str) -> Result<(), String> {
+    for words in split_pipeline(command) {
+        if words.first().map(String::as_str) != Some("filebyte") {
+            continue;
+        }
+        crate::build_cli()
+            .try_get_matches_from(&words)
+            .map_err(|e| e.kind().to_string())?;
+    }
+    Ok(())
+}
+
+/// Split a shell line into the words of each pipeline stage, honoring
+/// single and double quotes
+fn split_pipeline(line: &str) -> Vec<Vec<String>> {
+    let mut stages = vec![Vec::new()];
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quote = None;
+    for c in line.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => word.push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                in_word = true;
+            }
+            (None, c) if c.is_whitespace() || c == '|' => {
+                if in_word {
+                    stages.last_mut().unwrap().push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+                if c == '|' {
+                    stages.push(Vec::new());
+                }
+            }
+            (None, c) => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if in_word {
+        stages.last_mut().unwrap().push(word);
+    }
+    stages
+}
@@ -0,0 +1,103 @@
+//! Git working tree status for `--tree`.
+//!
+//! The status comes from `git status --porcelain -z --ignored`, run once for
+//! the whole tree. Untracked and ignored directories are reported as a
+//! single entry, so their contents inherit the directory's status.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// What git thinks of one path
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GitMark {
+    /// Tracked with changes; the two status letters (staged, unstaged) with
+    /// the unchanged side dropped, e.g. `M`, `A` or `MM`
+    Changed(String),
+    Untracked,
+    Ignored,
+}
+
+impl GitMark {
+    /// The marker shown next to a name, as in `git status --short`
+    pub fn as_str(&self) -> &str {
+        match self {
+            GitMark::Changed(code) => code,
+            GitMark::Untracked => "??",
+            GitMark::Ignored => "!!",
+        }
+    }
+}
+
+/// The status of every changed, untracked and ignored path in a repository
+#[derive(Debug, Clone, Default)]
+pub struct GitStatus {
+    /// Where the scanned directory sits below the repository root
+    base: PathBuf,
+    marks: HashMap<PathBuf, GitMark>,
+}
+
+/// Parse `git status --porcelain=v1 -z` output. Paths are relative to the
+/// repository root, without the trailing slash git puts on directories.
+pub fn parse_porcelain(output: &str) -> HashMap<PathBuf, GitMark> {
+    let mut marks = HashMap::new();
+    let mut fields = output.split('\0');
+    while let Some(field) = fields.next() {
+        if field.len() < 4 {
+            continue;
+        }
+        let (code, path) = field.split_at(3);
+        let code = &code[..2];
+        // Renames and copies are followed by the original path
+        if code.contains(['R', 'C']) {
+            fields.next();
+        }
+        let mark = match code {
+            "??" => GitMark::Untracked,
+            "!!" => GitMark::Ignored,
+            _ => GitMark::Changed(code.trim().to_string()),
+        };
+        marks.insert(PathBuf::from(path.trim_end_matches('/')), mark);
+    }
+    marks
+}
+
+impl GitStatus {
+    /// Status of the repository `dir` is in, or `None` outside a repository
+    /// or when git is not installed
+    pub fn for_dir(dir: &Path) -> Option<GitStatus> {
+        let git = |args: &[&str]| {
+            let output = Command::new("git")
+                .arg("-C")
+                .arg(dir)
+                .args(args)
+                .stdin(Stdio::null())
+                .stderr(Stdio::null())
+                .output()
+                .ok()?;
+            output
+                .status
+                .success()
+                .then(|| String::from_utf8_lossy(&output.stdout).to_string())
+        };
+        let prefix = git(&["rev-parse", "--show-prefix"])?;
+        let status = git(&["status", "--porcelain=v1", "-z", "--ignored"])?;
+        Some(GitStatus {
+            base: PathBuf::from(prefix.trim_end()),
+            marks: parse_porcelain(&status),
+        })
+    }
+
+    /// The mark for `relative`, a path below the scanned directory. Entries
+    /// inside an untracked or ignored directory share its mark.
+    pub fn mark(&self, relative: &Path) -> Option<&GitMark> {
+        let path = self.base.join(relative);
+        if let Some(mark) = self.marks.get(&path) {
+            return Some(mark);
+        }
+        path.ancestors()
+            .skip(1)
+            .filter_map(|dir| self.marks.get(dir))
+            .find(|mark| matches!(mark, GitMark::Untracked | GitMark::Ignored))
+    }
+}
@@ -0,0 +1,187 @@
+//! Reclaimable build artifacts, caches and trash (`--junk-report`).
+//!
+//! Each [`JunkProfile`] recognizes one kind of directory by its name and,
+//! where the name alone is too common (`target`, `venv`), by a marker file
+//! the tool that creates it leaves behind. A matched directory is sized as a
+//! whole and not searched further, so a `node_modules` nested in another
+//! counts once.
+
+use crate::perf;
+use crate::types::SizeUnit;
+use crate::utils::{device_id, directory_size_excluding, group_digits};
+use colored::Colorize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A kind of directory that can be deleted and recreated
+#[derive(Debug)]
+pub struct JunkProfile {
+    pub category: &'static str,
+    /// How the contents come back, or why they are safe to remove
+    pub hint: &'static str,
+    /// Whether `path`, named `name`, belongs to this category
+    pub detect: fn(path: &Path, name: &str) -> bool,
+}
+
+/// The built-in profiles, checked in order
+pub const PROFILES: &[JunkProfile] = &[
+    JunkProfile {
+        category: "node_modules",
+        hint: "npm install",
+        detect: |_, name| name == "node_modules",
+    },
+    JunkProfile {
+        category: "Rust build output",
+        hint: "cargo build",
+        detect: |path, name| {
+            name == "target"
+                && (path.join("CACHEDIR.TAG").is_file()
+                    || path.join(".rustc_info.json").is_file()
+                    || path.parent().is_some_and(|parent| parent.join("Cargo.toml").is_file()))
+        },
+    },
+    JunkProfile {
+        category: "Python virtualenvs",
+        hint: "python -m venv",
+        detect: |path, name| matches!(name, ".venv" | "venv" | "env") && path.join("pyvenv.cfg").is_file(),
+    },
+    JunkProfile {
+        category: "Python caches",
+        hint: "recreated on the next run",
+        detect: |_, name| matches!(name, "__pycache__" | ".pytest_cache" | ".mypy_cache" | ".ruff_cache"),
+    },
+    JunkProfile {
+        category: "Gradle caches",
+        hint: "recreated on the next build",
+        detect: |_, name| name == ".gradle",
+    },
+    JunkProfile {
+        category: "Trash",
+        hint: "deleted files",
+        detect: |path, name| {
+            name == ".Trash"
+                || name.starts_with(".Trash-")
+                || (name == "Trash" && path.parent().is_some_and(|parent| parent.ends_with(".local/share")))
+        },
+    },
+];
+
+/// The profile `path` matches, if any
+pub fn detect(path: &Path) -> Option<&'static JunkProfile> {
+    let name = path.file_name()?.to_string_lossy();
+    PROFILES.iter().find(|profile| (profile.detect)(path, &name))
+}
+
+/// A directory matched by a profile
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JunkDir {
+    pub path: PathBuf,
+    pub category: &'static str,
+    pub size: u64,
+}
+
+/// Find the junk directories below `dir`, largest first, leaving out
+/// `skip` and, with `one_file_system`, other filesystems
+pub fn find_junk(dir: &Path, skip: &[PathBuf], one_file_system: bool) -> Vec<JunkDir> {
+    let Ok(device) = fs::metadata(dir).map(|m| device_id(&m)) else {
+        return Vec::new();
+    };
+    let mut found = Vec::new();
+
+    fn walk(path: &Path, device: u64, one_file_system: bool, skip: &[PathBuf], found: &mut Vec<JunkDir>) {
+        let listing = fs::read_dir(path);
+        perf::count_listing(&listing);
+        let Ok(entries) = listing else {
+            return;
+        };
+        for entry in entries.flatten() {
+            // Not following symlinks: a linked dependency is not ours to delete
+            let metadata = entry.metadata();
+            perf::count_metadata(&metadata);
+            let Ok(metadata) = metadata else {
+                continue;
+            };
+            let entry_path = entry.path();
+            let other_device = one_file_system && device_id(&metadata) != device;
+            if !metadata.is_dir() || other_device || skip.contains(&entry_path) {
+                continue;
+            }
+            match detect(&entry_path) {
+                Some(profile) => found.push(JunkDir {
+                    size: directory_size_excluding(&entry_path, skip),
+                    path: entry_path,
+                    category: profile.category,
+                }),
+                None => walk(&entry_path, device, one_file_system, skip, found),
+            }
+        }
+    }
+
+    walk(dir, device, one_file_system, skip, &mut found);
+    found.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.path.cmp(&b.path)));
+    found
+}
+
+/// Total size and directory count per category, largest first
+pub fn totals_by_category(junk: &[JunkDir]) -> Vec<(&'static JunkProfile, usize, u64)> {
+    let mut totals: Vec<(&'static JunkProfile, usize, u64)> = PROFILES
+        .iter()
+        .map(|profile| {
+            let dirs = junk.iter().filter(|d| d.category == profile.category);
+            let (count, bytes) = dirs.fold((0, 0), |(count, bytes), d| (count + 1, bytes + d.size));
+            (profile, count, bytes)
+        })
+        .filter(|(_, count, _)| *count > 0)
+        .collect();
+    totals.sort_by_key(|&(_, _, bytes)| std::cmp::Reverse(bytes));
+    totals
+}
+
+/// Summarize the junk below `dir` per category, then list the `limit`
+/// largest directories
+pub fn show_junk_report(dir: &Path, limit: usize, skip: &[PathBuf], one_file_system: bool, color: bool) {
+    let junk = find_junk(dir, skip, one_file_system);
+    println!("Junk Report:");
+    println!("{}", "─".repeat(50));
+    if junk.is_empty() {
+        println!("No build artifacts, caches or trash found.");
+        return;
+    }
+
+    let totals = totals_by_category(&junk);
+    let width = totals.iter().map(|(profile, _, _)| profile.category.len()).max().unwrap_or(0);
+    for (profile, count, bytes) in &totals {
+        let size = format!("{:>10}", SizeUnit::auto_format_size(*bytes));
+        let dirs = format!("{:>5} dirs", group_digits(*count as u64));
+        let category = format!("{:<width$}", profile.category);
+        if color {
+            println!("{}  {}  {}  ({})", category.magenta(), size.cyan(), dirs, profile.hint.dimmed());
+        } else {
+            println!("{}  {}  {}  ({})", category, size, dirs, profile.hint);
+        }
+    }
+    let total: u64 = junk.iter().map(|d| d.size).sum();
+    let total = format!(
+        "Total: {} in {} directories",
+        SizeUnit::auto_format_size(total),
+        group_digits(junk.len() as u64)
+    );
+    if color {
+        println!("{}", total.green().bold());
+    } else {
+        println!("{}", total);
+    }
+
+    println!("\nLargest:");
+    for junk_dir in junk.iter().take(limit) {
+        let size = format!("{:>10}", SizeUnit::auto_format_size(junk_dir.size));
+        if color {
+            println!("{}  {}  ({})", size.cyan(), junk_dir.path.display(), junk_dir.category.dimmed());
+        } else {
+            println!("{}  {}  ({})", size, junk_dir.path.display(), junk_dir.category);
+        }
+    }
+    if junk.len() > limit {
+        println!("  ... and {} more", group_digits((junk.len() - limit) as u64));
+    }
+}
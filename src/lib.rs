@@ -0,0 +1,45 @@
+//! File and directory analysis used by the `filebyte` CLI.
+//!
+//! Embedders will usually start with [`scan::ScanBuilder`], which walks a
+//! tree and yields [`types::FileInfo`] records plus a [`scan::Summary`].
+
+pub mod analysis;
+pub mod archive;
+pub mod cache;
+pub mod checksum;
+pub mod cleanup;
+pub mod collect;
+pub mod compare;
+pub mod config;
+pub mod contains;
+pub mod daemon;
+#[cfg(feature = "db")]
+pub mod database;
+pub mod dimensions;
+pub mod display;
+pub mod disk;
+pub mod drift;
+pub mod encoding;
+pub mod entropy;
+pub mod git;
+pub mod junk;
+pub mod loc;
+pub mod manifest;
+pub mod merge;
+pub mod migrate;
+pub mod mismatch;
+pub mod mounts;
+pub mod perf;
+pub mod preview;
+pub mod quantiles;
+pub mod scan;
+pub mod secrets;
+pub mod similar;
+pub mod snapshot;
+#[cfg(feature = "smart")]
+pub mod smart;
+pub mod stream;
+pub mod streams;
+pub mod tree;
+pub mod types;
+pub mod utils;
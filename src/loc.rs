@@ -0,0 +1,290 @@
+//! Line counts per extension (`--loc`).
+//!
+//! Every text file is counted, where a file is text if its first
+//! [`SNIFF_LEN`] bytes hold no NUL byte. For the extensions in
+//! [`LANGUAGES`] lines are further split into code, comments and blanks;
+//! other text files only get lines and blanks. Comment markers inside string
+//! literals are not recognised, so the split is an estimate, much like a
+//! quick `tokei` run.
+
+use crate::perf::CountedFile;
+use crate::types::{FileInfo, FileKind, SizeUnit};
+use crate::utils::group_digits;
+use colored::Colorize;
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::Path;
+
+/// Bytes checked for a NUL byte before a file is treated as text
+pub const SNIFF_LEN: usize = 8 * 1024;
+
+/// How one language writes comments
+#[derive(Debug)]
+pub struct Language {
+    pub name: &'static str,
+    /// Lowercase, without the dot
+    pub extensions: &'static [&'static str],
+    pub line_comments: &'static [&'static str],
+    /// Start and end delimiters
+    pub block_comment: Option<(&'static str, &'static str)>,
+}
+
+const C_STYLE: Option<(&str, &str)> = Some(("/*", "*/"));
+
+/// The recognised languages
+pub const LANGUAGES: &[Language] = &[
+    Language { name: "Rust", extensions: &["rs"], line_comments: &["//"], block_comment: C_STYLE },
+    Language { name: "C", extensions: &["c", "h"], line_comments: &["//"], block_comment: C_STYLE },
+    Language {
+        name: "C++",
+        extensions: &["cc", "cpp", "cxx", "hh", "hpp"],
+        line_comments: &["//"],
+        block_comment: C_STYLE,
+    },
+    Language { name: "Go", extensions: &["go"], line_comments: &["//"], block_comment: C_STYLE },
+    Language { name: "Java", extensions: &["java"], line_comments: &["//"], block_comment: C_STYLE },
+    Language { name: "Kotlin", extensions: &["kt", "kts"], line_comments: &["//"], block_comment: C_STYLE },
+    Language { name: "C#", extensions: &["cs"], line_comments: &["//"], block_comment: C_STYLE },
+    Language { name: "Swift", extensions: &["swift"], line_comments: &["//"], block_comment: C_STYLE },
+    Language {
+        name: "JavaScript",
+        extensions: &["js", "mjs", "cjs", "jsx"],
+        line_comments: &["//"],
+        block_comment: C_STYLE,
+    },
+    Language { name: "TypeScript", extensions: &["ts", "tsx"], line_comments: &["//"], block_comment: C_STYLE },
+    Language { name: "CSS", extensions: &["css"], line_comments: &[], block_comment: C_STYLE },
+    Language { name: "Python", extensions: &["py"], line_comments: &["#"], block_comment: None },
+    Language { name: "Ruby", extensions: &["rb"], line_comments: &["#"], block_comment: None },
+    Language { name: "Shell", extensions: &["sh", "bash", "zsh"], line_comments: &["#"], block_comment: None },
+    Language { name: "Perl", extensions: &["pl", "pm"], line_comments: &["#"], block_comment: None },
+    Language { name: "TOML", extensions: &["toml"], line_comments: &["#"], block_comment: None },
+    Language { name: "YAML", extensions: &["yaml", "yml"], line_comments: &["#"], block_comment: None },
+    Language { name: "SQL", extensions: &["sql"], line_comments: &["--"], block_comment: C_STYLE },
+    Language { name: "Haskell", extensions: &["hs"], line_comments: &["--"], block_comment: Some(("{-", "-}")) },
+    Language { name: "HTML", extensions: &["html", "htm"], line_comments: &[], block_comment: Some(("<!--", "-->")) },
+    Language { name: "XML", extensions: &["xml", "svg"], line_comments: &[], block_comment: Some(("<!--", "-->")) },
+    Language { name: "Makefile", extensions: &["mk"], line_comments: &["#"], block_comment: None },
+];
+
+/// The language of `path`, from its extension
+pub fn language_for(path: &Path) -> Option<&'static Language> {
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    LANGUAGES.iter().find(|language| language.extensions.contains(&extension.as_str()))
+}
+
+/// Line counts of one file or many; `code + comments + blanks == lines`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LineCounts {
+    pub lines: u64,
+    pub code: u64,
+    pub comments: u64,
+    pub blanks: u64,
+}
+
+impl std::ops::AddAssign for LineCounts {
+    fn add_assign(&mut self, other: LineCounts) {
+        self.lines += other.lines;
+        self.code += other.code;
+        self.comments += other.comments;
+        self.blanks += other.blanks;
+    }
+}
+
+/// Whether `line` holds code, given whether it starts inside a block
+/// comment; `in_block` is updated for the next line
+fn is_code(line: &str, language: &Language, in_block: &mut bool) -> bool {
+    let mut rest = line;
+    let mut code = false;
+    loop {
+        rest = rest.trim_start();
+        if *in_block {
+            let (_, end) = language.block_comment.unwrap();
+            match rest.find(end) {
+                Some(i) => {
+                    *in_block = false;
+                    rest = &rest[i + end.len()..];
+                    continue;
+                }
+                None => return code,
+            }
+        }
+        if rest.is_empty() || language.line_comments.iter().any(|marker| rest.starts_with(marker)) {
+            return code;
+        }
+        match language.block_comment.and_then(|(start, _)| rest.find(start).map(|i| (i, start))) {
+            Some((i, start)) => {
+                code |= i > 0;
+                *in_block = true;
+                rest = &rest[i + start.len()..];
+            }
+            None => return true,
+        }
+    }
+}
+
+/// Count the lines of `text`, splitting out comments for `language`
+pub fn count_lines(text: impl BufRead, language: Option<&Language>) -> io::Result<LineCounts> {
+    let mut counts = LineCounts::default();
+    let mut in_block = false;
+    for line in text.split(b'\n') {
+        let line = line?;
+        let line = String::from_utf8_lossy(&line);
+        counts.lines += 1;
+        if line.trim().is_empty() {
+            counts.blanks += 1;
+        } else if language.is_none_or(|language| is_code(&line, language, &mut in_block)) {
+            counts.code += 1;
+        } else {
+            counts.comments += 1;
+        }
+    }
+    Ok(counts)
+}
+
+/// Open the file at `path` for reading line by line; `None` if its first
+/// [`SNIFF_LEN`] bytes hold a NUL byte, so it looks binary
+pub(crate) fn open_text(path: &Path) -> io::Result<Option<impl BufRead>> {
+    let mut reader = BufReader::new(CountedFile::open(path)?);
+    let mut head = Vec::with_capacity(SNIFF_LEN);
+    (&mut reader).take(SNIFF_LEN as u64).read_to_end(&mut head)?;
+    if head.contains(&0) {
+        return Ok(None);
+    }
+    Ok(Some(io::Cursor::new(head).chain(reader)))
+}
+
+/// Count the lines of the file at `path`; `None` if it looks binary
+pub fn count_file(path: &Path) -> io::Result<Option<LineCounts>> {
+    match open_text(path)? {
+        Some(text) => count_lines(text, language_for(path)).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Totals for one extension
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtensionLines {
+    /// Lowercased with the dot, or "(none)"
+    pub extension: String,
+    pub language: Option<&'static str>,
+    pub files: u64,
+    pub bytes: u64,
+    pub counts: LineCounts,
+}
+
+/// Count every text file in `files` and total them per extension, most
+/// lines first
+pub fn lines_by_extension(files: &[FileInfo]) -> Vec<ExtensionLines> {
+    let mut by_extension: HashMap<String, ExtensionLines> = HashMap::new();
+    for file in files.iter().filter(|f| f.kind == FileKind::File) {
+        let path = Path::new(&file.path);
+        let Ok(Some(counts)) = count_file(path) else {
+            continue;
+        };
+        let extension = path
+            .extension()
+            .map_or_else(|| "(none)".to_string(), |e| format!(".{}", e.to_string_lossy().to_ascii_lowercase()));
+        let entry = by_extension.entry(extension.clone()).or_insert_with(|| ExtensionLines {
+            extension,
+            language: language_for(path).map(|language| language.name),
+            files: 0,
+            bytes: 0,
+            counts: LineCounts::default(),
+        });
+        entry.files += 1;
+        entry.bytes += file.size;
+        entry.counts += counts;
+    }
+    let mut totals: Vec<ExtensionLines> = by_extension.into_values().collect();
+    totals.sort_by(|a, b| b.counts.lines.cmp(&a.counts.lines).then_with(|| a.extension.cmp(&b.extension)));
+    totals
+}
+
+/// Print the line counts of the `limit` extensions with the most lines,
+/// then the totals over all of them
+pub fn show_loc_report(files: &[FileInfo], limit: usize, color: bool) {
+    let totals = lines_by_extension(files);
+    println!("Lines of Code:");
+    println!("{}", "─".repeat(50));
+    if totals.is_empty() {
+        println!("No text files found.");
+        return;
+    }
+
+    let header = format!(
+        "{:<10} {:<12} {:>7} {:>9} {:>9} {:>9} {:>9} {:>10}",
+        "Extension", "Language", "Files", "Lines", "Code", "Comments", "Blanks", "Size"
+    );
+    if color {
+        println!("{}", header.bold());
+    } else {
+        println!("{}", header);
+    }
+    let row = |extension: &str, language: &str, files: u64, bytes: u64, counts: &LineCounts, known: bool| {
+        let comments = if known { group_digits(counts.comments) } else { "-".to_string() };
+        format!(
+            "{:<10} {:<12} {:>7} {:>9} {:>9} {:>9} {:>9} {:>10}",
+            extension,
+            language,
+            group_digits(files),
+            group_digits(counts.lines),
+            group_digits(counts.code),
+            comments,
+            group_digits(counts.blanks),
+            SizeUnit::auto_format_size(bytes)
+        )
+    };
+    for entry in totals.iter().take(limit) {
+        let line = row(
+            &entry.extension,
+            entry.language.unwrap_or(""),
+            entry.files,
+            entry.bytes,
+            &entry.counts,
+            entry.language.is_some(),
+        );
+        if color && entry.language.is_some() {
+            println!("{}", line.cyan());
+        } else {
+            println!("{}", line);
+        }
+    }
+    if totals.len() > limit {
+        println!("  ... and {} more extensions", group_digits((totals.len() - limit) as u64));
+    }
+
+    let mut all = LineCounts::default();
+    for entry in &totals {
+        all += entry.counts;
+    }
+    let files = totals.iter().map(|e| e.files).sum();
+    let bytes = totals.iter().map(|e| e.bytes).sum();
+    let known = totals.iter().any(|e| e.language.is_some());
+    println!("{}", "─".repeat(50));
+    let total = row("Total", "", files, bytes, &all, known);
+    if color {
+        println!("{}", total.green().bold());
+    } else {
+        println!("{}", total);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loc_splits_comments() {
+        let rust = language_for(std::path::Path::new("main.rs"));
+        let count = |text: &str| count_lines(text.as_bytes(), rust).unwrap();
+        let counts = |lines, code, comments, blanks| LineCounts { lines, code, comments, blanks };
+
+        assert_eq!(count(""), counts(0, 0, 0, 0));
+        assert_eq!(count("// a\n\n  let x = 1; // b\n"), counts(3, 1, 1, 1));
+        // Block comments span lines; code on either side of one still counts
+        assert_eq!(count("/* a\n b */\nx /* c */ y\nz /* d\n*/ w\n"), counts(5, 3, 2, 0));
+        assert_eq!(count("x\r\n\r\n"), counts(2, 1, 0, 1));
+        assert_eq!(count_lines("# not a comment\n".as_bytes(), None).unwrap(), counts(1, 1, 0, 0));
+    }
+}
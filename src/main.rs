@@ -1,27 +1,50 @@
 use chrono::{DateTime, Utc};
-use clap::{Arg, Command};
+use clap::{Arg, ArgGroup, ArgMatches, Command};
 use colored::Colorize;
-use infer;
+use regex::Regex;
 use std::fs;
-use std::io::{self, Write};
+use std::io::{self, Read, Write};
 use std::path::Path;
-use std::process;
+use std::time::{Duration, Instant};
 
-mod analysis;
-mod collect;
-mod display;
-mod disk;
-mod tree;
-mod types;
-mod utils;
+mod doctor;
+mod examples;
+mod usage;
 
-use analysis::{find_duplicates, show_detailed_analysis};
-use collect::{collect_files, collect_files_recursive};
-use display::{display_files, show_file_type_stats};
-use disk::{list_disks, show_disk_info};
-use tree::print_tree;
-use types::{SizeUnit, SortBy};
-use utils::{can_delete, format_unix_permissions, get_file_size};
+use filebyte::analysis::{find_duplicates, find_duplicates_in_paths, show_busiest_dirs, show_case_collisions, show_detailed_analysis, show_duplicate_names, show_largest_dirs, show_tree_comparison, DirRanking};
+use filebyte::compare::{compare_dirs, show_comparison, CompareOptions};
+use filebyte::config::{config_path, load_config, Config};
+use filebyte::contains::show_content_matches;
+use filebyte::checksum::{checksum_files, show_checksums, ChecksumStyle};
+use filebyte::cleanup::show_cleanup_suggestions;
+use filebyte::collect::{collect_files, collect_files_recursive, collect_from_paths, skipped_paths, total_size, virtual_exclusions};
+use filebyte::display::{display_files, export_to_csv, export_to_json, limit_files, show_file_type_stats, show_size_by_owner, show_size_by_type, show_virtual_exclusions};
+use filebyte::daemon::{parse_tokens, Daemon, DaemonConfig};
+use filebyte::disk::{
+    disk_records, format_porcelain, list_disks, list_mounts, show_disk_info, show_usage_alerts, usage_alerts, DiskFilter,
+    DiskView, DisksExport,
+};
+use filebyte::archive::{deep_contents, deep_totals, show_archive_report, show_archive_verification, show_deep_contents};
+use filebyte::encoding::{detect_file, show_encoding_report, TextEncoding};
+use filebyte::entropy::show_entropy_report;
+use filebyte::junk::show_junk_report;
+use filebyte::loc::show_loc_report;
+use filebyte::drift::{detect_changes, show_drift};
+use filebyte::manifest::{load_manifest, show_verification, verify_manifest, write_manifest};
+use filebyte::merge::merge_scans;
+use filebyte::mismatch::show_type_mismatches;
+use filebyte::perf;
+use filebyte::preview::show_preview;
+use filebyte::secrets::show_secrets_report;
+use filebyte::snapshot;
+use filebyte::stream::stream_files;
+use filebyte::streams::{self, show_ads_report, stream_summary};
+use filebyte::tree::{print_tree, DEFAULT_MAX_ENTRIES};
+use filebyte::types::{parse_size, Clock, CollectOptions, CsvSchema, DisplayOptions, DuplicateOptions, FileInfo, FileKind, Filter, HashAlgorithm, ScanMetadata, SizePrecision, SizeUnit, SortBy, SortSpec, TreeCharset, TreeOptions, TypeDetection, UnitSystem};
+use filebyte::utils::{
+    attribute_summary, detect_file_type, flag_summary, format_permissions, get_file_size, get_group_name, get_owner_name,
+    group_digits, quarantine_summary, short_permissions,
+};
 
 const VERSION: &str = "1.4.4";
 
@@ -46,27 +69,214 @@ fn return_to_menu(_color: bool) {
     clear_screen();
 }
 
-fn main() {
-    let matches = Command::new("filebyte")
+/// Read a newline or NUL separated path list from a file, or stdin for "-"
+fn read_ingest_paths(source: &str) -> Vec<String> {
+    let mut contents = String::new();
+    let result = if source == "-" {
+        io::stdin().read_to_string(&mut contents).map(|_| ())
+    } else {
+        fs::read_to_string(source).map(|c| contents = c)
+    };
+    if let Err(e) = result {
+        eprintln!("Error reading path list '{}': {}", source, e);
+        perf::exit(1);
+    }
+
+    let separator = if contents.contains('\0') { '\0' } else { '\n' };
+    contents
+        .split(separator)
+        .map(|line| line.trim_end_matches('\r'))
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.to_string())
+        .collect()
+}
+
+/// Export files to the database given by --export-db, if any
+fn export_db(files: &[FileInfo], url: Option<&String>) {
+    if let Some(url) = url {
+        #[cfg(feature = "db")]
+        if let Err(e) = filebyte::database::export_to_database(files, url) {
+            eprintln!("Database export failed: {}", e);
+            perf::exit(1);
+        }
+        #[cfg(not(feature = "db"))]
+        {
+            let _ = files;
+            eprintln!(
+                "Error: cannot export to '{}': filebyte was built without the 'db' feature",
+                filebyte::utils::redact_url(url)
+            );
+            perf::exit(1);
+        }
+    }
+}
+
+/// Creation or modification time as the analysis reports print it
+fn analysis_time(time: io::Result<std::time::SystemTime>) -> String {
+    DateTime::<Utc>::from(time.unwrap_or(std::time::SystemTime::UNIX_EPOCH))
+        .format("%Y-%m-%d %H:%M:%S UTC")
+        .to_string()
+}
+
+/// Print the File Analysis report shared by `FILE`, `-f`, `-p`, `--whole`
+/// and the interactive menu. `text` adds the encoding lines of `-p`.
+fn print_file_analysis(
+    path: &Path,
+    metadata: &fs::Metadata,
+    size: &str,
+    permissions: &str,
+    text: Option<&TextEncoding>,
+    color: bool,
+) {
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let full_path = path.canonicalize().unwrap_or(path.to_path_buf());
+    let file_type = detect_file_type(path);
+    let extension = if let Some(ext) = path.extension() {
+        ext.to_string_lossy().to_string()
+    } else if let Some(dotted) = file_name.strip_prefix('.').filter(|rest| !rest.is_empty()) {
+        dotted.to_string()
+    } else {
+        "none".to_string()
+    };
+    let owner = get_owner_name(metadata);
+    let group = get_group_name(metadata);
+    let created = analysis_time(metadata.created());
+    let modified = analysis_time(metadata.modified());
+    let optional = [
+        ("Attributes", attribute_summary(metadata)),
+        ("Flags", flag_summary(metadata)),
+        ("Quarantine", quarantine_summary(path)),
+        ("Alternate Streams", stream_summary(path)),
+    ];
+
+    println!();
+    println!("File Analysis:");
+    println!("{}", "─".repeat(50));
+    if color {
+        println!("Name: {}", file_name.blue().bold());
+        println!("Path: {}", full_path.display());
+        println!("Size: {}", size.green().bold());
+        println!("Type: {}", file_type.magenta());
+        println!("Extension: {}", extension.cyan());
+        if let Some(text) = text {
+            println!("Encoding: {}", text.describe().cyan());
+            println!("Line endings: {}", text.line_endings.describe().cyan());
+        }
+        println!("Permissions: {}", permissions.yellow());
+        println!("Owner: {}", owner.yellow());
+        println!("Group: {}", group.yellow());
+        println!("Created: {}", created.yellow());
+        println!("Modified: {}", modified.yellow());
+        for (label, value) in optional {
+            if let Some(value) = value {
+                println!("{}: {}", label, value.yellow());
+            }
+        }
+    } else {
+        println!("Name: {}", file_name);
+        println!("Path: {}", full_path.display());
+        println!("Size: {}", size);
+        println!("Type: {}", file_type);
+        println!("Extension: {}", extension);
+        if let Some(text) = text {
+            println!("Encoding: {}", text.describe());
+            println!("Line endings: {}", text.line_endings.describe());
+        }
+        println!("Permissions: {}", permissions);
+        println!("Owner: {}", owner);
+        println!("Group: {}", group);
+        println!("Created: {}", created);
+        println!("Modified: {}", modified);
+        for (label, value) in optional {
+            if let Some(value) = value {
+                println!("{}: {}", label, value);
+            }
+        }
+    }
+}
+
+/// Print the Directory Analysis report shared by `-d`, `--whole` and the
+/// interactive menu
+fn print_directory_analysis(path: &Path, metadata: &fs::Metadata, size: &str, permissions: &str, color: bool) {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let owner = get_owner_name(metadata);
+    let group = get_group_name(metadata);
+    let created = analysis_time(metadata.created());
+    let modified = analysis_time(metadata.modified());
+
+    println!();
+    println!("Directory Analysis:");
+    println!("{}", "─".repeat(50));
+    if color {
+        println!("Name: {}", name.blue().bold());
+        println!("Path: {}", path.display());
+        println!("Size: {}", size.green().bold());
+        println!("Permissions: {}", permissions.yellow());
+        println!("Owner: {}", owner.yellow());
+        println!("Group: {}", group.yellow());
+        println!("Created: {}", created.yellow());
+        println!("Modified: {}", modified.yellow());
+    } else {
+        println!("Name: {}", name);
+        println!("Path: {}", path.display());
+        println!("Size: {}", size);
+        println!("Permissions: {}", permissions);
+        println!("Owner: {}", owner);
+        println!("Group: {}", group);
+        println!("Created: {}", created);
+        println!("Modified: {}", modified);
+    }
+}
+
+/// Options that only change how results are shown; given on their own they
+/// still mean a full listing of the current directory
+const PRESENTATION_ARGS: [&str; 12] = [
+    "size",
+    "si",
+    "size_precision",
+    "truncate_sizes",
+    "now",
+    "stats",
+    "no-color",
+    "deep_archives",
+    "expand_archives",
+    "line_numbers",
+    "preview",
+    "interactive",
+];
+
+fn build_cli() -> Command {
+    let cli = Command::new("filebyte")
         .version(VERSION)
         .author("execRooted <rooted@execrooted.com>")
         .about("A CLI tool for file analysis")
+        .help_template("{name} {version}\n{author}\n{about}\n\n{usage-heading} {usage}\n\n{all-args}")
+        .override_usage(
+            "filebyte [OPTIONS] [PATH]\n       \
+             filebyte --disk <DISK> [OPTIONS]\n       \
+             filebyte -f <FILE> | --file <FILE>\n       \
+             filebyte -d <DIR> | --directory <DIR>\n       \
+             filebyte --ingest-paths <FILE|-> [OPTIONS]\n       \
+             filebyte <COMMAND>",
+        )
         .disable_version_flag(true)
         .disable_help_flag(true)
-        .arg(Arg::new("path").help("Path to file or directory").index(1))
+        .disable_help_subcommand(true)
+        .arg(Arg::new("path").help("Path to file or directory").value_name("PATH").index(1))
         .arg(
             Arg::new("version")
                 .short('v')
                 .long("version")
                 .help("Show version information")
-                .action(clap::ArgAction::SetTrue),
+                .action(clap::ArgAction::Version),
         )
         .arg(
             Arg::new("help")
                 .short('h')
                 .long("help")
                 .help("Show help information")
-                .action(clap::ArgAction::SetTrue),
+                .global(true)
+                .action(clap::ArgAction::Help),
         )
         .arg(
             Arg::new("size")
@@ -76,6 +286,109 @@ fn main() {
                 .value_name("UNIT")
                 .num_args(0..=1),
         )
+        .arg(
+            Arg::new("si")
+                .long("si")
+                .help("Use powers of 1000 (kB, MB, GB) instead of 1024 (KiB, MiB, GiB)")
+                .global(true)
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("size_precision")
+                .long("size-precision")
+                .help("Decimal places for sizes, 0-3 [default: 2]")
+                .value_name("N")
+                .global(true)
+                .value_parser(clap::value_parser!(u8).range(0..=3)),
+        )
+        .arg(
+            Arg::new("truncate_sizes")
+                .long("truncate-sizes")
+                .help("Truncate extra size digits instead of rounding")
+                .global(true)
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("age_colors")
+                .long("age-colors")
+                .help("Tint modified dates (or names) from fresh to untouched for years")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("size_colors")
+                .long("size-colors")
+                .help("Color sizes (or names) on a heat scale from tiny to multi-GB")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("now")
+                .long("now")
+                .help("Treat this time as now for age reports, e.g. 2024-01-01T00:00:00Z")
+                .value_name("TIME")
+                .global(true),
+        )
+        .arg(
+            Arg::new("stats")
+                .long("stats")
+                .help("When the command is done, report wall time, files and directories per second, bytes statted and read, scan cache hits and errors on stderr")
+                .global(true)
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("du")
+                .long("du")
+                .help("Compute total sizes of subdirectories (slower on large trees)")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("include_virtual")
+                .long("include-virtual")
+                .help("Also scan /proc, /sys, /dev and /run when scanning /")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("threads")
+                .long("threads")
+                .help("Threads hashing files for --duplicates [default: number of CPUs]")
+                .value_name("N")
+                .value_parser(clap::builder::RangedU64ValueParser::<usize>::new().range(1..)),
+        )
+        .arg(
+            Arg::new("stream")
+                .long("stream")
+                .help("With -r, print and export entries as they are found instead of holding them in memory")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("no_mime")
+                .long("no-mime")
+                .help("Guess file types from extensions instead of reading file contents")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("one_file_system")
+                .long("one-file-system")
+                .help("Don't descend into other filesystems mounted below the scanned directory")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("local_only")
+                .long("local-only")
+                .help("Skip network filesystems: in --disk list, and when scanning below a network mount")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("dimensions")
+                .long("dimensions")
+                .help("Show the width and height of PNG, JPEG, GIF, BMP and WebP images in listings and exports, read from their headers")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("refresh")
+                .long("refresh")
+                .help("Recursive scans: only re-read directories changed since the cached scan")
+                .action(clap::ArgAction::SetTrue),
+        )
         .arg(
             Arg::new("tree")
                 .short('t')
@@ -90,19 +403,208 @@ fn main() {
                 .help("Show detailed file properties and analysis")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("deep_archives")
+                .long("deep-archives")
+                .help("With -p, count what zip, tar, gzip and xz archives hold, opening archives inside archives down to DEPTH levels, and add the total size with archives expanded [default: 3]")
+                .value_name("DEPTH")
+                .num_args(0..=1)
+                .default_missing_value("3")
+                .value_parser(clap::builder::RangedU64ValueParser::<usize>::new().range(1..))
+                .requires("properties"),
+        )
         .arg(
             Arg::new("no-color")
                 .long("no-color")
                 .help("Disable colored output")
+                .global(true)
                 .action(clap::ArgAction::SetTrue),
         )
         .arg(
             Arg::new("disk")
                 .short('m')
                 .long("disk")
-                .help("Disk operations: 'list' to show all disks, or specify disk name for info")
+                .help("Disk operations: 'list' to show all disks, or a disk name, mount point, drive letter (C:) or UNC share for info")
                 .value_name("DISK"),
         )
+        .arg(
+            Arg::new("all")
+                .long("all")
+                .help("With --disk list, also show pseudo filesystems (tmpfs, squashfs, overlay) and loop devices")
+                .action(clap::ArgAction::SetTrue)
+                .requires("disk"),
+        )
+        .arg(
+            Arg::new("porcelain")
+                .long("porcelain")
+                .help("With --disk list, print df-style columns with sizes in bytes, for scripts")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with_all(["json", "health"])
+                .requires("disk"),
+        )
+        .arg(
+            Arg::new("json")
+                .long("json")
+                .help("With --disk list, print the disks as JSON, sizes in bytes")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with("health")
+                .requires("disk"),
+        )
+        .arg(
+            Arg::new("health")
+                .long("health")
+                .help("With --disk list, show SMART health, temperature and reallocated sectors (requires the 'smart' feature and smartctl)")
+                .action(clap::ArgAction::SetTrue)
+                .requires("disk"),
+        )
+        .arg(
+            Arg::new("io")
+                .long("io")
+                .help("With --disk <DISK>, show I/O since boot and throughput sampled over SECS seconds [default: 1]")
+                .value_name("SECS")
+                .num_args(0..=1)
+                .default_missing_value("1")
+                .value_parser(clap::value_parser!(u64).range(1..=60))
+                .requires("disk"),
+        )
+        .arg(
+            Arg::new("top_dirs")
+                .long("top-dirs")
+                .help("With --disk, walk the disk and show the N directories holding the most data")
+                .value_name("N")
+                .value_parser(clap::builder::RangedU64ValueParser::<usize>::new().range(1..))
+                .requires("disk"),
+        )
+        .arg(
+            Arg::new("largest_dirs")
+                .long("largest-dirs")
+                .help("Walk the directory and show the N subdirectories holding the most data")
+                .value_name("N")
+                .value_parser(clap::builder::RangedU64ValueParser::<usize>::new().range(1..)),
+        )
+        .arg(
+            Arg::new("most_files")
+                .long("most-files")
+                .help("Walk the directory and show the N directories holding the most files directly, with their average file size and depth")
+                .value_name("N")
+                .value_parser(clap::builder::RangedU64ValueParser::<usize>::new().range(1..)),
+        )
+        .arg(
+            Arg::new("depth")
+                .long("depth")
+                .help("Limit --tree to N levels, or rank --top-dirs/--largest-dirs directories up to N levels down [default for those: 3]")
+                .value_name("N")
+                .value_parser(clap::builder::RangedU64ValueParser::<usize>::new().range(1..)),
+        )
+        .arg(
+            Arg::new("summaries")
+                .long("summaries")
+                .help("With --tree, annotate each directory with its file count, directory count and total size")
+                .action(clap::ArgAction::SetTrue)
+                .requires("tree"),
+        )
+        .arg(
+            Arg::new("follow_symlinks")
+                .long("follow-symlinks")
+                .help("With --tree, descend into symlinked directories")
+                .action(clap::ArgAction::SetTrue)
+                .requires("tree"),
+        )
+        .arg(
+            Arg::new("charset")
+                .long("charset")
+                .help("Lines --tree is drawn with: utf8, or ascii for terminals and tools without UTF-8 [default: utf8 on a UTF-8 terminal, ascii otherwise]")
+                .value_name("CHARSET")
+                .value_parser(["utf8", "ascii"])
+                .requires("tree"),
+        )
+        .arg(
+            Arg::new("max_entries")
+                .long("max-entries")
+                .help("With --tree, print the first N entries of each directory and sum up the rest in one line [default: 1000]")
+                .value_name("N")
+                .value_parser(clap::builder::RangedU64ValueParser::<usize>::new().range(1..))
+                .requires("tree"),
+        )
+        .arg(
+            Arg::new("expand")
+                .long("expand")
+                .help("With --tree, print every entry of large directories")
+                .action(clap::ArgAction::SetTrue)
+                .requires("tree")
+                .conflicts_with("max_entries"),
+        )
+        .arg(
+            Arg::new("expand_archives")
+                .long("expand-archives")
+                .help("With --tree, show the files inside zip, tar and gzip archives below them, marked with the archive format")
+                .action(clap::ArgAction::SetTrue)
+                .requires("tree"),
+        )
+        .arg(
+            Arg::new("size_by_type")
+                .long("size-by-type")
+                .help("Show file count, total size and share of the total per extension and per MIME type, largest first (scans recursively)")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("size_by_owner")
+                .long("size-by-owner")
+                .help("Show file count, total size and share of the total per owner and per group, largest first (scans recursively)")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("type_mismatches")
+                .long("type-mismatches")
+                .help("List files whose extension disagrees with their content, executables first (scans recursively)")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with("no_mime"),
+        )
+        .arg(
+            Arg::new("entropy")
+                .long("entropy")
+                .help("Sample each file's byte entropy to split compressible from compressed or encrypted data, and list high-entropy files of unknown type (--top sets how many, default 20)")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("archives")
+                .long("archives")
+                .help("Compare each zip, tar, gzip and xz archive's size with its uncompressed contents, flagging archives that barely compress or look truncated (--top sets how many, default 20)")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("loc")
+                .long("loc")
+                .help("Count lines in text files, split into code, comments and blanks for recognized languages, with totals per extension (--top sets how many extensions, default 20)")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("junk_report")
+                .long("junk-report")
+                .help("Find node_modules, build output, virtualenvs, caches and trash, with the space each category takes (--top sets how many directories to list, default 10)")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("suggest_cleanup")
+                .long("suggest-cleanup")
+                .help("Rank directories by files of 1 MiB or more left unmodified and unread for 180+ days, with the space they would free (--top sets how many, default 10)")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("warn_usage")
+                .long("warn-usage")
+                .help("With --disk, warn and exit with status 1 when a listed (or the named) disk is more than PERCENT full")
+                .value_name("PERCENT")
+                .value_parser(clap::value_parser!(u8).range(1..=100))
+                .requires("disk"),
+        )
+        .arg(
+            Arg::new("fs_type")
+                .long("fs-type")
+                .help("With --disk list, only show these filesystem types; prefix a type with 'no' to hide it (ext4,xfs or notmpfs)")
+                .value_name("TYPES")
+                .requires("disk"),
+        )
         .arg(
             Arg::new("search")
                 .short('e')
@@ -117,22 +619,162 @@ fn main() {
                 .help("Exclude files matching regex pattern")
                 .value_name("PATTERN"),
         )
+        .arg(
+            Arg::new("type")
+                .long("type")
+                .help("Only show entries of these kinds: file, dir, symlink, fifo, socket, char-device, block-device")
+                .value_name("KIND")
+                .value_delimiter(',')
+                .action(clap::ArgAction::Append),
+        )
         .arg(
             Arg::new("sort_by")
                 .long("sort-by")
-                .help("Sort files by: name, size, date")
+                .help("Sort files by: name, size, date, owner")
                 .value_name("CRITERIA"),
         )
+        .arg(
+            Arg::new("reverse")
+                .short('R')
+                .long("reverse")
+                .help("Reverse the active sort order")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("top")
+                .long("top")
+                .help("Only show the first N entries after sorting")
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize)),
+        )
         .arg(
             Arg::new("duplicates")
                 .long("duplicates")
                 .help("Find duplicate files")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("duplicates_against")
+                .long("duplicates-against")
+                .help("Compare the contents of DIR with PATH: files in both, only in DIR, only in PATH")
+                .value_name("DIR"),
+        )
+        .arg(
+            Arg::new("contains")
+                .long("contains")
+                .help("List the files passing the other filters whose contents match the regular expression PATTERN; exits with status 1 if none do")
+                .value_name("PATTERN"),
+        )
+        .arg(
+            Arg::new("line_numbers")
+                .short('n')
+                .long("line-numbers")
+                .help("With --contains, also print each matching line with its number")
+                .action(clap::ArgAction::SetTrue)
+                .requires("contains"),
+        )
+        .arg(
+            Arg::new("scan_secrets")
+                .long("scan-secrets")
+                .help("Check text files for AWS keys, private keys, API tokens and hard-coded passwords, listing file, line and rule; exits with status 1 if any are found")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("verify_archives")
+                .long("verify-archives")
+                .help("Read every zip, tar and gzip archive to the end, checking member CRCs and tar headers, and list truncated or corrupt ones; exits with status 1 if any are found")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("ads_report")
+                .long("ads-report")
+                .help("List files carrying NTFS alternate data streams, which file sizes leave out, flagging large streams and ones holding programs or scripts; exits with status 1 if any are flagged (Windows)")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("detect_changes")
+                .long("detect-changes")
+                .help("Hash every file below PATH and compare with the digests the last run recorded in the scan cache, listing files whose content changed while their size and modified time did not; exits with status 1 if any are found")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("manifest")
+                .long("manifest")
+                .help("'write FILE' records the path, size, modified time and SHA-256 of every file below PATH; 'verify FILE' reports missing, added and altered files and exits with status 1 if any are found")
+                .num_args(2)
+                .value_names(["MODE", "FILE"]),
+        )
+        .arg(
+            Arg::new("check_case_collisions")
+                .long("check-case-collisions")
+                .help("List names in the same directory that differ only in case and would collide on Windows or macOS; exits with status 1 if any are found")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("check_encoding")
+                .long("check-encoding")
+                .help("List text files that are not valid UTF-8 (Latin-1, UTF-16), start with a BOM, or use CRLF, CR or mixed line endings; exits with status 1 if any are not UTF-8")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("duplicate_names")
+                .long("duplicate-names")
+                .help("List file names found in more than one directory, with each path and size, however different the contents (--top sets how many names, default 20)")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("dup_min_size")
+                .long("dup-min-size")
+                .help("With --duplicates, ignore files smaller than SIZE, e.g. 1M or 500KiB")
+                .value_name("SIZE")
+                .value_parser(parse_size),
+        )
+        .arg(
+            Arg::new("dedupe")
+                .long("dedupe")
+                .help("With --duplicates, replace extra copies with links to the first: hardlink, symlink")
+                .value_name("MODE")
+                .value_parser(["hardlink", "symlink"])
+                .requires("duplicates"),
+        )
+        .arg(
+            Arg::new("dry_run")
+                .long("dry-run")
+                .help("With --dedupe, only show what would be replaced and the space freed")
+                .action(clap::ArgAction::SetTrue)
+                .requires("dedupe"),
+        )
+        .arg(
+            Arg::new("similar_images")
+                .long("similar-images")
+                .help("With --duplicates, also group images that look the same at other sizes or encodings (PNG, JPEG, GIF, WebP, BMP)")
+                .action(clap::ArgAction::SetTrue)
+                .requires("duplicates"),
+        )
         .arg(
             Arg::new("export")
                 .long("export")
                 .help("Export results to file (json/csv)")
+                .global(true)
+                .value_name("FILE"),
+        )
+        .arg(
+            Arg::new("csv_schema")
+                .long("csv-schema")
+                .help("Columns of CSV exports: default, or windows for PowerShell's Import-Csv")
+                .value_name("SCHEMA")
+                .value_parser(["default", "windows"]),
+        )
+        .arg(
+            Arg::new("export_db")
+                .long("export-db")
+                .help("Export results to a database table (postgres://… or clickhouse://…, requires the 'db' feature)")
+                .value_name("URL"),
+        )
+        .arg(
+            Arg::new("ingest_paths")
+                .long("ingest-paths")
+                .help("Analyze a list of paths from a file, or '-' for stdin (e.g. fd/rg output)")
                 .value_name("FILE"),
         )
         .arg(
@@ -142,6 +784,16 @@ fn main() {
                 .help("Analyze a specific file")
                 .value_name("FILE"),
         )
+        .arg(
+            Arg::new("preview")
+                .long("preview")
+                .help("With --file, show the first N lines of a text file, or a hex dump of the first N rows of 16 bytes of any other file [default: 10]")
+                .value_name("N")
+                .num_args(0..=1)
+                .default_missing_value("10")
+                .value_parser(clap::value_parser!(usize))
+                .requires("file"),
+        )
         .arg(
             Arg::new("directory")
                 .short('d')
@@ -170,48 +822,258 @@ fn main() {
                 .help("Enable interactive menu mode")
                 .action(clap::ArgAction::SetTrue),
         )
-        .get_matches();
+        .subcommand(
+            Command::new("merge")
+                .about("Merge JSON scans from several hosts into one dataset")
+                .arg(
+                    Arg::new("scans")
+                        .help("JSON scans produced with --export")
+                        .value_name("SCAN")
+                        .num_args(1..)
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            Command::new("diff")
+                .about("Compare two directories by structure, size, modification time and optionally content")
+                .arg(Arg::new("a").required(true).value_name("A"))
+                .arg(Arg::new("b").required(true).value_name("B"))
+                .arg(
+                    Arg::new("hash")
+                        .long("hash")
+                        .help("Also compare the contents of files with the same size")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("ignore_mtime")
+                        .long("ignore-mtime")
+                        .help("Don't report files that only differ in modification time")
+                        .action(clap::ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("hash")
+                .about("Print checksums of a file or of every file in a tree, in sha256sum or BSD format")
+                .arg(Arg::new("path").required(true).value_name("PATH"))
+                .arg(
+                    Arg::new("algorithm")
+                        .short('a')
+                        .long("algorithm")
+                        .help("md5, sha1, sha256 or blake3 [default: sha256]")
+                        .value_name("ALGORITHM")
+                        .value_parser(|s: &str| s.parse::<HashAlgorithm>()),
+                )
+                .arg(
+                    Arg::new("tag")
+                        .long("tag")
+                        .help("Print BSD-style lines, SHA256 (FILE) = DIGEST, like sha256sum --tag")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("threads")
+                        .long("threads")
+                        .help("Files hashed at once [default: one per CPU]")
+                        .value_name("N")
+                        .value_parser(clap::builder::RangedU64ValueParser::<usize>::new().range(1..)),
+                )
+                .arg(
+                    Arg::new("export")
+                        .long("export")
+                        .help("Also write the files with their digests to a .json or .csv file")
+                        .value_name("FILE"),
+                ),
+        )
+        .subcommand(
+            Command::new("mounts")
+                .about("List mount points with filesystem type, options and device"),
+        )
+        .subcommand(
+            Command::new("serve")
+                .about("Run an HTTP daemon that queues and runs scans")
+                .arg(
+                    Arg::new("bind")
+                        .long("bind")
+                        .help("Address to listen on [default: 127.0.0.1:7878]")
+                        .value_name("ADDR"),
+                )
+                .arg(
+                    Arg::new("max_scans")
+                        .long("max-scans")
+                        .help("Scans running at once; more wait in the queue [default: 2]")
+                        .value_name("N")
+                        .value_parser(clap::builder::RangedU64ValueParser::<usize>::new().range(1..)),
+                )
+                .arg(
+                    Arg::new("rate_limit")
+                        .long("rate-limit")
+                        .help("Requests per minute per client, 0 for no limit [default: 120]")
+                        .value_name("N")
+                        .value_parser(clap::value_parser!(u32)),
+                )
+                .arg(
+                    Arg::new("cache_ttl")
+                        .long("cache-ttl")
+                        .help("Seconds a finished scan answers identical scan requests, 0 to always rescan [default: 30]")
+                        .value_name("SECS")
+                        .value_parser(clap::value_parser!(u64)),
+                )
+                .arg(
+                    Arg::new("token_file")
+                        .long("token-file")
+                        .help("Require API tokens listed as '<read|admin> <token>' lines")
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new("tls_cert")
+                        .long("tls-cert")
+                        .help("Serve HTTPS with the PEM certificate chain in FILE (needs --tls-key)")
+                        .value_name("FILE")
+                        .requires("tls_key"),
+                )
+                .arg(
+                    Arg::new("tls_key")
+                        .long("tls-key")
+                        .help("PEM private key for --tls-cert")
+                        .value_name("FILE")
+                        .requires("tls_cert"),
+                ),
+        )
+        .subcommand(
+            Command::new("snapshot")
+                .about("Save and browse snapshots of a directory's metadata")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("save")
+                        .about("Record every entry below a directory with its size and modification time")
+                        .arg(Arg::new("name").help("Snapshot name: letters, digits, '.', '_' and '-'").required(true))
+                        .arg(Arg::new("dir").help("Directory to record [default: .]").value_name("PATH"))
+                        .arg(
+                            Arg::new("hash")
+                                .long("hash")
+                                .help("Also record each file's SHA-256")
+                                .action(clap::ArgAction::SetTrue),
+                        )
+                        .arg(
+                            Arg::new("force")
+                                .long("force")
+                                .help("Replace an existing snapshot with the same name")
+                                .action(clap::ArgAction::SetTrue),
+                        ),
+                )
+                .subcommand(
+                    Command::new("list")
+                        .about("List saved snapshots"),
+                )
+                .subcommand(
+                    Command::new("diff")
+                        .about("Show what was added, removed, grew or shrank between two snapshots")
+                        .arg(Arg::new("old").required(true).value_name("OLD"))
+                        .arg(Arg::new("new").required(true).value_name("NEW"))
+                        .arg(
+                            Arg::new("top")
+                                .long("top")
+                                .help("Entries listed per section [default: 20]")
+                                .value_name("N")
+                                .value_parser(clap::builder::RangedU64ValueParser::<usize>::new().range(1..)),
+                        ),
+                )
+                .subcommand(
+                    Command::new("show")
+                        .about("Summarize a snapshot and list a directory in it, largest first")
+                        .arg(Arg::new("name").required(true))
+                        .arg(Arg::new("subdir").help("Directory inside the snapshot [default: its root]").value_name("DIR")),
+                ),
+        )
+        .subcommand(
+            Command::new("examples")
+                .about("Print copy-pastable command recipes, optionally for one topic")
+                .arg(Arg::new("topic").help("cleanup, ci, backups, duplicates, disk or daemon").index(1)),
+        )
+        .subcommand(
+            Command::new("doctor")
+                .about("Check saved files, the cache directory, optional tools and the terminal"),
+        )
+        .subcommand(
+            Command::new("stats-self")
+                .about("Show opt-in local usage stats (scan times, features used)")
+                .arg(
+                    Arg::new("enable")
+                        .long("enable")
+                        .help("Start recording local usage stats")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("disable")
+                        .long("disable")
+                        .help("Stop recording and delete the stats file")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("reset")
+                        .long("reset")
+                        .help("Clear recorded stats")
+                        .action(clap::ArgAction::SetTrue),
+                ),
+        );
 
-    if matches.get_flag("version") {
-        println!("filebyte {}", VERSION);
-        return;
+    // Anything else asks for something specific, so the slow-scan warning is
+    // shown only when none of it is given
+    let targets: Vec<_> = cli
+        .get_arguments()
+        .filter(|arg| !matches!(arg.get_action(), clap::ArgAction::Help | clap::ArgAction::Version))
+        .map(|arg| arg.get_id().clone())
+        .filter(|id| !PRESENTATION_ARGS.contains(&id.as_str()))
+        .collect();
+    cli.group(ArgGroup::new("target").args(targets).multiple(true))
+}
+
+fn main() {
+    let matches = build_cli().get_matches();
+    let started = Instant::now();
+    let config = user_config();
+    if matches.get_flag("stats") {
+        perf::report_at_exit(!matches.get_flag("no-color") && !config.no_color);
     }
+    run(&matches, &config);
+    perf::finish();
+    usage::record_run(&matches, started.elapsed());
+}
 
-    if matches.get_flag("help") {
-        println!();
-        println!("filebyte {}", VERSION);
-        println!("execRooted <rooted@execrooted.com>");
-        println!("A CLI tool for file analysis");
-        println!();
-        println!("USAGE:");
-        println!("    filebyte [OPTIONS] [PATH]");
-        println!("    filebyte --disk <DISK> [OPTIONS]");
-        println!("    filebyte -f <FILE> | --file <FILE>");
-        println!("    filebyte -d <DIR> | --directory <DIR>");
-        println!();
-        println!("ARGS:");
-        println!("    <PATH>    Path to file or directory");
-        println!();
-        println!("OPTIONS:");
-        println!("    -v, --version                    Show version information");
-        println!("    -h, --help                       Show help information");
-        println!("    -s, --size <UNIT>                Size unit (auto, b/bytes, kb/kilobytes, mb/megabytes, gb/gigabytes, tb/terabytes) [default: auto]");
-        println!("    -t, --tree                       Show directory tree");
-        println!("    -p, --properties                 Show file properties");
-        println!("        --no-color                   Disable colored output");
-        println!("    -m, --disk <DISK>                Disk operations: 'list' to show all disks, or specify disk name for info");
-        println!("    -e, --search <PATTERN>           Search for files using regex pattern");
-        println!("    -x, --excluding <PATTERN>        Exclude files matching regex pattern");
-        println!("        --sort-by <CRITERIA>         Sort files by: name, size, date");
-        println!("        --duplicates                 Find duplicate files");
-        println!("        --export <FILE>              Export results to file (json/csv)");
-        println!("    -f, --file <FILE>                Analyze a specific file");
-        println!("    -d, --directory <DIR>            Analyze a directory as a whole");
-        println!("    -r, --recursive                  Enable recursive searching and analysis");
-        println!("    -w, --whole                      Analyze the path as a whole (auto-detects if file or directory)");
-        println!("    -i, --interactive                 Enable interactive menu mode");
-        println!();
-        return;
+/// Defaults from `config.toml`; a file that cannot be read is reported and
+/// ignored rather than stopping the run
+fn user_config() -> Config {
+    let Some(path) = config_path() else {
+        return Config::default();
+    };
+    load_config(&path).unwrap_or_else(|e| {
+        eprintln!("Warning: ignoring {}: {}", path.display(), e);
+        Config::default()
+    })
+}
+
+fn run(matches: &ArgMatches, config: &Config) {
+    if matches.get_flag("si") || config.si {
+        UnitSystem::set_active(UnitSystem::Si);
+    }
+    if let Some(now) = matches.get_one::<String>("now") {
+        match Clock::parse(now) {
+            Ok(clock) => Clock::set_active(clock),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                perf::exit(1);
+            }
+        }
+    }
+    SizePrecision::set_active(SizePrecision {
+        digits: matches
+            .get_one::<u8>("size_precision")
+            .copied()
+            .or(config.size_precision)
+            .unwrap_or(2),
+        truncate: matches.get_flag("truncate_sizes") || config.truncate_sizes,
+    });
+    if matches.get_flag("no_mime") || config.no_mime {
+        TypeDetection::set_active(TypeDetection::Extension);
     }
 
     let show_size = matches.contains_id("size");
@@ -219,41 +1081,83 @@ fn main() {
         .get_one::<String>("size")
         .unwrap_or(&"auto".to_string())
         .clone();
-    let auto_size = size_unit_str == "auto";
-    let size_unit = match SizeUnit::from_str(&size_unit_str) {
+    let size_unit = match size_unit_str.parse::<SizeUnit>() {
         Ok(unit) => unit,
         Err(e) => {
             eprintln!("Error: {}", e);
             eprintln!("Available options are: auto, b/bytes, kb/kilobytes, mb/megabytes, gb/gigabytes, tb/terabytes");
-            process::exit(1);
+            perf::exit(1);
         }
     };
 
-    let color = !matches.get_flag("no-color");
-    let show_detailed_permissions = true;
+    let color = !matches.get_flag("no-color") && !config.no_color;
+    let display = DisplayOptions {
+        size_unit,
+        color,
+        properties: matches.get_flag("properties"),
+        show_size,
+        show_detailed_permissions: true,
+        age_colors: matches.get_flag("age_colors"),
+        size_colors: matches.get_flag("size_colors"),
+        csv_schema: matches
+            .get_one::<String>("csv_schema")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or_default(),
+    };
+    let size_unit = &display.size_unit;
+
+    match matches.subcommand() {
+        Some(("merge", sub_matches)) => {
+            let scans: Vec<String> = sub_matches
+                .get_many::<String>("scans")
+                .map(|values| values.cloned().collect())
+                .unwrap_or_default();
+            merge_scans(&scans, sub_matches.get_one::<String>("export"), color);
+            return;
+        }
+        Some(("examples", sub_matches)) => {
+            examples::run_examples(sub_matches.get_one::<String>("topic").map(String::as_str), color);
+            return;
+        }
+        Some(("diff", sub_matches)) => {
+            run_diff(sub_matches, color);
+            return;
+        }
+        Some(("hash", sub_matches)) => {
+            run_hash(sub_matches);
+            return;
+        }
+        Some(("doctor", _)) => {
+            doctor::run_doctor(color);
+            return;
+        }
+        Some(("mounts", _)) => {
+            list_mounts(color);
+            return;
+        }
+        Some(("serve", sub_matches)) => {
+            run_daemon(sub_matches, color);
+            return;
+        }
+        Some(("snapshot", sub_matches)) => {
+            run_snapshot(sub_matches, color);
+            return;
+        }
+        Some(("stats-self", sub_matches)) => {
+            usage::run_stats_self(sub_matches, color);
+            return;
+        }
+        _ => {}
+    }
 
     // Interactive menu mode
     if matches.get_flag("interactive") {
-        run_interactive_mode(color, &size_unit, auto_size);
+        run_interactive_mode(&display);
         return;
     }
 
     // Warn if no arguments provided
-    let no_args = !matches.contains_id("path")
-        && !matches.contains_id("file")
-        && !matches.contains_id("directory")
-        && !matches.contains_id("disk")
-        && !matches.get_flag("version")
-        && !matches.get_flag("help")
-        && !matches.get_flag("tree")
-        && !matches.get_flag("properties")
-        && !matches.get_flag("duplicates")
-        && !matches.get_flag("recursive")
-        && !matches.get_flag("whole")
-        && !matches.contains_id("search")
-        && !matches.contains_id("excluding")
-        && !matches.contains_id("sort_by")
-        && !matches.contains_id("export");
+    let no_args = !matches.contains_id("target");
 
     if no_args {
         if color {
@@ -263,38 +1167,161 @@ fn main() {
         }
     }
 
-    let search_pattern = matches.get_one::<String>("search");
-    let excluding_pattern = matches.get_one::<String>("excluding");
-    let sort_by = matches
-        .get_one::<String>("sort_by")
-        .map(|s| match s.to_lowercase().as_str() {
-            "name" => SortBy::Name,
-            "size" => SortBy::Size,
-            "date" => SortBy::Date,
-            _ => SortBy::Name,
-        });
+    let kinds = match matches
+        .get_many::<String>("type")
+        .map(|values| values.map(|v| v.parse::<FileKind>()).collect::<Result<Vec<_>, _>>())
+        .transpose()
+    {
+        Ok(kinds) => kinds.unwrap_or_default(),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            eprintln!("Available types are: file, dir, symlink, fifo, socket, char-device, block-device");
+            perf::exit(1);
+        }
+    };
+    let filter = Filter {
+        search_pattern: matches.get_one::<String>("search").cloned(),
+        excluding_pattern: matches.get_one::<String>("excluding").cloned(),
+        kinds,
+    };
+    let collect = CollectOptions {
+        du: matches.get_flag("du"),
+        include_virtual: matches.get_flag("include_virtual"),
+        refresh: matches.get_flag("refresh"),
+        one_file_system: matches.get_flag("one_file_system"),
+        local_only: matches.get_flag("local_only"),
+        dimensions: matches.get_flag("dimensions"),
+    };
+    let mut duplicates = DuplicateOptions::default();
+    if let Some(threads) = matches.get_one::<usize>("threads") {
+        duplicates.threads = *threads;
+    }
+    if let Some(min_size) = matches.get_one::<u64>("dup_min_size") {
+        duplicates.min_size = *min_size;
+    }
+    duplicates.dedupe = matches.get_one::<String>("dedupe").and_then(|mode| mode.parse().ok());
+    duplicates.dry_run = matches.get_flag("dry_run");
+    duplicates.similar_images = matches.get_flag("similar_images");
+    duplicates.export = matches.get_one::<String>("export").cloned();
+    let sort = SortSpec {
+        sort_by: matches
+            .get_one::<String>("sort_by")
+            .map(|s| match s.to_lowercase().as_str() {
+                "name" => SortBy::Name,
+                "size" => SortBy::Size,
+                "date" => SortBy::Date,
+                "owner" => SortBy::Owner,
+                _ => SortBy::Name,
+            }),
+        reverse: matches.get_flag("reverse"),
+        top: matches.get_one::<usize>("top").copied(),
+    };
+
+    let tree_options = TreeOptions {
+        max_depth: matches.get_one::<usize>("depth").copied(),
+        export: matches.get_one::<String>("export").cloned(),
+        summaries: matches.get_flag("summaries"),
+        follow_symlinks: matches.get_flag("follow_symlinks"),
+        charset: matches
+            .get_one::<String>("charset")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or_else(TreeCharset::detect),
+        max_entries: if matches.get_flag("expand") {
+            None
+        } else {
+            Some(matches.get_one::<usize>("max_entries").copied().unwrap_or(DEFAULT_MAX_ENTRIES))
+        },
+        expand_archives: matches.get_flag("expand_archives"),
+    };
 
     if let Some(disk_arg) = matches.get_one::<String>("disk") {
-        if disk_arg == "list" {
-            list_disks(color, &size_unit, auto_size);
-            return;
+        let mut disk_filter = DiskFilter {
+            all: matches.get_flag("all"),
+            local_only: matches.get_flag("local_only"),
+            ..DiskFilter::default()
+        };
+        if let Some(fs_types) = matches.get_one::<String>("fs_type") {
+            disk_filter = disk_filter.with_fs_types(fs_types);
+        }
+        let listing = disk_arg == "list";
+        if listing {
+            if matches.get_flag("health") && !cfg!(feature = "smart") {
+                eprintln!("Error: --health needs filebyte built with the 'smart' feature");
+                perf::exit(1);
+            }
+            if matches.get_flag("porcelain") {
+                print!("{}", format_porcelain(&disk_records(&disk_filter)));
+            } else if matches.get_flag("json") {
+                let export = DisksExport {
+                    metadata: ScanMetadata::current(),
+                    disks: disk_records(&disk_filter),
+                };
+                println!("{}", serde_json::to_string_pretty(&export).unwrap_or_default());
+            } else {
+                list_disks(&display, &disk_filter, matches.get_flag("health"));
+            }
         } else {
+            let view = if let Some(seconds) = matches.get_one::<u64>("io") {
+                DiskView::Io(*seconds)
+            } else if matches.get_flag("duplicates") {
+                DiskView::Duplicates(&duplicates)
+            } else if let Some(limit) = matches.get_one::<usize>("top_dirs") {
+                DiskView::LargestDirs(DirRanking {
+                    limit: *limit,
+                    max_depth: matches.get_one::<usize>("depth").copied().unwrap_or(3),
+                    one_file_system: true,
+                })
+            } else if matches.get_flag("tree") {
+                DiskView::Tree(&tree_options)
+            } else {
+                DiskView::Listing
+            };
             show_disk_info(
                 disk_arg,
-                &size_unit,
-                color,
-                auto_size,
-                matches.get_flag("tree"),
-                matches.get_flag("properties"),
-                search_pattern,
-                excluding_pattern,
-                sort_by,
-                matches.get_flag("duplicates"),
-                show_size,
-                show_detailed_permissions,
+                &filter,
+                &sort,
+                &collect,
+                &display,
+                view,
             );
+        }
+        if let Some(threshold) = matches.get_one::<u8>("warn_usage") {
+            let threshold = f64::from(*threshold);
+            let alerts = usage_alerts(threshold, (!listing).then_some(disk_arg.as_str()), &disk_filter);
+            show_usage_alerts(&alerts, threshold, color);
+            if !alerts.is_empty() {
+                perf::exit(1);
+            }
+        }
+        return;
+    }
+
+    if let Some(source) = matches.get_one::<String>("ingest_paths") {
+        let paths = read_ingest_paths(source);
+        if matches.get_flag("duplicates") {
+            find_duplicates_in_paths(&paths, &duplicates, color);
             return;
         }
+
+        let files = collect_from_paths(&paths, &filter, &sort, &collect);
+        if files.is_empty() {
+            println!("No files found.");
+        } else if matches.get_flag("properties") {
+            show_file_type_stats(&files, color);
+            show_detailed_analysis(&files, color);
+        } else {
+            display_files(
+                limit_files(&files, &sort, color),
+                &DisplayOptions {
+                    properties: false,
+                    ..display.clone()
+                },
+                matches.get_one::<String>("export"),
+            );
+            show_file_type_stats(&files, color);
+        }
+        export_db(&files, matches.get_one::<String>("export_db"));
+        return;
     }
 
     let file_path = matches.get_one::<String>("file");
@@ -306,152 +1333,40 @@ fn main() {
             let path = Path::new(path_str);
             if !path.exists() {
                 eprintln!("Error: Path '{}' does not exist", path_str);
-                process::exit(1);
-            }
-
-            if path.is_file() {
-                let size = get_file_size(path);
-                let size_str = if auto_size {
-                    SizeUnit::auto_format_size(size)
-                } else {
-                    size_unit.format_size(size)
-                };
-                let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+                perf::exit(1);
+            }
 
+            if path.is_file() {
                 let metadata = match fs::metadata(path) {
                     Ok(m) => m,
                     Err(e) => {
                         eprintln!("Error reading metadata: {}", e);
-                        process::exit(1);
-                    }
-                };
-
-                let permissions = if metadata.permissions().readonly() {
-                    if can_delete(path) { "r-x" } else { "r--" }
-                } else {
-                    if can_delete(path) { "rwx" } else { "rw-" }
-                };
-                let modified = metadata.modified().unwrap_or(std::time::SystemTime::UNIX_EPOCH);
-                let created = metadata.created().unwrap_or(std::time::SystemTime::UNIX_EPOCH);
-                let modified_str = DateTime::<Utc>::from(modified)
-                    .format("%Y-%m-%d %H:%M:%S UTC")
-                    .to_string();
-                let created_str = DateTime::<Utc>::from(created)
-                    .format("%Y-%m-%d %H:%M:%S UTC")
-                    .to_string();
-
-                let file_type = infer::get_from_path(path)
-                    .ok()
-                    .flatten()
-                    .map(|kind| kind.mime_type().to_string())
-                    .unwrap_or_else(|| "unknown".to_string());
-
-                let extension = if let Some(ext) = path.extension() {
-                    ext.to_string_lossy().to_string()
-                } else if let Some(file_name) = path.file_name().and_then(|n| n.to_str()) {
-                    if file_name.starts_with('.') {
-                        let parts: Vec<&str> = file_name.split('.').collect();
-                        if parts.len() >= 2 {
-                            parts[1..].join(".")
-                        } else {
-                            "none".to_string()
-                        }
-                    } else {
-                        "none".to_string()
+                        perf::exit(1);
                     }
-                } else {
-                    "none".to_string()
                 };
-
-                println!("");
-                println!("File Analysis:");
-                println!("{}", "─".repeat(50));
-                if color {
-                    println!("Name: {}", file_name.blue().bold());
-                    println!(
-                        "Path: {}",
-                        path.canonicalize().unwrap_or(path.to_path_buf()).display()
-                    );
-                    println!("Size: {}", size_str.green().bold());
-                    println!("Type: {}", file_type.magenta());
-                    println!("Extension: {}", extension.cyan());
-                    println!("Permissions: {}", permissions.yellow());
-                    println!("Created: {}", created_str.yellow());
-                    println!("Modified: {}", modified_str.yellow());
-                } else {
-                    println!("Name: {}", file_name);
-                    println!(
-                        "Path: {}",
-                        path.canonicalize().unwrap_or(path.to_path_buf()).display()
-                    );
-                    println!("Size: {}", size_str);
-                    println!("Type: {}", file_type);
-                    println!("Extension: {}", extension);
-                    println!("Permissions: {}", permissions);
-                    println!("Created: {}", created_str);
-                    println!("Modified: {}", modified_str);
-                }
+                let size_str = size_unit.format_size(get_file_size(path));
+                print_file_analysis(path, &metadata, &size_str, &short_permissions(path, &metadata), None, color);
             } else if path.is_dir() {
-                let dir_size = get_file_size(path);
-                let size_str = if auto_size {
-                    SizeUnit::auto_format_size(dir_size)
-                } else {
-                    size_unit.format_size(dir_size)
-                };
-
+                let size_str = size_unit.format_size(total_size(path, &collect));
                 let metadata = match fs::metadata(path) {
                     Ok(m) => m,
                     Err(e) => {
                         eprintln!("Error reading metadata: {}", e);
-                        process::exit(1);
+                        perf::exit(1);
                     }
                 };
-
-                let permissions = format_unix_permissions(&metadata, show_detailed_permissions);
-                let modified = metadata.modified().unwrap_or(std::time::SystemTime::UNIX_EPOCH);
-                let created = metadata.created().unwrap_or(std::time::SystemTime::UNIX_EPOCH);
-                let modified_str = DateTime::<Utc>::from(modified)
-                    .format("%Y-%m-%d %H:%M:%S UTC")
-                    .to_string();
-                let created_str = DateTime::<Utc>::from(created)
-                    .format("%Y-%m-%d %H:%M:%S UTC")
-                    .to_string();
-
-                println!("");
-                println!("Directory Analysis:");
-                println!("{}", "─".repeat(50));
-                if color {
-                    println!(
-                        "Name: {}",
-                        path.file_name()
-                            .unwrap_or_default()
-                            .to_string_lossy()
-                            .blue()
-                            .bold()
-                    );
-                    println!("Path: {}", path.display());
-                    println!("Size: {}", size_str.green().bold());
-                    println!("Permissions: {}", permissions.yellow());
-                    println!("Created: {}", created_str.yellow());
-                    println!("Modified: {}", modified_str.yellow());
-                } else {
-                    println!("Name: {}", path.file_name().unwrap_or_default().to_string_lossy());
-                    println!("Path: {}", path.display());
-                    println!("Size: {}", size_str);
-                    println!("Permissions: {}", permissions);
-                    println!("Created: {}", created_str);
-                    println!("Modified: {}", modified_str);
-                }
+                let permissions = format_permissions(path, &metadata, display.show_detailed_permissions);
+                print_directory_analysis(path, &metadata, &size_str, &permissions, color);
             } else {
                 eprintln!(
                     "Error: Path '{}' is neither a file nor a directory",
                     path_str
                 );
-                process::exit(1);
+                perf::exit(1);
             }
         } else {
             eprintln!("Error: --whole requires a path argument");
-            process::exit(1);
+            perf::exit(1);
         }
         return;
     }
@@ -460,89 +1375,27 @@ fn main() {
         let path = Path::new(file);
         if !path.exists() {
             eprintln!("Error: File '{}' not found", file);
-            process::exit(1);
+            perf::exit(1);
         }
         if !path.is_file() {
             eprintln!("Error: '{}' is not a file", file);
-            process::exit(1);
+            perf::exit(1);
         }
 
-        let size = get_file_size(path);
-        let size_str = if auto_size {
-            SizeUnit::auto_format_size(size)
-        } else {
-            size_unit.format_size(size)
-        };
-        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
-
         let metadata = match fs::metadata(path) {
             Ok(m) => m,
             Err(e) => {
                 eprintln!("Error reading metadata: {}", e);
-                process::exit(1);
+                perf::exit(1);
             }
         };
-
-        let permissions = format_unix_permissions(&metadata, show_detailed_permissions);
-        let modified = metadata.modified().unwrap_or(std::time::SystemTime::UNIX_EPOCH);
-        let created = metadata.created().unwrap_or(std::time::SystemTime::UNIX_EPOCH);
-        let modified_str = DateTime::<Utc>::from(modified)
-            .format("%Y-%m-%d %H:%M:%S UTC")
-            .to_string();
-        let created_str = DateTime::<Utc>::from(created)
-            .format("%Y-%m-%d %H:%M:%S UTC")
-            .to_string();
-
-        let file_type = infer::get_from_path(path)
-            .ok()
-            .flatten()
-            .map(|kind| kind.mime_type().to_string())
-            .unwrap_or_else(|| "unknown".to_string());
-
-        let extension = if let Some(ext) = path.extension() {
-            ext.to_string_lossy().to_string()
-        } else if let Some(file_name) = path.file_name().and_then(|n| n.to_str()) {
-            if file_name.starts_with('.') {
-                let parts: Vec<&str> = file_name.split('.').collect();
-                if parts.len() >= 2 {
-                    parts[1..].join(".")
-                } else {
-                    "none".to_string()
-                }
-            } else {
-                "none".to_string()
+        let permissions = format_permissions(path, &metadata, display.show_detailed_permissions);
+        print_file_analysis(path, &metadata, &size_unit.format_size(get_file_size(path)), &permissions, None, color);
+        if let Some(limit) = matches.get_one::<usize>("preview") {
+            if let Err(e) = show_preview(path, *limit, color) {
+                eprintln!("Error: cannot read {}: {}", file, e);
+                perf::exit(1);
             }
-        } else {
-            "none".to_string()
-        };
-
-        println!("");
-        println!("File Analysis:");
-        println!("{}", "─".repeat(50));
-        if color {
-            println!("Name: {}", file_name.blue().bold());
-            println!(
-                "Path: {}",
-                path.canonicalize().unwrap_or(path.to_path_buf()).display()
-            );
-            println!("Size: {}", size_str.green().bold());
-            println!("Type: {}", file_type.magenta());
-            println!("Extension: {}", extension.cyan());
-            println!("Permissions: {}", permissions.yellow());
-            println!("Created: {}", created_str.yellow());
-            println!("Modified: {}", modified_str.yellow());
-        } else {
-            println!("Name: {}", file_name);
-            println!(
-                "Path: {}",
-                path.canonicalize().unwrap_or(path.to_path_buf()).display()
-            );
-            println!("Size: {}", size_str);
-            println!("Type: {}", file_type);
-            println!("Extension: {}", extension);
-            println!("Permissions: {}", permissions);
-            println!("Created: {}", created_str);
-            println!("Modified: {}", modified_str);
         }
         return;
     }
@@ -551,59 +1404,23 @@ fn main() {
         let path = Path::new(dir);
         if !path.exists() {
             eprintln!("Error: Directory '{}' not found", dir);
-            process::exit(1);
+            perf::exit(1);
         }
         if !path.is_dir() {
             eprintln!("Error: '{}' is not a directory", dir);
-            process::exit(1);
+            perf::exit(1);
         }
 
-        let dir_size = get_file_size(path);
-        let size_str = if auto_size {
-            SizeUnit::auto_format_size(dir_size)
-        } else {
-            size_unit.format_size(dir_size)
-        };
-
+        let size_str = size_unit.format_size(total_size(path, &collect));
         let metadata = match fs::metadata(path) {
             Ok(m) => m,
             Err(e) => {
                 eprintln!("Error reading metadata: {}", e);
-                process::exit(1);
+                perf::exit(1);
             }
         };
-
-        let permissions = format_unix_permissions(&metadata, show_detailed_permissions);
-        let modified = metadata.modified().unwrap_or(std::time::SystemTime::UNIX_EPOCH);
-        let created = metadata.created().unwrap_or(std::time::SystemTime::UNIX_EPOCH);
-        let modified_str = DateTime::<Utc>::from(modified)
-            .format("%Y-%m-%d %H:%M:%S UTC")
-            .to_string();
-        let created_str = DateTime::<Utc>::from(created)
-            .format("%Y-%m-%d %H:%M:%S UTC")
-            .to_string();
-
-        println!("");
-        println!("Directory Analysis:");
-        println!("{}", "─".repeat(50));
-        if color {
-            println!(
-                "Name: {}",
-                path.file_name().unwrap_or_default().to_string_lossy().blue().bold()
-            );
-            println!("Path: {}", path.display());
-            println!("Size: {}", size_str.green().bold());
-            println!("Permissions: {}", permissions.yellow());
-            println!("Created: {}", created_str.yellow());
-            println!("Modified: {}", modified_str.yellow());
-        } else {
-            println!("Name: {}", path.file_name().unwrap_or_default().to_string_lossy());
-            println!("Path: {}", path.display());
-            println!("Size: {}", size_str);
-            println!("Permissions: {}", permissions);
-            println!("Created: {}", created_str);
-            println!("Modified: {}", modified_str);
-        }
+        let permissions = format_permissions(path, &metadata, display.show_detailed_permissions);
+        print_directory_analysis(path, &metadata, &size_str, &permissions, color);
         return;
     }
 
@@ -615,7 +1432,116 @@ fn main() {
 
     if !path.exists() {
         eprintln!("Error: Path '{}' does not exist", path.display());
-        process::exit(1);
+        perf::exit(1);
+    }
+
+    if let Some(against) = matches.get_one::<String>("duplicates_against") {
+        let against = Path::new(against);
+        if !against.is_dir() || !path.is_dir() {
+            eprintln!("Error: --duplicates-against compares two directories");
+            perf::exit(1);
+        }
+        if !show_tree_comparison(against, path, &duplicates, color) {
+            perf::exit(1);
+        }
+        return;
+    }
+
+    if let Some(mut values) = matches.get_many::<String>("manifest") {
+        let (mode, file) = (values.next().unwrap(), Path::new(values.next().unwrap()));
+        if !path.is_dir() {
+            eprintln!("Error: --manifest can only be used with directories");
+            perf::exit(1);
+        }
+        run_manifest(mode, file, path, duplicates.threads, &collect, color);
+        return;
+    }
+
+    if matches.get_flag("detect_changes") {
+        if !path.is_dir() {
+            eprintln!("Error: --detect-changes can only be used with directories");
+            perf::exit(1);
+        }
+        match detect_changes(path, duplicates.threads, &collect) {
+            Ok(report) => {
+                if !show_drift(path, &report, color) {
+                    perf::exit(1);
+                }
+            }
+            Err(e) => {
+                eprintln!("Error: cannot check {}: {}", path.display(), e);
+                perf::exit(1);
+            }
+        }
+        return;
+    }
+
+    if let Some(pattern) = matches.get_one::<String>("contains") {
+        let pattern = match Regex::new(pattern) {
+            Ok(pattern) => pattern,
+            Err(e) => {
+                eprintln!("Error: Invalid pattern '{}': {}", pattern, e);
+                perf::exit(1);
+            }
+        };
+        let files = if path.is_dir() {
+            show_virtual_exclusions(&virtual_exclusions(path, &collect), color);
+            // Contents are read anyway; types are not shown
+            TypeDetection::set_active(TypeDetection::Extension);
+            collect_files_recursive(path, &filter, &sort, &collect)
+        } else {
+            collect_from_paths(&[path.to_string_lossy().to_string()], &filter, &sort, &collect)
+        };
+        if !show_content_matches(&files, &pattern, matches.get_flag("line_numbers"), sort.top, color) {
+            perf::exit(1);
+        }
+        return;
+    }
+
+    if matches.get_flag("scan_secrets") {
+        let files = if path.is_dir() {
+            show_virtual_exclusions(&virtual_exclusions(path, &collect), color);
+            TypeDetection::set_active(TypeDetection::Extension);
+            collect_files_recursive(path, &filter, &sort, &collect)
+        } else {
+            collect_from_paths(&[path.to_string_lossy().to_string()], &filter, &sort, &collect)
+        };
+        if !show_secrets_report(&files, color) {
+            perf::exit(1);
+        }
+        return;
+    }
+
+    if matches.get_flag("verify_archives") {
+        let files = if path.is_dir() {
+            show_virtual_exclusions(&virtual_exclusions(path, &collect), color);
+            TypeDetection::set_active(TypeDetection::Extension);
+            collect_files_recursive(path, &filter, &sort, &collect)
+        } else {
+            collect_from_paths(&[path.to_string_lossy().to_string()], &filter, &sort, &collect)
+        };
+        if !show_archive_verification(&files, color) {
+            perf::exit(1);
+        }
+        return;
+    }
+
+    if matches.get_flag("ads_report") {
+        if !streams::SUPPORTED {
+            eprintln!("Error: --ads-report needs Windows; alternate data streams only exist on NTFS");
+            perf::exit(1);
+        }
+        let files = if path.is_dir() {
+            show_virtual_exclusions(&virtual_exclusions(path, &collect), color);
+            TypeDetection::set_active(TypeDetection::Extension);
+            collect_files_recursive(path, &filter, &sort, &collect)
+        } else {
+            collect_from_paths(&[path.to_string_lossy().to_string()], &filter, &sort, &collect)
+        };
+        if !show_ads_report(&files, color) {
+            perf::exit(1);
+        }
+        return;
     }
 
     if path.is_file()
@@ -623,163 +1549,55 @@ fn main() {
         && !matches.get_flag("properties")
         && !matches.get_flag("duplicates")
         && !matches.get_flag("recursive")
-        && search_pattern.is_none()
-        && excluding_pattern.is_none()
-        && sort_by.is_none()
+        && !filter.is_active()
+        && !sort.is_active()
         && matches.get_one::<String>("export").is_none()
+        && matches.get_one::<String>("export_db").is_none()
     {
-        let size = get_file_size(path);
-        let size_str = if auto_size {
-            SizeUnit::auto_format_size(size)
-        } else {
-            size_unit.format_size(size)
-        };
-        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
-
         let metadata = match fs::metadata(path) {
             Ok(m) => m,
             Err(e) => {
                 eprintln!("Error reading metadata: {}", e);
-                process::exit(1);
-            }
-        };
-
-        let permissions = format_unix_permissions(&metadata, show_detailed_permissions);
-        let modified = metadata.modified().unwrap_or(std::time::SystemTime::UNIX_EPOCH);
-        let created = metadata.created().unwrap_or(std::time::SystemTime::UNIX_EPOCH);
-        let modified_str = DateTime::<Utc>::from(modified)
-            .format("%Y-%m-%d %H:%M:%S UTC")
-            .to_string();
-        let created_str = DateTime::<Utc>::from(created)
-            .format("%Y-%m-%d %H:%M:%S UTC")
-            .to_string();
-
-        let file_type = infer::get_from_path(path)
-            .ok()
-            .flatten()
-            .map(|kind| kind.mime_type().to_string())
-            .unwrap_or_else(|| "unknown".to_string());
-
-        let extension = if let Some(ext) = path.extension() {
-            ext.to_string_lossy().to_string()
-        } else if let Some(file_name) = path.file_name().and_then(|n| n.to_str()) {
-            if file_name.starts_with('.') {
-                let parts: Vec<&str> = file_name.split('.').collect();
-                if parts.len() >= 2 {
-                    parts[1..].join(".")
-                } else {
-                    "none".to_string()
-                }
-            } else {
-                "none".to_string()
+                perf::exit(1);
             }
-        } else {
-            "none".to_string()
         };
-
-        println!("");
-        println!("File Analysis:");
-        println!("{}", "─".repeat(50));
-        if color {
-            println!("Name: {}", file_name.blue().bold());
-            println!(
-                "Path: {}",
-                path.canonicalize().unwrap_or(path.to_path_buf()).display()
-            );
-            println!("Size: {}", size_str.green().bold());
-            println!("Type: {}", file_type.magenta());
-            println!("Extension: {}", extension.cyan());
-            println!("Permissions: {}", permissions.yellow());
-            println!("Created: {}", created_str.yellow());
-            println!("Modified: {}", modified_str.yellow());
-        } else {
-            println!("Name: {}", file_name);
-            println!(
-                "Path: {}",
-                path.canonicalize().unwrap_or(path.to_path_buf()).display()
-            );
-            println!("Size: {}", size_str);
-            println!("Type: {}", file_type);
-            println!("Extension: {}", extension);
-            println!("Permissions: {}", permissions);
-            println!("Created: {}", created_str);
-            println!("Modified: {}", modified_str);
-        }
+        let permissions = format_permissions(path, &metadata, display.show_detailed_permissions);
+        print_file_analysis(path, &metadata, &size_unit.format_size(get_file_size(path)), &permissions, None, color);
         return;
     }
 
     if matches.get_flag("tree") {
         if path.is_dir() {
-            println!("{}", path.display());
-            print_tree(path, "", color);
+            print_tree(path, &filter, &collect, &display, &tree_options);
         } else {
             eprintln!("Error: --tree can only be used with directories");
-            process::exit(1);
+            perf::exit(1);
         }
     } else if matches.get_flag("properties") {
         if path.is_file() {
             let size = get_file_size(path);
-            let size_str = if auto_size {
-                SizeUnit::auto_format_size(size)
-            } else {
-                size_unit.format_size(size)
-            };
-            let file_name = path.file_name().unwrap_or_default().to_string_lossy();
-
             let metadata = match fs::metadata(path) {
                 Ok(m) => m,
                 Err(e) => {
                     eprintln!("Error reading metadata: {}", e);
-                    process::exit(1);
+                    perf::exit(1);
                 }
             };
-
-            let permissions = format_unix_permissions(&metadata, show_detailed_permissions);
-            let modified = metadata.modified().unwrap_or(std::time::SystemTime::UNIX_EPOCH);
-            let created = metadata.created().unwrap_or(std::time::SystemTime::UNIX_EPOCH);
-            let modified_str = DateTime::<Utc>::from(modified)
-                .format("%Y-%m-%d %H:%M:%S UTC")
-                .to_string();
-            let created_str = DateTime::<Utc>::from(created)
-                .format("%Y-%m-%d %H:%M:%S UTC")
-                .to_string();
-
-            let file_type = infer::get_from_path(path)
-                .ok()
-                .flatten()
-                .map(|kind| kind.mime_type().to_string())
-                .unwrap_or_else(|| "unknown".to_string());
-
-            let extension = path
-                .extension()
-                .map(|ext| ext.to_string_lossy().to_string())
-                .unwrap_or_else(|| "none".to_string());
-
-            println!("");
-            println!("File Analysis:");
-            println!("{}", "─".repeat(50));
-            if color {
-                println!("Name: {}", file_name.blue().bold());
-                println!("Path: {}", path.display());
-                println!("Size: {}", size_str.green().bold());
-                println!("Type: {}", file_type.magenta());
-                println!("Extension: {}", extension.cyan());
-                println!("Permissions: {}", permissions.yellow());
-                println!("Created: {}", created_str.yellow());
-                println!("Modified: {}", modified_str.yellow());
-            } else {
-                println!("Name: {}", file_name);
-                println!("Path: {}", path.display());
-                println!("Size: {}", size_str);
-                println!("Type: {}", file_type);
-                println!("Extension: {}", extension);
-                println!("Permissions: {}", permissions);
-                println!("Created: {}", created_str);
-                println!("Modified: {}", modified_str);
+            let permissions = format_permissions(path, &metadata, display.show_detailed_permissions);
+            let text = detect_file(path).ok().flatten();
+            print_file_analysis(path, &metadata, &size_unit.format_size(size), &permissions, text.as_ref(), color);
+            if let Some(&depth) = matches.get_one::<usize>("deep_archives") {
+                match deep_contents(path, depth) {
+                    Ok(contents) => show_deep_contents(&contents.unwrap_or_default(), size, depth, color),
+                    Err(e) => {
+                        eprintln!("Error: cannot read archive {}: {}", path.display(), e);
+                        perf::exit(1);
+                    }
+                }
             }
         } else if path.is_dir() {
-            let files =
-                collect_files_recursive(path, search_pattern, excluding_pattern, sort_by);
+            show_virtual_exclusions(&virtual_exclusions(path, &collect), color);
+            let files = collect_files_recursive(path, &filter, &sort, &collect);
             if files.is_empty() {
                 println!("No files found in directory.");
             } else {
@@ -787,8 +1605,8 @@ fn main() {
                 let total_dirs = files.iter().filter(|f| f.is_directory).count();
                 let total_regular_files = total_files - total_dirs;
                 let _total_size: u64 = files.iter().map(|f| f.size).sum();
-                let dir_size = get_file_size(path);
-                println!("");
+                let dir_size = total_size(path, &collect);
+                println!();
                 if color {
                     println!("Directory: {}", path.display());
                     println!(
@@ -808,61 +1626,461 @@ fn main() {
                     );
                     println!("Total Size: {}", SizeUnit::auto_format_size(dir_size));
                 }
-                println!("");
+                if let Some(&depth) = matches.get_one::<usize>("deep_archives") {
+                    show_deep_contents(&deep_totals(&files, depth), dir_size, depth, color);
+                }
+                println!();
                 show_file_type_stats(&files, color);
                 show_detailed_analysis(&files, color);
             }
         } else {
             eprintln!("Error: Path '{}' does not exist", path.display());
-            process::exit(1);
+            perf::exit(1);
         }
     } else {
         if matches.get_flag("duplicates") {
-            find_duplicates(path, color);
+            find_duplicates(path, &duplicates, color);
         } else if matches.get_flag("tree") {
             if path.is_dir() {
-                println!("{}", path.display());
-                print_tree(path, "", color);
+                print_tree(path, &filter, &collect, &display, &tree_options);
             } else {
                 eprintln!("Error: --tree can only be used with directories");
-                process::exit(1);
+                perf::exit(1);
+            }
+        } else if let Some(limit) = matches.get_one::<usize>("largest_dirs") {
+            if !path.is_dir() {
+                eprintln!("Error: --largest-dirs can only be used with directories");
+                perf::exit(1);
+            }
+            let ranking = DirRanking {
+                limit: *limit,
+                max_depth: matches.get_one::<usize>("depth").copied().unwrap_or(3),
+                one_file_system: collect.one_file_system,
+            };
+            show_virtual_exclusions(&virtual_exclusions(path, &collect), color);
+            show_largest_dirs(path, ranking, &skipped_paths(path, &collect), color);
+        } else if matches.get_flag("check_case_collisions") {
+            if !path.is_dir() {
+                eprintln!("Error: --check-case-collisions can only be used with directories");
+                perf::exit(1);
+            }
+            show_virtual_exclusions(&virtual_exclusions(path, &collect), color);
+            // Only names matter here, so skip reading file contents
+            TypeDetection::set_active(TypeDetection::Extension);
+            let files = collect_files_recursive(path, &filter, &sort, &collect);
+            if !show_case_collisions(&files, color) {
+                perf::exit(1);
+            }
+        } else if matches.get_flag("check_encoding") {
+            if !path.is_dir() {
+                eprintln!("Error: --check-encoding can only be used with directories");
+                perf::exit(1);
+            }
+            show_virtual_exclusions(&virtual_exclusions(path, &collect), color);
+            TypeDetection::set_active(TypeDetection::Extension);
+            let files = collect_files_recursive(path, &filter, &sort, &collect);
+            if !show_encoding_report(&files, color) {
+                perf::exit(1);
+            }
+        } else if matches.get_flag("duplicate_names") {
+            if !path.is_dir() {
+                eprintln!("Error: --duplicate-names can only be used with directories");
+                perf::exit(1);
+            }
+            show_virtual_exclusions(&virtual_exclusions(path, &collect), color);
+            let files = collect_files_recursive(path, &filter, &sort, &collect);
+            show_duplicate_names(&files, sort.top.unwrap_or(20), color);
+        } else if let Some(limit) = matches.get_one::<usize>("most_files") {
+            if !path.is_dir() {
+                eprintln!("Error: --most-files can only be used with directories");
+                perf::exit(1);
+            }
+            show_virtual_exclusions(&virtual_exclusions(path, &collect), color);
+            show_busiest_dirs(path, *limit, collect.one_file_system, &skipped_paths(path, &collect), color);
+        } else if matches.get_flag("size_by_type") || matches.get_flag("size_by_owner") {
+            let flag = if matches.get_flag("size_by_type") { "--size-by-type" } else { "--size-by-owner" };
+            if !path.is_dir() {
+                eprintln!("Error: {} can only be used with directories", flag);
+                perf::exit(1);
+            }
+            show_virtual_exclusions(&virtual_exclusions(path, &collect), color);
+            let files = collect_files_recursive(path, &filter, &sort, &collect);
+            if files.iter().all(|f| f.is_directory) {
+                println!("No files found.");
+            } else if matches.get_flag("size_by_type") {
+                show_size_by_type(&files, &display);
+            } else {
+                show_size_by_owner(&files, &display);
+            }
+        } else if matches.get_flag("type_mismatches") {
+            if !path.is_dir() {
+                eprintln!("Error: --type-mismatches can only be used with directories");
+                perf::exit(1);
+            }
+            show_virtual_exclusions(&virtual_exclusions(path, &collect), color);
+            let files = collect_files_recursive(path, &filter, &sort, &collect);
+            show_type_mismatches(&files, color);
+        } else if matches.get_flag("entropy") {
+            if !path.is_dir() {
+                eprintln!("Error: --entropy can only be used with directories");
+                perf::exit(1);
+            }
+            show_virtual_exclusions(&virtual_exclusions(path, &collect), color);
+            let files = collect_files_recursive(path, &filter, &sort, &collect);
+            show_entropy_report(&files, sort.top.unwrap_or(20), color);
+        } else if matches.get_flag("archives") {
+            if !path.is_dir() {
+                eprintln!("Error: --archives can only be used with directories");
+                perf::exit(1);
+            }
+            show_virtual_exclusions(&virtual_exclusions(path, &collect), color);
+            // Archives are recognized by their own headers
+            TypeDetection::set_active(TypeDetection::Extension);
+            let files = collect_files_recursive(path, &filter, &sort, &collect);
+            show_archive_report(&files, sort.top.unwrap_or(20), color);
+        } else if matches.get_flag("loc") {
+            if !path.is_dir() {
+                eprintln!("Error: --loc can only be used with directories");
+                perf::exit(1);
+            }
+            show_virtual_exclusions(&virtual_exclusions(path, &collect), color);
+            // Each file is read for its lines anyway; types are not shown
+            TypeDetection::set_active(TypeDetection::Extension);
+            let files = collect_files_recursive(path, &filter, &sort, &collect);
+            show_loc_report(&files, sort.top.unwrap_or(20), color);
+        } else if matches.get_flag("junk_report") {
+            if !path.is_dir() {
+                eprintln!("Error: --junk-report can only be used with directories");
+                perf::exit(1);
+            }
+            show_virtual_exclusions(&virtual_exclusions(path, &collect), color);
+            let limit = sort.top.unwrap_or(10);
+            show_junk_report(path, limit, &skipped_paths(path, &collect), collect.one_file_system, color);
+        } else if matches.get_flag("suggest_cleanup") {
+            if !path.is_dir() {
+                eprintln!("Error: --suggest-cleanup can only be used with directories");
+                perf::exit(1);
+            }
+            // Sniffing types reads each file, which would reset the access
+            // times the suggestions rely on
+            TypeDetection::set_active(TypeDetection::Extension);
+            show_virtual_exclusions(&virtual_exclusions(path, &collect), color);
+            let limit = sort.top.unwrap_or(10);
+            show_cleanup_suggestions(path, limit, &filter, &collect, color);
+        } else if matches.get_flag("stream") && matches.get_flag("recursive") && path.is_dir() {
+            if matches.contains_id("export_db") {
+                eprintln!("Error: --export-db needs the full listing and cannot be combined with --stream");
+                perf::exit(1);
             }
+            show_virtual_exclusions(&virtual_exclusions(path, &collect), color);
+            stream_files(path, &filter, &sort, &collect, &display, matches.get_one::<String>("export"));
         } else {
+            show_virtual_exclusions(&virtual_exclusions(path, &collect), color);
             let files = if matches.get_flag("recursive") {
-                collect_files_recursive(path, search_pattern, excluding_pattern, sort_by)
+                collect_files_recursive(path, &filter, &sort, &collect)
             } else {
-                collect_files(path, search_pattern, excluding_pattern, sort_by)
+                collect_files(path, &filter, &sort, &collect)
             };
             if files.is_empty() {
-                if let Some(pattern) = search_pattern {
+                if let Some(pattern) = &filter.search_pattern {
                     println!("No files found matching pattern: {}", pattern);
                 } else {
                     println!("No files found.");
                 }
             } else {
-                if search_pattern.is_some() {
+                if filter.search_pattern.is_some() {
                     show_file_type_stats(&files, color);
                 } else {
                     display_files(
-                        &files,
-                        &size_unit,
-                        color,
-                        matches.get_flag("properties"),
-                        auto_size,
-                        show_size,
+                        limit_files(&files, &sort, color),
+                        &display,
                         matches.get_one::<String>("export"),
-                        show_detailed_permissions,
                     );
                     if !matches.get_flag("properties") && matches.get_flag("recursive") {
                         show_file_type_stats(&files, color);
                     }
+                    export_db(&files, matches.get_one::<String>("export_db"));
+                }
+            }
+        }
+    }
+}
+
+/// `filebyte hash PATH`, exiting with status 1 if a file could not be read
+fn run_hash(matches: &ArgMatches) {
+    let path = Path::new(matches.get_one::<String>("path").unwrap());
+    let algorithm = matches.get_one::<HashAlgorithm>("algorithm").copied().unwrap_or_default();
+    let threads = matches
+        .get_one::<usize>("threads")
+        .copied()
+        .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |n| n.get()));
+    let files = match checksum_files(path, algorithm, threads, &CollectOptions::default()) {
+        Ok(files) => files,
+        Err(e) => {
+            eprintln!("Error: cannot hash {}: {}", path.display(), e);
+            perf::exit(1);
+        }
+    };
+    let style = if matches.get_flag("tag") { ChecksumStyle::Bsd } else { ChecksumStyle::Gnu };
+    let complete = show_checksums(&files, algorithm, style);
+    if let Some(export) = matches.get_one::<String>("export") {
+        if export.ends_with(".json") {
+            export_to_json(&files, export);
+        } else if export.ends_with(".csv") {
+            export_to_csv(&files, export, CsvSchema::Default);
+        } else {
+            eprintln!("Unsupported export format for {}: use .json or .csv", export);
+            perf::exit(1);
+        }
+    }
+    if !complete {
+        perf::exit(1);
+    }
+}
+
+fn run_manifest(mode: &str, file: &Path, root: &Path, threads: usize, collect: &CollectOptions, color: bool) {
+    // Types are not recorded, so don't read every file twice
+    TypeDetection::set_active(TypeDetection::Extension);
+    match mode {
+        "write" => {
+            let (manifest, unreadable) = match write_manifest(root, file, threads, collect) {
+                Ok(written) => written,
+                Err(e) => {
+                    eprintln!("Error: cannot write manifest {}: {}", file.display(), e);
+                    perf::exit(1);
+                }
+            };
+            for path in &unreadable {
+                eprintln!("Warning: cannot read {}, left out of the manifest", path);
+            }
+            let total: u64 = manifest.files.iter().map(|f| f.size).sum();
+            println!(
+                "Manifest written to {}: {} files, {}",
+                file.display(),
+                group_digits(manifest.files.len() as u64),
+                SizeUnit::auto_format_size(total)
+            );
+        }
+        "verify" => {
+            let verified = load_manifest(file)
+                .and_then(|manifest| verify_manifest(&manifest, file, root, threads, collect).map(|r| (manifest, r)));
+            match verified {
+                Ok((manifest, result)) => {
+                    if !show_verification(&manifest, &result, color) {
+                        perf::exit(1);
+                    }
+                }
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    perf::exit(1);
+                }
+            }
+        }
+        _ => {
+            eprintln!("Error: Invalid manifest mode '{}': use 'write' or 'verify'", mode);
+            perf::exit(1);
+        }
+    }
+}
+
+/// `filebyte diff A B`, exiting like diff(1): 0 when the trees match, 1
+/// when they differ and 2 on errors
+fn run_diff(matches: &ArgMatches, color: bool) {
+    let a = Path::new(matches.get_one::<String>("a").unwrap());
+    let b = Path::new(matches.get_one::<String>("b").unwrap());
+    for dir in [a, b] {
+        if !dir.is_dir() {
+            eprintln!("Error: '{}' is not a directory", dir.display());
+            perf::exit(2);
+        }
+    }
+    let options = CompareOptions {
+        hash: matches.get_flag("hash"),
+        ignore_mtime: matches.get_flag("ignore_mtime"),
+        threads: std::thread::available_parallelism().map_or(1, |n| n.get()),
+    };
+    match compare_dirs(a, b, &options, &CollectOptions::default()) {
+        Ok(comparison) => {
+            show_comparison(a, b, &comparison, color);
+            if !comparison.is_identical() {
+                perf::exit(1);
+            }
+        }
+        Err(e) => {
+            eprintln!("Error comparing {} and {}: {}", a.display(), b.display(), e);
+            perf::exit(2);
+        }
+    }
+}
+
+/// `filebyte snapshot save|list|show|diff`
+fn run_snapshot(matches: &ArgMatches, color: bool) {
+    let Some(dir) = snapshot::snapshots_dir() else {
+        eprintln!("Error: cannot determine a data directory (HOME is not set)");
+        perf::exit(1);
+    };
+    let name = matches
+        .subcommand()
+        .and_then(|(_, sub_matches)| sub_matches.try_get_one::<String>("name").ok().flatten());
+    if let Some(name) = name {
+        if !snapshot::is_valid_name(name) {
+            eprintln!("Error: invalid snapshot name '{}': use letters, digits, '.', '_' and '-'", name);
+            perf::exit(1);
+        }
+    }
+    let open = |name: &str| {
+        let Some(path) = snapshot::find(&dir, name).filter(|_| snapshot::is_valid_name(name)) else {
+            eprintln!("Error: no snapshot named '{}' (see 'filebyte snapshot list')", name);
+            perf::exit(1);
+        };
+        match snapshot::load(&path) {
+            Ok(taken) => taken,
+            Err(e) => {
+                eprintln!("Error: cannot read {}: {}", path.display(), e);
+                perf::exit(1);
+            }
+        }
+    };
+
+    match matches.subcommand() {
+        Some(("save", sub_matches)) => {
+            let name = name.unwrap();
+            if snapshot::find(&dir, name).is_some() && !sub_matches.get_flag("force") {
+                eprintln!("Error: snapshot '{}' already exists (use --force to replace it)", name);
+                perf::exit(1);
+            }
+            let root = Path::new(sub_matches.get_one::<String>("dir").map_or(".", String::as_str));
+            if !root.is_dir() {
+                eprintln!("Error: '{}' is not a directory", root.display());
+                perf::exit(1);
+            }
+            let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+            let taken = snapshot::take(root, name, sub_matches.get_flag("hash"), threads, &CollectOptions::default())
+                .and_then(|taken| snapshot::save(&taken, &dir).map(|path| (taken, path)));
+            match taken {
+                Ok((taken, path)) => println!(
+                    "Saved snapshot {}: {} entries, {} ({})",
+                    name,
+                    taken.entries.len(),
+                    SizeUnit::auto_format_size(taken.total_size()),
+                    path.display()
+                ),
+                Err(e) => {
+                    eprintln!("Error saving snapshot {}: {}", name, e);
+                    perf::exit(1);
                 }
             }
         }
+        Some(("list", _)) => snapshot::show_snapshot_list(&dir, color),
+        Some(("show", sub_matches)) => {
+            let subdir = sub_matches.get_one::<String>("subdir").map_or("", String::as_str);
+            if let Err(e) = snapshot::show_snapshot(&open(name.unwrap()), subdir, color) {
+                eprintln!("Error: {}", e);
+                perf::exit(1);
+            }
+        }
+        Some(("diff", sub_matches)) => {
+            let old = open(sub_matches.get_one::<String>("old").unwrap());
+            let new = open(sub_matches.get_one::<String>("new").unwrap());
+            let limit = sub_matches.get_one::<usize>("top").copied().unwrap_or(20);
+            snapshot::show_snapshot_diff(&old, &new, limit, color);
+        }
+        _ => unreachable!("a snapshot subcommand is required"),
+    }
+}
+
+/// Bind and run `filebyte serve` until the process is stopped
+fn run_daemon(matches: &ArgMatches, color: bool) {
+    let bind = matches
+        .get_one::<String>("bind")
+        .map(String::as_str)
+        .unwrap_or("127.0.0.1:7878");
+    let tokens = match matches.get_one::<String>("token_file") {
+        Some(path) => match fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|contents| parse_tokens(&contents))
+        {
+            Ok(tokens) if tokens.is_empty() => {
+                eprintln!("Error: no tokens in {}", path);
+                perf::exit(1);
+            }
+            Ok(tokens) => tokens,
+            Err(e) => {
+                eprintln!("Error: cannot load tokens from {}: {}", path, e);
+                perf::exit(1);
+            }
+        },
+        None => Vec::new(),
+    };
+    let tls = matches.get_one::<String>("tls_cert").zip(matches.get_one::<String>("tls_key"));
+    #[cfg(not(feature = "tls"))]
+    if tls.is_some() {
+        eprintln!("Error: --tls-cert needs filebyte built with the 'tls' feature");
+        perf::exit(1);
+    }
+    #[cfg(feature = "tls")]
+    let tls = match tls.map(|(cert, key)| filebyte::daemon::load_tls(Path::new(cert), Path::new(key))) {
+        Some(Ok(tls)) => Some(tls),
+        Some(Err(e)) => {
+            eprintln!("Error: {}", e);
+            perf::exit(1);
+        }
+        None => None,
+    };
+    let https = tls.is_some();
+    let config = DaemonConfig {
+        max_concurrent: matches.get_one::<usize>("max_scans").copied().unwrap_or(2),
+        tokens,
+        rate_limit: Some(matches.get_one::<u32>("rate_limit").copied().unwrap_or(120)).filter(|n| *n > 0),
+        cache_ttl: Some(matches.get_one::<u64>("cache_ttl").copied().unwrap_or(30))
+            .filter(|secs| *secs > 0)
+            .map(Duration::from_secs),
+        #[cfg(feature = "tls")]
+        tls,
+        ..DaemonConfig::default()
+    };
+    let daemon = match Daemon::bind(bind, config.clone()) {
+        Ok(daemon) => daemon,
+        Err(e) => {
+            eprintln!("Error: cannot listen on {}: {}", bind, e);
+            perf::exit(1);
+        }
+    };
+    let addr = daemon.local_addr().map(|a| a.to_string()).unwrap_or_else(|_| bind.to_string());
+    let message = format!(
+        "Listening on {}://{} (up to {} concurrent scans)",
+        if https { "https" } else { "http" },
+        addr,
+        config.max_concurrent
+    );
+    if color {
+        println!("{}", message.green());
+    } else {
+        println!("{}", message);
+    }
+    let loopback = daemon.local_addr().is_ok_and(|a| a.ip().is_loopback());
+    let warning = if loopback {
+        None
+    } else if config.tokens.is_empty() {
+        Some("Warning: no --token-file given; anyone who can reach this address can start scans")
+    } else if !https {
+        Some("Warning: tokens are sent over plain HTTP; serve HTTPS with --tls-cert and --tls-key")
+    } else {
+        None
+    };
+    if let Some(warning) = warning {
+        if color {
+            eprintln!("{}", warning.yellow());
+        } else {
+            eprintln!("{}", warning);
+        }
     }
+    daemon.run();
 }
 
-fn run_interactive_mode(color: bool, size_unit: &SizeUnit, auto_size: bool) {
+fn run_interactive_mode(display: &DisplayOptions) {
+    let color = display.color;
+    let size_unit = &display.size_unit;
     loop {
         clear_screen();
         println!();
@@ -909,11 +2127,24 @@ fn run_interactive_mode(color: bool, size_unit: &SizeUnit, auto_size: bool) {
                 };
                 let path = Path::new(target_path);
                 if path.is_dir() {
-                    let files = collect_files(path, None, None, None);
+                    let files = collect_files(
+                        path,
+                        &Filter::default(),
+                        &SortSpec::default(),
+                        &CollectOptions::default(),
+                    );
                     if files.is_empty() {
                         println!("No files found.");
                     } else {
-                        display_files(&files, size_unit, color, false, auto_size, false, None, true);
+                        display_files(
+                            &files,
+                            &DisplayOptions {
+                                properties: false,
+                                show_size: false,
+                                ..display.clone()
+                            },
+                            None,
+                        );
                     }
                     println!();
                     print!("Press Enter to return to menu... ");
@@ -934,48 +2165,12 @@ fn run_interactive_mode(color: bool, size_unit: &SizeUnit, auto_size: bool) {
                 let path_str = path_input.trim();
                 let path = Path::new(path_str);
                 if path.is_file() {
-                    let size = get_file_size(path);
-                    let size_str = if auto_size {
-                        SizeUnit::auto_format_size(size)
-                    } else {
-                        size_unit.format_size(size)
-                    };
-                    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
-                    
-                    let metadata = fs::metadata(path).ok();
-                    let permissions = metadata
-                        .as_ref()
-                        .map(|m| {
-                            if m.permissions().readonly() {
-                                if can_delete(path) { "r-x" } else { "r--" }
-                            } else {
-                                if can_delete(path) { "rwx" } else { "rw-" }
-                            }
-                        })
-                        .unwrap_or("unknown");
-                    
-                    let file_type = infer::get_from_path(path)
-                        .ok()
-                        .flatten()
-                        .map(|kind| kind.mime_type().to_string())
-                        .unwrap_or_else(|| "unknown".to_string());
-
-                    if color {
-                        println!();
-                        println!("{}", "─".repeat(50));
-                        println!("Name: {}", file_name.blue().bold());
-                        println!("Path: {}", path.display());
-                        println!("Size: {}", size_str.green().bold());
-                        println!("Type: {}", file_type.magenta());
-                        println!("Permissions: {}", permissions.yellow());
-                    } else {
-                        println!();
-                        println!("{}", "─".repeat(50));
-                        println!("Name: {}", file_name);
-                        println!("Path: {}", path.display());
-                        println!("Size: {}", size_str);
-                        println!("Type: {}", file_type);
-                        println!("Permissions: {}", permissions);
+                    match fs::metadata(path) {
+                        Ok(metadata) => {
+                            let size_str = size_unit.format_size(get_file_size(path));
+                            print_file_analysis(path, &metadata, &size_str, &short_permissions(path, &metadata), None, color);
+                        }
+                        Err(e) => eprintln!("Error reading metadata: {}", e),
                     }
                     println!();
                     print!("Press Enter to return to menu... ");
@@ -996,40 +2191,12 @@ fn run_interactive_mode(color: bool, size_unit: &SizeUnit, auto_size: bool) {
                 let path_str = path_input.trim();
                 let path = Path::new(path_str);
                 if path.is_dir() {
-                    let dir_size = get_file_size(path);
-                    let size_str = if auto_size {
-                        SizeUnit::auto_format_size(dir_size)
-                    } else {
-                        size_unit.format_size(dir_size)
-                    };
-                    let dir_name = path.file_name().unwrap_or_default().to_string_lossy();
-                    
-                    let metadata = fs::metadata(path).ok();
-                    let permissions = metadata
-                        .as_ref()
-                        .map(|m| {
-                            if m.permissions().readonly() {
-                                if can_delete(path) { "r-x" } else { "r--" }
-                            } else {
-                                if can_delete(path) { "rwx" } else { "rw-" }
-                            }
-                        })
-                        .unwrap_or("unknown");
-
-                    if color {
-                        println!();
-                        println!("{}", "─".repeat(50));
-                        println!("Name: {}", dir_name.blue().bold());
-                        println!("Path: {}", path.display());
-                        println!("Size: {}", size_str.green().bold());
-                        println!("Permissions: {}", permissions.yellow());
-                    } else {
-                        println!();
-                        println!("{}", "─".repeat(50));
-                        println!("Name: {}", dir_name);
-                        println!("Path: {}", path.display());
-                        println!("Size: {}", size_str);
-                        println!("Permissions: {}", permissions);
+                    match fs::metadata(path) {
+                        Ok(metadata) => {
+                            let size_str = size_unit.format_size(total_size(path, &CollectOptions::default()));
+                            print_directory_analysis(path, &metadata, &size_str, &short_permissions(path, &metadata), color);
+                        }
+                        Err(e) => eprintln!("Error reading metadata: {}", e),
                     }
                     println!();
                     print!("Press Enter to return to menu... ");
@@ -1049,7 +2216,7 @@ fn run_interactive_mode(color: bool, size_unit: &SizeUnit, auto_size: bool) {
                 let path_str = path_input.trim();
                 let path = Path::new(path_str);
                 if path.is_dir() {
-                    find_duplicates(path, color);
+                    find_duplicates(path, &DuplicateOptions::default(), color);
                     println!();
                     print!("Press Enter to return to menu... ");
                     io::stdout().flush().unwrap();
@@ -1069,7 +2236,11 @@ fn run_interactive_mode(color: bool, size_unit: &SizeUnit, auto_size: bool) {
                 let path_str = path_input.trim();
                 let path = Path::new(path_str);
                 if path.is_dir() {
-                    print_tree(path, "", color);
+                    let tree = TreeOptions {
+                        max_entries: Some(DEFAULT_MAX_ENTRIES),
+                        ..TreeOptions::default()
+                    };
+                    print_tree(path, &Filter::default(), &CollectOptions::default(), display, &tree);
                     println!();
                     print!("Press Enter to return to menu... ");
                     io::stdout().flush().unwrap();
@@ -1082,7 +2253,7 @@ fn run_interactive_mode(color: bool, size_unit: &SizeUnit, auto_size: bool) {
             }
             "6" => {
                 // List all disks
-                list_disks(color, size_unit, auto_size);
+                list_disks(display, &DiskFilter::default(), false);
                 println!();
                 print!("Press Enter to return to menu... ");
                 io::stdout().flush().unwrap();
@@ -1111,7 +2282,15 @@ fn run_interactive_mode(color: bool, size_unit: &SizeUnit, auto_size: bool) {
                 let path = Path::new(target_path);
                 
                 if path.is_dir() {
-                    let files = collect_files(path, Some(&pattern.to_string()), None, None);
+                    let files = collect_files(
+                        path,
+                        &Filter {
+                            search_pattern: Some(pattern.to_string()),
+                            ..Filter::default()
+                        },
+                        &SortSpec::default(),
+                        &CollectOptions::default(),
+                    );
                     if files.is_empty() {
                         println!("No files found matching pattern: {}", pattern);
                     } else {
@@ -1136,7 +2315,12 @@ fn run_interactive_mode(color: bool, size_unit: &SizeUnit, auto_size: bool) {
                 let path_str = path_input.trim();
                 let path = Path::new(path_str);
                 if path.is_dir() {
-                    let files = collect_files_recursive(path, None, None, None);
+                    let files = collect_files_recursive(
+                        path,
+                        &Filter::default(),
+                        &SortSpec::default(),
+                        &CollectOptions::default(),
+                    );
                     show_file_type_stats(&files, color);
                     println!();
                     print!("Press Enter to return to menu... ");
@@ -1203,7 +2387,7 @@ fn run_interactive_mode(color: bool, size_unit: &SizeUnit, auto_size: bool) {
                     println!("Terabits:     {:.2} Tb", bits / 1_000_000_000_000.0);
                     println!();
                     println!("Bytes (B):    {:.0}", bits / 8.0);
-                    println!("Kilobytes:    {:.2} KB", bits / 8.0 / 1000.0);
+                    println!("Kilobytes:    {:.2} kB", bits / 8.0 / 1000.0);
                     println!("Megabytes:    {:.2} MB", bits / 8.0 / 1_000_000.0);
                     println!("Gigabytes:    {:.2} GB", bits / 8.0 / 1_000_000_000.0);
                     println!("Terabytes:    {:.2} TB", bits / 8.0 / 1_000_000_000_000.0);
@@ -0,0 +1,227 @@
+//! Integrity manifests (`--manifest write|verify`).
+//!
+//! A manifest lists every file below a directory with its size,
+//! modification time and content hash, in the versioned
+//! [`migrate::MANIFEST`] format. Verifying walks the tree again and reports
+//! files that went missing, appeared or changed. Only files whose size still
+//! matches are hashed, since a different size already means a change.
+
+use crate::analysis::hash_in_parallel;
+use crate::checksum::checksum_files;
+use crate::collect::walk_files;
+use crate::migrate::{self, MANIFEST};
+use crate::types::{CollectOptions, FileInfo, FileKind, Filter, HashAlgorithm, ScanMetadata, SizeUnit};
+use crate::utils::{file_hasher, group_digits};
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::path::Path;
+
+/// The recorded state of a tree
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Manifest {
+    pub metadata: ScanMetadata,
+    /// As accepted by `filebyte hash --algorithm`
+    pub algorithm: String,
+    /// Sorted by path
+    pub files: Vec<ManifestEntry>,
+}
+
+/// One file in a [`Manifest`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// Relative to the manifest's root, with `/` separators
+    pub path: String,
+    pub size: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified: Option<String>,
+    pub hash: String,
+}
+
+/// What differs between a manifest and the tree it describes
+#[derive(Debug, Clone, Default)]
+pub struct Verification {
+    pub missing: Vec<ManifestEntry>,
+    /// Relative path and size
+    pub added: Vec<(String, u64)>,
+    /// Relative path and what changed
+    pub altered: Vec<(String, String)>,
+    pub intact: usize,
+}
+
+impl Verification {
+    pub fn is_intact(&self) -> bool {
+        self.missing.is_empty() && self.added.is_empty() && self.altered.is_empty()
+    }
+}
+
+/// `path` relative to `root` with `/` separators, or `None` when it is the
+/// manifest itself, given as an absolute path
+fn relative_path(root: &Path, path: &str, manifest: &Path) -> Option<String> {
+    let path = Path::new(path);
+    if std::path::absolute(path).ok()? == manifest {
+        return None;
+    }
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    Some(relative.to_string_lossy().replace('\\', "/"))
+}
+
+/// Hash every file below `root` and write the manifest to `manifest_path`,
+/// which is left out if it lies inside the tree. Files that cannot be read
+/// are left out too and returned by path.
+pub fn write_manifest(
+    root: &Path,
+    manifest_path: &Path,
+    threads: usize,
+    collect: &CollectOptions,
+) -> io::Result<(Manifest, Vec<String>)> {
+    let algorithm = HashAlgorithm::default();
+    let manifest_path = std::path::absolute(manifest_path)?;
+    let files = checksum_files(root, algorithm, threads, collect)?;
+    let mut unreadable = Vec::new();
+    let mut entries = Vec::new();
+    for file in files {
+        let Some(relative) = relative_path(root, &file.path, &manifest_path) else {
+            continue;
+        };
+        match file.hash {
+            Some(hash) => entries.push(ManifestEntry {
+                path: relative,
+                size: file.size,
+                modified: file.modified,
+                hash,
+            }),
+            None => unreadable.push(file.path),
+        }
+    }
+    let manifest = Manifest {
+        metadata: ScanMetadata::current(),
+        algorithm: algorithm.as_str().to_string(),
+        files: entries,
+    };
+    migrate::save(&manifest_path, &MANIFEST, &manifest)?;
+    Ok((manifest, unreadable))
+}
+
+/// Read a manifest written by [`write_manifest`]
+pub fn load_manifest(path: &Path) -> Result<Manifest, String> {
+    match migrate::load::<Manifest>(path, &MANIFEST) {
+        Ok(Some(manifest)) => Ok(manifest),
+        Ok(None) => Err(format!("cannot read {}", path.display())),
+        Err(e) => Err(format!("{}: {}", path.display(), e)),
+    }
+}
+
+/// Compare the tree below `root` with `manifest`, read from `manifest_path`
+pub fn verify_manifest(
+    manifest: &Manifest,
+    manifest_path: &Path,
+    root: &Path,
+    threads: usize,
+    collect: &CollectOptions,
+) -> Result<Verification, String> {
+    let algorithm: HashAlgorithm = manifest.algorithm.parse()?;
+    let manifest_path = std::path::absolute(manifest_path).map_err(|e| e.to_string())?;
+    let mut current: HashMap<String, FileInfo> = HashMap::new();
+    walk_files(root, &Filter::default(), collect, &mut |file| {
+        if file.kind == FileKind::File {
+            if let Some(relative) = relative_path(root, &file.path, &manifest_path) {
+                current.insert(relative, file);
+            }
+        }
+    });
+
+    let mut result = Verification::default();
+    let mut to_hash = Vec::new();
+    for entry in &manifest.files {
+        match current.get(&entry.path) {
+            None => result.missing.push(entry.clone()),
+            Some(file) if file.size != entry.size => result.altered.push((
+                entry.path.clone(),
+                format!(
+                    "size {} -> {}",
+                    SizeUnit::auto_format_size(entry.size),
+                    SizeUnit::auto_format_size(file.size)
+                ),
+            )),
+            Some(file) => to_hash.push((entry, file)),
+        }
+    }
+    let paths: Vec<(u64, &String)> = to_hash.iter().map(|(_, file)| (file.size, &file.path)).collect();
+    let hashes = hash_in_parallel(&paths, threads, file_hasher(algorithm));
+    for ((entry, _), hash) in to_hash.iter().zip(hashes) {
+        match hash {
+            Some(hash) if hash == entry.hash => result.intact += 1,
+            Some(_) => result.altered.push((entry.path.clone(), "content".to_string())),
+            None => result.altered.push((entry.path.clone(), "unreadable".to_string())),
+        }
+    }
+
+    let recorded: HashSet<&str> = manifest.files.iter().map(|e| e.path.as_str()).collect();
+    result.added = current
+        .iter()
+        .filter(|(path, _)| !recorded.contains(path.as_str()))
+        .map(|(path, file)| (path.clone(), file.size))
+        .collect();
+    result.added.sort();
+    result.altered.sort();
+    Ok(result)
+}
+
+/// Print a [`Verification`]; returns whether the tree matched
+pub fn show_verification(manifest: &Manifest, result: &Verification, color: bool) -> bool {
+    let recorded = format!(
+        "Manifest of {} files taken {} on {}",
+        group_digits(manifest.files.len() as u64),
+        manifest.metadata.scanned_at,
+        manifest.metadata.hostname
+    );
+    println!("{}", recorded);
+
+    let missing: Vec<(String, String)> = result
+        .missing
+        .iter()
+        .map(|e| (e.path.clone(), SizeUnit::auto_format_size(e.size)))
+        .collect();
+    let added: Vec<(String, String)> = result
+        .added
+        .iter()
+        .map(|(path, size)| (path.clone(), SizeUnit::auto_format_size(*size)))
+        .collect();
+    let sections = [("Missing", &missing), ("Added", &added), ("Altered", &result.altered)];
+    for (title, entries) in sections {
+        if entries.is_empty() {
+            continue;
+        }
+        println!();
+        println!("{} ({}):", title, group_digits(entries.len() as u64));
+        for (path, detail) in entries {
+            if color {
+                let path = if title == "Added" { path.green() } else { path.red() };
+                println!("  {}: {}", path, detail);
+            } else {
+                println!("  {}: {}", path, detail);
+            }
+        }
+    }
+
+    println!();
+    if result.is_intact() {
+        let message = format!("All {} files intact", group_digits(result.intact as u64));
+        if color {
+            println!("{}", message.green().bold());
+        } else {
+            println!("{}", message);
+        }
+        return true;
+    }
+    println!(
+        "{} intact, {} missing, {} added, {} altered",
+        group_digits(result.intact as u64),
+        group_digits(result.missing.len() as u64),
+        group_digits(result.added.len() as u64),
+        group_digits(result.altered.len() as u64)
+    );
+    false
+}
@@ -0,0 +1,168 @@
+use crate::types::{FileInfo, ScanExport, ScanMetadata, SizeUnit};
+use colored::Colorize;
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+
+/// A file row from a merged dataset, tagged with the host it came from
+#[derive(Debug, Clone, Serialize)]
+pub struct MergedFileInfo {
+    pub host: String,
+    pub os: String,
+    #[serde(flatten)]
+    pub file: FileInfo,
+}
+
+/// Load a scan exported with `--export scan.json`. Older exports without
+/// metadata are plain arrays; those use the file name as the host.
+pub fn load_scan(path: &Path) -> Result<ScanExport, String> {
+    let contents = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let value: serde_json::Value = serde_json::from_str(&contents).map_err(|e| e.to_string())?;
+
+    if value.is_array() {
+        let files: Vec<FileInfo> = serde_json::from_value(value).map_err(|e| e.to_string())?;
+        let host = path
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        Ok(ScanExport {
+            metadata: ScanMetadata {
+                hostname: host,
+                os: "unknown".to_string(),
+                scanned_at: "unknown".to_string(),
+            },
+            files,
+        })
+    } else {
+        serde_json::from_value(value).map_err(|e| e.to_string())
+    }
+}
+
+/// Merge several per-host scans into one dataset with host/os columns
+pub fn merge_scans(scan_paths: &[String], export_path: Option<&String>, color: bool) {
+    let mut scans = Vec::new();
+    for scan_path in scan_paths {
+        match load_scan(Path::new(scan_path)) {
+            Ok(scan) => scans.push(scan),
+            Err(e) => {
+                eprintln!("Error reading scan '{}': {}", scan_path, e);
+                crate::perf::exit(1);
+            }
+        }
+    }
+
+    let merged: Vec<MergedFileInfo> = scans
+        .iter()
+        .flat_map(|scan| {
+            scan.files.iter().map(|file| MergedFileInfo {
+                host: scan.metadata.hostname.clone(),
+                os: scan.metadata.os.clone(),
+                file: file.clone(),
+            })
+        })
+        .collect();
+
+    match export_path {
+        Some(export_file) => {
+            if export_file.ends_with(".csv") {
+                export_merged_to_csv(&merged, export_file);
+            } else {
+                let written = serde_json::to_string_pretty(&merged)
+                    .map(|json| fs::write(export_file, json).is_ok())
+                    .unwrap_or(false);
+                if written {
+                    println!("Results exported to {}", export_file);
+                } else {
+                    eprintln!("Failed to write to {}", export_file);
+                }
+            }
+            show_host_summary(&scans, color);
+        }
+        None => match serde_json::to_string_pretty(&merged) {
+            Ok(json) => println!("{}", json),
+            Err(_) => eprintln!("Failed to serialize data to JSON"),
+        },
+    }
+}
+
+fn export_merged_to_csv(merged: &[MergedFileInfo], filename: &str) {
+    let mut wtr = match csv::Writer::from_path(filename) {
+        Ok(wtr) => wtr,
+        Err(e) => {
+            eprintln!("Failed to write to {}: {}", filename, e);
+            return;
+        }
+    };
+    let _ = wtr.write_record([
+        "host",
+        "os",
+        "name",
+        "path",
+        "size",
+        "size_human",
+        "file_type",
+        "created",
+        "modified",
+        "permissions",
+        "owner",
+        "group",
+        "is_directory",
+        "kind",
+    ]);
+    for row in merged {
+        let _ = wtr.write_record([
+            row.host.as_str(),
+            row.os.as_str(),
+            row.file.name.as_str(),
+            row.file.path.as_str(),
+            &row.file.size.to_string(),
+            row.file.size_human.as_str(),
+            row.file.file_type.as_str(),
+            row.file.created.as_deref().unwrap_or(""),
+            row.file.modified.as_deref().unwrap_or(""),
+            row.file.permissions.as_str(),
+            row.file.owner.as_str(),
+            row.file.group.as_str(),
+            &row.file.is_directory.to_string(),
+            row.file.kind.as_str(),
+        ]);
+    }
+    if wtr.flush().is_ok() {
+        println!("Results exported to {}", filename);
+    } else {
+        eprintln!("Failed to write to {}", filename);
+    }
+}
+
+fn show_host_summary(scans: &[ScanExport], color: bool) {
+    println!();
+    println!("Merged Scans:");
+    println!("{}", "─".repeat(50));
+    for scan in scans {
+        let total_files = scan.files.iter().filter(|f| !f.is_directory).count();
+        let total_size: u64 = scan
+            .files
+            .iter()
+            .filter(|f| !f.is_directory)
+            .map(|f| f.size)
+            .sum();
+        if color {
+            println!(
+                "{} ({}): {} files, {}",
+                scan.metadata.hostname.blue().bold(),
+                scan.metadata.os,
+                total_files.to_string().cyan(),
+                SizeUnit::auto_format_size(total_size).green()
+            );
+        } else {
+            println!(
+                "{} ({}): {} files, {}",
+                scan.metadata.hostname,
+                scan.metadata.os,
+                total_files,
+                SizeUnit::auto_format_size(total_size)
+            );
+        }
+    }
+}
@@ -0,0 +1,311 @@
+//! Versioned on-disk formats.
+//!
+//! Every file filebyte keeps between runs is a JSON object (a TOML table for
+//! `config.toml`) with a top-level `"version"`. Files from before versioning
+//! have none and count as version 0.
+//! Loading an older file backs it up next to the original
+//! (`usage.json.v0.bak`), upgrades it one version at a time and writes the
+//! result back, so upgrading filebyte never loses saved data. Files written by
+//! a newer filebyte are left untouched.
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Upgrade a document from one version to the next
+pub type Migration = fn(Value) -> Result<Value, String>;
+
+/// How a format is written on disk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Syntax {
+    /// Compact JSON, for large files like the scan cache
+    Json,
+    /// Indented JSON
+    PrettyJson,
+    /// A TOML table of plain values, for files people edit by hand
+    Toml,
+}
+
+/// A persisted file format and how to bring old versions up to date
+pub struct Format {
+    /// Shown in messages, e.g. "scan cache"
+    pub name: &'static str,
+    /// Version written by this build
+    pub version: u64,
+    /// `migrations[n]` upgrades version `n` to `n + 1`
+    pub migrations: &'static [Migration],
+    pub syntax: Syntax,
+}
+
+/// Version 0 files hold the same fields, just without `"version"`
+fn add_version(document: Value) -> Result<Value, String> {
+    Ok(document)
+}
+
+/// Version 1 cached entries have no group; dropping the cached directories
+/// makes the next `--refresh` list them again. Recorded hashes are kept.
+fn rescan_for_groups(mut document: Value) -> Result<Value, String> {
+    if let Some(dirs) = document.get_mut("dirs") {
+        *dirs = Value::Object(Default::default());
+    }
+    Ok(document)
+}
+
+/// `~/.cache/filebyte/scans/*.json`, see [`crate::cache`]
+pub const SCAN_CACHE: Format = Format {
+    name: "scan cache",
+    version: 2,
+    migrations: &[add_version, rescan_for_groups],
+    syntax: Syntax::Json,
+};
+
+/// `~/.config/filebyte/config.toml`, see [`crate::config`]
+pub const CONFIG: Format = Format {
+    name: "config",
+    version: 1,
+    migrations: &[add_version],
+    syntax: Syntax::Toml,
+};
+
+/// `~/.local/share/filebyte/usage.json`, the run history kept by `stats-self`
+pub const USAGE_STATS: Format = Format {
+    name: "usage stats",
+    version: 1,
+    migrations: &[add_version],
+    syntax: Syntax::PrettyJson,
+};
+
+/// Version 1 snapshots recorded no modes or owners. Their entries load
+/// without them and are left out of permission comparisons.
+fn without_ownership(document: Value) -> Result<Value, String> {
+    Ok(document)
+}
+
+/// `~/.local/share/filebyte/snapshots/*.json.gz`, see [`crate::snapshot`]
+pub const SNAPSHOT: Format = Format {
+    name: "snapshot",
+    version: 2,
+    migrations: &[add_version, without_ownership],
+    syntax: Syntax::Json,
+};
+
+/// Written by `--manifest write`, see [`crate::manifest`]
+pub const MANIFEST: Format = Format {
+    name: "manifest",
+    version: 1,
+    migrations: &[add_version],
+    syntax: Syntax::PrettyJson,
+};
+
+/// Why a versioned file could not be loaded
+#[derive(Debug)]
+pub enum LoadError {
+    /// Written by a newer filebyte; the file was not modified
+    TooNew { found: u64, supported: u64 },
+    /// Not valid for this format. The original is kept at `backup` when it
+    /// could be copied, so saving over it loses nothing.
+    Invalid { reason: String, backup: Option<PathBuf> },
+}
+
+impl std::fmt::Display for LoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoadError::TooNew { found, supported } => write!(
+                f,
+                "written by a newer filebyte (format version {}, this build reads up to {})",
+                found, supported
+            ),
+            LoadError::Invalid { reason, backup: Some(backup) } => {
+                write!(f, "{} (original kept at {})", reason, backup.display())
+            }
+            LoadError::Invalid { reason, backup: None } => write!(f, "{}", reason),
+        }
+    }
+}
+
+/// Where a file is backed up before migrating away from `version`
+pub fn backup_path(path: &Path, version: u64) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".v{}.bak", version));
+    path.with_file_name(name)
+}
+
+/// Read a versioned file, migrating it in place if it is older than `format`.
+/// `Ok(None)` means the file does not exist.
+pub fn load<T: DeserializeOwned>(path: &Path, format: &Format) -> Result<Option<T>, LoadError> {
+    let Ok(contents) = fs::read_to_string(path) else {
+        return Ok(None);
+    };
+    let invalid = |reason: String, version: u64| {
+        let backup = backup_path(path, version);
+        let backup = fs::copy(path, &backup).ok().map(|_| backup);
+        LoadError::Invalid { reason, backup }
+    };
+
+    let mut document = parse_document(&contents, format.syntax).map_err(|e| invalid(e, 0))?;
+    let found = document.get("version").and_then(Value::as_u64).unwrap_or(0);
+    if found > format.version {
+        return Err(LoadError::TooNew {
+            found,
+            supported: format.version,
+        });
+    }
+
+    if found < format.version {
+        fs::copy(path, backup_path(path, found))
+            .map_err(|e| invalid(format!("cannot back up before migrating: {}", e), found))?;
+        for (version, migrate) in format.migrations.iter().enumerate().skip(found as usize) {
+            document = migrate(document)
+                .map_err(|e| invalid(format!("cannot migrate from version {}: {}", version, e), found))?;
+        }
+        if let Some(object) = document.as_object_mut() {
+            object.insert("version".to_string(), Value::from(format.version));
+        }
+        // Best effort: the migrated document is used either way
+        let _ = write_document(path, format, &document);
+    }
+
+    serde_json::from_value(document)
+        .map(Some)
+        .map_err(|e| invalid(format!("unreadable {}: {}", format.name, e), found))
+}
+
+/// Bring a document read by other means than [`load`], such as a compressed
+/// file, up to the current version in memory. The file is not rewritten.
+pub fn upgrade(mut document: Value, format: &Format) -> Result<Value, LoadError> {
+    let found = document.get("version").and_then(Value::as_u64).unwrap_or(0);
+    if found > format.version {
+        return Err(LoadError::TooNew {
+            found,
+            supported: format.version,
+        });
+    }
+    for (version, migrate) in format.migrations.iter().enumerate().skip(found as usize) {
+        document = migrate(document).map_err(|e| LoadError::Invalid {
+            reason: format!("cannot migrate from version {}: {}", version, e),
+            backup: None,
+        })?;
+    }
+    if let Some(object) = document.as_object_mut() {
+        object.insert("version".to_string(), Value::from(format.version));
+    }
+    Ok(document)
+}
+
+/// `value` as a JSON document tagged with the current version of `format`
+pub fn versioned<T: Serialize>(format: &Format, value: &T) -> serde_json::Result<Value> {
+    let mut document = serde_json::to_value(value)?;
+    if let Some(object) = document.as_object_mut() {
+        object.insert("version".to_string(), Value::from(format.version));
+    }
+    Ok(document)
+}
+
+/// Write `value` with the current version of `format`
+pub fn save<T: Serialize>(path: &Path, format: &Format, value: &T) -> std::io::Result<()> {
+    let document = versioned(format, value).map_err(std::io::Error::other)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    write_document(path, format, &document)
+}
+
+fn parse_document(contents: &str, syntax: Syntax) -> Result<Value, String> {
+    match syntax {
+        Syntax::Json | Syntax::PrettyJson => {
+            serde_json::from_str(contents).map_err(|e| format!("not valid JSON: {}", e))
+        }
+        Syntax::Toml => {
+            let document: toml_edit::DocumentMut =
+                contents.parse().map_err(|e| format!("not valid TOML: {}", e))?;
+            Ok(toml_table(document.as_table()))
+        }
+    }
+}
+
+fn toml_table(table: &toml_edit::Table) -> Value {
+    Value::Object(
+        table
+            .iter()
+            .filter_map(|(key, item)| Some((key.to_string(), toml_item(item)?)))
+            .collect(),
+    )
+}
+
+fn toml_item(item: &toml_edit::Item) -> Option<Value> {
+    match item {
+        toml_edit::Item::None => None,
+        toml_edit::Item::Value(value) => Some(toml_value(value)),
+        toml_edit::Item::Table(table) => Some(toml_table(table)),
+        toml_edit::Item::ArrayOfTables(tables) => Some(Value::Array(tables.iter().map(toml_table).collect())),
+    }
+}
+
+fn toml_value(value: &toml_edit::Value) -> Value {
+    match value {
+        toml_edit::Value::String(s) => Value::from(s.value().as_str()),
+        toml_edit::Value::Integer(i) => Value::from(*i.value()),
+        toml_edit::Value::Float(f) => Value::from(*f.value()),
+        toml_edit::Value::Boolean(b) => Value::from(*b.value()),
+        // Kept as written; no format reads dates from TOML yet
+        toml_edit::Value::Datetime(d) => Value::from(d.value().to_string()),
+        toml_edit::Value::Array(array) => Value::Array(array.iter().map(toml_value).collect()),
+        toml_edit::Value::InlineTable(table) => Value::Object(
+            table
+                .iter()
+                .map(|(key, value)| (key.to_string(), toml_value(value)))
+                .collect(),
+        ),
+    }
+}
+
+/// A JSON object as TOML: plain values first, then one `[section]` per
+/// nested object. Nulls are left out, as TOML has none.
+fn write_toml(document: &Value) -> Result<String, String> {
+    fn key(name: &str) -> String {
+        if !name.is_empty() && name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_') {
+            name.to_string()
+        } else {
+            Value::from(name).to_string()
+        }
+    }
+    fn plain(value: &Value) -> Result<String, String> {
+        match value {
+            Value::Bool(_) | Value::Number(_) | Value::String(_) => Ok(value.to_string()),
+            Value::Array(items) => {
+                let items = items.iter().map(plain).collect::<Result<Vec<_>, _>>()?;
+                Ok(format!("[{}]", items.join(", ")))
+            }
+            Value::Object(_) | Value::Null => Err("nested tables and nulls need a section".to_string()),
+        }
+    }
+
+    let table = document.as_object().ok_or("not a table")?;
+    let mut toml = String::new();
+    let mut sections = String::new();
+    for (name, item) in table {
+        match item {
+            Value::Null => {}
+            Value::Object(section) => {
+                sections.push_str(&format!("\n[{}]\n", key(name)));
+                for (name, item) in section.iter().filter(|(_, item)| !item.is_null()) {
+                    sections.push_str(&format!("{} = {}\n", key(name), plain(item)?));
+                }
+            }
+            item => toml.push_str(&format!("{} = {}\n", key(name), plain(item)?)),
+        }
+    }
+    toml.push_str(&sections);
+    Ok(toml)
+}
+
+fn write_document(path: &Path, format: &Format, document: &Value) -> std::io::Result<()> {
+    let contents = match format.syntax {
+        Syntax::Json => serde_json::to_string(document).map_err(std::io::Error::other)?,
+        Syntax::PrettyJson => serde_json::to_string_pretty(document).map_err(std::io::Error::other)?,
+        Syntax::Toml => write_toml(document).map_err(std::io::Error::other)?,
+    };
+    fs::write(path, contents)
+}
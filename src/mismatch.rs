@@ -0,0 +1,119 @@
+//! Files whose extension disagrees with their content (`--type-mismatches`).
+//!
+//! The expected type comes from [`mime_from_extension`] and the actual one
+//! from content sniffing, so both use the same labels. Text extensions have
+//! no signature to check, but binary content under one is still reported.
+//! Executables hiding behind another extension are listed first, as those
+//! are the ones worth a closer look.
+
+use crate::types::{FileInfo, FileKind};
+use crate::utils::{group_digits, mime_from_extension};
+use colored::Colorize;
+use std::path::Path;
+
+/// Extensions of plain-text formats, which sniffing never recognises
+const TEXT_EXTENSIONS: &[&str] = &[
+    "txt", "md", "csv", "tsv", "log", "json", "xml", "html", "htm", "css", "js", "ts", "yaml", "yml", "toml", "ini",
+    "cfg", "conf", "sh", "py", "rs", "c", "h", "svg",
+];
+
+/// Detected types that run code
+const EXECUTABLE_TYPES: &[&str] = &[
+    "application/x-executable",
+    "application/vnd.microsoft.portable-executable",
+    "application/x-mach-binary",
+    "application/x-msdownload",
+    "application/x-shellscript",
+];
+
+/// A file whose extension promises something its content is not
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeMismatch {
+    pub path: String,
+    /// Lowercased, without the dot
+    pub extension: String,
+    /// What the extension implies; `None` for text extensions
+    pub expected: Option<&'static str>,
+    /// What sniffing found, or "unknown"
+    pub detected: String,
+}
+
+impl TypeMismatch {
+    pub fn is_executable(&self) -> bool {
+        EXECUTABLE_TYPES.contains(&self.detected.as_str())
+    }
+
+    pub fn describe(&self) -> String {
+        if self.detected == "unknown" {
+            format!(".{} but the content is not recognised", self.extension)
+        } else {
+            format!(".{} but the content is {}", self.extension, self.detected)
+        }
+    }
+}
+
+/// Compare extension and content for one sniffed file
+pub fn check(file: &FileInfo) -> Option<TypeMismatch> {
+    if file.kind != FileKind::File || file.size == 0 {
+        return None;
+    }
+    let path = Path::new(&file.path);
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    let expected = mime_from_extension(path);
+    let mismatched = match expected {
+        Some(expected) => expected != file.file_type,
+        None => TEXT_EXTENSIONS.contains(&extension.as_str()) && file.file_type != "unknown",
+    };
+    mismatched.then(|| TypeMismatch {
+        path: file.path.clone(),
+        extension,
+        expected,
+        detected: file.file_type.clone(),
+    })
+}
+
+/// Every mismatched file, executables first, then by path
+pub fn find_mismatches(files: &[FileInfo]) -> Vec<TypeMismatch> {
+    let mut mismatches: Vec<TypeMismatch> = files.iter().filter_map(check).collect();
+    mismatches.sort_by(|a, b| b.is_executable().cmp(&a.is_executable()).then_with(|| a.path.cmp(&b.path)));
+    mismatches
+}
+
+/// List the files whose extension disagrees with their content
+pub fn show_type_mismatches(files: &[FileInfo], color: bool) {
+    let mismatches = find_mismatches(files);
+    let checked = files.iter().filter(|f| !f.is_directory).count();
+    println!("Extension Mismatches:");
+    println!("{}", "─".repeat(50));
+    if mismatches.is_empty() {
+        println!("All {} files match their extension.", group_digits(checked as u64));
+        return;
+    }
+
+    let (executables, others): (Vec<&TypeMismatch>, Vec<&TypeMismatch>) =
+        mismatches.iter().partition(|m| m.is_executable());
+    let sections = [
+        ("Executable content under another extension", executables),
+        ("Other mismatches", others),
+    ];
+    for (title, entries) in sections {
+        if entries.is_empty() {
+            continue;
+        }
+        println!("{} ({}):", title, group_digits(entries.len() as u64));
+        for mismatch in entries {
+            if color && mismatch.is_executable() {
+                println!("  {}: {}", mismatch.path.red().bold(), mismatch.describe());
+            } else if color {
+                println!("  {}: {}", mismatch.path.yellow(), mismatch.describe());
+            } else {
+                println!("  {}: {}", mismatch.path, mismatch.describe());
+            }
+        }
+    }
+    println!(
+        "\n{} of {} files do not match their extension",
+        group_digits(mismatches.len() as u64),
+        group_digits(checked as u64)
+    );
+}
@@ -0,0 +1,162 @@
+//! The mount table and mapping paths to the filesystem they live on.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Mount options worth surfacing; the rest are mostly tuning parameters
+const NOTABLE_OPTIONS: [&str; 5] = ["ro", "rw", "noexec", "nosuid", "nodev"];
+
+/// Filesystems whose data lives on another machine
+pub const NETWORK_FS_TYPES: &[&str] = &[
+    "nfs", "nfs4", "cifs", "smb3", "smbfs", "fuse.sshfs", "sshfs", "9p", "ceph", "glusterfs", "fuse.glusterfs",
+    "afs", "davfs", "fuse.davfs2", "lustre", "gpfs", "fuse.rclone",
+];
+
+/// One entry of the mount table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mount {
+    /// Backing device or source, e.g. `/dev/sda1`, `tmpfs` or `server:/export`
+    pub device: String,
+    pub mount_point: PathBuf,
+    pub fs_type: String,
+    /// Mount options as listed by the kernel; empty when unknown
+    pub options: Vec<String>,
+}
+
+impl Mount {
+    pub fn is_read_only(&self) -> bool {
+        self.options.iter().any(|o| o == "ro")
+    }
+
+    /// Served by another machine: a network filesystem type, or a source
+    /// like `server:/export` or `//server/share`
+    pub fn is_network(&self) -> bool {
+        is_network_source(&self.device, &self.fs_type)
+    }
+
+    /// The access-related options (ro/rw, noexec, nosuid, nodev)
+    pub fn notable_options(&self) -> Vec<&str> {
+        self.options
+            .iter()
+            .map(String::as_str)
+            .filter(|o| NOTABLE_OPTIONS.contains(o))
+            .collect()
+    }
+}
+
+/// Whether a filesystem with this device and type is a network mount
+pub fn is_network_source(device: &str, fs_type: &str) -> bool {
+    NETWORK_FS_TYPES.contains(&fs_type)
+        || device.starts_with("//")
+        || device.starts_with("\\\\")
+        || device.split_once(":/").is_some_and(|(host, _)| !host.is_empty() && !host.contains('/'))
+}
+
+/// All mounts, in mount order
+#[cfg(target_os = "linux")]
+pub fn list_mounts() -> Vec<Mount> {
+    fs::read_to_string("/proc/self/mounts")
+        .map(|table| parse_mount_table(&table))
+        .unwrap_or_default()
+}
+
+/// All mounts, in mount order. Options are not available here.
+#[cfg(not(target_os = "linux"))]
+pub fn list_mounts() -> Vec<Mount> {
+    sysinfo::Disks::new_with_refreshed_list()
+        .iter()
+        .map(|disk| Mount {
+            device: disk.name().to_string_lossy().to_string(),
+            mount_point: disk.mount_point().to_path_buf(),
+            fs_type: disk.file_system().to_string_lossy().to_string(),
+            options: Vec::new(),
+        })
+        .collect()
+}
+
+/// Parse a table in `/proc/self/mounts` (fstab) format
+pub fn parse_mount_table(table: &str) -> Vec<Mount> {
+    table
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let device = unescape(fields.next()?);
+            let mount_point = PathBuf::from(unescape(fields.next()?));
+            let fs_type = fields.next()?.to_string();
+            let options = fields
+                .next()
+                .map(|o| o.split(',').map(str::to_string).collect())
+                .unwrap_or_default();
+            Some(Mount {
+                device,
+                mount_point,
+                fs_type,
+                options,
+            })
+        })
+        .collect()
+}
+
+/// Undo the octal escapes (`\040` for space etc.) used in the mount table
+fn unescape(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let digits = bytes.get(i + 1..i + 4).unwrap_or_default();
+        if bytes[i] == b'\\' && digits.len() == 3 && digits.iter().all(|d| (b'0'..=b'7').contains(d)) {
+            out.push(digits.iter().fold(0u8, |code, d| code.wrapping_mul(8).wrapping_add(d - b'0')));
+            i += 4;
+            continue;
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).to_string()
+}
+
+/// The mount a path lives on: the deepest mount point containing it, with
+/// later mounts shadowing earlier ones at the same point
+pub fn path_to_mount(path: &Path) -> Option<Mount> {
+    let path = fs::canonicalize(path).ok()?;
+    mount_for(&list_mounts(), &path).cloned()
+}
+
+fn mount_for<'a>(mounts: &'a [Mount], path: &Path) -> Option<&'a Mount> {
+    mounts
+        .iter()
+        .filter(|m| path.starts_with(&m.mount_point))
+        .fold(None, |best: Option<&Mount>, m| match best {
+            Some(b) if b.mount_point.components().count() > m.mount_point.components().count() => {
+                Some(b)
+            }
+            _ => Some(m),
+        })
+}
+
+/// Mount points strictly below `dir`, spelled relative to `dir` as given so
+/// they compare equal to paths met while walking it
+pub fn mounts_below(dir: &Path) -> Vec<PathBuf> {
+    mounts_below_where(dir, |_| true)
+}
+
+/// Network mount points strictly below `dir`, as in [`mounts_below`]
+pub fn network_mounts_below(dir: &Path) -> Vec<PathBuf> {
+    mounts_below_where(dir, Mount::is_network)
+}
+
+fn mounts_below_where(dir: &Path, keep: impl Fn(&Mount) -> bool) -> Vec<PathBuf> {
+    let Ok(root) = fs::canonicalize(dir) else {
+        return Vec::new();
+    };
+    list_mounts()
+        .into_iter()
+        .filter(|m| m.mount_point != root && keep(m))
+        .filter_map(|m| {
+            m.mount_point
+                .strip_prefix(&root)
+                .ok()
+                .map(|relative| dir.join(relative))
+        })
+        .collect()
+}
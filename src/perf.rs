@@ -0,0 +1,177 @@
+//! Scan counters for `--stats`.
+//!
+//! The directory walkers, the scan cache and every reader of file contents
+//! bump process-wide counters as they go, and `--stats` prints them once the
+//! command is done, including when it ends early through [`exit`]. Counting
+//! is always on: a relaxed atomic add costs nothing next to the system call
+//! it counts.
+
+use crate::types::SizeUnit;
+use crate::utils::group_digits;
+use colored::Colorize;
+use std::fs;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+static DIRS: AtomicU64 = AtomicU64::new(0);
+static FILES: AtomicU64 = AtomicU64::new(0);
+static BYTES_STATTED: AtomicU64 = AtomicU64::new(0);
+static BYTES_READ: AtomicU64 = AtomicU64::new(0);
+static CACHE_HITS: AtomicU64 = AtomicU64::new(0);
+static ERRORS: AtomicU64 = AtomicU64::new(0);
+
+/// Set by `--stats`: when the command started and whether to use color
+static REPORT: OnceLock<(Instant, bool)> = OnceLock::new();
+
+/// Count one directory listing, and an error if it failed
+pub fn count_listing<T>(listing: &io::Result<T>) {
+    DIRS.fetch_add(1, Ordering::Relaxed);
+    if listing.is_err() {
+        count_error();
+    }
+}
+
+/// Count one entry's metadata: a file, with its size, or an error.
+/// Directories are counted when listed, see [`count_listing`].
+pub fn count_metadata(metadata: &io::Result<fs::Metadata>) {
+    match metadata {
+        Ok(metadata) if metadata.is_dir() => {}
+        Ok(metadata) => {
+            FILES.fetch_add(1, Ordering::Relaxed);
+            if metadata.is_file() {
+                BYTES_STATTED.fetch_add(metadata.len(), Ordering::Relaxed);
+            }
+        }
+        Err(_) => count_error(),
+    }
+}
+
+/// Count a directory whose entries came from the scan cache
+pub fn count_cache_hit() {
+    CACHE_HITS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Count an entry or file that could not be read
+pub fn count_error() {
+    ERRORS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// A file whose reads add to the bytes read. Used in place of
+/// [`fs::File`] wherever contents are read: hashing, type detection and
+/// the content analyses.
+#[derive(Debug)]
+pub struct CountedFile(fs::File);
+
+impl CountedFile {
+    pub fn open(path: &Path) -> io::Result<CountedFile> {
+        fs::File::open(path).map(CountedFile).inspect_err(|_| count_error())
+    }
+
+    pub fn metadata(&self) -> io::Result<fs::Metadata> {
+        self.0.metadata()
+    }
+}
+
+impl Read for CountedFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.0.read(buf).inspect_err(|_| count_error())?;
+        BYTES_READ.fetch_add(read as u64, Ordering::Relaxed);
+        Ok(read)
+    }
+}
+
+impl Seek for CountedFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.0.seek(pos)
+    }
+}
+
+/// The counters so far
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ScanStats {
+    /// Directories listed, including the ones served from the cache
+    pub dirs: u64,
+    /// Entries other than directories whose metadata was read
+    pub files: u64,
+    /// Total size of the regular files among them
+    pub bytes_statted: u64,
+    /// File contents read
+    pub bytes_read: u64,
+    /// Directories whose entries came from the scan cache
+    pub cache_hits: u64,
+    /// Listings, metadata and contents that could not be read
+    pub errors: u64,
+}
+
+impl ScanStats {
+    pub fn current() -> ScanStats {
+        ScanStats {
+            dirs: DIRS.load(Ordering::Relaxed) + CACHE_HITS.load(Ordering::Relaxed),
+            files: FILES.load(Ordering::Relaxed),
+            bytes_statted: BYTES_STATTED.load(Ordering::Relaxed),
+            bytes_read: BYTES_READ.load(Ordering::Relaxed),
+            cache_hits: CACHE_HITS.load(Ordering::Relaxed),
+            errors: ERRORS.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// `12,345 (6,172/s)`: a count and its rate over `elapsed`
+fn with_rate(count: u64, elapsed: Duration) -> String {
+    let secs = elapsed.as_secs_f64();
+    if secs <= 0.0 {
+        return group_digits(count);
+    }
+    format!("{} ({}/s)", group_digits(count), group_digits((count as f64 / secs).round() as u64))
+}
+
+/// Print `stats` for a command that took `elapsed`. Goes to stderr so the
+/// command's own output, exports included, stays as it is.
+pub fn show_scan_stats(stats: &ScanStats, elapsed: Duration, color: bool) {
+    if color {
+        eprintln!("\n{}", "Scan Statistics:".bold());
+    } else {
+        eprintln!("\nScan Statistics:");
+    }
+    eprintln!("{}", "─".repeat(50));
+    let rows = [
+        ("Wall time", format!("{:.3} s", elapsed.as_secs_f64())),
+        ("Files", with_rate(stats.files, elapsed)),
+        ("Directories", with_rate(stats.dirs, elapsed)),
+        ("Bytes statted", SizeUnit::auto_format_size(stats.bytes_statted)),
+        ("Bytes read", SizeUnit::auto_format_size(stats.bytes_read)),
+        ("Cache hits", format!("{} directories", group_digits(stats.cache_hits))),
+        ("Errors", group_digits(stats.errors)),
+    ];
+    for (label, value) in rows {
+        if color && label == "Errors" && stats.errors > 0 {
+            eprintln!("{}: {}", label, value.red().bold());
+        } else if color {
+            eprintln!("{}: {}", label, value.cyan());
+        } else {
+            eprintln!("{}: {}", label, value);
+        }
+    }
+}
+
+/// Print the statistics when the command ends, see [`finish`] and [`exit`]
+pub fn report_at_exit(color: bool) {
+    let _ = REPORT.set((Instant::now(), color));
+}
+
+/// Print the statistics if [`report_at_exit`] asked for them
+pub fn finish() {
+    if let Some((started, color)) = REPORT.get() {
+        show_scan_stats(&ScanStats::current(), started.elapsed(), *color);
+    }
+}
+
+/// End the process with `code` after [`finish`]; commands exit through
+/// here rather than `std::process::exit` so `--stats` is never skipped
+pub fn exit(code: i32) -> ! {
+    finish();
+    std::process::exit(code)
+}
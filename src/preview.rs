@@ -0,0 +1,126 @@
+//! File previews (`-f FILE --preview`).
+//!
+//! Text files show their first lines, anything else a hex dump in the
+//! layout of `hexdump -C`, so an unknown file can be identified without
+//! reaching for `head` or `xxd`. A file counts as text when
+//! [`crate::loc::open_text`] accepts it.
+
+use crate::loc::open_text;
+use crate::utils::group_digits;
+use colored::Colorize;
+use std::fs;
+use std::io::{self, BufRead, Read};
+use std::path::Path;
+
+/// Lines shown when `--preview` is given without a count
+pub const DEFAULT_LINES: usize = 10;
+
+/// Characters of a text line shown before it is cut off
+pub const MAX_LINE_CHARS: usize = 200;
+
+/// Bytes per hex dump row
+pub const ROW_BYTES: usize = 16;
+
+/// The first `limit` lines of `text`, cut to [`MAX_LINE_CHARS`] and with
+/// control characters other than tabs shown as `.`, and whether more follow
+pub fn head_lines(text: impl BufRead, limit: usize) -> io::Result<(Vec<String>, bool)> {
+    let mut lines = Vec::new();
+    for line in text.split(b'\n') {
+        if lines.len() == limit {
+            return Ok((lines, true));
+        }
+        let line = line?;
+        let line = String::from_utf8_lossy(&line);
+        let line = line.strip_suffix('\r').unwrap_or(&line);
+        // Escape sequences in the file must not reach the terminal
+        let mut shown: String = line
+            .chars()
+            .take(MAX_LINE_CHARS)
+            .map(|c| if c.is_control() && c != '\t' { '.' } else { c })
+            .collect();
+        if shown.len() < line.len() {
+            shown.push('…');
+        }
+        lines.push(shown);
+    }
+    Ok((lines, false))
+}
+
+/// One `hexdump -C` row: offset, two groups of eight bytes, and the
+/// printable ASCII characters
+pub fn hex_row(offset: usize, bytes: &[u8]) -> String {
+    let mut hex = String::new();
+    for i in 0..ROW_BYTES {
+        match bytes.get(i) {
+            Some(byte) => hex.push_str(&format!("{:02x} ", byte)),
+            None => hex.push_str("   "),
+        }
+        if i == ROW_BYTES / 2 - 1 {
+            hex.push(' ');
+        }
+    }
+    let ascii: String = bytes
+        .iter()
+        .map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' })
+        .collect();
+    format!("{:08x}  {} |{}|", offset, hex, ascii)
+}
+
+/// Print the first `limit` lines of the file at `path`, or `limit` hex dump
+/// rows if it looks binary
+pub fn show_preview(path: &Path, limit: usize, color: bool) -> io::Result<()> {
+    println!();
+    if let Some(text) = open_text(path)? {
+        let (lines, more) = head_lines(text, limit)?;
+        println!("Preview (first {} lines):", group_digits(lines.len() as u64));
+        println!("{}", "─".repeat(50));
+        if lines.is_empty() {
+            println!("(empty file)");
+        }
+        let width = lines.len().to_string().len();
+        for (index, line) in lines.iter().enumerate() {
+            let number = format!("{:>width$}", index + 1);
+            if color {
+                println!("{}  {}", number.dimmed(), line);
+            } else {
+                println!("{}  {}", number, line);
+            }
+        }
+        if more {
+            println!("...");
+        }
+        return Ok(());
+    }
+
+    let size = fs::metadata(path)?.len();
+    let mut bytes = Vec::new();
+    fs::File::open(path)?.take((limit * ROW_BYTES) as u64).read_to_end(&mut bytes)?;
+    println!("Hex dump (first {} bytes):", group_digits(bytes.len() as u64));
+    println!("{}", "─".repeat(50));
+    for (row, chunk) in bytes.chunks(ROW_BYTES).enumerate() {
+        let line = hex_row(row * ROW_BYTES, chunk);
+        if color {
+            let (offset, rest) = line.split_at(8);
+            println!("{}{}", offset.dimmed(), rest);
+        } else {
+            println!("{}", line);
+        }
+    }
+    if size > bytes.len() as u64 {
+        println!("... and {} more bytes", group_digits(size - bytes.len() as u64));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_rows_line_up() {
+        let full = hex_row(0, b"0123456789abcdef");
+        let short = hex_row(0x1230, b"a\tb");
+        assert_eq!(short, "00001230  61 09 62                                          |a.b|");
+        assert_eq!(full.find('|'), short.find('|'));
+    }
+}
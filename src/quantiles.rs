@@ -0,0 +1,123 @@
+//! File size percentiles for the detailed analysis.
+//!
+//! Sizes are kept exactly up to a limit. Past it they move into a sketch of
+//! logarithmic buckets: each bucket spans sizes within [`RELATIVE_ACCURACY`]
+//! of its midpoint, so any percentile is off by at most that fraction while
+//! memory only grows with the number of distinct magnitudes.
+
+use std::collections::BTreeMap;
+
+/// Sizes kept exactly before switching to the sketch (8 MiB of samples)
+pub const DEFAULT_EXACT_LIMIT: usize = 1 << 20;
+
+/// Largest relative error of a sketched percentile
+pub const RELATIVE_ACCURACY: f64 = 0.01;
+
+/// Mean and percentiles of a stream of file sizes
+#[derive(Debug, Clone)]
+pub struct SizeQuantiles {
+    exact_limit: usize,
+    exact: Vec<u64>,
+    /// Bucket index to count, see [`SizeQuantiles::bucket`]; empty files
+    /// have no logarithm and are counted in `zeros`
+    buckets: BTreeMap<i32, u64>,
+    zeros: u64,
+    count: u64,
+    total: u128,
+}
+
+impl Default for SizeQuantiles {
+    fn default() -> Self {
+        SizeQuantiles::with_exact_limit(DEFAULT_EXACT_LIMIT)
+    }
+}
+
+impl SizeQuantiles {
+    pub fn new() -> Self {
+        SizeQuantiles::default()
+    }
+
+    /// Keep at most `limit` sizes exactly
+    pub fn with_exact_limit(limit: usize) -> Self {
+        SizeQuantiles {
+            exact_limit: limit,
+            exact: Vec::new(),
+            buckets: BTreeMap::new(),
+            zeros: 0,
+            count: 0,
+            total: 0,
+        }
+    }
+
+    pub fn push(&mut self, size: u64) {
+        self.count += 1;
+        self.total += u128::from(size);
+        if self.is_exact() {
+            if self.exact.len() < self.exact_limit {
+                self.exact.push(size);
+                return;
+            }
+            for size in std::mem::take(&mut self.exact) {
+                self.sketch(size);
+            }
+        }
+        self.sketch(size);
+    }
+
+    fn sketch(&mut self, size: u64) {
+        if size == 0 {
+            self.zeros += 1;
+        } else {
+            *self.buckets.entry(Self::bucket(size)).or_insert(0) += 1;
+        }
+    }
+
+    fn gamma() -> f64 {
+        (1.0 + RELATIVE_ACCURACY) / (1.0 - RELATIVE_ACCURACY)
+    }
+
+    /// Bucket `i` holds the sizes in (gamma^(i-1), gamma^i]
+    fn bucket(size: u64) -> i32 {
+        (size as f64).log(Self::gamma()).ceil() as i32
+    }
+
+    /// Whether every size is still held exactly
+    pub fn is_exact(&self) -> bool {
+        self.buckets.is_empty() && self.zeros == 0
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn mean(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.total as f64 / self.count as f64)
+    }
+
+    /// The size at or below which a `q` share of the sizes lie (nearest
+    /// rank), for `q` in 0..=1
+    pub fn quantile(&mut self, q: f64) -> Option<u64> {
+        if self.count == 0 {
+            return None;
+        }
+        let rank = ((q.clamp(0.0, 1.0) * self.count as f64).ceil() as u64).max(1);
+        if self.is_exact() {
+            self.exact.sort_unstable();
+            return Some(self.exact[rank as usize - 1]);
+        }
+        if rank <= self.zeros {
+            return Some(0);
+        }
+        let mut seen = self.zeros;
+        let gamma = Self::gamma();
+        for (&bucket, &count) in &self.buckets {
+            seen += count;
+            if seen >= rank {
+                // The midpoint that keeps the error within the accuracy
+                // for every size in the bucket
+                return Some((2.0 * gamma.powi(bucket) / (gamma + 1.0)).round() as u64);
+            }
+        }
+        None
+    }
+}
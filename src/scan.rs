@@ -0,0 +1,248 @@
+use crate::collect::{build_file_info, matches_search};
+use crate::perf;
+use crate::types::FileInfo;
+use crate::utils::hash_file;
+use regex::Regex;
+use std::collections::HashSet;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// Totals for the entries a [`Scan`] has yielded so far
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Summary {
+    pub files: usize,
+    pub directories: usize,
+    pub total_size: u64,
+    pub errors: usize,
+}
+
+/// Configures a scan of a directory tree.
+///
+/// ```no_run
+/// use filebyte::scan::ScanBuilder;
+///
+/// let mut scan = ScanBuilder::new("/var/log")
+///     .max_depth(2)
+///     .search(r"\.log$")
+///     .threads(4)
+///     .build()
+///     .unwrap();
+/// for file in scan.by_ref() {
+///     println!("{} {}", file.path, file.size_human);
+/// }
+/// println!("{:?}", scan.summary());
+/// ```
+#[derive(Debug, Clone)]
+pub struct ScanBuilder {
+    root: PathBuf,
+    max_depth: Option<usize>,
+    follow_symlinks: bool,
+    search_pattern: Option<String>,
+    excluding_pattern: Option<String>,
+    threads: usize,
+    hashing: bool,
+}
+
+impl ScanBuilder {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        ScanBuilder {
+            root: root.into(),
+            max_depth: None,
+            follow_symlinks: false,
+            search_pattern: None,
+            excluding_pattern: None,
+            threads: 1,
+            hashing: false,
+        }
+    }
+
+    /// Only yield entries up to `depth` levels below the root (1 = direct
+    /// children, 0 = nothing)
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    /// Descend into symlinked directories. Each directory is read once, so
+    /// links back to a directory above them do not loop.
+    pub fn follow_symlinks(mut self, follow: bool) -> Self {
+        self.follow_symlinks = follow;
+        self
+    }
+
+    /// Only yield entries whose name matches, using the same rules as `--search`
+    pub fn search(mut self, pattern: impl Into<String>) -> Self {
+        self.search_pattern = Some(pattern.into());
+        self
+    }
+
+    /// Skip entries (and whole subtrees) whose name matches the regex
+    pub fn excluding(mut self, pattern: impl Into<String>) -> Self {
+        self.excluding_pattern = Some(pattern.into());
+        self
+    }
+
+    /// Number of worker threads reading metadata and hashing files
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Fill in `FileInfo::hash` with the SHA-256 of each regular file
+    pub fn hashing(mut self, hashing: bool) -> Self {
+        self.hashing = hashing;
+        self
+    }
+
+    /// Start the scan. Fails only if the exclusion pattern is not a valid regex.
+    pub fn build(self) -> Result<Scan, regex::Error> {
+        let excluding_regex = self.excluding_pattern.as_deref().map(Regex::new).transpose()?;
+        let errors = Arc::new(AtomicUsize::new(0));
+
+        let (path_tx, path_rx) = mpsc::channel::<PathBuf>();
+        let (file_tx, file_rx) = mpsc::channel::<FileInfo>();
+
+        let walker_errors = Arc::clone(&errors);
+        let root = self.root.clone();
+        let max_depth = self.max_depth;
+        let follow_symlinks = self.follow_symlinks;
+        let search_pattern = self.search_pattern.clone();
+        thread::spawn(move || {
+            let mut pending = if max_depth == Some(0) { Vec::new() } else { vec![(root, 0usize)] };
+            // Canonical paths of the directories read, when following links
+            let mut visited = HashSet::new();
+            while let Some((dir, depth)) = pending.pop() {
+                if follow_symlinks && fs::canonicalize(&dir).is_ok_and(|canonical| !visited.insert(canonical)) {
+                    continue;
+                }
+                let listing = fs::read_dir(&dir);
+                perf::count_listing(&listing);
+                let entries = match listing {
+                    Ok(entries) => entries,
+                    Err(_) => {
+                        walker_errors.fetch_add(1, Ordering::Relaxed);
+                        continue;
+                    }
+                };
+                for entry in entries.flatten() {
+                    let entry_path = entry.path();
+                    let file_name = entry.file_name().to_string_lossy().to_string();
+
+                    if let Some(regex) = &excluding_regex {
+                        if regex.is_match(&file_name) {
+                            continue;
+                        }
+                    }
+
+                    if matches_search(&file_name, search_pattern.as_ref())
+                        && path_tx.send(entry_path.clone()).is_err()
+                    {
+                        return;
+                    }
+
+                    let is_dir = match entry.file_type() {
+                        Ok(t) if t.is_symlink() => follow_symlinks && entry_path.is_dir(),
+                        Ok(t) => t.is_dir(),
+                        Err(_) => false,
+                    };
+                    if is_dir && max_depth.is_none_or(|max| depth + 1 < max) {
+                        pending.push((entry_path, depth + 1));
+                    }
+                }
+            }
+        });
+
+        let path_rx = Arc::new(Mutex::new(path_rx));
+        for _ in 0..self.threads {
+            let path_rx = Arc::clone(&path_rx);
+            let file_tx = file_tx.clone();
+            let worker_errors = Arc::clone(&errors);
+            let hashing = self.hashing;
+            let follow_symlinks = self.follow_symlinks;
+            thread::spawn(move || loop {
+                let next = path_rx.lock().map(|rx| rx.recv());
+                let path = match next {
+                    Ok(Ok(path)) => path,
+                    _ => return,
+                };
+                // Unless following links, a link to a directory is a link
+                let metadata = if follow_symlinks {
+                    fs::metadata(&path).or_else(|_| fs::symlink_metadata(&path))
+                } else {
+                    fs::symlink_metadata(&path)
+                };
+                perf::count_metadata(&metadata);
+                let metadata = match metadata {
+                    Ok(metadata) => metadata,
+                    Err(_) => {
+                        worker_errors.fetch_add(1, Ordering::Relaxed);
+                        continue;
+                    }
+                };
+                let mut file = build_file_info(&path, &metadata);
+                if hashing && metadata.is_file() {
+                    match hash_file(&path) {
+                        Ok(hash) => file.hash = Some(hash),
+                        Err(_) => {
+                            worker_errors.fetch_add(1, Ordering::Relaxed);
+                        }
+                    }
+                }
+                if file_tx.send(file).is_err() {
+                    return;
+                }
+            });
+        }
+
+        Ok(Scan {
+            files: file_rx,
+            summary: Summary::default(),
+            errors,
+        })
+    }
+}
+
+/// A running scan. Iterate it to receive entries as they are found; entries
+/// arrive in no particular order when more than one thread is used.
+pub struct Scan {
+    files: Receiver<FileInfo>,
+    summary: Summary,
+    errors: Arc<AtomicUsize>,
+}
+
+impl Scan {
+    /// Totals for everything yielded so far; complete once iteration ends
+    pub fn summary(&self) -> Summary {
+        Summary {
+            errors: self.errors.load(Ordering::Relaxed),
+            ..self.summary.clone()
+        }
+    }
+
+    /// Drain the scan into a Vec along with its final summary
+    pub fn collect_all(mut self) -> (Vec<FileInfo>, Summary) {
+        let files: Vec<FileInfo> = self.by_ref().collect();
+        let summary = self.summary();
+        (files, summary)
+    }
+}
+
+impl Iterator for Scan {
+    type Item = FileInfo;
+
+    fn next(&mut self) -> Option<FileInfo> {
+        let file = self.files.recv().ok()?;
+        if file.is_directory {
+            self.summary.directories += 1;
+        } else {
+            self.summary.files += 1;
+            self.summary.total_size += file.size;
+        }
+        Some(file)
+    }
+}
+
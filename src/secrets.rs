@@ -0,0 +1,162 @@
+//! Secret scanning (`--scan-secrets`).
+//!
+//! Text files are matched line by line against [`RULES`], a short list of
+//! well-known credential formats. The rules favour precision over recall:
+//! they catch keys pasted into a config file, not every high-entropy string.
+//! Findings are shown redacted, so the report itself can be shared.
+
+use crate::loc::open_text;
+use crate::types::{FileInfo, FileKind};
+use crate::utils::group_digits;
+use colored::Colorize;
+use regex::Regex;
+use std::io::{self, BufRead};
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// A kind of secret and the regular expression that finds it
+#[derive(Debug)]
+pub struct SecretRule {
+    pub name: &'static str,
+    pub pattern: &'static str,
+}
+
+/// The built-in rules, checked in order; a line is reported once per rule
+pub const RULES: &[SecretRule] = &[
+    SecretRule {
+        name: "AWS access key ID",
+        pattern: r"\b(?:AKIA|ASIA)[0-9A-Z]{16}\b",
+    },
+    SecretRule {
+        name: "AWS secret access key",
+        pattern: r#"(?i)aws.{0,20}secret.{0,20}[:=]\s*['"]?[0-9a-zA-Z/+]{40}\b"#,
+    },
+    SecretRule {
+        name: "Private key",
+        pattern: r"-----BEGIN (?:[A-Z]+ )?PRIVATE KEY(?: BLOCK)?-----",
+    },
+    SecretRule {
+        name: "GitHub token",
+        pattern: r"\bgh[pousr]_[0-9A-Za-z]{36}\b",
+    },
+    SecretRule {
+        name: "GitLab token",
+        pattern: r"\bglpat-[0-9A-Za-z_-]{20}\b",
+    },
+    SecretRule {
+        name: "Slack token",
+        pattern: r"\bxox[abposr]-[0-9A-Za-z-]{10,}",
+    },
+    SecretRule {
+        name: "Stripe live key",
+        pattern: r"\b[sr]k_live_[0-9A-Za-z]{24,}",
+    },
+    SecretRule {
+        name: "Google API key",
+        pattern: r"\bAIza[0-9A-Za-z_-]{35}\b",
+    },
+    SecretRule {
+        name: "JSON web token",
+        pattern: r"\beyJ[0-9A-Za-z_-]{10,}\.eyJ[0-9A-Za-z_-]{10,}\.[0-9A-Za-z_-]{10,}",
+    },
+    SecretRule {
+        name: "Password assignment",
+        pattern: r#"(?i)\w*(?:password|passwd|pwd|secret|api_?key|auth_?token)\w*\s*[:=]\s*['"][^'"\s]{8,}['"]"#,
+    },
+];
+
+fn compiled_rules() -> &'static [(&'static SecretRule, Regex)] {
+    static COMPILED: OnceLock<Vec<(&'static SecretRule, Regex)>> = OnceLock::new();
+    COMPILED.get_or_init(|| {
+        RULES
+            .iter()
+            .map(|rule| (rule, Regex::new(rule.pattern).expect("built-in secret rule")))
+            .collect()
+    })
+}
+
+/// A line that looks like it holds a secret
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SecretFinding {
+    pub path: String,
+    /// Starting at 1
+    pub line_number: u64,
+    pub rule: &'static str,
+    /// The matched text, redacted with [`redact`]
+    pub excerpt: String,
+}
+
+/// The first four characters of `secret`, the rest masked
+pub fn redact(secret: &str) -> String {
+    let shown: String = secret.chars().take(4).collect();
+    let hidden = secret.chars().count().saturating_sub(4).min(12);
+    format!("{}{}", shown, "*".repeat(hidden))
+}
+
+/// Scan the file at `path`; `None` if it looks binary
+pub fn scan_file(path: &Path) -> io::Result<Option<Vec<SecretFinding>>> {
+    let Some(text) = open_text(path)? else {
+        return Ok(None);
+    };
+    let mut findings = Vec::new();
+    for (index, line) in text.split(b'\n').enumerate() {
+        let line = line?;
+        let line = String::from_utf8_lossy(&line);
+        for (rule, regex) in compiled_rules() {
+            if let Some(found) = regex.find(&line) {
+                findings.push(SecretFinding {
+                    path: path.to_string_lossy().to_string(),
+                    line_number: index as u64 + 1,
+                    rule: rule.name,
+                    excerpt: redact(found.as_str()),
+                });
+            }
+        }
+    }
+    Ok(Some(findings))
+}
+
+/// Scan every regular file in `files` in path order, returning the findings
+/// and how many text files were scanned. Unreadable files are skipped.
+pub fn scan_files(files: &[FileInfo]) -> (Vec<SecretFinding>, usize) {
+    let mut files: Vec<&FileInfo> = files.iter().filter(|f| f.kind == FileKind::File).collect();
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    let mut scanned = 0;
+    let mut findings = Vec::new();
+    for file in files {
+        if let Ok(Some(found)) = scan_file(Path::new(&file.path)) {
+            scanned += 1;
+            findings.extend(found);
+        }
+    }
+    (findings, scanned)
+}
+
+/// List the possible secrets in `files`; returns whether none were found
+pub fn show_secrets_report(files: &[FileInfo], color: bool) -> bool {
+    let (findings, scanned) = scan_files(files);
+    println!("Secrets Scan:");
+    println!("{}", "─".repeat(50));
+    if findings.is_empty() {
+        println!("No secrets found in {} text files.", group_digits(scanned as u64));
+        return true;
+    }
+
+    for finding in &findings {
+        let location = format!("{}:{}", finding.path, finding.line_number);
+        if color {
+            println!("{}  {}  {}", location.red(), finding.rule.yellow(), finding.excerpt.dimmed());
+        } else {
+            println!("{}  {}  {}", location, finding.rule, finding.excerpt);
+        }
+    }
+    let mut affected: Vec<&str> = findings.iter().map(|f| f.path.as_str()).collect();
+    affected.dedup();
+    println!(
+        "\n{} possible secrets in {} of {} text files",
+        group_digits(findings.len() as u64),
+        group_digits(affected.len() as u64),
+        group_digits(scanned as u64)
+    );
+    false
+}
@@ -0,0 +1,137 @@
+//! Near-duplicate images (`--similar-images`).
+//!
+//! Each image is shrunk to 9x8 grayscale pixels and reduced to a 64-bit
+//! difference hash (dHash): one bit per pixel pair, set when the left pixel
+//! is brighter than its right neighbour. Resizing, re-encoding and small
+//! color changes barely move the hash, so images whose hashes differ in at
+//! most [`MAX_DISTANCE`] bits are reported as the same picture. PNG, JPEG,
+//! GIF, WebP and BMP files are decoded in-process; anything else is skipped.
+
+use crate::analysis::hash_in_parallel;
+use crate::types::{DuplicateGroup, DuplicateOptions, SizeUnit};
+use crate::utils::detect_file_type;
+use colored::Colorize;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Differing hash bits still considered the same picture
+pub const MAX_DISTANCE: u32 = 8;
+
+/// Thumbnail size hashed by [`dhash`]: one extra column for the comparisons
+pub const THUMB_WIDTH: usize = 9;
+pub const THUMB_HEIGHT: usize = 8;
+
+/// Difference hash of a row-major `THUMB_WIDTH` x `THUMB_HEIGHT` grayscale
+/// thumbnail
+pub fn dhash(pixels: &[u8]) -> u64 {
+    let mut hash = 0u64;
+    for row in pixels.chunks_exact(THUMB_WIDTH).take(THUMB_HEIGHT) {
+        for pair in row.windows(2) {
+            hash = (hash << 1) | u64::from(pair[0] > pair[1]);
+        }
+    }
+    hash
+}
+
+/// Number of bits in which two hashes differ
+pub fn distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+/// Decode an image into the thumbnail [`dhash`] expects, averaging each
+/// thumbnail pixel over the area it covers
+pub fn thumbnail(path: &Path) -> io::Result<Vec<u8>> {
+    let image = image::open(path).map_err(|e| match e {
+        image::ImageError::IoError(e) => e,
+        e => io::Error::new(io::ErrorKind::InvalidData, e.to_string()),
+    })?;
+    let small = image.thumbnail_exact(THUMB_WIDTH as u32, THUMB_HEIGHT as u32);
+    Ok(small.to_luma8().into_raw())
+}
+
+/// [`dhash`] of an image file as 16 hex digits
+pub fn image_hash(path: &Path) -> io::Result<String> {
+    Ok(format!("{:016x}", dhash(&thumbnail(path)?)))
+}
+
+/// Cluster images whose hashes are within `max_distance` bits, directly or
+/// through a chain of other images. Images similar to nothing are dropped;
+/// each group's paths are sorted, and groups are ordered by their first path.
+pub fn group_similar(hashes: &[(String, u64)], max_distance: u32) -> Vec<Vec<String>> {
+    // Union-find over every pair; image collections are small next to the
+    // cost of decoding each image
+    let mut parent: Vec<usize> = (0..hashes.len()).collect();
+    fn root(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+    for i in 0..hashes.len() {
+        for j in i + 1..hashes.len() {
+            if distance(hashes[i].1, hashes[j].1) <= max_distance {
+                let (a, b) = (root(&mut parent, i), root(&mut parent, j));
+                parent[a.max(b)] = a.min(b);
+            }
+        }
+    }
+
+    let mut groups: HashMap<usize, Vec<String>> = HashMap::new();
+    for (i, (path, _)) in hashes.iter().enumerate() {
+        groups.entry(root(&mut parent, i)).or_default().push(path.clone());
+    }
+    let mut groups: Vec<Vec<String>> = groups.into_values().filter(|paths| paths.len() > 1).collect();
+    for paths in &mut groups {
+        paths.sort();
+    }
+    groups.sort();
+    groups
+}
+
+/// Print groups of visually identical images among the candidates, after the
+/// exact duplicates. Exact copies are represented by their first path only,
+/// so a group always holds different files.
+pub fn report_similar_images(
+    by_size: &HashMap<u64, Vec<String>>,
+    exact: &[DuplicateGroup],
+    options: &DuplicateOptions,
+    color: bool,
+) {
+    let copies: HashSet<&String> = exact.iter().flat_map(|group| group.paths.iter().skip(1)).collect();
+    let mut images: Vec<(u64, &String)> = by_size
+        .iter()
+        .filter(|(size, _)| **size >= options.min_size)
+        .flat_map(|(size, paths)| paths.iter().map(move |path| (*size, path)))
+        .filter(|(_, path)| !copies.contains(path) && detect_file_type(Path::new(path)).starts_with("image/"))
+        .collect();
+    images.sort_by(|a, b| a.1.cmp(b.1));
+
+    let hashes: Vec<(String, u64)> = images
+        .iter()
+        .zip(hash_in_parallel(&images, options.threads, image_hash))
+        .filter_map(|((_, path), hash)| Some(((*path).clone(), u64::from_str_radix(&hash?, 16).ok()?)))
+        .collect();
+    let groups = group_similar(&hashes, MAX_DISTANCE);
+
+    if groups.is_empty() {
+        println!("No similar images found.");
+        return;
+    }
+    println!("Similar images (same picture, different files):");
+    println!("{}", "─".repeat(50));
+    for paths in &groups {
+        if color {
+            println!("{} images", paths.len().to_string().yellow());
+        } else {
+            println!("{} images", paths.len());
+        }
+        for path in paths {
+            let size = fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+            println!("  {} ({})", path, SizeUnit::auto_format_size(size));
+        }
+        println!();
+    }
+}
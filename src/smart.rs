@@ -0,0 +1,102 @@
+//! Drive health from SMART data (`--disk list --health`, `smart` feature).
+//!
+//! Attributes are read with `smartctl --json` from smartmontools, so no
+//! platform code is needed here. Most drives only answer to root.
+
+use serde_json::Value;
+use std::process::Command;
+
+/// The SMART values worth a glance next to capacity
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SmartHealth {
+    /// The drive's overall self-assessment
+    pub passed: Option<bool>,
+    pub temperature_celsius: Option<i64>,
+    /// ATA attribute 5, sectors remapped after read or write errors
+    pub reallocated_sectors: Option<u64>,
+    /// NVMe drives count unrecovered data errors instead
+    pub media_errors: Option<u64>,
+}
+
+impl std::fmt::Display for SmartHealth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let verdict = match self.passed {
+            Some(true) => "PASSED",
+            Some(false) => "FAILED",
+            None => "unknown",
+        };
+        write!(f, "Health: {}", verdict)?;
+        if let Some(temperature) = self.temperature_celsius {
+            write!(f, " | Temperature: {}°C", temperature)?;
+        }
+        if let Some(sectors) = self.reallocated_sectors {
+            write!(f, " | Reallocated sectors: {}", sectors)?;
+        }
+        if let Some(errors) = self.media_errors {
+            write!(f, " | Media errors: {}", errors)?;
+        }
+        Ok(())
+    }
+}
+
+/// The whole drive a partition lives on: `/dev/sda1` is on `/dev/sda`,
+/// `/dev/nvme0n1p2` on `/dev/nvme0n1`. `None` for names that are not plain
+/// block devices (device mapper, loop devices, network shares).
+pub fn physical_device(name: &str) -> Option<String> {
+    let device = name.strip_prefix("/dev/")?;
+    if device.is_empty() || device.contains('/') || device.starts_with("loop") || device.starts_with("dm-") {
+        return None;
+    }
+    let drive = if device.starts_with("nvme") || device.starts_with("mmcblk") {
+        match device.rsplit_once('p') {
+            Some((drive, partition))
+                if !partition.is_empty() && partition.bytes().all(|b| b.is_ascii_digit()) =>
+            {
+                drive
+            }
+            _ => device,
+        }
+    } else {
+        device.trim_end_matches(|c: char| c.is_ascii_digit())
+    };
+    Some(format!("/dev/{}", drive))
+}
+
+/// Parse the output of `smartctl --json -H -A`
+pub fn parse_smartctl(json: &str) -> Result<SmartHealth, String> {
+    let document: Value = serde_json::from_str(json).map_err(|e| format!("unreadable smartctl output: {}", e))?;
+    let health = SmartHealth {
+        passed: document.pointer("/smart_status/passed").and_then(Value::as_bool),
+        temperature_celsius: document.pointer("/temperature/current").and_then(Value::as_i64),
+        reallocated_sectors: document
+            .pointer("/ata_smart_attributes/table")
+            .and_then(Value::as_array)
+            .and_then(|table| table.iter().find(|attribute| attribute["id"] == 5))
+            .and_then(|attribute| attribute.pointer("/raw/value"))
+            .and_then(Value::as_u64),
+        media_errors: document
+            .pointer("/nvme_smart_health_information_log/media_errors")
+            .and_then(Value::as_u64),
+    };
+    if health != SmartHealth::default() {
+        return Ok(health);
+    }
+
+    let message = document
+        .pointer("/smartctl/messages")
+        .and_then(Value::as_array)
+        .and_then(|messages| messages.iter().find_map(|m| m["string"].as_str()));
+    Err(message.unwrap_or("no SMART data").to_string())
+}
+
+/// Read a drive's SMART data with smartctl
+pub fn query(device: &str) -> Result<SmartHealth, String> {
+    let output = Command::new("smartctl")
+        .args(["--json", "-H", "-A"])
+        .arg(device)
+        .output()
+        .map_err(|e| format!("could not run smartctl: {}", e))?;
+    // The exit status is a bit mask that is also set for drives that answered
+    // but report problems, so the JSON decides
+    parse_smartctl(&String::from_utf8_lossy(&output.stdout))
+}
@@ -0,0 +1,573 @@
+//! Saved snapshots of a tree's metadata (`filebyte snapshot`).
+//!
+//! A snapshot records every entry below a directory with its size,
+//! modification time, permissions, owner and optionally a content hash, so
+//! later runs can tell how the tree changed. Snapshots live in the data directory as
+//! gzip-compressed JSON in the [`migrate::SNAPSHOT`] format. The comment of
+//! the gzip header holds a [`SnapshotSummary`], so listing snapshots reads
+//! only their headers. Plain `.json` snapshots are still read.
+
+use crate::analysis::hash_in_parallel;
+use crate::collect::walk_files;
+use crate::migrate::{self, SNAPSHOT};
+use crate::types::{CollectOptions, FileKind, Filter, ScanMetadata, SizeUnit};
+use crate::utils::{group_digits, hash_file};
+use colored::Colorize;
+use flate2::bufread::GzDecoder;
+use flate2::read::MultiGzDecoder;
+use flate2::{Compression, GzBuilder};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// A tree's metadata at one point in time
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub name: String,
+    /// Canonical path of the snapshotted directory
+    pub root: String,
+    pub metadata: ScanMetadata,
+    /// Whether files carry content hashes
+    pub hashed: bool,
+    /// In walk order, directories after their contents
+    pub entries: Vec<SnapshotEntry>,
+}
+
+/// One file or directory in a [`Snapshot`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotEntry {
+    /// Relative to the snapshot root, with `/` separators
+    pub path: String,
+    /// A file's size, or the total size of the files below a directory
+    pub size: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub dir: bool,
+    /// SHA-256 of the contents, for files in hashed snapshots
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+    /// Unix permission bits, e.g. 0o644; not recorded before format version 2
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uid: Option<u32>,
+    /// The owner's user name, or the uid when it has none
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub owner: String,
+}
+
+impl Snapshot {
+    /// Total size of the files in the snapshot
+    pub fn total_size(&self) -> u64 {
+        self.entries.iter().filter(|e| !e.dir).map(|e| e.size).sum()
+    }
+
+    pub fn file_count(&self) -> usize {
+        self.entries.iter().filter(|e| !e.dir).count()
+    }
+
+    pub fn summary(&self) -> SnapshotSummary {
+        SnapshotSummary {
+            root: self.root.clone(),
+            scanned_at: self.metadata.scanned_at.clone(),
+            entries: self.entries.len(),
+            total_size: self.total_size(),
+        }
+    }
+}
+
+/// What `snapshot list` shows of a snapshot
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotSummary {
+    pub root: String,
+    pub scanned_at: String,
+    pub entries: usize,
+    pub total_size: u64,
+}
+
+/// An entry that differs between two snapshots
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotChange {
+    pub path: String,
+    pub dir: bool,
+    /// `None` when the entry was added
+    pub old_size: Option<u64>,
+    /// `None` when the entry was removed
+    pub new_size: Option<u64>,
+}
+
+impl SnapshotChange {
+    /// Bytes gained, negative when the entry shrank or went away
+    pub fn delta(&self) -> i128 {
+        i128::from(self.new_size.unwrap_or(0)) - i128::from(self.old_size.unwrap_or(0))
+    }
+}
+
+/// An entry whose permissions or owner differ between two snapshots
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OwnershipChange {
+    pub path: String,
+    pub dir: bool,
+    pub old_mode: u32,
+    pub new_mode: u32,
+    pub old_uid: u32,
+    pub new_uid: u32,
+    pub old_owner: String,
+    pub new_owner: String,
+}
+
+impl OwnershipChange {
+    /// Anyone may write to the entry now but could not before
+    pub fn became_world_writable(&self) -> bool {
+        self.new_mode & 0o002 != 0 && self.old_mode & 0o002 == 0
+    }
+
+    /// What changed, e.g. `mode 0644 -> 0600, owner alice -> bob`. An owner
+    /// whose name did not change is shown with both uids.
+    pub fn details(&self) -> String {
+        let mut details = Vec::new();
+        if self.old_mode != self.new_mode {
+            details.push(format!("mode {:04o} -> {:04o}", self.old_mode, self.new_mode));
+        }
+        if self.old_uid != self.new_uid {
+            if self.old_owner != self.new_owner {
+                details.push(format!("owner {} -> {}", self.old_owner, self.new_owner));
+            } else {
+                details.push(format!("owner {} (uid {} -> {})", self.new_owner, self.old_uid, self.new_uid));
+            }
+        }
+        details.join(", ")
+    }
+}
+
+/// How a tree changed between two snapshots. Added and removed entries
+/// are only listed at the top of a new or deleted subtree; each list is
+/// ordered by the size of the change, largest first.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SnapshotDiff {
+    pub added: Vec<SnapshotChange>,
+    pub removed: Vec<SnapshotChange>,
+    pub grown: Vec<SnapshotChange>,
+    pub shrunk: Vec<SnapshotChange>,
+    /// Entries in both snapshots with other permissions or another owner,
+    /// newly world-writable ones first
+    pub ownership: Vec<OwnershipChange>,
+    /// Change in total file size
+    pub net: i128,
+    pub files_added: usize,
+    pub files_removed: usize,
+}
+
+/// The topmost entries of subtrees found in `entries` but not in `other`,
+/// and how many files they hold
+pub(crate) fn one_sided<'a>(
+    entries: &'a [SnapshotEntry],
+    other: &HashMap<&str, &SnapshotEntry>,
+) -> (Vec<&'a SnapshotEntry>, usize) {
+    let missing: HashSet<&str> = entries
+        .iter()
+        .map(|e| e.path.as_str())
+        .filter(|path| !other.contains_key(path))
+        .collect();
+    let tops: Vec<&SnapshotEntry> = entries
+        .iter()
+        .filter(|e| missing.contains(e.path.as_str()))
+        .filter(|e| !Path::new(&e.path).ancestors().skip(1).any(|a| missing.contains(a.to_string_lossy().as_ref())))
+        .collect();
+    let files = entries.iter().filter(|e| !e.dir && missing.contains(e.path.as_str())).count();
+    (tops, files)
+}
+
+/// Compare two snapshots of the same tree entry by entry
+pub fn diff(old: &Snapshot, new: &Snapshot) -> SnapshotDiff {
+    let old_entries: HashMap<&str, &SnapshotEntry> = old.entries.iter().map(|e| (e.path.as_str(), e)).collect();
+    let new_entries: HashMap<&str, &SnapshotEntry> = new.entries.iter().map(|e| (e.path.as_str(), e)).collect();
+    let mut result = SnapshotDiff {
+        net: i128::from(new.total_size()) - i128::from(old.total_size()),
+        ..SnapshotDiff::default()
+    };
+
+    let (added, files_added) = one_sided(&new.entries, &old_entries);
+    let (removed, files_removed) = one_sided(&old.entries, &new_entries);
+    result.files_added = files_added;
+    result.files_removed = files_removed;
+    result.added = added
+        .into_iter()
+        .map(|e| SnapshotChange { path: e.path.clone(), dir: e.dir, old_size: None, new_size: Some(e.size) })
+        .collect();
+    result.removed = removed
+        .into_iter()
+        .map(|e| SnapshotChange { path: e.path.clone(), dir: e.dir, old_size: Some(e.size), new_size: None })
+        .collect();
+
+    for entry in &new.entries {
+        let Some(before) = old_entries.get(entry.path.as_str()) else {
+            continue;
+        };
+        let change = SnapshotChange {
+            path: entry.path.clone(),
+            dir: entry.dir,
+            old_size: Some(before.size),
+            new_size: Some(entry.size),
+        };
+        if entry.size > before.size {
+            result.grown.push(change);
+        } else if entry.size < before.size {
+            result.shrunk.push(change);
+        }
+        // Snapshots from before format version 2 carry no modes or owners
+        if let (Some(old_mode), Some(new_mode), Some(old_uid), Some(new_uid)) =
+            (before.mode, entry.mode, before.uid, entry.uid)
+        {
+            if old_mode != new_mode || old_uid != new_uid {
+                result.ownership.push(OwnershipChange {
+                    path: entry.path.clone(),
+                    dir: entry.dir,
+                    old_mode,
+                    new_mode,
+                    old_uid,
+                    new_uid,
+                    old_owner: before.owner.clone(),
+                    new_owner: entry.owner.clone(),
+                });
+            }
+        }
+    }
+
+    for list in [&mut result.added, &mut result.removed, &mut result.grown, &mut result.shrunk] {
+        list.sort_by(|a, b| b.delta().abs().cmp(&a.delta().abs()).then_with(|| a.path.cmp(&b.path)));
+    }
+    result
+        .ownership
+        .sort_by(|a, b| b.became_world_writable().cmp(&a.became_world_writable()).then_with(|| a.path.cmp(&b.path)));
+    result
+}
+
+/// A byte count with its sign, e.g. "+1.50 GiB" or "-300 B"
+pub fn format_delta(delta: i128) -> String {
+    let sign = if delta < 0 { '-' } else { '+' };
+    let bytes = u64::try_from(delta.unsigned_abs()).unwrap_or(u64::MAX);
+    format!("{}{}", sign, SizeUnit::auto_format_size(bytes))
+}
+
+/// Print what changed from `old` to `new`, at most `limit` entries per list
+pub fn show_snapshot_diff(old: &Snapshot, new: &Snapshot, limit: usize, color: bool) {
+    let result = diff(old, new);
+    println!(
+        "Comparing {} ({}) with {} ({})",
+        old.name, old.metadata.scanned_at, new.name, new.metadata.scanned_at
+    );
+    if old.root != new.root {
+        println!("Note: the snapshots are of different directories ({} and {})", old.root, new.root);
+    }
+    let net = format!(
+        "{} ({} -> {})",
+        format_delta(result.net),
+        SizeUnit::auto_format_size(old.total_size()),
+        SizeUnit::auto_format_size(new.total_size())
+    );
+    if color {
+        let net = if result.net > 0 { net.red() } else { net.green() };
+        println!("Net change: {}", net.bold());
+    } else {
+        println!("Net change: {}", net);
+    }
+    println!(
+        "Files: {} added, {} removed; entries: {} grown, {} shrunk",
+        group_digits(result.files_added as u64),
+        group_digits(result.files_removed as u64),
+        group_digits(result.grown.len() as u64),
+        group_digits(result.shrunk.len() as u64)
+    );
+
+    let sections = [
+        ("Added", &result.added),
+        ("Removed", &result.removed),
+        ("Grown", &result.grown),
+        ("Shrunk", &result.shrunk),
+    ];
+    for (title, changes) in sections {
+        if changes.is_empty() {
+            continue;
+        }
+        println!();
+        println!("{} ({}):", title, group_digits(changes.len() as u64));
+        for change in changes.iter().take(limit) {
+            let delta = format!("{:>12}", format_delta(change.delta()));
+            let name = if change.dir { format!("{}/", change.path) } else { change.path.clone() };
+            let sizes = match (change.old_size, change.new_size) {
+                (Some(old), Some(new)) => format!(
+                    " ({} -> {})",
+                    SizeUnit::auto_format_size(old),
+                    SizeUnit::auto_format_size(new)
+                ),
+                _ => String::new(),
+            };
+            if color {
+                let delta = if change.delta() > 0 { delta.red() } else { delta.green() };
+                println!("{}  {}{}", delta, name, sizes);
+            } else {
+                println!("{}  {}{}", delta, name, sizes);
+            }
+        }
+        if changes.len() > limit {
+            println!("  ... and {} more", group_digits((changes.len() - limit) as u64));
+        }
+    }
+    show_ownership_changes(&result.ownership, limit, color);
+}
+
+/// The "Permissions/owner changed" section of a snapshot diff
+fn show_ownership_changes(changes: &[OwnershipChange], limit: usize, color: bool) {
+    if changes.is_empty() {
+        return;
+    }
+    let world_writable = changes.iter().filter(|c| c.became_world_writable()).count();
+    println!();
+    if world_writable > 0 {
+        println!(
+            "Permissions/owner changed ({}, {} now world-writable):",
+            group_digits(changes.len() as u64),
+            group_digits(world_writable as u64)
+        );
+    } else {
+        println!("Permissions/owner changed ({}):", group_digits(changes.len() as u64));
+    }
+    for change in changes.iter().take(limit) {
+        let name = if change.dir { format!("{}/", change.path) } else { change.path.clone() };
+        let flag = if change.became_world_writable() { "  world-writable" } else { "" };
+        if color {
+            println!("  {}  {}{}", name, change.details(), flag.red().bold());
+        } else {
+            println!("  {}  {}{}", name, change.details(), flag);
+        }
+    }
+    if changes.len() > limit {
+        println!("  ... and {} more", group_digits((changes.len() - limit) as u64));
+    }
+}
+
+/// Directory holding the snapshots, following the XDG data directory
+/// convention
+pub fn snapshots_dir() -> Option<PathBuf> {
+    let data_home = std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".local/share")))?;
+    Some(data_home.join("filebyte").join("snapshots"))
+}
+
+/// Snapshot names become file names: letters, digits, `.`, `_` and `-`,
+/// not starting with a dot
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with('.')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+}
+
+/// Record `root` and everything below it. With `hash`, files are hashed on
+/// `threads` workers.
+pub fn take(root: &Path, name: &str, hash: bool, threads: usize, collect: &CollectOptions) -> io::Result<Snapshot> {
+    let root = fs::canonicalize(root)?;
+    let collect = CollectOptions {
+        du: true,
+        ..collect.clone()
+    };
+    let mut files = Vec::new();
+    walk_files(&root, &Filter::default(), &collect, &mut |file| files.push(file));
+
+    let hashes = if hash {
+        let regular: Vec<(u64, &String)> = files
+            .iter()
+            .filter(|f| f.kind == FileKind::File)
+            .map(|f| (f.size, &f.path))
+            .collect();
+        hash_in_parallel(&regular, threads, hash_file)
+    } else {
+        Vec::new()
+    };
+    let mut hashes = hashes.into_iter();
+
+    let entries = files
+        .into_iter()
+        .map(|file| {
+            let path = Path::new(&file.path);
+            let relative = path.strip_prefix(&root).unwrap_or(path);
+            SnapshotEntry {
+                path: relative.to_string_lossy().replace('\\', "/"),
+                size: file.size,
+                modified: file.modified,
+                dir: file.is_directory,
+                hash: if file.kind == FileKind::File { hashes.next().flatten() } else { None },
+                mode: file.mode,
+                uid: file.uid,
+                owner: file.owner,
+            }
+        })
+        .collect();
+
+    Ok(Snapshot {
+        name: name.to_string(),
+        root: root.to_string_lossy().to_string(),
+        metadata: ScanMetadata::current(),
+        hashed: hash,
+        entries,
+    })
+}
+
+/// Where the snapshot called `name` is stored in `dir`, if it exists
+pub fn find(dir: &Path, name: &str) -> Option<PathBuf> {
+    [format!("{}.json.gz", name), format!("{}.json", name)]
+        .into_iter()
+        .map(|file| dir.join(file))
+        .find(|path| path.exists())
+}
+
+/// Write a snapshot into `dir` and return the file it was written to. An
+/// existing snapshot of the same name is only replaced once the new one is
+/// completely written.
+pub fn save(snapshot: &Snapshot, dir: &Path) -> io::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let document = migrate::versioned(&SNAPSHOT, snapshot).map_err(io::Error::other)?;
+    let json = serde_json::to_vec(&document).map_err(io::Error::other)?;
+    // serde_json escapes control characters, so the comment holds no NUL
+    let summary = serde_json::to_vec(&snapshot.summary()).map_err(io::Error::other)?;
+    let path = dir.join(format!("{}.json.gz", snapshot.name));
+    let file = tempfile::NamedTempFile::new_in(dir)?;
+    let mut encoder = GzBuilder::new().comment(summary).write(file, Compression::default());
+    encoder.write_all(&json)?;
+    encoder.finish()?.persist(&path).map_err(|e| e.error)?;
+    // Only one file per name, replacing one stored uncompressed
+    let stale = dir.join(format!("{}.json", snapshot.name));
+    if stale.exists() {
+        fs::remove_file(stale)?;
+    }
+    Ok(path)
+}
+
+/// Read the snapshot at `path`
+pub fn load(path: &Path) -> Result<Snapshot, String> {
+    let contents = fs::read(path).map_err(|e| e.to_string())?;
+    let json = if path.extension().is_some_and(|ext| ext == "gz") {
+        let mut json = Vec::new();
+        MultiGzDecoder::new(&contents[..])
+            .read_to_end(&mut json)
+            .map_err(|e| format!("cannot decompress: {}", e))?;
+        json
+    } else {
+        contents
+    };
+    let document: Value = serde_json::from_slice(&json).map_err(|e| format!("not valid JSON: {}", e))?;
+    let document = migrate::upgrade(document, &SNAPSHOT).map_err(|e| e.to_string())?;
+    serde_json::from_value(document).map_err(|e| format!("unreadable snapshot: {}", e))
+}
+
+/// The summary of the snapshot at `path`, from its gzip header; snapshots
+/// saved before the header held one, or uncompressed, are read in full
+pub fn load_summary(path: &Path) -> Result<SnapshotSummary, String> {
+    if path.extension().is_some_and(|ext| ext == "gz") {
+        let file = fs::File::open(path).map_err(|e| e.to_string())?;
+        let decoder = GzDecoder::new(io::BufReader::new(file));
+        let comment = decoder.header().and_then(|header| header.comment());
+        if let Some(summary) = comment.and_then(|comment| serde_json::from_slice(comment).ok()) {
+            return Ok(summary);
+        }
+    }
+    load(path).map(|snapshot| snapshot.summary())
+}
+
+/// The summary of every snapshot in `dir` with its file, by name;
+/// unreadable ones with the reason
+pub fn list(dir: &Path) -> Vec<(String, PathBuf, Result<SnapshotSummary, String>)> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut snapshots: Vec<_> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            let name = file_name
+                .strip_suffix(".json.gz")
+                .or_else(|| file_name.strip_suffix(".json"))?
+                .to_string();
+            let path = entry.path();
+            let summary = load_summary(&path);
+            Some((name, path, summary))
+        })
+        .collect();
+    snapshots.sort_by(|a, b| a.0.cmp(&b.0));
+    snapshots
+}
+
+/// Print the saved snapshots by name
+pub fn show_snapshot_list(dir: &Path, color: bool) {
+    let snapshots = list(dir);
+    if snapshots.is_empty() {
+        println!("No snapshots yet. Save one with 'filebyte snapshot save <NAME> [PATH]'.");
+        return;
+    }
+    println!("Snapshots in {}:", dir.display());
+    println!("{}", "─".repeat(50));
+    for (name, path, snapshot) in &snapshots {
+        match snapshot {
+            Ok(summary) => {
+                let size = SizeUnit::auto_format_size(summary.total_size);
+                let details = format!("{}  {} entries, {}  {}", summary.scanned_at, summary.entries, size, summary.root);
+                if color {
+                    println!("{}  {}", name.cyan(), details);
+                } else {
+                    println!("{}  {}", name, details);
+                }
+            }
+            Err(e) => eprintln!("Error reading {}: {}", path.display(), e),
+        }
+    }
+}
+
+/// Print a snapshot's summary and the entries directly inside `subdir`
+/// (the root for an empty path), largest first
+pub fn show_snapshot(snapshot: &Snapshot, subdir: &str, color: bool) -> Result<(), String> {
+    let subdir = subdir.trim_matches('/');
+    let prefix = if subdir.is_empty() { String::new() } else { format!("{}/", subdir) };
+    if !subdir.is_empty() && !snapshot.entries.iter().any(|e| e.dir && e.path == subdir) {
+        return Err(format!("no directory '{}' in snapshot {}", subdir, snapshot.name));
+    }
+
+    let label = |label: &str| if color { label.magenta().to_string() } else { label.to_string() };
+    println!("{}: {}", label("Snapshot"), snapshot.name);
+    println!("{}: {}", label("Root"), snapshot.root);
+    println!("{}: {} ({})", label("Taken"), snapshot.metadata.scanned_at, snapshot.metadata.hostname);
+    println!(
+        "{}: {} ({} files, {} dirs)",
+        label("Entries"),
+        snapshot.entries.len(),
+        snapshot.file_count(),
+        snapshot.entries.len() - snapshot.file_count()
+    );
+    println!("{}: {}", label("Total Size"), SizeUnit::auto_format_size(snapshot.total_size()));
+    println!("{}: {}", label("Hashes"), if snapshot.hashed { "yes" } else { "no" });
+
+    let mut children: Vec<&SnapshotEntry> = snapshot
+        .entries
+        .iter()
+        .filter(|e| e.path.strip_prefix(&prefix).is_some_and(|rest| !rest.is_empty() && !rest.contains('/')))
+        .collect();
+    children.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.path.cmp(&b.path)));
+    println!();
+    println!("{}/", if subdir.is_empty() { "." } else { subdir });
+    println!("{}", "─".repeat(50));
+    for entry in children {
+        let size = format!("{:>10}", SizeUnit::auto_format_size(entry.size));
+        let name = &entry.path[prefix.len()..];
+        let name = if entry.dir { format!("{}/", name) } else { name.to_string() };
+        if color {
+            println!("{}  {}", size.cyan(), if entry.dir { name.blue().bold().to_string() } else { name });
+        } else {
+            println!("{}  {}", size, name);
+        }
+    }
+    Ok(())
+}
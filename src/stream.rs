@@ -0,0 +1,318 @@
+//! Memory-bounded listing for huge trees (`--stream`).
+//!
+//! Entries are printed, exported and tallied as the walk finds them instead of
+//! being gathered into one `Vec<FileInfo>`. When a sort is requested they go
+//! through an [`ExternalSorter`], which spills sorted runs to anonymous
+//! temporary files and merges them back, at most [`MAX_OPEN_RUNS`] at a time.
+
+use crate::collect::{compare_files, walk_files};
+use crate::display::{csv_writer, format_file, show_limit_note, show_type_counts, write_csv_row};
+use crate::types::{Clock, CollectOptions, CsvSchema, DisplayOptions, FileInfo, Filter, ScanMetadata, SortSpec};
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// Entries held in memory before a sorted run is spilled to disk
+pub const DEFAULT_CHUNK_SIZE: usize = 100_000;
+
+/// Sorted runs kept open at once. When a spill reaches this many, they are
+/// merged into a single run, so the number of file descriptors stays bounded
+/// however large the input.
+pub const MAX_OPEN_RUNS: usize = 64;
+
+/// Sorts more entries than fit in memory: full chunks are sorted and written
+/// to temporary files as JSON lines, then merged when the input is complete
+pub struct ExternalSorter {
+    sort: SortSpec,
+    chunk_size: usize,
+    chunk: Vec<FileInfo>,
+    runs: Vec<File>,
+    len: usize,
+}
+
+impl ExternalSorter {
+    pub fn new(sort: SortSpec, chunk_size: usize) -> Self {
+        ExternalSorter {
+            sort,
+            chunk_size: chunk_size.max(1),
+            chunk: Vec::new(),
+            runs: Vec::new(),
+            len: 0,
+        }
+    }
+
+    /// Number of entries pushed so far
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Number of sorted runs currently spilled to disk
+    pub fn open_runs(&self) -> usize {
+        self.runs.len()
+    }
+
+    pub fn push(&mut self, file: FileInfo) -> io::Result<()> {
+        self.chunk.push(file);
+        self.len += 1;
+        if self.chunk.len() >= self.chunk_size {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    fn spill(&mut self) -> io::Result<()> {
+        let sort = self.sort.clone();
+        self.chunk.sort_by(|a, b| compare_files(a, b, &sort));
+        let mut run = RunWriter::new()?;
+        for file in self.chunk.drain(..) {
+            run.write(&file)?;
+        }
+        self.runs.push(run.finish()?);
+        if self.runs.len() >= MAX_OPEN_RUNS {
+            // Earlier runs come first, so ties keep their input order
+            let mut merged = RunWriter::new()?;
+            merge_runs(std::mem::take(&mut self.runs), &self.sort, &mut |file| merged.write(&file))?;
+            self.runs.push(merged.finish()?);
+        }
+        Ok(())
+    }
+
+    /// Hand every entry to `visit` in sorted order
+    pub fn finish(mut self, visit: &mut dyn FnMut(FileInfo)) -> io::Result<()> {
+        if self.runs.is_empty() {
+            let sort = self.sort.clone();
+            self.chunk.sort_by(|a, b| compare_files(a, b, &sort));
+            self.chunk.drain(..).for_each(visit);
+            return Ok(());
+        }
+        if !self.chunk.is_empty() {
+            self.spill()?;
+        }
+        merge_runs(std::mem::take(&mut self.runs), &self.sort, &mut |file| {
+            visit(file);
+            Ok(())
+        })
+    }
+}
+
+/// A sorted run being written as JSON lines
+struct RunWriter(BufWriter<File>);
+
+impl RunWriter {
+    fn new() -> io::Result<Self> {
+        // Unlinked as soon as it is created, so no other user can open or
+        // replace a run, and the OS removes it however filebyte exits
+        Ok(RunWriter(BufWriter::new(tempfile::tempfile()?)))
+    }
+
+    fn write(&mut self, file: &FileInfo) -> io::Result<()> {
+        serde_json::to_writer(&mut self.0, file)?;
+        self.0.write_all(b"\n")
+    }
+
+    /// The finished run, rewound for reading
+    fn finish(self) -> io::Result<File> {
+        let mut run = self.0.into_inner().map_err(|e| e.into_error())?;
+        run.seek(SeekFrom::Start(0))?;
+        Ok(run)
+    }
+}
+
+/// Merge sorted runs into one sorted sequence, stopping at the first error
+fn merge_runs(
+    runs: Vec<File>,
+    sort: &SortSpec,
+    visit: &mut dyn FnMut(FileInfo) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut readers: Vec<_> = runs.into_iter().map(|run| BufReader::new(run).lines()).collect();
+    let mut heap = BinaryHeap::new();
+    for (run, reader) in readers.iter_mut().enumerate() {
+        if let Some(file) = read_entry(reader)? {
+            heap.push(Head { file, run, sort });
+        }
+    }
+    while let Some(Head { file, run, .. }) = heap.pop() {
+        if let Some(next) = read_entry(&mut readers[run])? {
+            heap.push(Head { file: next, run, sort });
+        }
+        visit(file)?;
+    }
+    Ok(())
+}
+
+fn read_entry(lines: &mut io::Lines<BufReader<File>>) -> io::Result<Option<FileInfo>> {
+    match lines.next() {
+        Some(line) => Ok(Some(serde_json::from_str(&line?)?)),
+        None => Ok(None),
+    }
+}
+
+/// The next entry of one sorted run, ordered so the max-heap pops the
+/// smallest entry first
+struct Head<'a> {
+    file: FileInfo,
+    run: usize,
+    sort: &'a SortSpec,
+}
+
+impl Ord for Head<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        compare_files(&other.file, &self.file, self.sort).then_with(|| other.run.cmp(&self.run))
+    }
+}
+
+impl PartialOrd for Head<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Head<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Head<'_> {}
+
+/// Writes `--export` files one entry at a time. JSON exports keep the
+/// `{"metadata": ..., "files": [...]}` layout of regular exports.
+enum StreamExporter {
+    Json { writer: BufWriter<File>, first: bool },
+    Csv(Box<csv::Writer<File>>, CsvSchema),
+}
+
+impl StreamExporter {
+    fn create(path: &str, schema: CsvSchema) -> io::Result<Option<StreamExporter>> {
+        if path.ends_with(".json") {
+            let mut writer = BufWriter::new(File::create(path)?);
+            let metadata = serde_json::to_string(&ScanMetadata::current())?;
+            write!(writer, "{{\"metadata\":{},\"files\":[", metadata)?;
+            Ok(Some(StreamExporter::Json { writer, first: true }))
+        } else if path.ends_with(".csv") {
+            Ok(Some(StreamExporter::Csv(Box::new(csv_writer(path, schema)?), schema)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn write(&mut self, file: &FileInfo) -> io::Result<()> {
+        match self {
+            StreamExporter::Json { writer, first } => {
+                if !*first {
+                    writer.write_all(b",")?;
+                }
+                *first = false;
+                writer.write_all(b"\n")?;
+                serde_json::to_writer(&mut *writer, file)?;
+                Ok(())
+            }
+            StreamExporter::Csv(writer, schema) => {
+                write_csv_row(writer, file, *schema).map_err(io::Error::other)
+            }
+        }
+    }
+
+    fn finish(self) -> io::Result<()> {
+        match self {
+            StreamExporter::Json { mut writer, .. } => {
+                writer.write_all(b"\n]}\n")?;
+                writer.flush()
+            }
+            StreamExporter::Csv(mut writer, _) => writer.flush(),
+        }
+    }
+}
+
+/// List a directory recursively without holding the whole listing in
+/// memory, then show file type statistics. Unsorted output is in walk order.
+pub fn stream_files(
+    dir: &Path,
+    filter: &Filter,
+    sort: &SortSpec,
+    collect: &CollectOptions,
+    options: &DisplayOptions,
+    export_path: Option<&String>,
+) {
+    let color = options.color;
+    let now = Clock::active().now();
+    let mut exporter = match export_path.map(|path| StreamExporter::create(path, options.csv_schema)) {
+        Some(Ok(exporter)) => exporter,
+        Some(Err(e)) => {
+            eprintln!("Failed to write to {}: {}", export_path.unwrap(), e);
+            None
+        }
+        None => None,
+    };
+    let mut type_counts: HashMap<String, u64> = HashMap::new();
+    let mut total_files = 0u64;
+    let mut entries = 0u64;
+    let mut failed = None;
+
+    let mut tally = |file: &FileInfo| {
+        entries += 1;
+        if !file.is_directory {
+            *type_counts.entry(file.file_type.clone()).or_insert(0) += 1;
+            total_files += 1;
+        }
+    };
+    let mut show = |file: FileInfo| {
+        println!("{}", format_file(&file, options, now));
+        if let Some(exporter) = exporter.as_mut() {
+            if let Err(e) = exporter.write(&file) {
+                failed.get_or_insert(e);
+            }
+        }
+    };
+
+    if sort.is_active() {
+        let mut sorter = ExternalSorter::new(sort.clone(), DEFAULT_CHUNK_SIZE);
+        walk_files(dir, filter, collect, &mut |file| {
+            tally(&file);
+            // Nothing can be listed without the runs, so give up on the walk
+            if let Err(e) = sorter.push(file) {
+                eprintln!("Error: could not write sort runs to {}: {}", std::env::temp_dir().display(), e);
+                crate::perf::exit(1);
+            }
+        });
+
+        let limit = sort.top.unwrap_or(usize::MAX);
+        if limit < sorter.len() {
+            show_limit_note(limit, sorter.len(), color);
+        }
+        let mut shown = 0;
+        let merged = sorter.finish(&mut |file| {
+            if shown < limit {
+                show(file);
+                shown += 1;
+            }
+        });
+        if let Err(e) = merged {
+            eprintln!("Error: could not read sort runs: {}", e);
+            crate::perf::exit(1);
+        }
+    } else {
+        walk_files(dir, filter, collect, &mut |file| {
+            tally(&file);
+            show(file);
+        });
+    }
+
+    if let Some(exporter) = exporter {
+        let path = export_path.unwrap();
+        match failed.map_or_else(|| exporter.finish(), Err) {
+            Ok(()) => println!("Results exported to {}", path),
+            Err(e) => eprintln!("Failed to write to {}: {}", path, e),
+        }
+    }
+    if entries == 0 {
+        println!("No files found.");
+    }
+    show_type_counts(&type_counts, total_files, color);
+}
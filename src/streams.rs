@@ -0,0 +1,262 @@
+//! NTFS alternate data streams (`-f` and `--ads-report`).
+//!
+//! A file on NTFS can carry named streams next to its main data. Explorer,
+//! `dir` and file sizes only count the main stream, so a few gigabytes or an
+//! executable can sit in `notes.txt:payload` unseen. Windows itself adds
+//! small ones, such as the `Zone.Identifier` that marks downloads; those
+//! are counted but not listed.
+//!
+//! Streams are enumerated with `FindFirstStreamW`, so this only does
+//! anything on Windows; elsewhere [`alternate_streams`] finds none.
+
+use crate::types::{FileInfo, FileKind, SizeUnit};
+use crate::utils::group_digits;
+use colored::Colorize;
+use std::io;
+use std::path::Path;
+
+/// Whether streams can be listed on this platform
+pub const SUPPORTED: bool = cfg!(windows);
+
+/// Streams this size or larger are flagged
+pub const LARGE_STREAM: u64 = 1024 * 1024;
+
+/// Streams Windows, browsers and sync clients write as a matter of course
+const KNOWN_STREAMS: &[&str] = &[
+    "Zone.Identifier",
+    "SmartScreen",
+    "encryptable",
+    "favicon",
+    "ms-properties",
+    "OECustomProperty",
+    "AFP_AfpInfo",
+    "AFP_Resource",
+    "com.dropbox.attributes",
+    "com.dropbox.attrs",
+];
+
+/// A named stream of a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataStream {
+    /// Without the leading `:` and the `:$DATA` type
+    pub name: String,
+    pub size: u64,
+    /// Why the stream stands out, see [`concerns`]
+    pub concerns: Vec<&'static str>,
+}
+
+impl DataStream {
+    /// Written by Windows or a well-known tool, and unremarkable
+    pub fn is_routine(&self) -> bool {
+        self.concerns.is_empty() && KNOWN_STREAMS.iter().any(|known| known.eq_ignore_ascii_case(&self.name))
+    }
+}
+
+/// The name of a stream as `FindFirstStreamW` reports it (`:name:$DATA`);
+/// `None` for the main stream, `::$DATA`
+pub fn stream_name(raw: &str) -> Option<&str> {
+    let name = raw.strip_prefix(':')?;
+    let name = name.strip_suffix(":$DATA").unwrap_or(name);
+    (!name.is_empty()).then_some(name)
+}
+
+/// What makes a stream of `size` bytes starting with `head` worth a look:
+/// `large` from [`LARGE_STREAM`] up, `executable` for a Windows program
+/// and `script` for a `#!` line
+pub fn concerns(size: u64, head: &[u8]) -> Vec<&'static str> {
+    let mut concerns = Vec::new();
+    if size >= LARGE_STREAM {
+        concerns.push("large");
+    }
+    if head.starts_with(b"MZ") {
+        concerns.push("executable");
+    } else if head.starts_with(b"#!") {
+        concerns.push("script");
+    }
+    concerns
+}
+
+#[cfg(windows)]
+mod ffi {
+    use std::ffi::c_void;
+
+    pub const FIND_STREAM_INFO_STANDARD: i32 = 0;
+    pub const ERROR_HANDLE_EOF: i32 = 38;
+
+    /// `WIN32_FIND_STREAM_DATA`
+    #[repr(C)]
+    pub struct FindStreamData {
+        pub stream_size: i64,
+        pub stream_name: [u16; 260 + 36],
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        pub fn FindFirstStreamW(name: *const u16, level: i32, data: *mut FindStreamData, flags: u32) -> *mut c_void;
+        pub fn FindNextStreamW(handle: *mut c_void, data: *mut FindStreamData) -> i32;
+        pub fn FindClose(handle: *mut c_void) -> i32;
+    }
+}
+
+/// The named streams of `path`, with their sizes and [`concerns`]
+#[cfg(windows)]
+pub fn alternate_streams(path: &Path) -> io::Result<Vec<DataStream>> {
+    use std::io::Read;
+    use std::os::windows::ffi::OsStrExt;
+
+    let wide: Vec<u16> = path.as_os_str().encode_wide().chain([0]).collect();
+    let mut data = ffi::FindStreamData {
+        stream_size: 0,
+        stream_name: [0; 296],
+    };
+    // SAFETY: `wide` is NUL-terminated and `data` is a valid out pointer
+    let handle = unsafe { ffi::FindFirstStreamW(wide.as_ptr(), ffi::FIND_STREAM_INFO_STANDARD, &mut data, 0) };
+    if handle as isize == -1 {
+        let error = io::Error::last_os_error();
+        return match error.raw_os_error() {
+            Some(ffi::ERROR_HANDLE_EOF) => Ok(Vec::new()),
+            _ => Err(error),
+        };
+    }
+    let mut raw_names = Vec::new();
+    loop {
+        let len = data.stream_name.iter().position(|&c| c == 0).unwrap_or(data.stream_name.len());
+        raw_names.push((String::from_utf16_lossy(&data.stream_name[..len]), data.stream_size as u64));
+        // SAFETY: `handle` came from FindFirstStreamW and is not closed yet
+        if unsafe { ffi::FindNextStreamW(handle, &mut data) } == 0 {
+            break;
+        }
+    }
+    // SAFETY: as above; the handle is not used afterwards
+    unsafe { ffi::FindClose(handle) };
+
+    let mut streams = Vec::new();
+    for (raw, size) in &raw_names {
+        let Some(name) = stream_name(raw) else {
+            continue;
+        };
+        let mut head = Vec::new();
+        let stream = format!("{}:{}", path.display(), name);
+        if let Ok(file) = std::fs::File::open(stream) {
+            // Unreadable streams are still listed, only without a verdict on their contents
+            let _ = file.take(2).read_to_end(&mut head);
+        }
+        streams.push(DataStream {
+            name: name.to_string(),
+            size: *size,
+            concerns: concerns(*size, &head),
+        });
+    }
+    Ok(streams)
+}
+
+#[cfg(not(windows))]
+pub fn alternate_streams(_path: &Path) -> io::Result<Vec<DataStream>> {
+    Ok(Vec::new())
+}
+
+/// A file's streams spelled out for `-f`, e.g. `Zone.Identifier (26 B)`;
+/// `None` where there are none to list
+pub fn stream_summary(path: &Path) -> Option<String> {
+    if !SUPPORTED {
+        return None;
+    }
+    let streams = alternate_streams(path).ok()?;
+    if streams.is_empty() {
+        return Some("none".to_string());
+    }
+    let described: Vec<String> = streams
+        .iter()
+        .map(|stream| {
+            let mut description = format!("{} ({})", stream.name, SizeUnit::auto_format_size(stream.size));
+            if !stream.concerns.is_empty() {
+                description.push_str(&format!(" [{}]", stream.concerns.join(", ")));
+            }
+            description
+        })
+        .collect();
+    Some(described.join(", "))
+}
+
+/// List the files carrying streams other than routine ones, flagging the
+/// large, executable and script ones; returns whether none were flagged.
+/// Files on volumes without streams (FAT, network shares) are skipped.
+pub fn show_ads_report(files: &[FileInfo], color: bool) -> bool {
+    println!("Alternate Data Streams:");
+    println!("{}", "─".repeat(50));
+    let (mut count, mut carriers, mut bytes, mut flagged) = (0u64, 0u64, 0u64, 0u64);
+    for file in files.iter().filter(|f| f.kind == FileKind::File) {
+        let Ok(streams) = alternate_streams(Path::new(&file.path)) else {
+            continue;
+        };
+        if streams.is_empty() {
+            continue;
+        }
+        count += streams.len() as u64;
+        carriers += 1;
+        bytes += streams.iter().map(|s| s.size).sum::<u64>();
+        if streams.iter().all(DataStream::is_routine) {
+            continue;
+        }
+        if color {
+            println!("{}", file.path.bold());
+        } else {
+            println!("{}", file.path);
+        }
+        for stream in streams.iter().filter(|s| !s.is_routine()) {
+            let size = SizeUnit::auto_format_size(stream.size);
+            if stream.concerns.is_empty() {
+                println!("  :{}  {}", stream.name, size);
+                continue;
+            }
+            flagged += 1;
+            let concerns = format!("[{}]", stream.concerns.join(", "));
+            if color {
+                println!("  :{}  {}  {}", stream.name.red(), size, concerns.red().bold());
+            } else {
+                println!("  :{}  {}  {}", stream.name, size, concerns);
+            }
+        }
+    }
+
+    if count == 0 {
+        println!("No alternate data streams in {} files.", group_digits(files.len() as u64));
+        return true;
+    }
+    println!(
+        "\n{} streams on {} files, {} not counted in file sizes",
+        group_digits(count),
+        group_digits(carriers),
+        SizeUnit::auto_format_size(bytes)
+    );
+    if flagged > 0 {
+        let warning = format!(
+            "{} suspicious streams: {} or more, or holding a program or script",
+            group_digits(flagged),
+            SizeUnit::auto_format_size(LARGE_STREAM)
+        );
+        if color {
+            println!("{}", warning.red().bold());
+        } else {
+            println!("{}", warning);
+        }
+    }
+    flagged == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn alternate_stream_names_and_concerns() {
+        assert_eq!(stream_name("::$DATA"), None);
+        assert_eq!(stream_name(":Zone.Identifier:$DATA"), Some("Zone.Identifier"));
+        assert_eq!(stream_name(":a:b:$DATA"), Some("a:b"));
+        assert_eq!(stream_name("junk"), None);
+
+        assert!(concerns(26, b"[Z").is_empty());
+        assert_eq!(concerns(LARGE_STREAM, b"MZ"), vec!["large", "executable"]);
+        assert_eq!(concerns(LARGE_STREAM - 1, b"#!"), vec!["script"]);
+    }
+}
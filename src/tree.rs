@@ -1,50 +1,520 @@
+//! `--tree`: the directory is read into a [`TreeNode`] hierarchy in one walk,
+//! which also totals directory sizes bottom-up, and then rendered.
+//!
+//! The walk applies the same filters as listings: `--excluding` and skipped
+//! paths prune whole subtrees, while with `--search` or `--type` only
+//! matching entries and the directories leading to them are kept.
+//!
+//! Symlinks are shown as `name -> target` and not followed unless asked to;
+//! a followed link is never descended into when it leads back to one of its
+//! own ancestors.
+//!
+//! Inside a git repository entries carry their `git status --short` marker,
+//! see [`crate::git`]; ignored ones are dimmed.
+//!
+//! With `--expand-archives` zip, tar and gzip files get their members as
+//! children, marked with the archive format and drawn in magenta. The
+//! members obey the same depth limit and filters, but stay out of directory
+//! totals, which keep counting what is on disk.
+
+use crate::archive::{list_archive, ArchiveMember};
+use crate::collect::{matches_search, skipped_paths};
+use crate::display::{age_color, size_color};
+use crate::git::{GitMark, GitStatus};
+use crate::perf;
+use crate::types::{Clock, CollectOptions, DisplayOptions, FileKind, Filter, ScanMetadata, TreeCharset, TreeOptions};
+use crate::utils::group_digits;
 use colored::Colorize;
+use regex::Regex;
+use serde::Serialize;
+use std::collections::BTreeMap;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// One entry of a directory tree. The JSON form nests `children` arrays,
+/// the layout hierarchy views such as treemaps and sunbursts read.
+#[derive(Debug, Clone, Serialize)]
+pub struct TreeNode {
+    pub name: String,
+    #[serde(skip)]
+    pub path: PathBuf,
+    /// The entry itself; a symlink is `Symlink` whatever it points to
+    #[serde(rename = "type")]
+    pub kind: FileKind,
+    /// Where a symlink points, as stored in the link
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target: Option<PathBuf>,
+    /// A file's size, or the total size of the files below a directory
+    pub size: u64,
+    #[serde(skip)]
+    pub modified: Option<SystemTime>,
+    /// Non-directories and directories anywhere below this one
+    pub files: u64,
+    pub dirs: u64,
+    /// Sorted by name; empty for anything but directories and followed
+    /// links to them, and for directories at the depth limit, see `truncated`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<TreeNode>,
+    /// Below the depth limit: the entries were counted but not kept
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
+    /// The entry itself passes the search and type filters
+    #[serde(skip)]
+    pub matched: bool,
+    /// An archive whose members are its children; its `size` is still its
+    /// own, and `files` and `dirs` count the members
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub archive: Option<ArchiveNode>,
+    /// Inside an archive rather than on disk
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub packed: bool,
+}
+
+/// How an archive in the tree was read
+#[derive(Debug, Clone, Serialize)]
+pub struct ArchiveNode {
+    /// `zip`, `tar`, `tar.gz` and so on
+    pub format: &'static str,
+    /// The archive ends early or fails to decompress, so members may be
+    /// missing
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub damaged: bool,
+}
+
+/// JSON layout of `--tree --export`
+#[derive(Debug, Serialize)]
+pub struct TreeExport<'a> {
+    pub metadata: ScanMetadata,
+    pub root: &'a TreeNode,
+}
 
-/// Print a directory tree structure
-pub fn print_tree(path: &Path, prefix: &str, color: bool) {
-    let entries = match fs::read_dir(path) {
-        Ok(entries) => entries.collect::<Vec<_>>(),
-        Err(e) => {
-            eprintln!("Error reading directory {}: {}", path.display(), e);
-            return;
+/// What a tree walk leaves out and where it descends, see the module docs
+pub struct TreeWalk<'a> {
+    filter: &'a Filter,
+    excluding: Option<Regex>,
+    skipped: Vec<PathBuf>,
+    follow_symlinks: bool,
+    expand_archives: bool,
+}
+
+impl<'a> TreeWalk<'a> {
+    pub fn new(dir: &Path, filter: &'a Filter, collect: &CollectOptions, tree: &TreeOptions) -> Self {
+        TreeWalk {
+            filter,
+            excluding: filter.excluding_pattern.as_ref().and_then(|p| Regex::new(p).ok()),
+            skipped: skipped_paths(dir, collect),
+            follow_symlinks: tree.follow_symlinks,
+            expand_archives: tree.expand_archives,
         }
+    }
+
+    fn is_excluded(&self, name: &str) -> bool {
+        self.excluding.as_ref().is_some_and(|regex| regex.is_match(name))
+    }
+
+    /// Whether only matching entries are kept
+    fn is_selective(&self) -> bool {
+        self.filter.search_pattern.is_some() || !self.filter.kinds.is_empty()
+    }
+}
+
+/// Read `path` and everything below it that `walk` keeps, keeping nodes
+/// down to `max_depth` levels (all of them for `None`). Deeper entries still
+/// count towards sizes and totals. Unreadable directories are reported on
+/// stderr and kept as empty nodes.
+pub fn build_tree(path: &Path, max_depth: Option<usize>, walk: &TreeWalk) -> TreeNode {
+    build_node(path, max_depth, walk, &mut Vec::new())
+}
+
+/// `ancestors` holds the canonical paths of the directories being read
+fn build_node(path: &Path, max_depth: Option<usize>, walk: &TreeWalk, ancestors: &mut Vec<PathBuf>) -> TreeNode {
+    let metadata = fs::symlink_metadata(path);
+    perf::count_metadata(&metadata);
+    let metadata = metadata.ok();
+    let kind = metadata
+        .as_ref()
+        .map_or(FileKind::File, |m| FileKind::from_file_type(m.file_type()));
+    let name = path.file_name().unwrap_or(path.as_os_str()).to_string_lossy().to_string();
+    let mut node = TreeNode {
+        matched: matches_search(&name, walk.filter.search_pattern.as_ref()) && walk.filter.matches_kind(kind),
+        name,
+        path: path.to_path_buf(),
+        kind,
+        target: fs::read_link(path).ok(),
+        size: 0,
+        modified: metadata.as_ref().and_then(|m| m.modified().ok()),
+        files: 0,
+        dirs: 0,
+        children: Vec::new(),
+        truncated: false,
+        archive: None,
+        packed: false,
     };
 
-    for (i, entry) in entries.iter().enumerate() {
-        let entry = match entry {
-            Ok(entry) => entry,
-            Err(e) => {
-                eprintln!("Error reading entry: {}", e);
-                continue;
+    let descend = match kind {
+        FileKind::Dir => true,
+        FileKind::Symlink => walk.follow_symlinks && path.is_dir(),
+        _ => false,
+    };
+    let canonical = if descend { fs::canonicalize(path).ok() } else { None };
+    match canonical {
+        Some(canonical) if !ancestors.contains(&canonical) => {
+            ancestors.push(canonical);
+            let listing = fs::read_dir(path);
+            perf::count_listing(&listing);
+            let entries = match listing {
+                Ok(entries) => entries,
+                Err(e) => {
+                    eprintln!("Error reading directory {}: {}", path.display(), e);
+                    ancestors.pop();
+                    return node;
+                }
+            };
+            let child_depth = max_depth.map(|depth| depth.saturating_sub(1));
+            for entry in entries {
+                match entry {
+                    Ok(entry) => {
+                        let entry_path = entry.path();
+                        if walk.is_excluded(&entry.file_name().to_string_lossy()) || walk.skipped.contains(&entry_path) {
+                            continue;
+                        }
+                        let child = build_node(&entry_path, child_depth, walk, ancestors);
+                        adopt(&mut node, child, walk);
+                    }
+                    Err(e) => eprintln!("Error reading entry: {}", e),
+                }
             }
-        };
+            finish(&mut node, max_depth);
+            ancestors.pop();
+        }
+        // A link back to a directory being read is shown but not descended
+        Some(_) => {}
+        None if kind == FileKind::File => {
+            node.size = metadata.map_or(0, |m| m.len());
+            if walk.expand_archives {
+                expand_archive(&mut node, max_depth, walk);
+            }
+        }
+        None => {}
+    }
+    node
+}
 
-        let path = entry.path();
-        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
-        let is_last = i == entries.len() - 1;
-        let connector = if is_last { "└── " } else { "├── " };
-        let new_prefix = format!("{}{}", prefix, if is_last { "    " } else { "│   " });
+/// Add `child` to `node`'s children and totals, unless the search and type
+/// filters leave nothing of it. Members of an archive add to neither its
+/// size nor the counts of the directories above it.
+fn adopt(node: &mut TreeNode, child: TreeNode, walk: &TreeWalk) {
+    let has_matches = child.files + child.dirs > 0;
+    if walk.is_selective() && !child.matched && !has_matches {
+        return;
+    }
+    if node.archive.is_none() {
+        node.size += child.size;
+    }
+    if child.archive.is_none() {
+        node.files += child.files;
+        node.dirs += child.dirs;
+    }
+    if child.kind == FileKind::Dir {
+        node.dirs += 1;
+    } else {
+        node.files += 1;
+    }
+    node.children.push(child);
+}
 
-        let display_name = if path.is_dir() {
-            if color {
-                format!("{}{}", connector, file_name.blue().bold())
-            } else {
-                format!("{}{}", connector, file_name)
-            }
-        } else {
-            if color {
-                format!("{}{}", connector, file_name)
+/// Apply the depth limit to a node whose children are all read
+fn finish(node: &mut TreeNode, max_depth: Option<usize>) {
+    if max_depth == Some(0) {
+        node.truncated = !node.children.is_empty();
+        node.children.clear();
+    }
+    node.children.sort_by(|a, b| a.name.cmp(&b.name));
+}
+
+/// Archive members grouped by directory. Directories a zip or tar only
+/// implies through its member paths get an entry too.
+#[derive(Default)]
+struct PackedDir {
+    is_dir: bool,
+    size: u64,
+    children: BTreeMap<String, PackedDir>,
+}
+
+impl PackedDir {
+    fn insert(&mut self, member: &ArchiveMember) {
+        let mut components = member.path.split('/').filter(|c| !c.is_empty() && *c != ".").peekable();
+        let mut dir = self;
+        while let Some(component) = components.next() {
+            dir = dir.children.entry(component.to_string()).or_default();
+            if components.peek().is_some() || member.is_dir {
+                dir.is_dir = true;
             } else {
-                format!("{}{}", connector, file_name)
+                dir.size = member.size;
             }
+        }
+    }
+}
+
+/// Give an archive file its members as children; files that are not zip,
+/// tar, gzip or xz archives, or cannot be read, are left alone
+fn expand_archive(node: &mut TreeNode, max_depth: Option<usize>, walk: &TreeWalk) {
+    let Ok(Some(listing)) = list_archive(&node.path) else {
+        return;
+    };
+    node.archive = Some(ArchiveNode {
+        format: listing.format.name(),
+        damaged: listing.truncated,
+    });
+    let mut members = PackedDir::default();
+    for member in &listing.members {
+        members.insert(member);
+    }
+    adopt_packed(node, members, max_depth, walk);
+}
+
+fn adopt_packed(node: &mut TreeNode, dir: PackedDir, max_depth: Option<usize>, walk: &TreeWalk) {
+    let child_depth = max_depth.map(|depth| depth.saturating_sub(1));
+    for (name, entry) in dir.children {
+        if walk.is_excluded(&name) {
+            continue;
+        }
+        let kind = if entry.is_dir { FileKind::Dir } else { FileKind::File };
+        let mut child = TreeNode {
+            matched: matches_search(&name, walk.filter.search_pattern.as_ref()) && walk.filter.matches_kind(kind),
+            path: node.path.join(&name),
+            name,
+            kind,
+            target: None,
+            size: entry.size,
+            modified: None,
+            files: 0,
+            dirs: 0,
+            children: Vec::new(),
+            truncated: false,
+            archive: None,
+            packed: true,
         };
+        adopt_packed(&mut child, entry, child_depth, walk);
+        adopt(node, child, walk);
+    }
+    finish(node, max_depth);
+}
+
+/// Children `--tree` prints per directory unless told otherwise
+pub const DEFAULT_MAX_ENTRIES: usize = 1000;
+
+/// Print a directory tree structure. File names are tinted by size with
+/// `size_colors`, or by how long ago they were modified with `age_colors`;
+/// `show_size` adds each file's size and each directory's total, and
+/// `summaries` each directory's entry counts along with its total.
+pub fn print_tree(path: &Path, filter: &Filter, collect: &CollectOptions, options: &DisplayOptions, tree: &TreeOptions) {
+    let walk = TreeWalk::new(path, filter, collect, tree);
+    let root = build_tree(path, tree.max_depth, &walk);
+    if tree.summaries {
+        println!("{} {}", path.display(), summary(&root, options));
+    } else if options.show_size {
+        let size = options.size_unit.format_size(root.size);
+        if options.color {
+            println!("{} ({})", path.display(), size.cyan());
+        } else {
+            println!("{} ({})", path.display(), size);
+        }
+    } else {
+        println!("{}", path.display());
+    }
+    // Matches stand out from the directories shown only as their context
+    let style = TreeStyle {
+        highlight: walk.is_selective(),
+        summaries: tree.summaries,
+        root: path.to_path_buf(),
+        git: GitStatus::for_dir(path),
+        charset: tree.charset,
+        max_entries: tree.max_entries,
+    };
+    print_children(&root, "", options, &style);
+
+    if let Some(filename) = &tree.export {
+        export_tree(&root, filename);
+    }
+}
+
+/// Write a tree as nested JSON, see [`TreeExport`]
+pub fn export_tree(root: &TreeNode, filename: &str) {
+    if !filename.ends_with(".json") {
+        eprintln!("Unsupported export format for {}: --tree exports to .json", filename);
+        return;
+    }
+    let export = TreeExport {
+        metadata: ScanMetadata::current(),
+        root,
+    };
+    let result = serde_json::to_string_pretty(&export)
+        .map_err(std::io::Error::other)
+        .and_then(|json| fs::write(filename, json));
+    match result {
+        Ok(()) => println!("Results exported to {}", filename),
+        Err(e) => eprintln!("Failed to write to {}: {}", filename, e),
+    }
+}
+
+/// Per-line choices made once for the whole tree
+struct TreeStyle {
+    /// Set the names that passed `--search` or `--type` apart
+    highlight: bool,
+    summaries: bool,
+    root: PathBuf,
+    git: Option<GitStatus>,
+    charset: TreeCharset,
+    max_entries: Option<usize>,
+}
+
+/// Connectors for an entry and the indent below it: (branch, last branch,
+/// continuing line, blank)
+fn connectors(charset: TreeCharset) -> [&'static str; 4] {
+    match charset {
+        TreeCharset::Utf8 => ["├── ", "└── ", "│   ", "    "],
+        TreeCharset::Ascii => ["|-- ", "`-- ", "|   ", "    "],
+    }
+}
 
-        println!("{}{}", prefix, display_name);
+fn print_children(node: &TreeNode, prefix: &str, options: &DisplayOptions, style: &TreeStyle) {
+    let [branch, last_branch, line, blank] = connectors(style.charset);
+    let shown = style.max_entries.unwrap_or(usize::MAX).min(node.children.len());
+    let rest = &node.children[shown..];
+    for (i, child) in node.children[..shown].iter().enumerate() {
+        let is_last = i == node.children.len() - 1;
+        let connector = if is_last { last_branch } else { branch };
+        println!("{}{}{}", prefix, connector, format_node(child, options, style));
+
+        if !child.children.is_empty() {
+            let new_prefix = format!("{}{}", prefix, if is_last { blank } else { line });
+            print_children(child, &new_prefix, options, style);
+        }
+    }
 
-        if path.is_dir() {
-            print_tree(&path, &new_prefix, color);
+    if !rest.is_empty() {
+        let ellipsis = if style.charset == TreeCharset::Utf8 { "…" } else { "..." };
+        let size = options.size_unit.format_size(rest.iter().map(|child| child.size).sum());
+        let more = format!("{} and {} more ({})", ellipsis, group_digits(rest.len() as u64), size);
+        if options.color {
+            println!("{}{}{}", prefix, last_branch, more.dimmed());
+        } else {
+            println!("{}{}{}", prefix, last_branch, more);
+        }
+    }
+}
+
+fn format_node(node: &TreeNode, options: &DisplayOptions, style: &TreeStyle) -> String {
+    let color = options.color;
+    let name = &node.name;
+    // `exists` follows the link, so it fails for dangling ones
+    let dangling = node.kind == FileKind::Symlink && !node.path.exists();
+    let git_mark = style
+        .git
+        .as_ref()
+        .filter(|_| !node.packed)
+        .zip(node.path.strip_prefix(&style.root).ok())
+        .and_then(|(git, relative)| git.mark(relative));
+    let mut line = if color && style.highlight && node.matched {
+        name.yellow().bold().to_string()
+    } else if color && git_mark == Some(&GitMark::Ignored) {
+        name.dimmed().to_string()
+    } else if color && dangling {
+        name.red().to_string()
+    } else if color && node.packed {
+        name.magenta().to_string()
+    } else if node.kind == FileKind::Symlink {
+        if color {
+            name.cyan().to_string()
+        } else {
+            name.to_string()
         }
+    } else if node.kind == FileKind::Dir {
+        if color {
+            name.blue().bold().to_string()
+        } else {
+            name.to_string()
+        }
+    } else if color && options.size_colors {
+        name.color(size_color(node.size)).to_string()
+    } else if color && options.age_colors {
+        match node
+            .modified
+            .and_then(|modified| Clock::active().now().duration_since(modified).ok())
+        {
+            Some(age) => name.color(age_color(age.as_secs())).to_string(),
+            None => name.to_string(),
+        }
+    } else {
+        name.to_string()
+    };
+    if let Some(target) = &node.target {
+        let target = target.display().to_string();
+        if color && dangling {
+            line.push_str(&format!(" -> {}", target.red()));
+        } else {
+            line.push_str(&format!(" -> {}", target));
+        }
+    }
+    if let Some(archive) = &node.archive {
+        let marker = if archive.damaged {
+            format!("[{}, damaged]", archive.format)
+        } else {
+            format!("[{}]", archive.format)
+        };
+        if color {
+            line.push_str(&format!(" {}", marker.magenta()));
+        } else {
+            line.push_str(&format!(" {}", marker));
+        }
+    }
+    if let Some(mark) = git_mark {
+        let marker = mark.as_str();
+        if color {
+            let marker = match mark {
+                GitMark::Changed(_) => marker.yellow(),
+                GitMark::Untracked => marker.red(),
+                GitMark::Ignored => marker.dimmed(),
+            };
+            line.push_str(&format!(" {}", marker));
+        } else {
+            line.push_str(&format!(" {}", marker));
+        }
+    }
+
+    if style.summaries && node.kind == FileKind::Dir {
+        // The counts already cover whatever the depth limit hides
+        line.push(' ');
+        line.push_str(&summary(node, options));
+        return line;
+    }
+    if options.show_size {
+        let size = options.size_unit.format_size(node.size);
+        if color {
+            line.push_str(&format!(" ({})", size.cyan()));
+        } else {
+            line.push_str(&format!(" ({})", size));
+        }
+    }
+    if node.truncated {
+        let hidden = format!("(+{} files, {} dirs)", node.files, node.dirs);
+        if color {
+            line.push_str(&format!(" {}", hidden.dimmed()));
+        } else {
+            line.push_str(&format!(" {}", hidden));
+        }
+    }
+    line
+}
+
+/// "(N files, M dirs, SIZE)" for a directory
+fn summary(node: &TreeNode, options: &DisplayOptions) -> String {
+    let size = options.size_unit.format_size(node.size);
+    if options.color {
+        format!("({} files, {} dirs, {})", node.files, node.dirs, size.cyan())
+    } else {
+        format!("({} files, {} dirs, {})", node.files, node.dirs, size)
     }
 }
@@ -1,7 +1,11 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU8, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 #[derive(Debug, Clone)]
 pub enum SizeUnit {
+    Auto,
     Bytes,
     Kilobytes,
     Megabytes,
@@ -14,14 +18,520 @@ pub enum SizeUnit {
     Terabits,
 }
 
-#[derive(Debug, Clone)]
+/// Whether byte sizes are shown in powers of 1024 (KiB, MiB, ...) or
+/// powers of 1000 (kB, MB, ...)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnitSystem {
+    Binary,
+    Si,
+}
+
+static SI_UNITS: AtomicBool = AtomicBool::new(false);
+
+impl UnitSystem {
+    /// Select the unit system used by all size formatting
+    pub fn set_active(system: UnitSystem) {
+        SI_UNITS.store(system == UnitSystem::Si, Ordering::Relaxed);
+    }
+
+    pub fn active() -> UnitSystem {
+        if SI_UNITS.load(Ordering::Relaxed) {
+            UnitSystem::Si
+        } else {
+            UnitSystem::Binary
+        }
+    }
+
+    pub fn base(self) -> u64 {
+        match self {
+            UnitSystem::Binary => 1024,
+            UnitSystem::Si => 1000,
+        }
+    }
+
+    /// Labels for bytes, kilo, mega, giga and tera in this system
+    pub fn labels(self) -> [&'static str; 5] {
+        match self {
+            UnitSystem::Binary => ["B", "KiB", "MiB", "GiB", "TiB"],
+            UnitSystem::Si => ["B", "kB", "MB", "GB", "TB"],
+        }
+    }
+}
+
+/// Number of decimal places shown for scaled sizes, and whether extra
+/// digits are rounded or truncated
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SizePrecision {
+    pub digits: u8,
+    pub truncate: bool,
+}
+
+static PRECISION_DIGITS: AtomicU8 = AtomicU8::new(2);
+static PRECISION_TRUNCATE: AtomicBool = AtomicBool::new(false);
+
+impl SizePrecision {
+    /// Select the precision used by all size formatting
+    pub fn set_active(precision: SizePrecision) {
+        PRECISION_DIGITS.store(precision.digits.min(3), Ordering::Relaxed);
+        PRECISION_TRUNCATE.store(precision.truncate, Ordering::Relaxed);
+    }
+
+    pub fn active() -> SizePrecision {
+        SizePrecision {
+            digits: PRECISION_DIGITS.load(Ordering::Relaxed),
+            truncate: PRECISION_TRUNCATE.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Format a scaled value with this precision
+    pub fn format(self, value: f64) -> String {
+        let digits = self.digits as usize;
+        let value = if self.truncate {
+            let factor = 10f64.powi(self.digits as i32);
+            (value * factor).trunc() / factor
+        } else {
+            value
+        };
+        format!("{:.*}", digits, value)
+    }
+}
+
+/// Where age-based features get "now" from. Reports are reproducible when
+/// the clock is pinned with `--now`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Clock {
+    System,
+    Fixed(SystemTime),
+}
+
+static CLOCK_FIXED: AtomicBool = AtomicBool::new(false);
+static CLOCK_FIXED_SECS: AtomicI64 = AtomicI64::new(0);
+
+impl Clock {
+    /// Select the clock used by all age calculations
+    pub fn set_active(clock: Clock) {
+        if let Clock::Fixed(time) = clock {
+            let secs = match time.duration_since(UNIX_EPOCH) {
+                Ok(after) => after.as_secs() as i64,
+                Err(before) => -(before.duration().as_secs() as i64),
+            };
+            CLOCK_FIXED_SECS.store(secs, Ordering::Relaxed);
+        }
+        CLOCK_FIXED.store(matches!(clock, Clock::Fixed(_)), Ordering::Relaxed);
+    }
+
+    pub fn active() -> Clock {
+        if CLOCK_FIXED.load(Ordering::Relaxed) {
+            let secs = CLOCK_FIXED_SECS.load(Ordering::Relaxed);
+            let time = if secs >= 0 {
+                UNIX_EPOCH + Duration::from_secs(secs as u64)
+            } else {
+                UNIX_EPOCH - Duration::from_secs(secs.unsigned_abs())
+            };
+            Clock::Fixed(time)
+        } else {
+            Clock::System
+        }
+    }
+
+    pub fn now(self) -> SystemTime {
+        match self {
+            Clock::System => SystemTime::now(),
+            Clock::Fixed(time) => time,
+        }
+    }
+
+    /// Parse a `--now` value: RFC 3339 (`2024-01-01T00:00:00Z`) or a plain date
+    pub fn parse(value: &str) -> Result<Clock, String> {
+        let parsed = chrono::DateTime::parse_from_rfc3339(value)
+            .map(|time| time.with_timezone(&chrono::Utc))
+            .or_else(|_| {
+                chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                    .map(|date| date.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc())
+            })
+            .map_err(|_| {
+                format!(
+                    "Invalid time '{}': expected RFC 3339 (2024-01-01T00:00:00Z) or a date (2024-01-01)",
+                    value
+                )
+            })?;
+        Ok(Clock::Fixed(parsed.into()))
+    }
+}
+
+/// How file types are detected: by reading the first bytes of each file, or
+/// (with `--no-mime`) from the extension alone, which never opens files
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TypeDetection {
+    Sniff,
+    Extension,
+}
+
+static TYPE_BY_EXTENSION: AtomicBool = AtomicBool::new(false);
+
+impl TypeDetection {
+    /// Select how all file types are detected
+    pub fn set_active(detection: TypeDetection) {
+        TYPE_BY_EXTENSION.store(detection == TypeDetection::Extension, Ordering::Relaxed);
+    }
+
+    pub fn active() -> TypeDetection {
+        if TYPE_BY_EXTENSION.load(Ordering::Relaxed) {
+            TypeDetection::Extension
+        } else {
+            TypeDetection::Sniff
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SortBy {
     Name,
     Size,
     Date,
+    Owner,
+}
+
+/// Name and type filters applied while collecting files
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    pub search_pattern: Option<String>,
+    pub excluding_pattern: Option<String>,
+    /// Only keep entries of these kinds (`--type`); empty keeps everything
+    pub kinds: Vec<FileKind>,
+}
+
+impl Filter {
+    pub fn is_active(&self) -> bool {
+        self.search_pattern.is_some() || self.excluding_pattern.is_some() || !self.kinds.is_empty()
+    }
+
+    pub fn matches_kind(&self, kind: FileKind) -> bool {
+        self.kinds.is_empty() || self.kinds.contains(&kind)
+    }
+}
+
+/// How collected files are ordered, and how many of them are shown
+#[derive(Debug, Clone, Default)]
+pub struct SortSpec {
+    pub sort_by: Option<SortBy>,
+    pub reverse: bool,
+    pub top: Option<usize>,
+}
+
+impl SortSpec {
+    pub fn is_active(&self) -> bool {
+        self.sort_by.is_some() || self.reverse || self.top.is_some()
+    }
+}
+
+/// What gets computed while collecting files
+#[derive(Debug, Clone, Default)]
+pub struct CollectOptions {
+    /// Compute each directory's total size (`--du`) instead of leaving it at 0
+    pub du: bool,
+    /// Descend into /proc, /sys, /dev and /run when scanning `/`
+    pub include_virtual: bool,
+    /// Reuse the scan cache for directories whose mtime is unchanged
+    pub refresh: bool,
+    /// Don't descend into other filesystems mounted below the scanned directory
+    pub one_file_system: bool,
+    /// Don't descend into network filesystems mounted below the scanned directory
+    pub local_only: bool,
+    /// Read the width and height of images from their headers
+    pub dimensions: bool,
+}
+
+/// How duplicate files are found, and what to do with them
+#[derive(Debug, Clone)]
+pub struct DuplicateOptions {
+    /// Worker threads hashing candidate files
+    pub threads: usize,
+    /// Files smaller than this are never reported
+    pub min_size: u64,
+    /// Replace extra copies with links to the first one
+    pub dedupe: Option<DedupeMode>,
+    /// Only report what `dedupe` would change
+    pub dry_run: bool,
+    /// Write the groups to this `.json` or `.csv` file
+    pub export: Option<String>,
+    /// Also group images that look the same, see [`crate::similar`]
+    pub similar_images: bool,
+}
+
+impl Default for DuplicateOptions {
+    fn default() -> Self {
+        DuplicateOptions {
+            threads: std::thread::available_parallelism().map_or(1, |n| n.get()),
+            min_size: 0,
+            dedupe: None,
+            dry_run: false,
+            export: None,
+            similar_images: false,
+        }
+    }
+}
+
+/// Kind of link that replaces a duplicate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DedupeMode {
+    Hardlink,
+    Symlink,
+}
+
+impl std::str::FromStr for DedupeMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s.to_lowercase().as_str() {
+            "hardlink" => Ok(DedupeMode::Hardlink),
+            "symlink" => Ok(DedupeMode::Symlink),
+            _ => Err(format!("Invalid dedupe mode: {}", s)),
+        }
+    }
+}
+
+/// Digest computed by `filebyte hash`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HashAlgorithm {
+    Md5,
+    Sha1,
+    #[default]
+    Sha256,
+    Blake3,
+}
+
+impl HashAlgorithm {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            HashAlgorithm::Md5 => "md5",
+            HashAlgorithm::Sha1 => "sha1",
+            HashAlgorithm::Sha256 => "sha256",
+            HashAlgorithm::Blake3 => "blake3",
+        }
+    }
+}
+
+impl std::str::FromStr for HashAlgorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s.to_lowercase().as_str() {
+            "md5" => Ok(HashAlgorithm::Md5),
+            "sha1" | "sha-1" => Ok(HashAlgorithm::Sha1),
+            "sha256" | "sha-256" => Ok(HashAlgorithm::Sha256),
+            "blake3" => Ok(HashAlgorithm::Blake3),
+            _ => Err(format!("Invalid hash algorithm: {} (use md5, sha1, sha256 or blake3)", s)),
+        }
+    }
+}
+
+/// Column layout of CSV exports
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CsvSchema {
+    /// One column per [`FileInfo`] field
+    #[default]
+    Default,
+    /// The columns and ISO 8601 dates of PowerShell's `Export-Csv`, for
+    /// `Import-Csv` and Windows admin scripts
+    Windows,
+}
+
+impl std::str::FromStr for CsvSchema {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s.to_lowercase().as_str() {
+            "default" => Ok(CsvSchema::Default),
+            "windows" | "powershell" => Ok(CsvSchema::Windows),
+            _ => Err(format!("Invalid CSV schema: {}", s)),
+        }
+    }
+}
+
+/// Characters `--tree` draws its branches with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TreeCharset {
+    /// Box-drawing lines: `├── ` and `└── `
+    #[default]
+    Utf8,
+    /// `|-- ` and `` `-- ``, for terminals and tools that only take ASCII
+    Ascii,
+}
+
+impl TreeCharset {
+    /// Box-drawing lines on a terminal with a UTF-8 locale, ASCII otherwise
+    pub fn detect() -> TreeCharset {
+        use std::io::IsTerminal;
+
+        if std::io::stdout().is_terminal() && (cfg!(windows) || locale_is_utf8()) {
+            TreeCharset::Utf8
+        } else {
+            TreeCharset::Ascii
+        }
+    }
+}
+
+/// Whether the locale in effect, by the usual `LC_ALL`, `LC_CTYPE`, `LANG`
+/// precedence, uses UTF-8
+fn locale_is_utf8() -> bool {
+    ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty())
+        .is_some_and(|locale| {
+            let locale = locale.to_lowercase();
+            locale.contains("utf-8") || locale.contains("utf8")
+        })
+}
+
+impl std::str::FromStr for TreeCharset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s.to_lowercase().as_str() {
+            "utf8" | "utf-8" => Ok(TreeCharset::Utf8),
+            "ascii" => Ok(TreeCharset::Ascii),
+            _ => Err(format!("Invalid charset: {}", s)),
+        }
+    }
+}
+
+/// How listings are rendered
+#[derive(Debug, Clone)]
+pub struct DisplayOptions {
+    pub size_unit: SizeUnit,
+    pub color: bool,
+    pub properties: bool,
+    pub show_size: bool,
+    pub show_detailed_permissions: bool,
+    pub age_colors: bool,
+    pub size_colors: bool,
+    pub csv_schema: CsvSchema,
+}
+
+impl Default for DisplayOptions {
+    fn default() -> Self {
+        DisplayOptions {
+            size_unit: SizeUnit::Auto,
+            color: true,
+            properties: false,
+            show_size: false,
+            show_detailed_permissions: true,
+            age_colors: false,
+            size_colors: false,
+            csv_schema: CsvSchema::Default,
+        }
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// What `--tree` shows beyond the names
+#[derive(Debug, Clone, Default)]
+pub struct TreeOptions {
+    /// Levels below the root to print; deeper entries are only counted
+    pub max_depth: Option<usize>,
+    /// Write the tree to this `.json` file
+    pub export: Option<String>,
+    /// Follow each directory with "(N files, M dirs, SIZE)"
+    pub summaries: bool,
+    /// Descend into symlinked directories instead of only naming the target
+    pub follow_symlinks: bool,
+    pub charset: TreeCharset,
+    /// Children printed per directory before the rest is summed up in one
+    /// line; `None` prints them all
+    pub max_entries: Option<usize>,
+    /// Show the members of zip, tar and gzip files below them
+    pub expand_archives: bool,
+}
+
+/// What kind of filesystem entry a [`FileInfo`] describes. Symlinks are
+/// reported as such rather than as their target's kind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum FileKind {
+    #[default]
+    File,
+    Dir,
+    Symlink,
+    Fifo,
+    Socket,
+    CharDevice,
+    BlockDevice,
+}
+
+impl FileKind {
+    pub fn from_file_type(file_type: std::fs::FileType) -> FileKind {
+        if file_type.is_symlink() {
+            FileKind::Symlink
+        } else if file_type.is_dir() {
+            FileKind::Dir
+        } else {
+            FileKind::special(file_type).unwrap_or(FileKind::File)
+        }
+    }
+
+    /// FIFOs, sockets and device nodes, which only Unix has
+    #[cfg(unix)]
+    fn special(file_type: std::fs::FileType) -> Option<FileKind> {
+        use std::os::unix::fs::FileTypeExt;
+
+        if file_type.is_fifo() {
+            Some(FileKind::Fifo)
+        } else if file_type.is_socket() {
+            Some(FileKind::Socket)
+        } else if file_type.is_char_device() {
+            Some(FileKind::CharDevice)
+        } else if file_type.is_block_device() {
+            Some(FileKind::BlockDevice)
+        } else {
+            None
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn special(_file_type: std::fs::FileType) -> Option<FileKind> {
+        None
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FileKind::File => "file",
+            FileKind::Dir => "dir",
+            FileKind::Symlink => "symlink",
+            FileKind::Fifo => "fifo",
+            FileKind::Socket => "socket",
+            FileKind::CharDevice => "char-device",
+            FileKind::BlockDevice => "block-device",
+        }
+    }
+
+    /// FIFOs, sockets and device nodes, which must not be opened for reading
+    pub fn is_special(self) -> bool {
+        matches!(
+            self,
+            FileKind::Fifo | FileKind::Socket | FileKind::CharDevice | FileKind::BlockDevice
+        )
+    }
+}
+
+impl std::str::FromStr for FileKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s.to_lowercase().as_str() {
+            "f" | "file" => Ok(FileKind::File),
+            "d" | "dir" | "directory" => Ok(FileKind::Dir),
+            "l" | "link" | "symlink" => Ok(FileKind::Symlink),
+            "p" | "pipe" | "fifo" => Ok(FileKind::Fifo),
+            "s" | "socket" => Ok(FileKind::Socket),
+            "c" | "char" | "char-device" => Ok(FileKind::CharDevice),
+            "b" | "block" | "block-device" => Ok(FileKind::BlockDevice),
+            _ => Err(format!("Invalid file type: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct FileInfo {
     pub name: String,
     pub path: String,
@@ -31,11 +541,80 @@ pub struct FileInfo {
     pub created: Option<String>,
     pub modified: Option<String>,
     pub permissions: String,
+    #[serde(default)]
+    pub owner: String,
+    #[serde(default)]
+    pub group: String,
     pub is_directory: bool,
+    #[serde(default)]
+    pub kind: FileKind,
+    #[serde(default)]
+    pub hash: Option<String>,
+    /// `WIDTHxHEIGHT` of images, read with `--dimensions`
+    #[serde(default)]
+    pub dimensions: Option<String>,
+    /// Unix permission bits of the entry itself, not a symlink's target;
+    /// kept for snapshots, not exported
+    #[serde(skip)]
+    pub mode: Option<u32>,
+    /// Owning uid of the entry itself; kept for snapshots, not exported
+    #[serde(skip)]
+    pub uid: Option<u32>,
 }
 
-impl SizeUnit {
-    pub fn from_str(s: &str) -> Result<Self, String> {
+/// Where and when a scan was taken, stored alongside exported results
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ScanMetadata {
+    pub hostname: String,
+    pub os: String,
+    pub scanned_at: String,
+}
+
+/// Files with identical contents
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct DuplicateGroup {
+    /// Position in the report, starting at 1
+    pub id: usize,
+    /// SHA-256 of the shared contents
+    pub hash: String,
+    /// Size of each copy
+    pub size: u64,
+    /// Bytes taken by all copies but one
+    pub wasted_bytes: u64,
+    pub paths: Vec<String>,
+}
+
+/// JSON layout of `--duplicates --export`
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct DuplicatesExport {
+    pub metadata: ScanMetadata,
+    pub total_wasted_bytes: u64,
+    pub groups: Vec<DuplicateGroup>,
+}
+
+/// JSON export layout: scan metadata followed by the collected files
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ScanExport {
+    pub metadata: ScanMetadata,
+    pub files: Vec<FileInfo>,
+}
+
+impl ScanMetadata {
+    pub fn current() -> Self {
+        ScanMetadata {
+            hostname: sysinfo::System::host_name().unwrap_or_else(|| "unknown".to_string()),
+            os: sysinfo::System::long_os_version().unwrap_or_else(|| std::env::consts::OS.to_string()),
+            scanned_at: chrono::DateTime::<chrono::Utc>::from(Clock::active().now())
+                .format("%Y-%m-%d %H:%M:%S UTC")
+                .to_string(),
+        }
+    }
+}
+
+impl std::str::FromStr for SizeUnit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
         match s.to_lowercase().as_str() {
             "b" | "bytes" => Ok(SizeUnit::Bytes),
             "kb" | "kilobytes" => Ok(SizeUnit::Kilobytes),
@@ -47,32 +626,48 @@ impl SizeUnit {
             "mbits" | "megabits" => Ok(SizeUnit::Megabits),
             "gbits" | "gigabits" => Ok(SizeUnit::Gigabits),
             "tbits" | "terabits" => Ok(SizeUnit::Terabits),
-            "auto" => Ok(SizeUnit::Bytes),
+            "auto" => Ok(SizeUnit::Auto),
             _ => Err(format!("Invalid size unit: {}", s)),
         }
     }
+}
 
+impl SizeUnit {
     pub fn format_size(&self, bytes: u64) -> String {
+        let system = UnitSystem::active();
+        let precision = SizePrecision::active();
+        let byte_unit = |power: u32| {
+            format!(
+                "{} {}",
+                precision.format(bytes as f64 / system.base().pow(power) as f64),
+                system.labels()[power as usize]
+            )
+        };
+        let bit_unit = |divisor: f64, label: &str| {
+            format!("{} {}", precision.format((bytes * 8) as f64 / divisor), label)
+        };
         match self {
+            SizeUnit::Auto => SizeUnit::auto_format_size(bytes),
             SizeUnit::Bytes => format!("{} B", bytes),
-            SizeUnit::Kilobytes => format!("{:.2} KB", bytes as f64 / 1024.0),
-            SizeUnit::Megabytes => format!("{:.2} MB", bytes as f64 / (1024.0 * 1024.0)),
-            SizeUnit::Gigabytes => format!("{:.2} GB", bytes as f64 / (1024.0 * 1024.0 * 1024.0)),
-            SizeUnit::Terabytes => format!("{:.2} TB", bytes as f64 / (1024.0 * 1024.0 * 1024.0 * 1024.0)),
+            SizeUnit::Kilobytes => byte_unit(1),
+            SizeUnit::Megabytes => byte_unit(2),
+            SizeUnit::Gigabytes => byte_unit(3),
+            SizeUnit::Terabytes => byte_unit(4),
             SizeUnit::Bits => format!("{} b", bytes * 8),
-            SizeUnit::Kilobits => format!("{:.2} Kb", (bytes * 8) as f64 / 1000.0),
-            SizeUnit::Megabits => format!("{:.2} Mb", (bytes * 8) as f64 / 1_000_000.0),
-            SizeUnit::Gigabits => format!("{:.2} Gb", (bytes * 8) as f64 / 1_000_000_000.0),
-            SizeUnit::Terabits => format!("{:.2} Tb", (bytes * 8) as f64 / 1_000_000_000_000.0),
+            SizeUnit::Kilobits => bit_unit(1000.0, "Kb"),
+            SizeUnit::Megabits => bit_unit(1_000_000.0, "Mb"),
+            SizeUnit::Gigabits => bit_unit(1_000_000_000.0, "Gb"),
+            SizeUnit::Terabits => bit_unit(1_000_000_000_000.0, "Tb"),
         }
     }
 
     pub fn auto_format_size(bytes: u64) -> String {
+        let base = UnitSystem::active().base();
         let units = [
-            (SizeUnit::Terabytes, 1024u64.pow(4)),
-            (SizeUnit::Gigabytes, 1024u64.pow(3)),
-            (SizeUnit::Megabytes, 1024u64.pow(2)),
-            (SizeUnit::Kilobytes, 1024u64),
+            (SizeUnit::Terabytes, base.pow(4)),
+            (SizeUnit::Gigabytes, base.pow(3)),
+            (SizeUnit::Megabytes, base.pow(2)),
+            (SizeUnit::Kilobytes, base),
             (SizeUnit::Bytes, 1),
         ];
 
@@ -84,3 +679,41 @@ impl SizeUnit {
         format!("{} B", bytes)
     }
 }
+
+/// Parse a human-readable size such as `512`, `10K`, `1.5 MiB` or `2GB`.
+/// IEC suffixes (KiB, MiB, …) and bare letters (K, M, …) are powers of 1024;
+/// SI suffixes (kB, MB, …) are powers of 1000. Accepts anything
+/// `auto_format_size` produces in either unit system.
+pub fn parse_size(input: &str) -> Result<u64, String> {
+    let input = input.trim();
+    let split = input
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(input.len());
+    let (number, unit) = input.split_at(split);
+    let multiplier: u64 = match unit.trim().to_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "ki" | "kib" => 1 << 10,
+        "m" | "mi" | "mib" => 1 << 20,
+        "g" | "gi" | "gib" => 1 << 30,
+        "t" | "ti" | "tib" => 1 << 40,
+        "kb" => 1_000,
+        "mb" => 1_000_000,
+        "gb" => 1_000_000_000,
+        "tb" => 1_000_000_000_000,
+        _ => return Err(format!("Invalid size '{}': unknown unit '{}'", input, unit.trim())),
+    };
+
+    if let Ok(whole) = number.parse::<u64>() {
+        return whole
+            .checked_mul(multiplier)
+            .ok_or_else(|| format!("Invalid size '{}': too large", input));
+    }
+    let value: f64 = number
+        .parse()
+        .map_err(|_| format!("Invalid size '{}': expected a number", input))?;
+    let bytes = (value * multiplier as f64).round();
+    if bytes > u64::MAX as f64 {
+        return Err(format!("Invalid size '{}': too large", input));
+    }
+    Ok(bytes as u64)
+}
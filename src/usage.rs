@@ -0,0 +1,180 @@
+use clap::parser::ValueSource;
+use clap::ArgMatches;
+use colored::Colorize;
+use filebyte::migrate::{self, LoadError, USAGE_STATS};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Local-only usage statistics. Nothing is recorded until the user opts in
+/// with `filebyte stats-self --enable`, and the file is never transmitted.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct UsageStats {
+    pub features: BTreeMap<String, u64>,
+    pub roots: BTreeMap<String, RootStats>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct RootStats {
+    pub runs: u64,
+    pub total_ms: u64,
+    pub last_ms: u64,
+}
+
+/// Location of the stats file, following the XDG data directory convention
+pub fn stats_path() -> Option<PathBuf> {
+    let data_home = std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".local/share")))?;
+    Some(data_home.join("filebyte").join("usage.json"))
+}
+
+fn load(path: &Path) -> Result<UsageStats, LoadError> {
+    migrate::load(path, &USAGE_STATS).map(Option::unwrap_or_default)
+}
+
+fn save(path: &Path, stats: &UsageStats) -> std::io::Result<()> {
+    migrate::save(path, &USAGE_STATS, stats)
+}
+
+/// Record the flags used and time taken by a run, if stats are enabled
+pub fn record_run(matches: &ArgMatches, elapsed: Duration) {
+    let path = match stats_path() {
+        Some(path) if path.exists() => path,
+        _ => return,
+    };
+    if matches.subcommand_name() == Some("stats-self") {
+        return;
+    }
+
+    // Leave a file we cannot read alone rather than overwrite it
+    let Ok(mut stats) = load(&path) else {
+        return;
+    };
+
+    let mut features: Vec<String> = matches
+        .ids()
+        .filter(|id| matches.value_source(id.as_str()) == Some(ValueSource::CommandLine))
+        .map(|id| id.to_string())
+        // The positional path, and the group of analysis options around it
+        .filter(|id| id != "path" && id != "target")
+        .collect();
+    if let Some(subcommand) = matches.subcommand_name() {
+        features.push(subcommand.to_string());
+    }
+    if features.is_empty() {
+        features.push("list".to_string());
+    }
+    for feature in features {
+        *stats.features.entry(feature).or_insert(0) += 1;
+    }
+
+    let root = ["path", "file", "directory", "disk", "ingest_paths"]
+        .iter()
+        .find_map(|id| matches.try_get_one::<String>(id).ok().flatten())
+        .map(|root| root.as_str())
+        .unwrap_or(".");
+    let root = fs::canonicalize(root)
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or_else(|_| root.to_string());
+
+    let elapsed_ms = elapsed.as_millis() as u64;
+    let entry = stats.roots.entry(root).or_default();
+    entry.runs += 1;
+    entry.total_ms += elapsed_ms;
+    entry.last_ms = elapsed_ms;
+
+    let _ = save(&path, &stats);
+}
+
+/// Handle `filebyte stats-self [--enable|--disable|--reset]`
+pub fn run_stats_self(matches: &ArgMatches, color: bool) {
+    let path = match stats_path() {
+        Some(path) => path,
+        None => {
+            eprintln!("Error: cannot determine a data directory (HOME is not set)");
+            crate::perf::exit(1);
+        }
+    };
+
+    if matches.get_flag("enable") || matches.get_flag("reset") {
+        let stats = if matches.get_flag("reset") {
+            UsageStats::default()
+        } else {
+            match load(&path) {
+                Ok(stats) => stats,
+                Err(e) => {
+                    eprintln!("Error reading {}: {}", path.display(), e);
+                    eprintln!("Run 'filebyte stats-self --reset' to start over.");
+                    crate::perf::exit(1);
+                }
+            }
+        };
+        match save(&path, &stats) {
+            Ok(()) => println!("Local usage stats enabled: {}", path.display()),
+            Err(e) => eprintln!("Error writing {}: {}", path.display(), e),
+        }
+        return;
+    }
+
+    if matches.get_flag("disable") {
+        match fs::remove_file(&path) {
+            Ok(()) => println!("Local usage stats disabled and deleted"),
+            Err(_) => println!("Local usage stats are not enabled"),
+        }
+        return;
+    }
+
+    if !path.exists() {
+        println!("Local usage stats are not enabled.");
+        println!("Run 'filebyte stats-self --enable' to start recording scan times and");
+        println!("feature usage to {} (never transmitted).", path.display());
+        return;
+    }
+
+    let stats = match load(&path) {
+        Ok(stats) => stats,
+        Err(e) => {
+            eprintln!("Error reading {}: {}", path.display(), e);
+            crate::perf::exit(1);
+        }
+    };
+
+    println!();
+    println!("Scan Times By Root:");
+    println!("{}", "─".repeat(50));
+    let mut roots: Vec<_> = stats.roots.iter().collect();
+    roots.sort_by_key(|(_, root_stats)| std::cmp::Reverse(root_stats.total_ms));
+    for (root, root_stats) in roots {
+        let average = root_stats.total_ms / root_stats.runs.max(1);
+        if color {
+            println!(
+                "{}: {} runs, avg {} ms, last {} ms",
+                root.blue().bold(),
+                root_stats.runs.to_string().cyan(),
+                average.to_string().yellow(),
+                root_stats.last_ms.to_string().yellow()
+            );
+        } else {
+            println!(
+                "{}: {} runs, avg {} ms, last {} ms",
+                root, root_stats.runs, average, root_stats.last_ms
+            );
+        }
+    }
+
+    println!();
+    println!("Feature Usage:");
+    println!("{}", "─".repeat(50));
+    let mut features: Vec<_> = stats.features.iter().collect();
+    features.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+    for (feature, count) in features {
+        if color {
+            println!("{}: {}", feature.magenta(), count.to_string().cyan());
+        } else {
+            println!("{}: {}", feature, count);
+        }
+    }
+}
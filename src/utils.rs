@@ -1,5 +1,15 @@
+use crate::perf::{self, CountedFile};
+use crate::types::{FileKind, HashAlgorithm, TypeDetection};
+#[cfg(unix)]
+use std::collections::HashMap;
 use std::fs;
-use std::path::Path;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+#[cfg(unix)]
+use std::sync::OnceLock;
+
+/// How much of a file is read when sniffing its contents for a MIME type
+const SNIFF_LIMIT: u64 = 8 * 1024;
 
 pub fn can_delete(path: &Path) -> bool {
     if let Some(parent) = path.parent() {
@@ -13,53 +23,564 @@ pub fn can_delete(path: &Path) -> bool {
     }
 }
 
+/// Label FIFOs, sockets and device nodes, which must not be opened for reading
+pub fn special_file_type(metadata: &fs::Metadata) -> Option<&'static str> {
+    let kind = FileKind::from_file_type(metadata.file_type());
+    if kind.is_special() {
+        Some(kind.as_str())
+    } else {
+        None
+    }
+}
+
+/// Detect a file's MIME type from its first few KB. Special files are
+/// labelled without being opened, and empty files (including most of /proc)
+/// are never read.
+pub fn detect_file_type(path: &Path) -> String {
+    let metadata = match fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(_) => return "unknown".to_string(),
+    };
+    if let Some(special) = special_file_type(&metadata) {
+        return special.to_string();
+    }
+    if !metadata.is_file() || metadata.len() == 0 {
+        return "unknown".to_string();
+    }
+    if TypeDetection::active() == TypeDetection::Extension {
+        return mime_from_extension(path).unwrap_or("unknown").to_string();
+    }
+
+    let mut prefix = Vec::with_capacity(SNIFF_LIMIT as usize);
+    match CountedFile::open(path).and_then(|file| file.take(SNIFF_LIMIT).read_to_end(&mut prefix)) {
+        Ok(_) => infer::get(&prefix)
+            .map(|kind| kind.mime_type().to_string())
+            .unwrap_or_else(|| "unknown".to_string()),
+        Err(_) => "unknown".to_string(),
+    }
+}
+
+/// Guess a MIME type from a file's extension, for the formats content
+/// sniffing recognises, so both modes report the same labels
+pub fn mime_from_extension(path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    let mime = match extension.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "bmp" => "image/bmp",
+        "tif" | "tiff" => "image/tiff",
+        "ico" => "image/vnd.microsoft.icon",
+        "heic" => "image/heif",
+        "avif" => "image/avif",
+        "mp4" | "m4v" => "video/mp4",
+        "mkv" => "video/x-matroska",
+        "webm" => "video/webm",
+        "mov" => "video/quicktime",
+        "avi" => "video/x-msvideo",
+        "mp3" => "audio/mpeg",
+        "flac" => "audio/x-flac",
+        "wav" => "audio/x-wav",
+        "ogg" => "audio/ogg",
+        "m4a" => "audio/m4a",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "gz" | "tgz" => "application/gzip",
+        "bz2" => "application/x-bzip2",
+        "xz" => "application/x-xz",
+        "zst" => "application/zstd",
+        "7z" => "application/x-7z-compressed",
+        "rar" => "application/vnd.rar",
+        "tar" => "application/x-tar",
+        "deb" => "application/vnd.debian.binary-package",
+        "rpm" => "application/x-rpm",
+        "exe" | "dll" => "application/vnd.microsoft.portable-executable",
+        "wasm" => "application/wasm",
+        "sqlite" | "sqlite3" => "application/vnd.sqlite3",
+        "epub" => "application/epub+zip",
+        "docx" => "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+        "xlsx" => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+        "pptx" => "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+        "woff" => "application/font-woff",
+        "woff2" => "application/font-woff2",
+        "ttf" => "application/font-sfnt",
+        _ => return None,
+    };
+    Some(mime)
+}
+
+/// Resolve the owner of a file to a user name, falling back to the numeric uid
+#[cfg(unix)]
+pub fn get_owner_name(metadata: &fs::Metadata) -> String {
+    use std::os::unix::fs::MetadataExt;
+
+    static USERS: OnceLock<HashMap<u32, String>> = OnceLock::new();
+    let users = USERS.get_or_init(|| parse_id_file("/etc/passwd"));
+    let uid = metadata.uid();
+    users.get(&uid).cloned().unwrap_or_else(|| uid.to_string())
+}
+
+#[cfg(unix)]
+pub fn get_group_name(metadata: &fs::Metadata) -> String {
+    use std::os::unix::fs::MetadataExt;
+
+    static GROUPS: OnceLock<HashMap<u32, String>> = OnceLock::new();
+    let groups = GROUPS.get_or_init(|| parse_id_file("/etc/group"));
+    let gid = metadata.gid();
+    groups.get(&gid).cloned().unwrap_or_else(|| gid.to_string())
+}
+
+/// Owners on Windows live in security descriptors, which std cannot read
+#[cfg(not(unix))]
+pub fn get_owner_name(_metadata: &fs::Metadata) -> String {
+    "unknown".to_string()
+}
+
+#[cfg(not(unix))]
+pub fn get_group_name(_metadata: &fs::Metadata) -> String {
+    "unknown".to_string()
+}
+
+/// Parse a passwd/group style file into an id -> name map
+#[cfg(unix)]
+fn parse_id_file(path: &str) -> HashMap<u32, String> {
+    let mut names = HashMap::new();
+    if let Ok(contents) = fs::read_to_string(path) {
+        for line in contents.lines() {
+            let fields: Vec<&str> = line.split(':').collect();
+            if fields.len() > 2 {
+                if let Ok(id) = fields[2].parse::<u32>() {
+                    names.entry(id).or_insert_with(|| fields[0].to_string());
+                }
+            }
+        }
+    }
+    names
+}
+
+/// SHA-256 of a file's contents as lowercase hex
+pub fn hash_file(path: &Path) -> std::io::Result<String> {
+    use sha2::{Digest, Sha256};
+
+    let mut hasher = Sha256::new();
+    read_chunks(path, |chunk| hasher.update(chunk))?;
+    Ok(to_hex(&hasher.finalize()))
+}
+
+/// MD5 of a file's contents as lowercase hex
+pub fn md5_file(path: &Path) -> std::io::Result<String> {
+    use md5::{Digest, Md5};
+
+    let mut hasher = Md5::new();
+    read_chunks(path, |chunk| hasher.update(chunk))?;
+    Ok(to_hex(&hasher.finalize()))
+}
+
+/// SHA-1 of a file's contents as lowercase hex
+pub fn sha1_file(path: &Path) -> std::io::Result<String> {
+    use sha1::{Digest, Sha1};
+
+    let mut hasher = Sha1::new();
+    read_chunks(path, |chunk| hasher.update(chunk))?;
+    Ok(to_hex(&hasher.finalize()))
+}
+
+/// BLAKE3 of a file's contents as lowercase hex
+pub fn blake3_file(path: &Path) -> std::io::Result<String> {
+    let mut hasher = blake3::Hasher::new();
+    read_chunks(path, |chunk| {
+        hasher.update(chunk);
+    })?;
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+/// The function computing `algorithm` for a file, as a lowercase hex string
+pub fn file_hasher(algorithm: HashAlgorithm) -> fn(&Path) -> std::io::Result<String> {
+    match algorithm {
+        HashAlgorithm::Md5 => md5_file,
+        HashAlgorithm::Sha1 => sha1_file,
+        HashAlgorithm::Sha256 => hash_file,
+        HashAlgorithm::Blake3 => blake3_file,
+    }
+}
+
+/// Feed a file's contents to `update` in 64 KiB chunks
+fn read_chunks(path: &Path, mut update: impl FnMut(&[u8])) -> std::io::Result<()> {
+    use std::io::Read;
+
+    let mut file = CountedFile::open(path)?;
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            return Ok(());
+        }
+        update(&buffer[..read]);
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
 pub fn get_file_size(path: &Path) -> u64 {
     if path.is_file() {
         fs::metadata(path).map(|m| m.len()).unwrap_or(0)
     } else if path.is_dir() {
-        let mut total = 0;
-        if let Ok(entries) = fs::read_dir(path) {
-            for entry in entries.flatten() {
-                total += get_file_size(&entry.path());
-            }
-        }
-        total
+        directory_size_excluding(path, &[])
     } else {
         0
     }
 }
 
-pub fn format_unix_permissions(metadata: &fs::Metadata, detailed: bool) -> String {
+/// A count with thousands separators: 4812 as "4,812"
+pub fn group_digits(n: u64) -> String {
+    let digits = n.to_string();
+    let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+/// Sum a directory's contents without following symlinks, so links such as
+/// /dev/fd or /proc/self/root cannot send the walk around the filesystem.
+/// Subdirectories listed in `exclude` are skipped.
+pub fn directory_size_excluding(path: &Path, exclude: &[PathBuf]) -> u64 {
+    let mut total = 0;
+    let listing = fs::read_dir(path);
+    perf::count_listing(&listing);
+    if let Ok(entries) = listing {
+        for entry in entries.flatten() {
+            match entry.file_type() {
+                Ok(t) if t.is_dir() => {
+                    let entry_path = entry.path();
+                    if !exclude.contains(&entry_path) {
+                        total += directory_size_excluding(&entry_path, exclude);
+                    }
+                }
+                Ok(t) if t.is_file() => {
+                    let metadata = entry.metadata();
+                    perf::count_metadata(&metadata);
+                    total += metadata.map(|m| m.len()).unwrap_or(0);
+                }
+                _ => {}
+            }
+        }
+    }
+    total
+}
+
+/// The short permissions kept in [`crate::types::FileInfo::permissions`].
+/// On Unix `r`, `w` from the read-only flag and `x` when the parent
+/// directory lets the entry be deleted; on Windows the file attributes, see
+/// [`format_permissions`].
+#[cfg(unix)]
+pub fn short_permissions(path: &Path, metadata: &fs::Metadata) -> String {
+    match (metadata.permissions().readonly(), can_delete(path)) {
+        (true, true) => "r-x",
+        (true, false) => "r--",
+        (false, true) => "rwx",
+        (false, false) => "rw-",
+    }
+    .to_string()
+}
+
+#[cfg(windows)]
+pub fn short_permissions(_path: &Path, metadata: &fs::Metadata) -> String {
+    windows_attributes(metadata)
+}
+
+/// Permissions as `-p` and `--detailed-permissions` show them. On Unix the
+/// mode string (`drwxr-xr-x`) when `detailed`, otherwise `rw-` or `r--`
+/// from the read-only flag alone. Windows has no mode bits: it gets the
+/// read-only, hidden, system, archive and reparse point attributes
+/// (`RHSAL`, dashes for the ones unset), and when `detailed`, a `d` for
+/// directories and the access this process actually has, e.g. `d-H-A- rw`.
+#[cfg(unix)]
+pub fn format_permissions(_path: &Path, metadata: &fs::Metadata, detailed: bool) -> String {
     use std::os::unix::fs::PermissionsExt;
 
-    if detailed {
-        let mode = metadata.permissions().mode();
-        let file_type = if metadata.is_dir() { 'd' } else { '-' };
+    if !detailed {
+        return if metadata.permissions().readonly() { "r--" } else { "rw-" }.to_string();
+    }
+    let mode = metadata.permissions().mode();
+    let file_type = if metadata.is_dir() { 'd' } else { '-' };
+    let bits: String = [0o400, 0o200, 0o100, 0o040, 0o020, 0o010, 0o004, 0o002, 0o001]
+        .iter()
+        .zip("rwxrwxrwx".chars())
+        .map(|(bit, c)| if mode & bit != 0 { c } else { '-' })
+        .collect();
+    format!("{}{}", file_type, bits)
+}
+
+#[cfg(windows)]
+pub fn format_permissions(path: &Path, metadata: &fs::Metadata, detailed: bool) -> String {
+    if !detailed {
+        return windows_attributes(metadata);
+    }
+    let file_type = if metadata.is_dir() { 'd' } else { '-' };
+    // Opening is the only way to learn what the ACLs allow this user
+    let readable = if metadata.is_dir() { fs::read_dir(path).is_ok() } else { fs::File::open(path).is_ok() };
+    let writable = !metadata.permissions().readonly()
+        && (metadata.is_dir() || fs::OpenOptions::new().write(true).open(path).is_ok());
+    let access = match (readable, writable) {
+        (true, true) => "rw",
+        (true, false) => "r-",
+        (false, true) => "-w",
+        (false, false) => "--",
+    };
+    format!("{}{} {}", file_type, windows_attributes(metadata), access)
+}
+
+/// The Windows file attributes filebyte shows: flag, letter and name
+#[cfg(windows)]
+const ATTRIBUTES: [(u32, char, &str); 5] = [
+    (0x1, 'R', "Read-only"),
+    (0x2, 'H', "Hidden"),
+    (0x4, 'S', "System"),
+    (0x20, 'A', "Archive"),
+    (0x400, 'L', "Reparse point"),
+];
 
-        let user_read = if mode & 0o400 != 0 { 'r' } else { '-' };
-        let user_write = if mode & 0o200 != 0 { 'w' } else { '-' };
-        let user_exec = if mode & 0o100 != 0 { 'x' } else { '-' };
+/// `RHSAL`: the read-only, hidden, system, archive and reparse point
+/// attributes, `-` for each one unset
+#[cfg(windows)]
+fn windows_attributes(metadata: &fs::Metadata) -> String {
+    use std::os::windows::fs::MetadataExt;
 
-        let group_read = if mode & 0o040 != 0 { 'r' } else { '-' };
-        let group_write = if mode & 0o020 != 0 { 'w' } else { '-' };
-        let group_exec = if mode & 0o010 != 0 { 'x' } else { '-' };
+    let attributes = metadata.file_attributes();
+    ATTRIBUTES
+        .iter()
+        .map(|&(flag, c, _)| if attributes & flag != 0 { c } else { '-' })
+        .collect()
+}
 
-        let other_read = if mode & 0o004 != 0 { 'r' } else { '-' };
-        let other_write = if mode & 0o002 != 0 { 'w' } else { '-' };
-        let other_exec = if mode & 0o001 != 0 { 'x' } else { '-' };
+/// The attributes set on an entry spelled out for `-f`, e.g. `Hidden,
+/// Archive`; `None` on platforms without them
+#[cfg(windows)]
+pub fn attribute_summary(metadata: &fs::Metadata) -> Option<String> {
+    use std::os::windows::fs::MetadataExt;
 
-        format!(
-            "{}{}{}{}{}{}{}{}{}{}",
-            file_type, user_read, user_write, user_exec,
-            group_read, group_write, group_exec,
-            other_read, other_write, other_exec
-        )
-    } else {
-        if metadata.permissions().readonly() {
-            if can_delete(&std::path::Path::new("")) { "r-x" } else { "r--" }
-        } else {
-            if can_delete(&std::path::Path::new("")) { "rwx" } else { "rw-" }
-        }
-        .to_string()
+    let attributes = metadata.file_attributes();
+    let names: Vec<&str> = ATTRIBUTES
+        .iter()
+        .filter(|&&(flag, _, _)| attributes & flag != 0)
+        .map(|&(_, _, name)| name)
+        .collect();
+    Some(if names.is_empty() { "none".to_string() } else { names.join(", ") })
+}
+
+#[cfg(not(windows))]
+pub fn attribute_summary(_metadata: &fs::Metadata) -> Option<String> {
+    None
+}
+
+/// The macOS file flags filebyte shows, named as `ls -lO` and `chflags`
+/// name them
+#[cfg(target_os = "macos")]
+const BSD_FLAGS: [(u32, &str); 9] = [
+    (0x1, "nodump"),
+    (0x2, "uchg"),
+    (0x4, "uappnd"),
+    (0x20, "compressed"),
+    (0x8000, "hidden"),
+    (0x10000, "arch"),
+    (0x20000, "schg"),
+    (0x40000, "sappnd"),
+    (0x80000, "restricted"),
+];
+
+/// `UF_HIDDEN`, set by `chflags hidden`; Finder hides the entry
+#[cfg(target_os = "macos")]
+const UF_HIDDEN: u32 = 0x8000;
+
+/// The BSD flags set on an entry spelled out for `-f`, e.g. `uchg,
+/// hidden`; `None` off macOS
+#[cfg(target_os = "macos")]
+pub fn flag_summary(metadata: &fs::Metadata) -> Option<String> {
+    use std::os::macos::fs::MetadataExt;
+
+    let flags = metadata.st_flags();
+    let names: Vec<&str> = BSD_FLAGS
+        .iter()
+        .filter(|&&(flag, _)| flags & flag != 0)
+        .map(|&(_, name)| name)
+        .collect();
+    Some(if names.is_empty() { "none".to_string() } else { names.join(", ") })
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn flag_summary(_metadata: &fs::Metadata) -> Option<String> {
+    None
+}
+
+/// Whether Finder hides `path` although its name has no leading dot,
+/// because of the `hidden` flag
+#[cfg(target_os = "macos")]
+pub fn finder_hidden(path: &Path) -> bool {
+    use std::os::macos::fs::MetadataExt;
+
+    fs::symlink_metadata(path).is_ok_and(|metadata| metadata.st_flags() & UF_HIDDEN != 0)
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn finder_hidden(_path: &Path) -> bool {
+    false
+}
+
+/// A `com.apple.quarantine` value (`0083;65a1b2c3;Safari;UUID`: flags,
+/// hex timestamp, the app that downloaded the file, event id) spelled out,
+/// e.g. `Safari, 2024-01-12 21:14:11 UTC, not opened yet`. Gatekeeper
+/// sets the 0x40 flag once the user has approved opening the file.
+pub fn describe_quarantine(value: &str) -> String {
+    let mut fields = value.trim_end_matches('\0').split(';');
+    let flags = fields.next().and_then(|f| u32::from_str_radix(f, 16).ok());
+    let when = fields
+        .next()
+        .and_then(|t| i64::from_str_radix(t, 16).ok())
+        .and_then(|t| chrono::DateTime::from_timestamp(t, 0))
+        .map(|t| t.format("%Y-%m-%d %H:%M:%S UTC").to_string());
+    let agent = fields.next().filter(|a| !a.is_empty());
+
+    let mut parts = vec![agent.unwrap_or("unknown app").to_string()];
+    parts.extend(when);
+    match flags {
+        Some(flags) if flags & 0x40 != 0 => parts.push("approved to open".to_string()),
+        Some(_) => parts.push("not opened yet".to_string()),
+        None => {}
+    }
+    parts.join(", ")
+}
+
+/// The quarantine macOS put on a download, see [`describe_quarantine`];
+/// `none` when there is none, `None` off macOS
+#[cfg(target_os = "macos")]
+pub fn quarantine_summary(path: &Path) -> Option<String> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let name = c"com.apple.quarantine";
+    let mut value = [0u8; 1024];
+    // SAFETY: both strings are NUL-terminated and `value` is writable for its length
+    let len = unsafe {
+        libc::getxattr(c_path.as_ptr(), name.as_ptr(), value.as_mut_ptr().cast(), value.len(), 0, 0)
+    };
+    if len < 0 {
+        return Some("none".to_string());
+    }
+    Some(describe_quarantine(&String::from_utf8_lossy(&value[..len as usize])))
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn quarantine_summary(_path: &Path) -> Option<String> {
+    None
+}
+
+/// The device an entry lives on, for staying on one filesystem. Windows
+/// only exposes volume serial numbers on nightly, so there every entry
+/// counts as being on the same one.
+#[cfg(unix)]
+pub fn device_id(metadata: &fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    metadata.dev()
+}
+
+#[cfg(not(unix))]
+pub fn device_id(_metadata: &fs::Metadata) -> u64 {
+    0
+}
+
+/// Device and inode, the same for every hard link to a file; `None` where
+/// the platform does not tell
+#[cfg(unix)]
+pub fn file_id(metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+pub fn file_id(_metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+/// Hard links to a file, counting itself; 1 where the platform does not tell
+#[cfg(unix)]
+pub fn link_count(metadata: &fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    metadata.nlink()
+}
+
+#[cfg(not(unix))]
+pub fn link_count(_metadata: &fs::Metadata) -> u64 {
+    1
+}
+
+/// A URL with its `user:password@` part removed, safe to print in messages
+pub fn redact_url(url: &str) -> String {
+    let Some((scheme, rest)) = url.split_once("://") else {
+        return url.to_string();
+    };
+    let authority_end = rest.find(['/', '?', '#']).unwrap_or(rest.len());
+    match rest[..authority_end].rfind('@') {
+        Some(at) => format!("{}://{}", scheme, &rest[at + 1..]),
+        None => url.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quarantine_values() {
+        assert_eq!(
+            describe_quarantine("0083;65a1aba3;Safari;8E5E6A1C-2D1B-4A8C-9E2F-0C6C2E0A9B11"),
+            "Safari, 2024-01-12 21:14:11 UTC, not opened yet"
+        );
+        assert_eq!(describe_quarantine("00c3;65a1aba3;Safari;"), "Safari, 2024-01-12 21:14:11 UTC, approved to open");
+        assert_eq!(describe_quarantine("0081;zz;;"), "unknown app, not opened yet");
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn windows_attribute_strings() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let file = dir.path().join("notes.txt");
+        std::fs::write(&file, "x").unwrap();
+        let mut permissions = std::fs::metadata(&file).unwrap().permissions();
+        permissions.set_readonly(true);
+        std::fs::set_permissions(&file, permissions).unwrap();
+        let metadata = std::fs::metadata(&file).unwrap();
+        assert!(short_permissions(&file, &metadata).starts_with('R'));
+        assert!(format_permissions(&file, &metadata, true).ends_with(" r-"));
+        assert!(attribute_summary(&metadata).unwrap().starts_with("Read-only"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unix_permission_strings() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let file = dir.path().join("notes.txt");
+        std::fs::write(&file, "x").unwrap();
+        std::fs::set_permissions(&file, std::fs::Permissions::from_mode(0o640)).unwrap();
+        let metadata = std::fs::metadata(&file).unwrap();
+        assert_eq!(format_permissions(&file, &metadata, true), "-rw-r-----");
+        assert_eq!(format_permissions(&file, &metadata, false), "rw-");
+        // Writable, and its directory lets it be deleted
+        assert_eq!(short_permissions(&file, &metadata), "rwx");
+
+        std::fs::set_permissions(dir.path(), std::fs::Permissions::from_mode(0o751)).unwrap();
+        let metadata = std::fs::metadata(dir.path()).unwrap();
+        assert_eq!(format_permissions(dir.path(), &metadata, true), "drwxr-x--x");
+        // Attributes are a Windows thing
+        assert_eq!(attribute_summary(&metadata), None);
     }
 }
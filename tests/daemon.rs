@@ -0,0 +1,546 @@
+use filebyte::daemon::{handle, parse_tokens, Daemon, DaemonConfig, RateLimiter, Request, ScanQueue};
+use serde_json::Value;
+use std::fs;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::thread;
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+
+fn start() -> SocketAddr {
+    start_with(DaemonConfig {
+        max_concurrent: 1,
+        ..DaemonConfig::default()
+    })
+}
+
+fn start_with(config: DaemonConfig) -> SocketAddr {
+    let daemon = Daemon::bind("127.0.0.1:0", config).unwrap();
+    let addr = daemon.local_addr().unwrap();
+    thread::spawn(move || daemon.run());
+    addr
+}
+
+fn request(addr: SocketAddr, method: &str, path: &str, body: &str) -> (u16, Value) {
+    request_as(addr, None, method, path, body)
+}
+
+fn request_as(
+    addr: SocketAddr,
+    token: Option<&str>,
+    method: &str,
+    path: &str,
+    body: &str,
+) -> (u16, Value) {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    let auth = token.map_or(String::new(), |t| format!("Authorization: Bearer {}\r\n", t));
+    write!(
+        stream,
+        "{} {} HTTP/1.1\r\nHost: localhost\r\n{}Content-Length: {}\r\n\r\n{}",
+        method,
+        path,
+        auth,
+        body.len(),
+        body
+    )
+    .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    let status = head.split_whitespace().nth(1).unwrap().parse().unwrap();
+    (status, serde_json::from_str(body).unwrap())
+}
+
+fn wait_until_finished(addr: SocketAddr, id: u64) -> Value {
+    for _ in 0..200 {
+        let (_, job) = request(addr, "GET", &format!("/scans/{}", id), "");
+        if !matches!(job["status"].as_str(), Some("queued" | "running")) {
+            return job;
+        }
+        thread::sleep(Duration::from_millis(25));
+    }
+    panic!("scan {} did not finish", id);
+}
+
+fn submit(addr: SocketAddr, body: &Value) -> u64 {
+    let (status, job) = request(addr, "POST", "/scans", &body.to_string());
+    assert_eq!(status, 202, "{}", job);
+    job["id"].as_u64().unwrap()
+}
+
+#[test]
+fn scans_run_to_completion() {
+    let dir = TempDir::new().unwrap();
+    fs::create_dir(dir.path().join("sub")).unwrap();
+    fs::write(dir.path().join("a.txt"), "hello").unwrap();
+    fs::write(dir.path().join("sub/b.txt"), "world!").unwrap();
+    let addr = start();
+    let root = dir.path().to_string_lossy();
+
+    let flat = submit(addr, &serde_json::json!({ "path": root }));
+    let deep = submit(addr, &serde_json::json!({ "path": root, "recursive": true }));
+    assert_eq!(wait_until_finished(addr, flat)["status"], "done");
+    assert_eq!(wait_until_finished(addr, deep)["status"], "done");
+
+    let (status, results) = request(addr, "GET", &format!("/scans/{}/results", flat), "");
+    assert_eq!(status, 200);
+    let names: Vec<&str> = results["files"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|f| f["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, ["sub", "a.txt"]);
+
+    let (_, results) = request(addr, "GET", &format!("/scans/{}/results", deep), "");
+    assert_eq!(results["files"].as_array().unwrap().len(), 3);
+
+    let (status, jobs) = request(addr, "GET", "/scans", "");
+    assert_eq!(status, 200);
+    assert_eq!(jobs.as_array().unwrap().len(), 2);
+
+    // Deleting a finished job forgets it
+    assert_eq!(request(addr, "DELETE", &format!("/scans/{}", flat), "").0, 200);
+    assert_eq!(request(addr, "GET", &format!("/scans/{}", flat), "").0, 404);
+}
+
+#[test]
+fn rejects_bad_requests() {
+    let dir = TempDir::new().unwrap();
+    let addr = start();
+
+    let (status, body) = request(addr, "POST", "/scans", "{\"recursive\": true}");
+    assert_eq!(status, 400);
+    assert!(body["error"].as_str().unwrap().contains("path"));
+
+    let missing = dir.path().join("missing");
+    let body = serde_json::json!({ "path": missing.to_string_lossy() }).to_string();
+    assert_eq!(request(addr, "POST", "/scans", &body).0, 400);
+
+    assert_eq!(request(addr, "GET", "/scans/42", "").0, 404);
+    assert_eq!(request(addr, "PUT", "/scans", "").0, 405);
+    assert_eq!(request(addr, "GET", "/nowhere", "").0, 404);
+    assert_eq!(request(addr, "GET", "/health", "").1["status"], "ok");
+}
+
+#[test]
+fn queued_scans_can_be_cancelled() {
+    let dir = TempDir::new().unwrap();
+    for i in 0..50 {
+        let sub = dir.path().join(format!("d{}", i));
+        fs::create_dir(&sub).unwrap();
+        for j in 0..50 {
+            fs::write(sub.join(format!("f{}", j)), "x").unwrap();
+        }
+    }
+    let addr = start();
+    let body = serde_json::json!({ "path": dir.path().to_string_lossy(), "recursive": true });
+
+    // With one worker the last job waits behind the others
+    let first = submit(addr, &body);
+    submit(addr, &body);
+    let last = submit(addr, &body);
+    let (status, job) = request(addr, "DELETE", &format!("/scans/{}", last), "");
+    assert_eq!(status, 200);
+    assert_eq!(job["status"], "cancelled");
+
+    assert_eq!(wait_until_finished(addr, first)["status"], "done");
+    let (status, body) = request(addr, "GET", &format!("/scans/{}/results", last), "");
+    assert_eq!(status, 409);
+    assert_eq!(body["error"], format!("Scan {} has no results (cancelled)", last));
+}
+
+#[test]
+fn tokens_are_scoped() {
+    let dir = TempDir::new().unwrap();
+    let tokens = parse_tokens("# ops\nadmin s3cret\n\nread viewer\n").unwrap();
+    let addr = start_with(DaemonConfig {
+        max_concurrent: 1,
+        tokens,
+        ..DaemonConfig::default()
+    });
+    let body = serde_json::json!({ "path": dir.path().to_string_lossy() }).to_string();
+
+    assert_eq!(request(addr, "GET", "/health", "").0, 200);
+    assert_eq!(request(addr, "GET", "/scans", "").0, 401);
+    assert_eq!(request_as(addr, Some("wrong"), "GET", "/scans", "").0, 401);
+    assert_eq!(request_as(addr, Some("viewer"), "GET", "/scans", "").0, 200);
+
+    let (status, error) = request_as(addr, Some("viewer"), "POST", "/scans", &body);
+    assert_eq!(status, 403);
+    assert_eq!(error["error"], "This action needs an admin token");
+
+    let (status, job) = request_as(addr, Some("s3cret"), "POST", "/scans", &body);
+    assert_eq!(status, 202);
+    let path = format!("/scans/{}", job["id"]);
+    assert_eq!(request_as(addr, Some("viewer"), "GET", &path, "").0, 200);
+    assert_eq!(request_as(addr, Some("viewer"), "DELETE", &path, "").0, 403);
+}
+
+#[test]
+fn token_file_errors() {
+    assert_eq!(
+        parse_tokens("admin a\nsuper b\n").unwrap_err(),
+        "line 2: Invalid token scope: super"
+    );
+    assert_eq!(
+        parse_tokens("admin\n").unwrap_err(),
+        "line 1: expected '<scope> <token>'"
+    );
+}
+
+#[test]
+fn clients_are_rate_limited() {
+    let addr = start_with(DaemonConfig {
+        rate_limit: Some(3),
+        ..DaemonConfig::default()
+    });
+    for _ in 0..3 {
+        assert_eq!(request(addr, "GET", "/scans", "").0, 200);
+    }
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream.write_all(b"GET /scans HTTP/1.1\r\n\r\n").unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 429 Too Many Requests"), "{}", response);
+    assert!(response.contains("Retry-After: 20\r\n"), "{}", response);
+    // Health checks are never limited
+    assert_eq!(request(addr, "GET", "/health", "").0, 200);
+}
+
+#[test]
+fn oversized_headers_are_refused() {
+    let addr = start();
+    let send = |request: &[u8]| {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(request).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    };
+    // A request line that never ends within the limit
+    let endless = [&b"GET /"[..], &[b'a'; 16 * 1024 - 5]].concat();
+    let response = send(&endless);
+    assert!(response.starts_with("HTTP/1.1 431 Request Header Fields Too Large"), "{}", response);
+    let headers = |count: usize| format!("GET /scans HTTP/1.1\r\n{}\r\n", "X-Filler: 1\r\n".repeat(count));
+    let response = send(headers(101).as_bytes());
+    assert!(response.starts_with("HTTP/1.1 431 Request Header Fields Too Large"), "{}", response);
+    let response = send(headers(100).as_bytes());
+    assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+}
+
+#[test]
+fn rate_limiter_refills() {
+    let limiter = RateLimiter::new(60);
+    let client = "10.0.0.1".parse().unwrap();
+    let other = "10.0.0.2".parse().unwrap();
+    let start = Instant::now();
+    for _ in 0..60 {
+        assert_eq!(limiter.check(client, start), Ok(()));
+    }
+    assert_eq!(limiter.check(client, start), Err(1));
+    assert_eq!(limiter.check(other, start), Ok(()));
+    assert_eq!(limiter.check(client, start + Duration::from_secs(1)), Ok(()));
+    assert_eq!(limiter.check(client, start + Duration::from_secs(1)), Err(1));
+}
+
+#[test]
+fn identical_scans_are_reused() {
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("a.txt"), "hello").unwrap();
+    let addr = start_with(DaemonConfig {
+        cache_ttl: Some(Duration::from_secs(60)),
+        ..DaemonConfig::default()
+    });
+    let body = serde_json::json!({ "path": dir.path().to_string_lossy(), "recursive": true });
+
+    let first = submit(addr, &body);
+    assert_eq!(wait_until_finished(addr, first)["status"], "done");
+    let (status, job) = request(addr, "POST", "/scans", &body.to_string());
+    assert_eq!(status, 200);
+    assert_eq!(job["id"], first);
+
+    // Different options are a different query
+    let flat = serde_json::json!({ "path": dir.path().to_string_lossy() });
+    assert_ne!(submit(addr, &flat), first);
+
+    // Once forgotten, the scan runs again
+    request(addr, "DELETE", &format!("/scans/{}", first), "");
+    assert_ne!(submit(addr, &body), first);
+}
+
+#[test]
+fn full_queue_refuses_scans() {
+    let dir = TempDir::new().unwrap();
+    let config = DaemonConfig {
+        max_queued: 2,
+        ..DaemonConfig::default()
+    };
+    // No workers, so submitted scans stay queued
+    let queue = ScanQueue::new(&config);
+    let post = Request {
+        method: "POST".to_string(),
+        path: "/scans".to_string(),
+        query: String::new(),
+        headers: Vec::new(),
+        body: serde_json::json!({ "path": dir.path().to_string_lossy() }).to_string().into_bytes(),
+    };
+    assert_eq!(handle(&post, &queue, &config).status, 202);
+    assert_eq!(handle(&post, &queue, &config).status, 202);
+    let full = handle(&post, &queue, &config);
+    assert_eq!(full.status, 503);
+    assert_eq!(serde_json::from_slice::<Value>(&full.body).unwrap()["error"], "Scan queue is full");
+
+    // Cancelling one makes room again
+    queue.cancel(1);
+    assert_eq!(handle(&post, &queue, &config).status, 202);
+}
+
+#[test]
+fn finished_jobs_are_forgotten() {
+    let dir = TempDir::new().unwrap();
+    let scan = || serde_json::from_value(serde_json::json!({ "path": dir.path().to_string_lossy() })).unwrap();
+
+    // Past the count, the oldest finished jobs go first
+    let queue = ScanQueue::new(&DaemonConfig {
+        max_finished: 2,
+        ..DaemonConfig::default()
+    });
+    for _ in 0..4 {
+        queue.submit(scan()).unwrap();
+    }
+    for id in [3, 1, 4] {
+        queue.cancel(id);
+    }
+    let ids: Vec<u64> = queue.list().iter().map(|job| job.id).collect();
+    // Job 3 finished first, so it is the one dropped
+    assert_eq!(ids, [1, 2, 4]);
+
+    // And all of them once their time is up
+    let queue = ScanQueue::new(&DaemonConfig {
+        results_ttl: Duration::ZERO,
+        ..DaemonConfig::default()
+    });
+    queue.submit(scan()).unwrap();
+    queue.submit(scan()).unwrap();
+    queue.cancel(1);
+    let ids: Vec<u64> = queue.list().iter().map(|job| job.id).collect();
+    assert_eq!(ids, [2]);
+    assert!(queue.status(1).is_none());
+}
+
+#[test]
+fn slow_requests_share_one_deadline() {
+    let addr = start_with(DaemonConfig {
+        request_timeout: Duration::from_secs(1),
+        ..DaemonConfig::default()
+    });
+    let mut stream = TcpStream::connect(addr).unwrap();
+    let mut reader = stream.try_clone().unwrap();
+    let started = Instant::now();
+    // One byte every 100 ms never lets a single read time out
+    let writer = thread::spawn(move || {
+        for byte in b"GET /scans HTTP/1.1\r\nX-Filler: ".iter().chain([b'a'; 100].iter()) {
+            if stream.write_all(&[*byte]).is_err() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+    });
+    let mut response = Vec::new();
+    let _ = reader.read_to_end(&mut response);
+    let elapsed = started.elapsed();
+    assert!(response.is_empty(), "{}", String::from_utf8_lossy(&response));
+    assert!(elapsed < Duration::from_secs(5), "connection held for {:?}", elapsed);
+    writer.join().unwrap();
+}
+
+#[test]
+fn connections_are_capped() {
+    let addr = start_with(DaemonConfig {
+        max_connections: 1,
+        request_timeout: Duration::from_secs(10),
+        ..DaemonConfig::default()
+    });
+    // An idle client holds the only slot
+    let idle = TcpStream::connect(addr).unwrap();
+    thread::sleep(Duration::from_millis(200));
+    let mut busy = TcpStream::connect(addr).unwrap();
+    let mut response = String::new();
+    busy.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 503 Service Unavailable"), "{}", response);
+
+    // Once it leaves, the slot is free again
+    drop(idle);
+    for _ in 0..200 {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(b"GET /health HTTP/1.1\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        if response.starts_with("HTTP/1.1 200 OK") {
+            return;
+        }
+        thread::sleep(Duration::from_millis(25));
+    }
+    panic!("the slot was never released");
+}
+
+/// Keys of a JSON object, sorted
+fn keys(value: &Value) -> Vec<String> {
+    let mut keys: Vec<String> = value.as_object().unwrap().keys().cloned().collect();
+    keys.sort();
+    keys
+}
+
+#[test]
+fn openapi_matches_responses() {
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("a.txt"), "hello").unwrap();
+    fs::write(dir.path().join("b.txt"), "hello").unwrap();
+    let addr = start_with(DaemonConfig {
+        max_concurrent: 1,
+        tokens: parse_tokens("admin s3cret").unwrap(),
+        ..DaemonConfig::default()
+    });
+
+    // The spec is public, like /health
+    let (status, spec) = request(addr, "GET", "/openapi.json", "");
+    assert_eq!(status, 200);
+    assert_eq!(spec["openapi"], "3.0.3");
+    assert_eq!(spec["info"]["version"], env!("CARGO_PKG_VERSION"));
+    let schemas = &spec["components"]["schemas"];
+    let properties = |name: &str| keys(&schemas[name]["properties"]);
+
+    let body = serde_json::json!({
+        "path": dir.path().to_string_lossy(),
+        "recursive": true,
+        "search": null,
+        "excluding": null,
+        "hashing": true
+    });
+    assert_eq!(keys(&body), properties("ScanRequest"));
+    let (_, job) = request_as(addr, Some("s3cret"), "POST", "/scans", &body.to_string());
+    assert_eq!(keys(&job), properties("JobSummary"));
+
+    let id = job["id"].as_u64().unwrap();
+    for _ in 0..200 {
+        let (_, job) = request_as(addr, Some("s3cret"), "GET", &format!("/scans/{}", id), "");
+        if job["status"] == "done" {
+            break;
+        }
+        thread::sleep(Duration::from_millis(25));
+    }
+    let (_, export) = request_as(addr, Some("s3cret"), "GET", &format!("/scans/{}/results", id), "");
+    assert_eq!(keys(&export), properties("ScanExport"));
+    assert_eq!(keys(&export["metadata"]), properties("ScanMetadata"));
+    assert_eq!(keys(&export["files"][0]), properties("FileInfo"));
+
+    let (status, stats) = request_as(addr, Some("s3cret"), "GET", &format!("/scans/{}/stats", id), "");
+    assert_eq!(status, 200);
+    assert_eq!(keys(&stats), properties("ScanStats"));
+    assert_eq!(stats["files"], 2);
+    assert_eq!(stats["total_bytes"], 10);
+    assert_eq!(keys(&stats["by_extension"][0]), properties("TypeSize"));
+    assert_eq!(stats["by_extension"][0]["key"], ".txt");
+
+    let (status, duplicates) = request_as(addr, Some("s3cret"), "GET", &format!("/scans/{}/duplicates", id), "");
+    assert_eq!(status, 200);
+    assert_eq!(keys(&duplicates), properties("DuplicatesExport"));
+    assert_eq!(keys(&duplicates["groups"][0]), properties("DuplicateGroup"));
+    assert_eq!(duplicates["total_wasted_bytes"], 5);
+    assert_eq!(duplicates["groups"][0]["paths"].as_array().unwrap().len(), 2);
+
+    let (_, error) = request(addr, "GET", "/scans", "");
+    assert_eq!(keys(&error), properties("Error"));
+
+    // Every schema the paths point at is defined
+    let text = spec.to_string();
+    for reference in text.split("\"$ref\":\"").skip(1) {
+        let name = reference.split('"').next().unwrap();
+        let name = name.strip_prefix("#/components/schemas/").unwrap();
+        assert!(schemas.get(name).is_some(), "{} is not defined", name);
+    }
+}
+
+#[test]
+fn results_of_unknown_or_unfinished_scans() {
+    let addr = start();
+    for route in ["results", "stats", "duplicates"] {
+        let (status, error) = request(addr, "GET", &format!("/scans/99/{}", route), "");
+        assert_eq!(status, 404);
+        assert_eq!(error["error"], "No scan 99");
+        let (status, _) = request(addr, "POST", &format!("/scans/99/{}", route), "");
+        assert_eq!(status, 405);
+    }
+}
+
+#[cfg(feature = "webui")]
+#[test]
+fn serves_web_ui() {
+    let addr = start();
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK"));
+    assert!(response.contains("Content-Type: text/html"));
+    assert!(response.contains("<title>filebyte</title>"));
+}
+
+/// `tests/tls` holds a test CA and a certificate for localhost and 127.0.0.1
+/// signed by it, both valid for a century
+#[cfg(feature = "tls")]
+fn tls_file(name: &str) -> std::path::PathBuf {
+    std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/tls").join(name)
+}
+
+#[cfg(feature = "tls")]
+#[test]
+fn serves_https() {
+    use rustls::pki_types::pem::PemObject;
+    use rustls::pki_types::{CertificateDer, ServerName};
+    use std::sync::Arc;
+
+    let tls = filebyte::daemon::load_tls(&tls_file("cert.pem"), &tls_file("key.pem")).unwrap();
+    let addr = start_with(DaemonConfig {
+        tokens: parse_tokens("read s3cret").unwrap(),
+        tls: Some(tls),
+        ..DaemonConfig::default()
+    });
+
+    let mut roots = rustls::RootCertStore::empty();
+    roots.add(CertificateDer::from_pem_file(tls_file("ca.pem")).unwrap()).unwrap();
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let client = rustls::ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    let connection = rustls::ClientConnection::new(Arc::new(client), ServerName::try_from("localhost").unwrap()).unwrap();
+    let mut stream = rustls::StreamOwned::new(connection, TcpStream::connect(addr).unwrap());
+    stream
+        .write_all(b"GET /scans HTTP/1.1\r\nHost: localhost\r\nAuthorization: Bearer s3cret\r\n\r\n")
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+    assert!(response.ends_with("[]"), "{}", response);
+
+    // Plain HTTP gets no answer
+    let mut plain = TcpStream::connect(addr).unwrap();
+    plain.write_all(b"GET /health HTTP/1.1\r\n\r\n").unwrap();
+    let mut response = Vec::new();
+    let _ = plain.read_to_end(&mut response);
+    assert!(!response.starts_with(b"HTTP/1.1"));
+}
+
+#[cfg(feature = "tls")]
+#[test]
+fn tls_files_are_checked() {
+    let missing = filebyte::daemon::load_tls(&tls_file("missing.pem"), &tls_file("key.pem")).unwrap_err();
+    assert!(missing.starts_with("cannot read certificates from "), "{}", missing);
+    let no_certs = filebyte::daemon::load_tls(&tls_file("key.pem"), &tls_file("key.pem")).unwrap_err();
+    assert!(no_certs.starts_with("no certificates in "), "{}", no_certs);
+    let no_key = filebyte::daemon::load_tls(&tls_file("cert.pem"), &tls_file("ca.pem")).unwrap_err();
+    assert!(no_key.starts_with("cannot read a private key from "), "{}", no_key);
+}
@@ -0,0 +1,135 @@
+//! Colored listing lines. Every test here forces colors on for the whole
+//! process, so they live apart from the plain-text tests.
+
+use colored::{Color, Colorize};
+use filebyte::display::{age_color, format_file, size_color};
+use filebyte::types::{DisplayOptions, FileInfo, FileKind, SizeUnit};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const DAY: u64 = 24 * 60 * 60;
+
+/// 2020-01-01 00:00:00 UTC
+fn new_year() -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(1_577_836_800)
+}
+
+fn file(name: &str, size: u64, is_directory: bool) -> FileInfo {
+    FileInfo {
+        name: name.to_string(),
+        path: format!("/nonexistent/{}", name),
+        size,
+        size_human: SizeUnit::auto_format_size(size),
+        file_type: "unknown".to_string(),
+        created: None,
+        modified: Some("2020-01-01 00:00:00 UTC".to_string()),
+        permissions: "rw-".to_string(),
+        owner: String::new(),
+        group: String::new(),
+        is_directory,
+        kind: if is_directory { FileKind::Dir } else { FileKind::File },
+        hash: None,
+        dimensions: None,
+        mode: None,
+        uid: None,
+    }
+}
+
+fn painted(text: &str, color: Color) -> String {
+    text.color(color).to_string()
+}
+
+#[test]
+fn age_colors_run_from_fresh_to_stale() {
+    let steps = [0, DAY - 1, DAY, 7 * DAY, 30 * DAY, 365 * DAY, 20 * 365 * DAY];
+    assert_eq!(
+        steps.map(age_color),
+        [
+            Color::BrightGreen,
+            Color::BrightGreen,
+            Color::Green,
+            Color::Yellow,
+            Color::Red,
+            Color::BrightBlack,
+            Color::BrightBlack,
+        ]
+    );
+}
+
+#[test]
+fn age_colors_tint_the_date_or_the_name() {
+    colored::control::set_override(true);
+    let options = DisplayOptions {
+        age_colors: true,
+        ..DisplayOptions::default()
+    };
+    let a = file("a.txt", 12, false);
+
+    let fresh = format_file(&a, &options, new_year() + Duration::from_secs(6 * 3600));
+    assert!(fresh.ends_with(&painted("2020-01-01", Color::BrightGreen)), "{:?}", fresh);
+    let stale = format_file(&a, &options, new_year() + Duration::from_secs(2 * 365 * DAY));
+    assert!(stale.ends_with(&painted("2020-01-01", Color::BrightBlack)), "{:?}", stale);
+    // Without the flag dates keep their usual color
+    let plain = format_file(&a, &DisplayOptions::default(), new_year());
+    assert!(plain.ends_with(&painted("2020-01-01", Color::Yellow)), "{:?}", plain);
+
+    // Size listings have no date column, so the name carries the age
+    let sized = DisplayOptions {
+        show_size: true,
+        ..options
+    };
+    let line = format_file(&a, &sized, new_year() + Duration::from_secs(10 * DAY));
+    assert!(line.starts_with(&painted("a.txt", Color::Yellow)), "{:?}", line);
+}
+
+#[test]
+fn size_colors_run_from_tiny_to_huge() {
+    const MIB: u64 = 1024 * 1024;
+    let sizes = [0, 1023, 1024, MIB, 100 * MIB, 1024 * MIB, u64::MAX];
+    assert_eq!(
+        sizes.map(size_color),
+        [
+            Color::BrightBlack,
+            Color::BrightBlack,
+            Color::Green,
+            Color::Yellow,
+            Color::Red,
+            Color::BrightRed,
+            Color::BrightRed,
+        ]
+    );
+}
+
+#[test]
+fn size_colors_tint_the_size_or_the_name() {
+    colored::control::set_override(true);
+    let options = DisplayOptions {
+        size_colors: true,
+        ..DisplayOptions::default()
+    };
+    let big = file("big.iso", 200 * 1024 * 1024, false);
+
+    // Long listings have no size column, so the name carries the size
+    let line = format_file(&big, &options, new_year());
+    assert!(line.starts_with(&painted("big.iso", Color::Red)), "{:?}", line);
+
+    let sized = DisplayOptions {
+        show_size: true,
+        ..options
+    };
+    let size = sized.size_unit.format_size(big.size);
+    let line = format_file(&big, &sized, new_year());
+    assert!(line.ends_with(&painted(&size, Color::Red)), "{:?}", line);
+    // Without the flag sizes keep their usual color
+    let plain = DisplayOptions {
+        show_size: true,
+        ..DisplayOptions::default()
+    };
+    let line = format_file(&file("tiny", 3, false), &plain, new_year());
+    assert!(line.ends_with(&painted("3 B", Color::Green)), "{:?}", line);
+
+    // Directory totals from --du are tinted too, and stay bold
+    let dir = file("sub", 2048, true);
+    let total = sized.size_unit.format_size(dir.size).color(Color::Green).bold().to_string();
+    let line = format_file(&dir, &sized, new_year());
+    assert!(line.contains(&total), "{:?}", line);
+}
@@ -0,0 +1,236 @@
+use filebyte::analysis::{dedupe, group_duplicates, partial_hash, paths_by_size, PARTIAL_HASH_BLOCK};
+use filebyte::similar::{dhash, distance, group_similar, image_hash, MAX_DISTANCE, THUMB_HEIGHT, THUMB_WIDTH};
+use filebyte::types::{DedupeMode, DuplicateOptions};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use tempfile::TempDir;
+
+const BLOCK: usize = PARTIAL_HASH_BLOCK as usize;
+
+/// Write each file and group them by size, as the duplicate scan does
+fn candidates(dir: &TempDir, files: &[(&str, Vec<u8>)]) -> HashMap<u64, Vec<String>> {
+    let mut by_size: HashMap<u64, Vec<String>> = HashMap::new();
+    for (name, contents) in files {
+        let path = dir.path().join(name);
+        fs::write(&path, contents).unwrap();
+        by_size
+            .entry(contents.len() as u64)
+            .or_default()
+            .push(path.to_string_lossy().to_string());
+    }
+    by_size
+}
+
+fn groups(dir: &TempDir, files: &[(&str, Vec<u8>)]) -> Vec<Vec<String>> {
+    let options = DuplicateOptions {
+        threads: 2,
+        ..DuplicateOptions::default()
+    };
+    group_duplicates(&candidates(dir, files), &options)
+        .into_iter()
+        .map(|group| {
+            group
+                .paths
+                .iter()
+                .map(|p| p.rsplit(['/', '\\']).next().unwrap().to_string())
+                .collect()
+        })
+        .collect()
+}
+
+/// A large file whose first and last blocks are `edge` and whose middle byte
+/// is `middle`
+fn large(edge: u8, middle: u8) -> Vec<u8> {
+    let mut contents = vec![edge; 3 * BLOCK];
+    contents[BLOCK + BLOCK / 2] = middle;
+    contents
+}
+
+#[test]
+fn identical_large_files_survive_both_tiers() {
+    let dir = TempDir::new().unwrap();
+    let files = [
+        ("a", large(1, 7)),
+        ("b", large(1, 7)),
+        ("c", large(1, 8)),
+        ("d", large(2, 7)),
+    ];
+    assert_eq!(groups(&dir, &files), [["a", "b"]]);
+}
+
+#[test]
+fn differences_in_the_last_block_are_caught_by_the_prefilter() {
+    let dir = TempDir::new().unwrap();
+    let mut tail = large(1, 1);
+    *tail.last_mut().unwrap() = 9;
+    let files = [("a", large(1, 1)), ("b", tail)];
+    assert!(groups(&dir, &files).is_empty());
+    assert_ne!(
+        partial_hash(&dir.path().join("a")).unwrap(),
+        partial_hash(&dir.path().join("b")).unwrap()
+    );
+}
+
+#[test]
+fn files_at_the_prefilter_threshold() {
+    let dir = TempDir::new().unwrap();
+    // Exactly two blocks are hashed whole by both tiers
+    let mut two_blocks = vec![0u8; 2 * BLOCK];
+    two_blocks[BLOCK] = 1;
+    // One byte over, the byte between the two blocks is only seen by the
+    // full hash
+    let over = vec![0u8; 2 * BLOCK + 1];
+    let mut over_changed = over.clone();
+    over_changed[BLOCK] = 1;
+    let files = [
+        ("a", two_blocks.clone()),
+        ("b", two_blocks),
+        ("c", vec![0u8; 2 * BLOCK]),
+        ("d", over),
+        ("e", over_changed),
+    ];
+    assert_eq!(groups(&dir, &files), [["a", "b"]]);
+}
+
+#[test]
+fn unreadable_candidates_are_dropped() {
+    let dir = TempDir::new().unwrap();
+    let mut by_size = candidates(&dir, &[("a", large(1, 1)), ("b", large(1, 1))]);
+    let missing = dir.path().join("missing").to_string_lossy().to_string();
+    by_size.get_mut(&(3 * BLOCK as u64)).unwrap().push(missing);
+    let options = DuplicateOptions {
+        threads: 1,
+        ..DuplicateOptions::default()
+    };
+    let duplicates = group_duplicates(&by_size, &options);
+    assert_eq!(duplicates.len(), 1);
+    assert_eq!(duplicates[0].paths.len(), 2);
+}
+
+#[cfg(unix)]
+#[test]
+fn ingested_symlinks_are_not_duplicates() {
+    let dir = TempDir::new().unwrap();
+    let a = dir.path().join("a.txt");
+    fs::write(&a, "same\n").unwrap();
+    std::os::unix::fs::symlink(&a, dir.path().join("link.txt")).unwrap();
+    let paths = ["a.txt", "link.txt"].map(|name| dir.path().join(name).to_string_lossy().to_string());
+    let by_size = paths_by_size(&paths);
+    assert_eq!(by_size, HashMap::from([(5, vec![paths[0].clone()])]));
+    assert!(group_duplicates(&by_size, &DuplicateOptions::default()).is_empty());
+}
+
+#[test]
+fn ingested_paths_listed_twice_count_once() {
+    let dir = TempDir::new().unwrap();
+    fs::create_dir(dir.path().join("sub")).unwrap();
+    let a = dir.path().join("a.txt");
+    fs::write(&a, "same\n").unwrap();
+    let a = a.to_string_lossy().to_string();
+    let respelled = dir.path().join("sub/../a.txt").to_string_lossy().to_string();
+    let by_size = paths_by_size(&[a.clone(), a.clone(), respelled]);
+    assert_eq!(by_size, HashMap::from([(5, vec![a])]));
+    assert!(group_duplicates(&by_size, &DuplicateOptions::default()).is_empty());
+}
+
+#[test]
+fn dedupe_skips_copies_changed_since_hashing() {
+    let dir = TempDir::new().unwrap();
+    let by_size = candidates(&dir, &[("a", large(1, 2)), ("b", large(1, 2))]);
+    let duplicates = group_duplicates(&by_size, &DuplicateOptions::default());
+    assert_eq!(duplicates.len(), 1);
+
+    // Same size, same edges, different middle: only a full compare notices
+    fs::write(dir.path().join("b"), large(1, 3)).unwrap();
+    let summary = dedupe(&duplicates, DedupeMode::Hardlink, false, false);
+    assert_eq!((summary.replaced, summary.skipped), (0, 1));
+    assert_eq!(fs::read(dir.path().join("b")).unwrap(), large(1, 3));
+}
+
+#[test]
+fn dedupe_leaves_files_named_like_its_temporary_links() {
+    let dir = TempDir::new().unwrap();
+    let by_size = candidates(&dir, &[("a", b"same".to_vec()), ("b", b"same".to_vec())]);
+    let bystander = dir.path().join(".b.filebyte-dedupe");
+    fs::write(&bystander, "keep me").unwrap();
+    let duplicates = group_duplicates(&by_size, &DuplicateOptions::default());
+
+    let summary = dedupe(&duplicates, DedupeMode::Hardlink, false, false);
+    assert_eq!((summary.replaced, summary.skipped, summary.freed), (1, 0, 4));
+    assert_eq!(fs::read_to_string(&bystander).unwrap(), "keep me");
+    let mut names: Vec<_> = fs::read_dir(dir.path())
+        .unwrap()
+        .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+        .collect();
+    names.sort();
+    assert_eq!(names, [".b.filebyte-dedupe", "a", "b"]);
+}
+
+/// A row-major grayscale thumbnail, as produced by `thumbnail`
+fn thumbnail(pixel: impl Fn(usize, usize) -> u8) -> Vec<u8> {
+    (0..THUMB_HEIGHT)
+        .flat_map(|y| (0..THUMB_WIDTH).map(move |x| (x, y)))
+        .map(|(x, y)| pixel(x, y))
+        .collect()
+}
+
+#[test]
+fn dhash_ignores_brightness_and_small_noise() {
+    let photo = thumbnail(|x, y| ((x * 7 + y * 13) % 32 * 8) as u8);
+    // Re-encoded: brighter, with a little noise that keeps most gradients
+    let reencoded = thumbnail(|x, y| ((x * 7 + y * 13) % 32 * 7 + 20 + (x * y) % 3) as u8);
+    let other = thumbnail(|x, y| ((x * 11 + y * 5) % 17 * 15) as u8);
+
+    assert!(distance(dhash(&photo), dhash(&reencoded)) <= MAX_DISTANCE);
+    assert!(distance(dhash(&photo), dhash(&other)) > MAX_DISTANCE);
+    // A flat image has no gradients at all
+    assert_eq!(dhash(&thumbnail(|_, _| 128)), 0);
+}
+
+/// Save a `width` x `height` picture, with the format taken from `name`
+fn picture(dir: &TempDir, name: &str, width: u32, height: u32, pixel: fn(f32, f32) -> u8) -> String {
+    let path = dir.path().join(name);
+    image::RgbImage::from_fn(width, height, |x, y| {
+        let v = pixel(x as f32 / width as f32, y as f32 / height as f32);
+        image::Rgb([v, v / 2, 255 - v])
+    })
+    .save(&path)
+    .unwrap();
+    path.to_string_lossy().to_string()
+}
+
+#[test]
+fn images_are_decoded_in_process() {
+    let dir = TempDir::new().unwrap();
+    let waves = |x: f32, y: f32| (((x * 9.0).sin() + (y * 5.0).cos()) * 60.0 + 128.0) as u8;
+    let hash = |path: &str| u64::from_str_radix(&image_hash(path.as_ref()).unwrap(), 16).unwrap();
+
+    let original = hash(&picture(&dir, "original.png", 320, 240, waves));
+    let smaller = hash(&picture(&dir, "smaller.png", 64, 48, waves));
+    let reencoded = hash(&picture(&dir, "reencoded.jpg", 320, 240, waves));
+    let other = hash(&picture(&dir, "other.bmp", 320, 240, |x, y| ((x * y * 40.0).cos() * 120.0 + 128.0) as u8));
+    assert!(distance(original, smaller) <= MAX_DISTANCE);
+    assert!(distance(original, reencoded) <= MAX_DISTANCE);
+    assert!(distance(original, other) > MAX_DISTANCE);
+
+    // Files that are not images, or not readable, are errors
+    fs::write(dir.path().join("fake.png"), b"not a picture").unwrap();
+    let fake = image_hash(&dir.path().join("fake.png")).unwrap_err();
+    assert_eq!(fake.kind(), io::ErrorKind::InvalidData);
+    let missing = image_hash(&dir.path().join("missing.png")).unwrap_err();
+    assert_eq!(missing.kind(), io::ErrorKind::NotFound);
+}
+
+#[test]
+fn similar_groups_are_transitive() {
+    let hashes = vec![
+        ("c.jpg".to_string(), 0b1111_1111),
+        ("a.jpg".to_string(), 0),
+        ("b.png".to_string(), 0b1111),
+        ("lonely.gif".to_string(), u64::MAX),
+    ];
+    // c is 8 bits from a but only 4 from b, which is 4 from a
+    assert_eq!(group_similar(&hashes, 4), vec![vec!["a.jpg", "b.png", "c.jpg"]]);
+    assert_eq!(group_similar(&hashes, 3), Vec::<Vec<String>>::new());
+}
@@ -0,0 +1,185 @@
+use filebyte::config::{load_config, Config};
+use filebyte::migrate::{self, backup_path, Format, LoadError, Syntax};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fs;
+use tempfile::TempDir;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Settings {
+    color: bool,
+    threads: u64,
+}
+
+/// v0 had no version, v1 renamed `colour` to `color`, v2 added `threads`
+const SETTINGS: Format = Format {
+    name: "settings",
+    version: 2,
+    migrations: &[rename_colour, add_threads],
+    syntax: Syntax::PrettyJson,
+};
+
+fn rename_colour(mut document: Value) -> Result<Value, String> {
+    let object = document.as_object_mut().ok_or("not an object")?;
+    let colour = object.remove("colour").unwrap_or(Value::Bool(true));
+    object.insert("color".to_string(), colour);
+    Ok(document)
+}
+
+fn add_threads(mut document: Value) -> Result<Value, String> {
+    document["threads"] = json!(4);
+    Ok(document)
+}
+
+#[test]
+fn old_files_are_backed_up_and_migrated() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("settings.json");
+    fs::write(&path, r#"{"colour": false}"#).unwrap();
+
+    let settings: Settings = migrate::load(&path, &SETTINGS).unwrap().unwrap();
+    assert_eq!(settings, Settings { color: false, threads: 4 });
+    assert_eq!(
+        fs::read_to_string(backup_path(&path, 0)).unwrap(),
+        r#"{"colour": false}"#
+    );
+    let rewritten: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(rewritten, json!({ "version": 2, "color": false, "threads": 4 }));
+
+    // Already current: loads as is, no new backup
+    fs::remove_file(backup_path(&path, 0)).unwrap();
+    let again: Settings = migrate::load(&path, &SETTINGS).unwrap().unwrap();
+    assert_eq!(again, settings);
+    assert!(!backup_path(&path, 0).exists());
+}
+
+#[test]
+fn migration_starts_at_the_file_version() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("settings.json");
+    fs::write(&path, r#"{"version": 1, "color": true}"#).unwrap();
+    let settings: Settings = migrate::load(&path, &SETTINGS).unwrap().unwrap();
+    assert_eq!(settings, Settings { color: true, threads: 4 });
+    assert!(backup_path(&path, 1).exists());
+}
+
+#[test]
+fn newer_files_are_left_alone() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("settings.json");
+    let contents = r#"{"version": 3, "color": true, "threads": 1, "theme": "dark"}"#;
+    fs::write(&path, contents).unwrap();
+    let error = migrate::load::<Settings>(&path, &SETTINGS).unwrap_err();
+    assert!(matches!(error, LoadError::TooNew { found: 3, supported: 2 }));
+    assert_eq!(
+        error.to_string(),
+        "written by a newer filebyte (format version 3, this build reads up to 2)"
+    );
+    assert_eq!(fs::read_to_string(&path).unwrap(), contents);
+}
+
+#[test]
+fn upgrade_migrates_in_memory() {
+    let upgraded = migrate::upgrade(json!({ "colour": false }), &SETTINGS).unwrap();
+    assert_eq!(upgraded, json!({ "version": 2, "color": false, "threads": 4 }));
+    let error = migrate::upgrade(json!({ "version": 3 }), &SETTINGS).unwrap_err();
+    assert!(matches!(error, LoadError::TooNew { found: 3, supported: 2 }));
+}
+
+#[test]
+fn corrupt_files_are_kept_aside() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("settings.json");
+    fs::write(&path, "{\"color\": tr").unwrap();
+    match migrate::load::<Settings>(&path, &SETTINGS) {
+        Err(LoadError::Invalid { backup: Some(backup), .. }) => {
+            assert_eq!(fs::read_to_string(backup).unwrap(), "{\"color\": tr");
+        }
+        other => panic!("expected an invalid file error, got {:?}", other),
+    }
+}
+
+#[test]
+fn missing_files_load_as_none() {
+    let dir = TempDir::new().unwrap();
+    let loaded = migrate::load::<Settings>(&dir.path().join("nope.json"), &SETTINGS).unwrap();
+    assert!(loaded.is_none());
+}
+
+#[test]
+fn save_writes_the_current_version() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("nested/settings.json");
+    migrate::save(&path, &SETTINGS, &Settings { color: true, threads: 8 }).unwrap();
+    let saved: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(saved["version"], 2);
+}
+
+#[test]
+fn scan_caches_without_groups_are_rescanned() {
+    let hashes = json!({ "a.txt": { "size": 1, "mtime_secs": 2, "mtime_nanos": 0, "sha256": "ab" } });
+    let v1 = json!({
+        "version": 1,
+        "root": "/data",
+        "dirs": { "/data": { "mtime_secs": 1, "mtime_nanos": 0, "entries": [] } },
+        "hashes": hashes
+    });
+    let upgraded = migrate::upgrade(v1, &migrate::SCAN_CACHE).unwrap();
+    assert_eq!(upgraded, json!({ "version": 2, "root": "/data", "dirs": {}, "hashes": hashes }));
+}
+
+#[test]
+fn configs_without_a_version_are_upgraded() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("config.toml");
+    let old = "# written by hand\nsi = true\nsize-precision = 1\n";
+    fs::write(&path, old).unwrap();
+
+    let expected = Config {
+        si: true,
+        size_precision: Some(1),
+        ..Config::default()
+    };
+    assert_eq!(load_config(&path).unwrap(), expected);
+    assert_eq!(fs::read_to_string(backup_path(&path, 0)).unwrap(), old);
+    assert_eq!(
+        fs::read_to_string(&path).unwrap(),
+        "si = true\nsize-precision = 1\nversion = 1\n"
+    );
+
+    // Current now: loads the same without another backup
+    fs::remove_file(backup_path(&path, 0)).unwrap();
+    assert_eq!(load_config(&path).unwrap(), expected);
+    assert!(!backup_path(&path, 0).exists());
+}
+
+#[test]
+fn bad_configs_are_refused() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("config.toml");
+    assert_eq!(load_config(&path).unwrap(), Config::default());
+
+    for contents in ["version = 1\nsize-precision = 7\n", "version = 1\ncolour = false\n", "si = "] {
+        fs::write(&path, contents).unwrap();
+        assert!(matches!(load_config(&path), Err(LoadError::Invalid { .. })), "{}", contents);
+    }
+    fs::write(&path, "version = 2\n").unwrap();
+    assert!(matches!(load_config(&path), Err(LoadError::TooNew { found: 2, supported: 1 })));
+}
+
+#[test]
+fn usage_history_without_a_version_is_upgraded() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("usage.json");
+    let old = json!({
+        "features": { "recursive": 3 },
+        "roots": { "/data": { "runs": 3, "total_ms": 120, "last_ms": 40 } }
+    });
+    fs::write(&path, old.to_string()).unwrap();
+
+    let loaded: Value = migrate::load(&path, &migrate::USAGE_STATS).unwrap().unwrap();
+    assert_eq!(loaded["version"], 1);
+    assert_eq!(loaded["roots"], old["roots"]);
+    let backup: Value = serde_json::from_str(&fs::read_to_string(backup_path(&path, 0)).unwrap()).unwrap();
+    assert_eq!(backup, old);
+}
@@ -0,0 +1,285 @@
+//! Mount table parsing, path-to-mount resolution and `--disk` helpers.
+
+use filebyte::mounts::{list_mounts, parse_mount_table, path_to_mount};
+use std::path::Path;
+
+const TABLE: &str = "\
+/dev/sda1 / ext4 rw,relatime 0 0
+proc /proc proc rw,nosuid,nodev,noexec,relatime 0 0
+/dev/sdb1 /media/usb\\040stick vfat ro,nosuid,nodev,fmask=0022 0 0
+";
+
+#[test]
+fn parses_the_mount_table() {
+    let mounts = parse_mount_table(TABLE);
+    assert_eq!(mounts.len(), 3);
+    assert_eq!(mounts[0].device, "/dev/sda1");
+    assert_eq!(mounts[0].fs_type, "ext4");
+    assert_eq!(mounts[1].notable_options(), ["rw", "nosuid", "nodev", "noexec"]);
+    assert_eq!(mounts[2].mount_point, Path::new("/media/usb stick"));
+    assert!(mounts[2].is_read_only());
+    assert!(!mounts[0].is_read_only());
+}
+
+#[test]
+fn resolves_paths_to_their_deepest_mount() {
+    let root = path_to_mount(Path::new("/")).expect("/ is mounted");
+    assert_eq!(root.mount_point, Path::new("/"));
+
+    let dir = tempfile::TempDir::new().unwrap();
+    let mount = path_to_mount(dir.path()).expect("temp dir is mounted");
+    let canonical = dir.path().canonicalize().unwrap();
+    assert!(canonical.starts_with(&mount.mount_point));
+    assert!(list_mounts()
+        .iter()
+        .filter(|m| canonical.starts_with(&m.mount_point))
+        .all(|m| m.mount_point.components().count() <= mount.mount_point.components().count()));
+}
+
+#[test]
+fn disks_match_by_name_mount_point_drive_or_share() {
+    use filebyte::disk::disk_matches;
+
+    assert!(disk_matches("/dev/sda1", Path::new("/home"), "/dev/sda1"));
+    assert!(disk_matches("/dev/sda1", Path::new("/home"), "/home"));
+    assert!(!disk_matches("/dev/sda1", Path::new("/home"), "/home/user"));
+
+    assert!(disk_matches("Local Disk", Path::new("C:\\"), "C:"));
+    assert!(disk_matches("Local Disk", Path::new("C:\\"), "c:\\"));
+    assert!(disk_matches("Local Disk", Path::new("C:\\"), "c:/"));
+    assert!(!disk_matches("Local Disk", Path::new("C:\\"), "D:"));
+    assert!(!disk_matches("Local Disk", Path::new("C:\\"), "C:\\Users"));
+
+    assert!(disk_matches("\\\\server\\share", Path::new("Z:\\"), "\\\\SERVER\\share\\"));
+    assert!(disk_matches("Projects", Path::new("\\\\server\\projects"), "//server/projects"));
+    assert!(!disk_matches("Projects", Path::new("\\\\server\\projects"), "\\\\server\\other"));
+}
+
+#[test]
+fn parses_vssadmin_shadow_storage() {
+    use filebyte::disk::{parse_shadow_storage, ShadowStorage};
+
+    let output = "\
+vssadmin 1.1 - Volume Shadow Copy Service administrative command-line tool
+(C) Copyright 2001-2013 Microsoft Corp.
+
+Shadow Copy Storage association
+   For volume: (C:)\\\\?\\Volume{3f1c8a2e-0000-0000-0000-100000000000}\\
+   Shadow Copy Storage volume: (C:)\\\\?\\Volume{3f1c8a2e-0000-0000-0000-100000000000}\\
+   Used Shadow Copy Storage space: 1.5 GB (1%)
+   Allocated Shadow Copy Storage space: 2 GB (1%)
+   Maximum Shadow Copy Storage space: UNBOUNDED (100%)
+
+Shadow Copy Storage association
+   For volume: (D:)\\\\?\\Volume{9b2d4c1a-0000-0000-0000-100000000000}\\
+   Shadow Copy Storage volume: (D:)\\\\?\\Volume{9b2d4c1a-0000-0000-0000-100000000000}\\
+   Used Shadow Copy Storage space: 0 bytes (0%)
+   Allocated Shadow Copy Storage space: 0 bytes (0%)
+   Maximum Shadow Copy Storage space: 512 MB (10%)
+";
+    assert_eq!(
+        parse_shadow_storage(output),
+        [
+            ShadowStorage {
+                volume: "C:".to_string(),
+                used: Some(3 << 29),
+                allocated: Some(2 << 30),
+                maximum: None,
+            },
+            ShadowStorage {
+                volume: "D:".to_string(),
+                used: Some(0),
+                allocated: Some(0),
+                maximum: Some(512 << 20),
+            },
+        ]
+    );
+}
+
+#[test]
+fn inode_usage_is_consistent() {
+    use filebyte::disk::{inode_usage, InodeUsage};
+
+    let dir = tempfile::TempDir::new().unwrap();
+    if let Some(inodes) = inode_usage(dir.path()) {
+        assert!(inodes.total > 0);
+        assert!(inodes.free <= inodes.total);
+    }
+    assert_eq!(inode_usage(&dir.path().join("missing")), None);
+
+    let inodes = InodeUsage { total: 1000, free: 750 };
+    assert_eq!(inodes.to_string(), "25.0% (250 of 1000)");
+}
+
+#[test]
+fn disk_filter_hides_pseudo_filesystems() {
+    use filebyte::disk::DiskFilter;
+
+    let default = DiskFilter::default();
+    assert!(default.matches("/dev/sda1", "ext4"));
+    assert!(!default.matches("tmpfs", "tmpfs"));
+    assert!(!default.matches("/dev/loop3", "squashfs"));
+    assert!(!default.matches("/dev/loop0", "ext4"));
+
+    let all = DiskFilter { all: true, ..DiskFilter::default() };
+    assert!(all.matches("tmpfs", "tmpfs"));
+
+    let only = DiskFilter::default().with_fs_types("ext4, tmpfs");
+    assert!(only.matches("tmpfs", "tmpfs"));
+    assert!(!only.matches("/dev/sdb1", "xfs"));
+
+    let except = all.with_fs_types("notmpfs,nosquashfs");
+    assert!(!except.matches("tmpfs", "tmpfs"));
+    assert!(except.matches("/dev/loop0", "ext4"));
+    assert!(except.matches("/dev/sdb1", "xfs"));
+}
+
+#[test]
+fn classifies_network_and_removable_disks() {
+    use filebyte::disk::{DiskFilter, DiskLocation};
+
+    let mounts = parse_mount_table(
+        "nas:/export/home /home nfs4 rw 0 0\n\
+         //fileserver/projects /mnt/projects cifs rw 0 0\n\
+         user@host:/srv /mnt/srv fuse.sshfs rw 0 0\n\
+         /dev/sda1 / ext4 rw 0 0\n",
+    );
+    let network: Vec<bool> = mounts.iter().map(|m| m.is_network()).collect();
+    assert_eq!(network, [true, true, true, false]);
+
+    assert_eq!(DiskLocation::classify("/dev/sda1", "ext4", false), DiskLocation::Local);
+    assert_eq!(DiskLocation::classify("/dev/sdc1", "vfat", true), DiskLocation::Removable);
+    assert_eq!(DiskLocation::classify("nas:/export", "nfs4", false), DiskLocation::Network);
+    assert_eq!(DiskLocation::classify("\\\\server\\share", "NTFS", true), DiskLocation::Network);
+
+    let local = DiskFilter { local_only: true, ..DiskFilter::default() };
+    assert!(!local.matches("nas:/export", "nfs4"));
+    assert!(local.matches("/dev/sdc1", "vfat"));
+}
+
+#[test]
+fn usage_percent_and_alert_thresholds() {
+    use filebyte::disk::{usage_alerts, usage_percent, DiskFilter};
+
+    assert_eq!(usage_percent(200, 50), 75.0);
+    assert_eq!(usage_percent(0, 0), 0.0);
+    // Nothing is ever more than 100% full
+    assert!(usage_alerts(100.0, None, &DiskFilter::default()).is_empty());
+    assert!(usage_alerts(0.0, Some("no-such-disk"), &DiskFilter::default()).is_empty());
+}
+
+#[test]
+fn ranks_directories_by_total_size() {
+    use filebyte::analysis::{find_largest_dirs, DirRanking};
+    use std::fs;
+
+    let dir = tempfile::TempDir::new().unwrap();
+    let root = dir.path();
+    for (path, size) in [("a/x.bin", 100), ("a/deep/y.bin", 400), ("b/z.bin", 300), ("skipped/w.bin", 900)] {
+        fs::create_dir_all(root.join(path).parent().unwrap()).unwrap();
+        fs::write(root.join(path), vec![0u8; size]).unwrap();
+    }
+    let skip = [root.join("skipped")];
+
+    let ranking = DirRanking { limit: 10, max_depth: 2, one_file_system: true };
+    let largest = find_largest_dirs(root, ranking, &skip);
+    assert_eq!(
+        largest,
+        [(root.join("a"), 500), (root.join("a/deep"), 400), (root.join("b"), 300)]
+    );
+
+    // Deeper directories still count towards their parents
+    let ranking = DirRanking { limit: 1, max_depth: 1, one_file_system: true };
+    assert_eq!(find_largest_dirs(root, ranking, &skip), [(root.join("a"), 500)]);
+}
+
+#[cfg(feature = "smart")]
+#[test]
+fn parses_smartctl_health() {
+    use filebyte::smart::{parse_smartctl, physical_device, SmartHealth};
+
+    assert_eq!(physical_device("/dev/sda1").as_deref(), Some("/dev/sda"));
+    assert_eq!(physical_device("/dev/nvme0n1p2").as_deref(), Some("/dev/nvme0n1"));
+    assert_eq!(physical_device("/dev/nvme0n1").as_deref(), Some("/dev/nvme0n1"));
+    assert_eq!(physical_device("/dev/mmcblk0p1").as_deref(), Some("/dev/mmcblk0"));
+    assert_eq!(physical_device("/dev/mapper/root"), None);
+    assert_eq!(physical_device("nas:/export"), None);
+
+    let ata = r#"{
+        "smart_status": {"passed": true},
+        "temperature": {"current": 34},
+        "ata_smart_attributes": {"table": [
+            {"id": 1, "name": "Raw_Read_Error_Rate", "raw": {"value": 0}},
+            {"id": 5, "name": "Reallocated_Sector_Ct", "raw": {"value": 8}}
+        ]}
+    }"#;
+    let health = parse_smartctl(ata).unwrap();
+    assert_eq!(
+        health,
+        SmartHealth {
+            passed: Some(true),
+            temperature_celsius: Some(34),
+            reallocated_sectors: Some(8),
+            media_errors: None,
+        }
+    );
+    assert_eq!(health.to_string(), "Health: PASSED | Temperature: 34°C | Reallocated sectors: 8");
+
+    let nvme = r#"{"smart_status": {"passed": false}, "nvme_smart_health_information_log": {"media_errors": 3}}"#;
+    assert_eq!(parse_smartctl(nvme).unwrap().to_string(), "Health: FAILED | Media errors: 3");
+
+    let denied = r#"{"smartctl": {"messages": [{"string": "Smartctl open device: /dev/sda failed: Permission denied", "severity": "error"}]}}"#;
+    assert_eq!(
+        parse_smartctl(denied).unwrap_err(),
+        "Smartctl open device: /dev/sda failed: Permission denied"
+    );
+}
+
+#[test]
+fn porcelain_output_matches_df_columns() {
+    use filebyte::disk::{format_porcelain, DiskLocation, DiskRecord};
+    use std::path::PathBuf;
+
+    let record = |filesystem: &str, size: u64, available: u64, mounted_on: &str| DiskRecord {
+        filesystem: filesystem.to_string(),
+        fs_type: "ext4".to_string(),
+        size,
+        used: size - available,
+        available,
+        use_percent: (size > 0).then(|| filebyte::disk::usage_percent(size, available).ceil() as u8),
+        mounted_on: PathBuf::from(mounted_on),
+        read_only: false,
+        location: DiskLocation::Local,
+        inodes_total: None,
+        inodes_used: None,
+    };
+    let records = [record("/dev/sda1", 1000, 333, "/"), record("/dev/sdb1", 0, 0, "/mnt/data disk")];
+    assert_eq!(
+        format_porcelain(&records),
+        "\
+Filesystem Type 1B-blocks Used Available Use% Mounted on
+/dev/sda1  ext4      1000  667       333  67% /
+/dev/sdb1  ext4         0    0         0    - /mnt/data disk
+"
+    );
+}
+
+#[test]
+fn parses_diskstats() {
+    use filebyte::disk::{parse_diskstats, IoCounters};
+
+    let stats = "\
+   8       0 sda 1200 30 96000 500 800 20 40000 900 0 1000 1400 0 0 0 0
+   8       1 sda1 1100 30 88000 450 790 20 39000 880 0 950 1330 0 0 0 0
+ 253       0 dm-0 10 0 80 1 2 0 16 1 0 2 2
+   7       0 loop0 0 0
+";
+    let counters = parse_diskstats(stats);
+    assert_eq!(
+        counters["sda1"],
+        IoCounters { reads: 1100, read_bytes: 88000 * 512, writes: 790, written_bytes: 39000 * 512 }
+    );
+    assert_eq!(counters["dm-0"].written_bytes, 16 * 512);
+    // Truncated lines are skipped
+    assert!(!counters.contains_key("loop0"));
+}
@@ -0,0 +1,134 @@
+use filebyte::collect::{collect_files, collect_files_recursive, virtual_exclusions, VIRTUAL_DIRS};
+use filebyte::scan::ScanBuilder;
+use filebyte::types::{CollectOptions, FileKind, Filter, SortBy, SortSpec};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::os::unix::fs::symlink;
+use tempfile::TempDir;
+
+#[test]
+fn followed_symlink_cycles_are_read_once() {
+    let root = TempDir::new().unwrap();
+    fs::create_dir_all(root.path().join("a/b")).unwrap();
+    fs::write(root.path().join("a/b/file.txt"), b"hello").unwrap();
+    // Both lead back up the tree
+    symlink("..", root.path().join("a/b/up")).unwrap();
+    symlink(root.path(), root.path().join("a/root")).unwrap();
+
+    let (files, summary) = ScanBuilder::new(root.path())
+        .follow_symlinks(true)
+        .threads(2)
+        .build()
+        .unwrap()
+        .collect_all();
+    let mut names: Vec<String> = files.iter().map(|f| f.name.clone()).collect();
+    names.sort();
+    assert_eq!(names, ["a", "b", "file.txt", "root", "up"]);
+    assert_eq!(summary.files, 1);
+    assert_eq!(summary.total_size, 5);
+}
+
+#[test]
+fn unfollowed_links_to_directories_are_not_directories() {
+    let root = TempDir::new().unwrap();
+    fs::create_dir(root.path().join("dir")).unwrap();
+    fs::write(root.path().join("dir/file.txt"), b"hello").unwrap();
+    symlink(root.path().join("dir"), root.path().join("link")).unwrap();
+
+    let (files, summary) = ScanBuilder::new(root.path()).threads(2).build().unwrap().collect_all();
+    let link = files.iter().find(|f| f.name == "link").unwrap();
+    assert!(!link.is_directory);
+    assert_eq!(files.len(), 3);
+    assert_eq!(summary.directories, 1);
+    assert_eq!(summary.files, 2);
+
+    // Followed, the link is the directory it points at
+    let (files, summary) = ScanBuilder::new(root.path())
+        .follow_symlinks(true)
+        .build()
+        .unwrap()
+        .collect_all();
+    assert!(files.iter().find(|f| f.name == "link").unwrap().is_directory);
+    assert_eq!(summary.directories, 2);
+}
+
+#[test]
+fn max_depth_zero_yields_nothing() {
+    let root = TempDir::new().unwrap();
+    fs::create_dir(root.path().join("dir")).unwrap();
+    fs::write(root.path().join("file.txt"), b"hello").unwrap();
+
+    let (files, summary) = ScanBuilder::new(root.path()).max_depth(0).build().unwrap().collect_all();
+    assert!(files.is_empty());
+    assert_eq!(summary, Default::default());
+
+    let (files, _) = ScanBuilder::new(root.path()).max_depth(1).build().unwrap().collect_all();
+    assert_eq!(files.len(), 2);
+}
+
+#[test]
+fn filter_and_sort_structs_drive_collection() {
+    let root = TempDir::new().unwrap();
+    fs::create_dir(root.path().join("sub")).unwrap();
+    fs::write(root.path().join("a.txt"), b"abc").unwrap();
+    fs::write(root.path().join("b.log"), b"0123456789").unwrap();
+    fs::write(root.path().join("sub/c.txt"), b"hello").unwrap();
+    symlink("a.txt", root.path().join("link.txt")).unwrap();
+    let names = |files: Vec<filebyte::types::FileInfo>| files.into_iter().map(|f| f.name).collect::<Vec<_>>();
+    let options = CollectOptions::default();
+
+    assert!(!Filter::default().is_active() && !SortSpec::default().is_active());
+    let txt = Filter {
+        search_pattern: Some(r"\.txt$".to_string()),
+        excluding_pattern: Some("^link".to_string()),
+        ..Filter::default()
+    };
+    let by_size = SortSpec {
+        sort_by: Some(SortBy::Size),
+        ..SortSpec::default()
+    };
+    assert!(txt.is_active() && by_size.is_active());
+    assert_eq!(names(collect_files_recursive(root.path(), &txt, &by_size, &options)), ["c.txt", "a.txt"]);
+    let smallest_first = SortSpec { reverse: true, ..by_size };
+    assert_eq!(names(collect_files_recursive(root.path(), &txt, &smallest_first, &options)), ["a.txt", "c.txt"]);
+
+    // Kinds come from the entry itself, not from what a link points at
+    let links = Filter {
+        kinds: vec![FileKind::Symlink],
+        ..Filter::default()
+    };
+    assert!(links.is_active() && links.matches_kind(FileKind::Symlink) && !links.matches_kind(FileKind::File));
+    assert_eq!(names(collect_files(root.path(), &links, &SortSpec::default(), &options)), ["link.txt"]);
+}
+
+#[test]
+fn virtual_filesystems_are_skipped_only_at_the_root() {
+    let root = Path::new("/");
+    let options = CollectOptions::default();
+    let virtual_dirs: Vec<PathBuf> = VIRTUAL_DIRS.iter().map(|name| root.join(name)).collect();
+    assert_eq!(virtual_exclusions(root, &options), virtual_dirs);
+
+    let everything = CollectOptions {
+        include_virtual: true,
+        ..CollectOptions::default()
+    };
+    assert!(virtual_exclusions(root, &everything).is_empty());
+    // Only a scan of `/` itself skips them, not any directory named like them
+    let elsewhere = TempDir::new().unwrap();
+    fs::create_dir(elsewhere.path().join("proc")).unwrap();
+    assert!(virtual_exclusions(elsewhere.path(), &options).is_empty());
+    let listed = collect_files(elsewhere.path(), &Filter::default(), &SortSpec::default(), &options);
+    assert_eq!(listed.len(), 1);
+
+    let names = |options: &CollectOptions| -> Vec<String> {
+        collect_files(root, &Filter::default(), &SortSpec::default(), options)
+            .into_iter()
+            .map(|f| f.name)
+            .filter(|name| VIRTUAL_DIRS.contains(&name.as_str()))
+            .collect()
+    };
+    assert!(names(&options).is_empty());
+    for name in VIRTUAL_DIRS.iter().filter(|name| root.join(name).is_dir()) {
+        assert!(names(&everything).iter().any(|n| n == name), "{} not listed", name);
+    }
+}
@@ -0,0 +1,270 @@
+//! Property tests for size formatting/parsing and the listing sort order,
+//! in memory and through the external sorter used by `--stream`.
+//!
+//! These run with the default unit system and precision (binary, 2 digits);
+//! nothing here changes the process-wide display settings.
+
+use filebyte::collect::{compare_files, sort_files};
+use filebyte::quantiles::{SizeQuantiles, RELATIVE_ACCURACY};
+use filebyte::stream::{ExternalSorter, MAX_OPEN_RUNS};
+use filebyte::types::{parse_size, FileInfo, FileKind, SizeUnit, SortBy, SortSpec};
+use filebyte::utils::group_digits;
+use proptest::prelude::*;
+use std::cmp::Ordering;
+
+/// The unit `auto_format_size` picks for a byte count, in binary units
+fn auto_unit(bytes: u64) -> u64 {
+    [1u64 << 40, 1 << 30, 1 << 20, 1 << 10]
+        .into_iter()
+        .find(|unit| bytes >= *unit)
+        .unwrap_or(1)
+}
+
+/// Byte counts biased toward unit boundaries and the ends of the range
+fn any_size() -> impl Strategy<Value = u64> {
+    prop_oneof![
+        any::<u64>(),
+        0u64..4096,
+        (0u32..5, -2i64..=2).prop_map(|(power, offset)| (1u64 << (10 * power))
+            .saturating_add_signed(offset)),
+        Just(u64::MAX),
+        (u64::MAX - 4096)..=u64::MAX,
+    ]
+}
+
+fn file_info() -> impl Strategy<Value = FileInfo> {
+    (
+        "[a-c]{0,2}",
+        0u64..4,
+        proptest::option::of("2020-01-0[1-3]"),
+        "[xy]?",
+        any::<bool>(),
+    )
+        .prop_map(|(name, size, modified, owner, is_directory)| FileInfo {
+            name: name.clone(),
+            path: name,
+            size,
+            size_human: SizeUnit::auto_format_size(size),
+            file_type: "unknown".to_string(),
+            created: None,
+            modified,
+            permissions: "rw-".to_string(),
+            owner,
+            group: String::new(),
+            is_directory,
+            kind: if is_directory { FileKind::Dir } else { FileKind::File },
+            hash: None,
+            dimensions: None,
+            mode: None,
+            uid: None,
+        })
+}
+
+/// The fields `compare_files` looks at; entries equal on all of them may
+/// come out of either sort in any order
+fn compare_key(file: &FileInfo) -> (bool, String, u64, Option<String>, String) {
+    (
+        file.is_directory,
+        file.name.clone(),
+        file.size,
+        file.modified.clone(),
+        file.owner.clone(),
+    )
+}
+
+fn sort_spec() -> impl Strategy<Value = SortSpec> {
+    (
+        proptest::option::of(prop_oneof![
+            Just(SortBy::Name),
+            Just(SortBy::Size),
+            Just(SortBy::Date),
+            Just(SortBy::Owner),
+        ]),
+        any::<bool>(),
+    )
+        .prop_map(|(sort_by, reverse)| SortSpec {
+            sort_by,
+            reverse,
+            top: None,
+        })
+}
+
+proptest! {
+    #[test]
+    fn auto_format_size_round_trips(bytes in any_size()) {
+        let formatted = SizeUnit::auto_format_size(bytes);
+        let parsed = parse_size(&formatted).unwrap();
+        // Two decimals of the chosen unit, plus float error on huge values
+        let tolerance = auto_unit(bytes) as f64 * 0.005 + bytes as f64 * 1e-12 + 1.0;
+        let error = (parsed as f64 - bytes as f64).abs();
+        prop_assert!(
+            error <= tolerance,
+            "{} -> {:?} -> {} (error {}, tolerance {})",
+            bytes, formatted, parsed, error, tolerance
+        );
+    }
+
+    #[test]
+    fn auto_format_size_is_monotonic(a in any_size(), b in any_size()) {
+        let (low, high) = if a <= b { (a, b) } else { (b, a) };
+        let low_parsed = parse_size(&SizeUnit::auto_format_size(low)).unwrap();
+        let high_parsed = parse_size(&SizeUnit::auto_format_size(high)).unwrap();
+        prop_assert!(low_parsed <= high_parsed);
+    }
+
+    #[test]
+    fn parse_size_accepts_plain_byte_counts(bytes in any::<u64>()) {
+        prop_assert_eq!(parse_size(&bytes.to_string()), Ok(bytes));
+        prop_assert_eq!(parse_size(&format!("{} B", bytes)), Ok(bytes));
+    }
+
+    #[test]
+    fn group_digits_only_adds_separators(n in any::<u64>()) {
+        let grouped = group_digits(n);
+        prop_assert_eq!(grouped.replace(',', ""), n.to_string());
+        prop_assert!(grouped.split(',').skip(1).all(|group| group.len() == 3));
+        prop_assert!((1..=3).contains(&grouped.split(',').next().unwrap().len()));
+    }
+
+    #[test]
+    fn exact_quantiles_use_nearest_rank(sizes in prop::collection::vec(any_size(), 1..200), q in 0.0f64..=1.0) {
+        let mut quantiles = SizeQuantiles::new();
+        for &size in &sizes {
+            quantiles.push(size);
+        }
+        let mut sorted = sizes.clone();
+        sorted.sort_unstable();
+        let rank = ((q * sorted.len() as f64).ceil() as usize).max(1);
+        prop_assert!(quantiles.is_exact());
+        prop_assert_eq!(quantiles.quantile(q), Some(sorted[rank - 1]));
+    }
+
+    #[test]
+    fn sketched_quantiles_stay_within_accuracy(sizes in prop::collection::vec(any_size(), 50..300), q in 0.0f64..=1.0) {
+        let mut exact = SizeQuantiles::new();
+        let mut sketched = SizeQuantiles::with_exact_limit(10);
+        for &size in &sizes {
+            exact.push(size);
+            sketched.push(size);
+        }
+        prop_assert!(!sketched.is_exact());
+        prop_assert_eq!(exact.mean(), sketched.mean());
+        let (expected, estimate) = (exact.quantile(q).unwrap() as f64, sketched.quantile(q).unwrap() as f64);
+        // Rounding the estimate to whole bytes adds up to half a byte
+        prop_assert!((estimate - expected).abs() <= expected * RELATIVE_ACCURACY + 0.5, "{} vs {}", estimate, expected);
+    }
+
+    #[test]
+    fn compare_files_is_a_total_order(
+        a in file_info(),
+        b in file_info(),
+        c in file_info(),
+        sort in sort_spec(),
+    ) {
+        prop_assert_eq!(compare_files(&a, &a, &sort), Ordering::Equal);
+        prop_assert_eq!(compare_files(&a, &b, &sort), compare_files(&b, &a, &sort).reverse());
+        if compare_files(&a, &b, &sort) != Ordering::Greater
+            && compare_files(&b, &c, &sort) != Ordering::Greater
+        {
+            prop_assert_ne!(compare_files(&a, &c, &sort), Ordering::Greater);
+        }
+    }
+
+    #[test]
+    fn external_sort_matches_in_memory_sort(
+        files in proptest::collection::vec(file_info(), 0..40),
+        chunk_size in 1usize..8,
+        sort in sort_spec(),
+    ) {
+        let mut expected = files.clone();
+        sort_files(&mut expected, &sort);
+
+        let mut sorter = ExternalSorter::new(sort.clone(), chunk_size);
+        for file in files {
+            sorter.push(file).unwrap();
+        }
+        let mut merged = Vec::new();
+        sorter.finish(&mut |file| merged.push(file)).unwrap();
+
+        let key = |files: &[FileInfo]| files.iter().map(compare_key).collect::<Vec<_>>();
+        prop_assert_eq!(key(&merged), key(&expected));
+    }
+}
+
+#[test]
+fn external_sort_keeps_a_bounded_number_of_runs_open() {
+    let by_size = SortSpec {
+        sort_by: Some(SortBy::Size),
+        ..SortSpec::default()
+    };
+    let count = 3 * MAX_OPEN_RUNS as u64 + 5;
+    let mut sorter = ExternalSorter::new(by_size, 1);
+    for i in 0..count {
+        // Every size twice, out of order
+        let size = (i * 37) % (count / 2);
+        let file = FileInfo {
+            name: format!("f{}", i),
+            path: format!("f{}", i),
+            size,
+            size_human: SizeUnit::auto_format_size(size),
+            file_type: "unknown".to_string(),
+            created: None,
+            modified: None,
+            permissions: "rw-".to_string(),
+            owner: String::new(),
+            group: String::new(),
+            is_directory: false,
+            kind: FileKind::File,
+            hash: None,
+            dimensions: None,
+            mode: None,
+            uid: None,
+        };
+        sorter.push(file).unwrap();
+        assert!(sorter.open_runs() < MAX_OPEN_RUNS, "{} runs open", sorter.open_runs());
+    }
+
+    let mut sizes = Vec::new();
+    sorter.finish(&mut |file| sizes.push(file.size)).unwrap();
+    assert_eq!(sizes.len() as u64, count);
+    // Largest first
+    assert!(sizes.windows(2).all(|pair| pair[0] >= pair[1]), "{:?}", sizes);
+}
+
+#[test]
+fn parse_size_edges() {
+    assert_eq!(parse_size("0"), Ok(0));
+    assert_eq!(parse_size("0 B"), Ok(0));
+    assert_eq!(parse_size("10K"), Ok(10 * 1024));
+    assert_eq!(parse_size("1.5 MiB"), Ok(1024 * 1024 * 3 / 2));
+    assert_eq!(parse_size("1.50 MB"), Ok(1_500_000));
+    assert_eq!(parse_size("2 kB"), Ok(2000));
+    assert_eq!(parse_size("16777216.00 TiB"), Ok(u64::MAX));
+    assert!(parse_size("16777217 TiB").is_err());
+    assert!(parse_size("12 parsecs").is_err());
+    assert!(parse_size("MB").is_err());
+    assert!(parse_size("").is_err());
+}
+
+#[test]
+fn quantiles_of_known_sizes() {
+    let mut quantiles = SizeQuantiles::new();
+    assert_eq!((quantiles.mean(), quantiles.quantile(0.5)), (None, None));
+    for size in (1..=100).rev() {
+        quantiles.push(size);
+    }
+    assert_eq!(quantiles.count(), 100);
+    assert_eq!(quantiles.mean(), Some(50.5));
+    let stats = [0.0, 0.5, 0.9, 0.99, 1.0].map(|q| quantiles.quantile(q));
+    assert_eq!(stats, [1, 50, 90, 99, 100].map(Some));
+
+    // Past the exact limit empty files still count as zero-byte sizes
+    let mut sketched = SizeQuantiles::with_exact_limit(2);
+    for size in [0, 0, 0, 1000] {
+        sketched.push(size);
+    }
+    assert!(!sketched.is_exact());
+    assert_eq!(sketched.quantile(0.75), Some(0));
+    let top = sketched.quantile(1.0).unwrap() as f64;
+    assert!((top - 1000.0).abs() <= 1000.0 * RELATIVE_ACCURACY + 0.5, "{}", top);
+}
@@ -0,0 +1,119 @@
+use filebyte::snapshot::{diff, format_delta, OwnershipChange, Snapshot, SnapshotChange, SnapshotEntry};
+use filebyte::types::ScanMetadata;
+
+/// A snapshot of `(path, size)` entries; paths ending in `/` are directories
+fn snapshot(entries: &[(&str, u64)]) -> Snapshot {
+    Snapshot {
+        name: "test".to_string(),
+        root: "/data".to_string(),
+        metadata: ScanMetadata {
+            hostname: "host".to_string(),
+            os: "linux".to_string(),
+            scanned_at: "2024-01-01 00:00:00 UTC".to_string(),
+        },
+        hashed: false,
+        entries: entries
+            .iter()
+            .map(|&(path, size)| SnapshotEntry {
+                path: path.trim_end_matches('/').to_string(),
+                size,
+                modified: None,
+                dir: path.ends_with('/'),
+                hash: None,
+                mode: None,
+                uid: None,
+                owner: String::new(),
+            })
+            .collect(),
+    }
+}
+
+fn change(path: &str, dir: bool, old_size: Option<u64>, new_size: Option<u64>) -> SnapshotChange {
+    SnapshotChange {
+        path: path.to_string(),
+        dir,
+        old_size,
+        new_size,
+    }
+}
+
+#[test]
+fn diff_lists_subtree_tops_by_size_of_change() {
+    let old = snapshot(&[
+        ("a", 10),
+        ("dir/x", 10),
+        ("dir/y", 20),
+        ("dir/", 30),
+        ("gone/z", 5),
+        ("gone/", 5),
+    ]);
+    let new = snapshot(&[
+        ("a", 15),
+        ("b", 1),
+        ("dir/x", 5),
+        ("dir/y", 20),
+        ("dir/", 25),
+        ("new/n1", 60),
+        ("new/n2", 40),
+        ("new/", 100),
+    ]);
+    let result = diff(&old, &new);
+
+    // A new or deleted directory is listed once, not file by file
+    assert_eq!(result.added, [change("new", true, None, Some(100)), change("b", false, None, Some(1))]);
+    assert_eq!(result.removed, [change("gone", true, Some(5), None)]);
+    assert_eq!((result.files_added, result.files_removed), (3, 1));
+    assert_eq!(result.grown, [change("a", false, Some(10), Some(15))]);
+    // Equal changes are ordered by path
+    assert_eq!(
+        result.shrunk,
+        [change("dir", true, Some(30), Some(25)), change("dir/x", false, Some(10), Some(5))]
+    );
+    assert_eq!(result.net, 141 - 45);
+    assert!(result.ownership.is_empty());
+}
+
+/// `snapshot(&[("a", 1)])` with `a`'s mode, uid and owner set
+fn owned(mode: u32, uid: u32, owner: &str) -> Snapshot {
+    let mut tree = snapshot(&[("a", 1)]);
+    tree.entries[0].mode = Some(mode);
+    tree.entries[0].uid = Some(uid);
+    tree.entries[0].owner = owner.to_string();
+    tree
+}
+
+#[test]
+fn ownership_changes_show_what_triggered_them() {
+    let details = |old: &Snapshot, new: &Snapshot| {
+        diff(old, new).ownership.iter().map(OwnershipChange::details).collect::<Vec<_>>()
+    };
+    let before = owned(0o644, 1000, "alice");
+    assert!(details(&before, &owned(0o644, 1000, "alice")).is_empty());
+    assert_eq!(details(&before, &owned(0o600, 1000, "alice")), ["mode 0644 -> 0600"]);
+    assert_eq!(details(&before, &owned(0o644, 1001, "bob")), ["owner alice -> bob"]);
+    assert_eq!(
+        details(&before, &owned(0o666, 1001, "bob")),
+        ["mode 0644 -> 0666, owner alice -> bob"]
+    );
+    // The uid changed but both resolve to the same name, e.g. on another host
+    assert_eq!(details(&before, &owned(0o644, 1001, "alice")), ["owner alice (uid 1000 -> 1001)"]);
+    // A renamed user still owns the file
+    assert!(details(&before, &owned(0o644, 1000, "alicia")).is_empty());
+}
+
+#[test]
+fn unchanged_snapshots_have_an_empty_diff() {
+    let tree = snapshot(&[("a", 1), ("dir/b", 2), ("dir/", 2)]);
+    let result = diff(&tree, &tree);
+    assert_eq!(result, Default::default());
+}
+
+#[test]
+fn deltas_are_signed() {
+    assert_eq!(change("a", false, Some(10), None).delta(), -10);
+    assert_eq!(change("a", false, None, Some(u64::MAX)).delta(), i128::from(u64::MAX));
+    assert_eq!(format_delta(0), "+0 B");
+    assert_eq!(format_delta(-300), "-300 B");
+    assert_eq!(format_delta(1536), "+1.50 KiB");
+    assert_eq!(format_delta(i128::MIN), "-16777216.00 TiB");
+}